@@ -15,7 +15,7 @@
 
 from os.path import join, dirname, exists
 from shutil import rmtree
-from json import load
+from json import load, loads
 from zipfile import ZipFile
 from appdirs import user_data_dir
 from ._native import ffi, lib
@@ -216,6 +216,51 @@ class Cache (object):
             from_pack['vendor'], from_pack['pack'], from_pack['version']))
         return open(dest, "r")
 
+    def _cdata_path(self):
+        if self.data_path:
+            return ffi.new("char[]", self.data_path.encode("utf-8"))
+        return ffi.NULL
+
+    def pdsc_path(self, vendor, name, version=None):
+        """Locate the PDSC file of a pack in the cache.
+
+        :param vendor: The vendor of the pack.
+        :type vendor: str
+        :param name: The name of the pack.
+        :type name: str
+        :param version: The version of the pack; the newest cached version
+                        when omitted.
+        :type version: str
+        :return: The path to the PDSC file, which may be gzip compressed,
+                 or None when the pack is not cached.
+        :rtype: str
+        """
+        cvendor = ffi.new("char[]", vendor.encode("utf-8"))
+        cname = ffi.new("char[]", name.encode("utf-8"))
+        if version:
+            cversion = ffi.new("char[]", version.encode("utf-8"))
+        else:
+            cversion = ffi.NULL
+        with _RaiseRust():
+            path = lib.cached_pdsc_path(self._cdata_path(), cvendor, cname,
+                                        cversion)
+        if not path:
+            return None
+        path = ffi.gc(path, lib.cstring_free)
+        return ffi.string(path).decode("utf-8")
+
+    def cached_pdscs(self):
+        """Parse every PDSC file in the cache.
+
+        :return: A list of dicts with the ``path`` of each file and the
+                 ``error`` met parsing it, None when it parsed.
+        :rtype: list
+        """
+        with _RaiseRust():
+            dumped = ffi.gc(lib.dumps_cached_pdscs_json(self._cdata_path()),
+                            lib.cstring_free)
+        return loads(ffi.string(dumped).decode("utf-8"))
+
     def pack_from_cache(self, device):
         """Low level inteface for extracting a PACK file from the cache.
 
@@ -232,6 +277,21 @@ class Cache (object):
                             from_pack['pack'],
                             from_pack['version'] + ".pack"))
 
+    @staticmethod
+    def core_display_name(core):
+        """Human friendly name of a core, as found in the index.
+
+        :param core: The core of a processor in the index, e.g. CortexM0Plus
+        :type core: str
+        :return: The display name of the core, e.g. Cortex-M0+
+        :rtype: str
+        """
+        ccore = ffi.new("char[]", core.encode("utf-8"))
+        with _RaiseRust():
+            label = lib.core_display_name(ccore)
+        label = ffi.gc(label, lib.cstring_free)
+        return ffi.string(label).decode("utf-8")
+
     @staticmethod
     def find_pdsc(zipfile):
         """Find the PDSC file within a PACK file
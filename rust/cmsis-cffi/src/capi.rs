@@ -0,0 +1,196 @@
+//! Functions for embedding the pack manager in C and C++ programs, such as
+//! IDEs. Strings returned are freed with `cstring_free`, everything else
+//! with the `_free` function of its type. A null or false return means the
+//! call failed, and `err_get_last_message` tells why.
+
+use slog::{Drain, Logger};
+use slog_async::Async;
+use slog_term::{FullFormat, TermDecorator};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::mem;
+use std::ptr::null;
+use std::slice;
+
+use failure::{err_msg, Error};
+
+use cmsis_update::{update, Context};
+use pack_desc::DeviceDatabase;
+use pi::config::{Config, ConfigBuilder};
+
+fn logger() -> Logger {
+    let decorator = TermDecorator::new().build();
+    let drain = FullFormat::new(decorator).build().fuse();
+    let drain = Async::new(drain).build().fuse();
+    Logger::root(drain, o!())
+}
+
+fn optional_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+    }
+}
+
+fn config(pack_store: *const c_char, vidx_list: *const c_char) -> Result<Config, Error> {
+    let mut conf_bld = ConfigBuilder::new();
+    if let Some(pack_store) = optional_str(pack_store) {
+        conf_bld = conf_bld.with_pack_store(pack_store);
+    }
+    if let Some(vidx_list) = optional_str(vidx_list) {
+        conf_bld = conf_bld.with_vidx_list(vidx_list);
+    }
+    conf_bld.build()
+}
+
+/// The devices of every cached description.
+pub struct CmsisDatabase(DeviceDatabase);
+
+#[repr(C)]
+pub struct CmsisDeviceNames {
+    pub names: *mut *mut c_char,
+    pub len: usize,
+}
+
+#[repr(C)]
+pub struct CmsisMemory {
+    pub name: *mut c_char,
+    pub start: u64,
+    pub size: u64,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    /// The device boots from this memory
+    pub startup: bool,
+}
+
+#[repr(C)]
+pub struct CmsisMemories {
+    pub memories: *mut CmsisMemory,
+    pub len: usize,
+}
+
+// Hand the elements of `items` over to C, as a pointer and a length
+fn into_raw_parts<T>(items: Vec<T>) -> (*mut T, usize) {
+    let mut items = items.into_boxed_slice();
+    let parts = (items.as_mut_ptr(), items.len());
+    mem::forget(items);
+    parts
+}
+
+unsafe fn from_raw_parts<T>(ptr: *mut T, len: usize) -> Vec<T> {
+    Box::from_raw(slice::from_raw_parts_mut(ptr, len)).into_vec()
+}
+
+cffi!{
+    /// Download the descriptions of the packs listed by the vendor indexes
+    /// into `pack_store`. Either argument may be null for the default.
+    fn cmsis_update_index(pack_store: *const c_char, vidx_list: *const c_char) -> Result<bool> {
+        let log = logger();
+        let conf = config(pack_store, vidx_list)?;
+        let vidx_list = conf.read_vidx_list(&log);
+        update(&Context::new(&conf, &log), vidx_list)?;
+        Ok(true)
+    }
+}
+
+cffi!{
+    /// Read the devices of the descriptions cached in `pack_store`, or the
+    /// default pack store when it is null.
+    fn cmsis_database_open(pack_store: *const c_char) -> Result<*mut CmsisDatabase> {
+        let conf = config(pack_store, null())?;
+        let database = DeviceDatabase::open(&conf, &logger())?;
+        Ok(Box::into_raw(Box::new(CmsisDatabase(database))))
+    }
+}
+
+cffi!{
+    fn cmsis_database_free(ptr: *mut CmsisDatabase) {
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) })
+        }
+    }
+}
+
+cffi!{
+    /// The names of the devices matching `pattern`, ignoring case, where
+    /// `*` and `?` match like in a shell glob.
+    fn cmsis_find_device(
+        database: *mut CmsisDatabase,
+        pattern: *const c_char,
+    ) -> Result<*mut CmsisDeviceNames> {
+        if database.is_null() || pattern.is_null() {
+            return Err(err_msg("Null passed into cmsis_find_device"));
+        }
+        let pattern = unsafe { CStr::from_ptr(pattern) }.to_string_lossy();
+        let database = unsafe { &*database };
+        let names = database
+            .0
+            .packs()
+            .iter()
+            .flat_map(|pack| pack.devices.find(&pattern))
+            .map(|dev| CString::new(dev.name.as_str()).map(CString::into_raw))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (names, len) = into_raw_parts(names);
+        Ok(Box::into_raw(Box::new(CmsisDeviceNames { names, len })))
+    }
+}
+
+cffi!{
+    fn cmsis_device_names_free(ptr: *mut CmsisDeviceNames) {
+        if !ptr.is_null() {
+            let found = unsafe { Box::from_raw(ptr) };
+            for name in unsafe { from_raw_parts(found.names, found.len) } {
+                drop(unsafe { CString::from_raw(name) })
+            }
+        }
+    }
+}
+
+cffi!{
+    /// The memories of the device named `name`.
+    fn cmsis_device_memories(
+        database: *mut CmsisDatabase,
+        name: *const c_char,
+    ) -> Result<*mut CmsisMemories> {
+        if database.is_null() || name.is_null() {
+            return Err(err_msg("Null passed into cmsis_device_memories"));
+        }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+        let database = unsafe { &*database };
+        let device = database
+            .0
+            .devices()
+            .map(|(_, dev)| dev)
+            .find(|dev| dev.name == name)
+            .ok_or_else(|| err_msg(format!("No device named {}", name)))?;
+        let memories = device
+            .memories
+            .iter()
+            .map(|(id, mem)| {
+                Ok(CmsisMemory {
+                    name: CString::new(id)?.into_raw(),
+                    start: mem.start(),
+                    size: mem.size(),
+                    read: mem.permissions().read(),
+                    write: mem.permissions().write(),
+                    execute: mem.permissions().execute(),
+                    startup: mem.startup(),
+                })
+            }).collect::<Result<Vec<_>, Error>>()?;
+        let (memories, len) = into_raw_parts(memories);
+        Ok(Box::into_raw(Box::new(CmsisMemories { memories, len })))
+    }
+}
+
+cffi!{
+    fn cmsis_memories_free(ptr: *mut CmsisMemories) {
+        if !ptr.is_null() {
+            let memories = unsafe { Box::from_raw(ptr) };
+            for memory in unsafe { from_raw_parts(memories.memories, memories.len) } {
+                drop(unsafe { CString::from_raw(memory.name) })
+            }
+        }
+    }
+}
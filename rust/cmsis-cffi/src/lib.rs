@@ -25,6 +25,7 @@ macro_rules! with_from_raw {
 #[macro_use]
 pub mod utils;
 
+pub mod capi;
 pub mod pack;
 pub mod pack_index;
 pub mod pdsc;
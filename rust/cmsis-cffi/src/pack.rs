@@ -4,7 +4,7 @@ use std::os::raw::c_char;
 
 use failure::err_msg;
 
-use cmsis_update::install;
+use cmsis_update::{install, Context};
 use pi::config::ConfigBuilder;
 
 use pdsc::ParsedPacks;
@@ -28,7 +28,7 @@ cffi!{
             };
             let conf = conf_bld.build()?;
             with_from_raw!(let packs = parsed_packs, {
-                install(&conf, packs.iter(), &log).map(|_| ())
+                install(&Context::new(&conf, &log), packs.iter()).map(|_| ())
             })
         } else {
             Err(err_msg("update packs received a Null pointer"))
@@ -6,7 +6,7 @@ use std::ptr::null_mut;
 
 use failure::err_msg;
 
-use cmsis_update::update;
+use cmsis_update::{update, Context};
 use pi::config::ConfigBuilder;
 
 pub struct UpdateReturn(Vec<PathBuf>);
@@ -48,7 +48,7 @@ cffi!{
         };
         let conf = conf_bld.build()?;
         let vidx_list = conf.read_vidx_list(&log);
-        update(&conf, vidx_list, &log).map(
+        update(&Context::new(&conf, &log), vidx_list).map(
             |updated| Box::into_raw(Box::new(UpdateReturn(updated))))
     }
 }
@@ -4,13 +4,19 @@ use slog_term::{FullFormat, TermDecorator};
 use std::borrow::Cow;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::panic::RefUnwindSafe;
 use std::path::{Path, PathBuf};
+use std::ptr::null;
 
 use failure::err_msg;
 
-use cmsis_utils::parse::FromElem;
+use cmsis_utils::parse::{FromElem, ParseOptions};
 use cmsis_utils::ResultLogExt;
-use pack_desc::{self, dump_devices, Package};
+use pack_desc::{
+    self, core_label, dump_devices, dump_devices_json, dump_index_json, dumps_cached_pdscs,
+    parse_packages, Package,
+};
+use pi::config::{Config, ConfigBuilder};
 
 use pack_index::UpdateReturn;
 
@@ -45,6 +51,38 @@ cffi!{
     }
 }
 
+cffi!{
+    fn dump_pyocd_index(packs: *mut ParsedPacks, index_dest: *const c_char) -> Result<()> {
+        if index_dest.is_null() {
+            return Err(err_msg("Null passed into dump_pyocd_index"));
+        }
+        let decorator = TermDecorator::new().build();
+        let drain = FullFormat::new(decorator).build().fuse();
+        let drain = Async::new(drain).build().fuse();
+        let log = Logger::root(drain, o!());
+        let dest = unsafe { CStr::from_ptr(index_dest) }.to_string_lossy().into_owned();
+        with_from_raw!(let parsed = packs, {
+            dump_index_json(&parsed.0, dest, &log)
+        })
+    }
+}
+
+cffi!{
+    fn dump_devices_json(packs: *mut ParsedPacks, dest: *const c_char) -> Result<()> {
+        if dest.is_null() {
+            return Err(err_msg("Null passed into dump_devices_json"));
+        }
+        let decorator = TermDecorator::new().build();
+        let drain = FullFormat::new(decorator).build().fuse();
+        let drain = Async::new(drain).build().fuse();
+        let log = Logger::root(drain, o!());
+        let dest = unsafe { CStr::from_ptr(dest) }.to_string_lossy().into_owned();
+        with_from_raw!(let parsed = packs, {
+            dump_devices_json(&parsed.0, dest, &log)
+        })
+    }
+}
+
 pub struct ParsedPacks(Vec<Package>);
 
 impl ParsedPacks {
@@ -79,9 +117,12 @@ cffi!{
                 let drain = Async::new(drain).build().fuse();
                 let log = Logger::root(drain, o!());
                 let pdsc_files = boxed.iter();
+                let opts = ParseOptions::default();
                 Ok(Box::into_raw(Box::new(ParsedPacks(
                     pdsc_files
-                        .filter_map(|input| Package::from_path(Path::new(input), &log).ok_warn(&log))
+                        .filter_map(|input| {
+                            Package::from_path(Path::new(input), &opts, &log).ok_warn(&log)
+                        })
                         .collect()))))
             })
         } else {
@@ -90,6 +131,56 @@ cffi!{
     }
 }
 
+/// A configuration made once by the caller and handed to the functions
+/// taking one.
+pub struct PackConfig(Config);
+
+// Only ever read through the pointers handed out
+impl RefUnwindSafe for PackConfig {}
+
+cffi!{
+    fn pack_config_new(
+        pack_store: *const c_char,
+        threads: u32,
+        niceness: i32,
+    ) -> Result<*mut PackConfig> {
+        let conf = builder_for(pack_store)
+            .with_parse_threads(threads as usize)
+            .with_parse_niceness(niceness)
+            .build()?;
+        Ok(Box::into_raw(Box::new(PackConfig(conf))))
+    }
+}
+
+cffi!{
+    fn pack_config_free(ptr: *mut PackConfig) {
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) })
+        }
+    }
+}
+
+cffi!{
+    fn parse_packs_in_background(
+        ptr: *mut UpdateReturn,
+        conf: *const PackConfig,
+    ) -> Result<*mut ParsedPacks> {
+        if !ptr.is_null() && !conf.is_null() {
+            let conf = unsafe { &(*conf).0 };
+            with_from_raw!(let boxed = ptr,{
+                let decorator = TermDecorator::new().build();
+                let drain = FullFormat::new(decorator).build().fuse();
+                let drain = Async::new(drain).build().fuse();
+                let log = Logger::root(drain, o!());
+                let parsed = parse_packages(boxed.iter().cloned(), conf, &log);
+                Ok(Box::into_raw(Box::new(ParsedPacks(parsed))))
+            })
+        } else {
+            Err(err_msg("Null Passed into parse packs in background."))
+        }
+    }
+}
+
 cffi!{
     fn parse_packs_free(ptr: *mut ParsedPacks) {
         if !ptr.is_null() {
@@ -107,3 +198,70 @@ cffi!{
         })
     }
 }
+
+fn builder_for(pack_store: *const c_char) -> ConfigBuilder {
+    let conf_bld = ConfigBuilder::new();
+    if !pack_store.is_null() {
+        let pstore = unsafe { CStr::from_ptr(pack_store) }.to_string_lossy();
+        conf_bld.with_pack_store(pstore.into_owned())
+    } else {
+        conf_bld
+    }
+}
+
+fn config_for(pack_store: *const c_char) -> Result<Config, ::failure::Error> {
+    builder_for(pack_store).build()
+}
+
+cffi!{
+    fn cached_pdsc_path(
+        pack_store: *const c_char,
+        vendor: *const c_char,
+        name: *const c_char,
+        version: *const c_char,
+    ) -> Result<*const c_char> {
+        if vendor.is_null() || name.is_null() {
+            return Err(err_msg("Null vendor or name passed into cached pdsc path"));
+        }
+        let conf = config_for(pack_store)?;
+        let vendor = unsafe { CStr::from_ptr(vendor) }.to_string_lossy();
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+        let version = if !version.is_null() {
+            Some(unsafe { CStr::from_ptr(version) }.to_string_lossy())
+        } else {
+            None
+        };
+        match conf.cached_pdsc(&vendor, &name, version.as_ref().map(|v| &**v)) {
+            Some(path) => match path.into_os_string().into_string() {
+                Ok(path) => Ok(CString::new(path)?.into_raw()),
+                Err(_) => Err(err_msg("Could not create a C string from a Rust String")),
+            },
+            None => Ok(null()),
+        }
+    }
+}
+
+cffi!{
+    fn dumps_cached_pdscs_json(pack_store: *const c_char) -> Result<*const c_char> {
+        let decorator = TermDecorator::new().build();
+        let drain = FullFormat::new(decorator).build().fuse();
+        let drain = Async::new(drain).build().fuse();
+        let log = Logger::root(drain, o!());
+        let conf = config_for(pack_store)?;
+        let dumped = dumps_cached_pdscs(&conf, &log)?;
+        Ok(CString::new(dumped)?.into_raw())
+    }
+}
+
+cffi!{
+    fn core_display_name(core: *const c_char) -> Result<*const c_char> {
+        if core.is_null() {
+            return Err(err_msg("Null passed into core display name"));
+        }
+        let core = unsafe { CStr::from_ptr(core) }.to_string_lossy();
+        match core_label(&core) {
+            Some(label) => Ok(CString::new(label)?.into_raw()),
+            None => Err(err_msg(format!("Unknown core {}", core))),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{create_dir_all, hard_link, remove_file, rename, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use slog::Logger;
+
+use pack_index::config::{Config, PackId};
+
+use install::pack_archive;
+use integrity::{children, pack_archives, record_checksum, recorded_checksum};
+use project::sha256_file;
+
+// Below the pack store, where no vendor is named with a leading dot
+const BLOB_DIR: &str = ".blobs";
+const INDEX_FILE: &str = "index";
+
+type Key = (String, String, String);
+
+fn key(id: &PackId) -> Key {
+    (id.vendor.clone(), id.name.clone(), id.version.clone())
+}
+
+fn blob_path(config: &Config, sha256: &str) -> PathBuf {
+    config.pack_store.join(BLOB_DIR).join(format!("{}.pack", sha256))
+}
+
+// The blob of every pack version stored, as lines of `sha256  Vendor/Name/version`
+fn read_index(config: &Config) -> BTreeMap<Key, String> {
+    let path = config.pack_store.join(BLOB_DIR).join(INDEX_FILE);
+    let fd = match File::open(path) {
+        Ok(fd) => fd,
+        Err(_) => return BTreeMap::new(),
+    };
+    BufReader::new(fd)
+        .lines()
+        .filter_map(Result::ok)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let sha256 = fields.next()?.to_string();
+            let mut id = fields.next()?.splitn(3, '/');
+            let key = (
+                id.next()?.to_string(),
+                id.next()?.to_string(),
+                id.next()?.to_string(),
+            );
+            Some((key, sha256))
+        }).collect()
+}
+
+fn write_index(config: &Config, index: &BTreeMap<Key, String>) -> Result<(), Error> {
+    let dir = config.pack_store.join(BLOB_DIR);
+    create_dir_all(&dir)?;
+    let path = dir.join(INDEX_FILE);
+    let temp = dir.join(format!("{}.new", INDEX_FILE));
+    {
+        let mut fd = File::create(&temp)?;
+        for (&(ref vendor, ref name, ref version), sha256) in index {
+            writeln!(fd, "{}  {}/{}/{}", sha256, vendor, name, version)?;
+        }
+    }
+    rename(temp, path)?;
+    Ok(())
+}
+
+// Replace `path` with a hard link to `to`, without a moment where `path` is
+// missing
+fn link_over(to: &Path, path: &Path) -> Result<(), Error> {
+    let temp = path.with_extension("pack.link");
+    let _ = remove_file(&temp);
+    hard_link(to, &temp)?;
+    rename(&temp, path)?;
+    Ok(())
+}
+
+/// Store a downloaded pack under its checksum. When a pack with the same
+/// content is stored already, `pack` becomes a link to it and the space of
+/// the download is given back.
+pub(crate) fn store_blob(
+    config: &Config,
+    id: &PackId,
+    pack: &Path,
+    l: &Logger,
+) -> Result<(), Error> {
+    let sha256 = match recorded_checksum(pack) {
+        Some(sha256) => sha256,
+        None => record_checksum(pack)?,
+    };
+    let blob = blob_path(config, &sha256);
+    if blob.exists() {
+        if blob.metadata()?.len() == pack.metadata()?.len() {
+            link_over(&blob, pack)?;
+            debug!(l, "{:?} has the content of {:?}", pack, blob);
+        }
+    } else {
+        create_dir_all(config.pack_store.join(BLOB_DIR))?;
+        hard_link(pack, &blob)?;
+    }
+    let mut index = read_index(config);
+    index.insert(key(id), sha256);
+    write_index(config, &index)
+}
+
+/// Put back the archive of a pack version removed from the store while its
+/// blob is still there, returning whether it did.
+pub(crate) fn restore_blob(config: &Config, id: &PackId, l: &Logger) -> bool {
+    let sha256 = match read_index(config).remove(&key(id)) {
+        Some(sha256) => sha256,
+        None => return false,
+    };
+    let blob = blob_path(config, &sha256);
+    let dest = pack_archive(config, &id.vendor, &id.name, &id.version);
+    let restored = blob.is_file()
+        && dest.parent().map(|p| create_dir_all(p).is_ok()).unwrap_or(false)
+        && hard_link(&blob, &dest).is_ok();
+    if restored {
+        match sha256_file(&dest) {
+            Ok(ref found) if *found == sha256 => {
+                let _ = record_checksum(&dest);
+                debug!(l, "Restored {:?} from {:?}", dest, blob);
+                return true;
+            }
+            _ => {
+                warn!(l, "{:?} does not match its checksum, downloading again", blob);
+                let _ = remove_file(&dest);
+            }
+        }
+    }
+    false
+}
+
+/// Forget the pack versions whose archive is gone and remove the blobs no
+/// longer linked from the store, returning what was removed and its size.
+pub(crate) fn prune_blobs(config: &Config, l: &Logger) -> Result<(Vec<PathBuf>, u64), Error> {
+    let stored: HashSet<Key> = pack_archives(config)
+        .into_iter()
+        .map(|(id, _)| key(&id))
+        .collect();
+    let index: BTreeMap<Key, String> = read_index(config)
+        .into_iter()
+        .filter(|&(ref key, _)| stored.contains(key))
+        .collect();
+    write_index(config, &index)?;
+    let used: HashSet<PathBuf> = index
+        .values()
+        .map(|sha256| blob_path(config, sha256))
+        .collect();
+    let mut removed = Vec::new();
+    let mut reclaimed = 0;
+    for blob in children(&config.pack_store.join(BLOB_DIR)) {
+        if blob.extension().map(|ext| ext != "pack").unwrap_or(true) || used.contains(&blob) {
+            continue;
+        }
+        let size = blob.metadata().map(|meta| meta.len()).unwrap_or(0);
+        match remove_file(&blob) {
+            Ok(()) => {
+                debug!(l, "Removed {:?}", blob);
+                reclaimed += size;
+                removed.push(blob);
+            }
+            Err(e) => warn!(l, "Could not remove {:?}: {}", blob, e),
+        }
+    }
+    Ok((removed, reclaimed))
+}
+
+/// The space taken by the archive of a pack version.
+#[derive(Debug, Clone)]
+pub struct PackUsage {
+    pub id: PackId,
+    pub size: u64,
+    pub sha256: Option<String>,
+    /// Other versions with the same content, which take no space of their own
+    pub shared_with: Vec<PackId>,
+}
+
+/// The space taken by every pack archive of the store.
+pub fn disk_usage(config: &Config) -> Vec<PackUsage> {
+    let index = read_index(config);
+    let mut by_blob: BTreeMap<&str, Vec<PackId>> = BTreeMap::new();
+    for (&(ref vendor, ref name, ref version), sha256) in &index {
+        by_blob.entry(sha256.as_str()).or_insert_with(Vec::new).push(PackId {
+            vendor: vendor.clone(),
+            name: name.clone(),
+            version: version.clone(),
+        });
+    }
+    pack_archives(config)
+        .into_iter()
+        .map(|(id, path)| {
+            let sha256 = index.get(&key(&id)).cloned();
+            let shared_with = sha256
+                .as_ref()
+                .and_then(|sha256| by_blob.get(sha256.as_str()))
+                .map(|ids| ids.iter().filter(|other| **other != id).cloned().collect())
+                .unwrap_or_default();
+            PackUsage {
+                size: path.metadata().map(|meta| meta.len()).unwrap_or(0),
+                id,
+                sha256,
+                shared_with,
+            }
+        }).collect()
+}
+
@@ -0,0 +1,365 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use failure::{err_msg, Error};
+use futures::future::{err, ok, Either};
+use futures::prelude::{async_block, await};
+use futures::{Future, IntoFuture, Poll};
+use futures_cpupool::CpuPool;
+use hyper::header::{Basic, Headers, ProxyAuthorization};
+use hyper::server::Service;
+use hyper::Uri;
+#[cfg(feature = "native")]
+use native_tls;
+use rustls::{ClientConfig, ClientSession, Session};
+use tokio_core::net::TcpStream;
+use tokio_core::reactor::{Handle, Timeout};
+use tokio_io::io::{read, write_all};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{ClientConfigExt, TlsStream};
+#[cfg(feature = "native")]
+use tokio_tls::{self, TlsConnectorExt};
+use slog::Logger;
+use webpki::DNSNameRef;
+
+use pack_index::config::{AddressFamily, CertificatePin, Config, Proxy, ProxyConfig, TlsBackend};
+
+use error::{OfflineRefused, ResolveFailed, TlsFailed};
+use pinning::{check_pins, pinned_keys, PinningVerifier};
+use roots::trust_roots;
+
+// Delay before racing the second address family, as suggested by RFC 8305
+const HAPPY_EYEBALLS_DELAY: u64 = 250;
+
+// Longest response to a CONNECT request accepted from a proxy
+const MAX_TUNNEL_RESPONSE: usize = 8192;
+
+type Connecting = Box<Future<Item = TcpStream, Error = io::Error>>;
+type Securing = Box<Future<Item = HttpsStream, Error = io::Error>>;
+
+/// A connection to a server, encrypted for https.
+pub enum HttpsStream {
+    Http(TcpStream),
+    Rustls(TlsStream<TcpStream, ClientSession>),
+    #[cfg(feature = "native")]
+    Native(tokio_tls::TlsStream<TcpStream>),
+}
+
+impl Read for HttpsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            HttpsStream::Http(ref mut s) => s.read(buf),
+            HttpsStream::Rustls(ref mut s) => s.read(buf),
+            #[cfg(feature = "native")]
+            HttpsStream::Native(ref mut s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for HttpsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            HttpsStream::Http(ref mut s) => s.write(buf),
+            HttpsStream::Rustls(ref mut s) => s.write(buf),
+            #[cfg(feature = "native")]
+            HttpsStream::Native(ref mut s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            HttpsStream::Http(ref mut s) => s.flush(),
+            HttpsStream::Rustls(ref mut s) => s.flush(),
+            #[cfg(feature = "native")]
+            HttpsStream::Native(ref mut s) => s.flush(),
+        }
+    }
+}
+
+impl AsyncRead for HttpsStream {}
+
+impl AsyncWrite for HttpsStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match *self {
+            HttpsStream::Http(ref mut s) => AsyncWrite::shutdown(s),
+            HttpsStream::Rustls(ref mut s) => s.shutdown(),
+            #[cfg(feature = "native")]
+            HttpsStream::Native(ref mut s) => s.shutdown(),
+        }
+    }
+}
+
+// The TLS implementation connections are secured with
+#[derive(Clone)]
+enum Tls {
+    Rustls(Arc<ClientConfig>),
+    /// The native libraries have no hook into their verification, so the
+    /// keys pinned are checked once the handshake is done
+    #[cfg(feature = "native")]
+    Native(Arc<native_tls::TlsConnector>, Vec<CertificatePin>),
+}
+
+impl Tls {
+    fn new(config: &Config, l: &Logger) -> Result<Self, Error> {
+        match config.tls_backend {
+            TlsBackend::Rustls => {
+                let mut tls = ClientConfig::new();
+                tls.root_store = trust_roots(config.trust_roots, l)?;
+                if !config.certificate_pins.is_empty() {
+                    let verifier = PinningVerifier::new(config.certificate_pins.clone());
+                    tls.dangerous().set_certificate_verifier(Arc::new(verifier));
+                }
+                Ok(Tls::Rustls(Arc::new(tls)))
+            }
+            #[cfg(feature = "native")]
+            TlsBackend::Native => {
+                let connector = native_tls::TlsConnector::builder()?.build()?;
+                Ok(Tls::Native(
+                    Arc::new(connector),
+                    config.certificate_pins.clone(),
+                ))
+            }
+            #[cfg(not(feature = "native"))]
+            TlsBackend::Native => Err(err_msg(
+                "the native TLS backend is not available in this build; \
+                 rebuild with the `native` feature or use rustls",
+            )),
+        }
+    }
+
+    // Negotiate TLS with `host` over `tcp`, checking the keys pinned for it
+    fn secure(&self, host: String, tcp: TcpStream) -> Securing {
+        match *self {
+            Tls::Rustls(ref config) => {
+                let connecting = match DNSNameRef::try_from_ascii_str(&host) {
+                    Ok(dns_name) => config.connect_async(dns_name, tcp),
+                    Err(_) => {
+                        return Box::new(err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "invalid DNS name",
+                        )))
+                    }
+                };
+                Box::new(connecting.map_err(TlsFailed::wrap).map(HttpsStream::Rustls))
+            }
+            #[cfg(feature = "native")]
+            Tls::Native(ref connector, ref pins) => {
+                let pinned = pinned_keys(pins, &host);
+                Box::new(
+                    connector
+                        .connect_async(&host, tcp)
+                        .map_err(|e| TlsFailed::wrap(io::Error::new(io::ErrorKind::Other, e)))
+                        .and_then(move |stream| {
+                            let leaf = stream
+                                .get_ref()
+                                .peer_certificate()
+                                .ok()
+                                .and_then(|cert| cert)
+                                .and_then(|cert| cert.to_der().ok());
+                            match check_pins(&host, leaf.as_ref().map(|der| &der[..]), &pinned) {
+                                Ok(()) => Ok(HttpsStream::Native(stream)),
+                                Err(msg) => Err(TlsFailed::wrap(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    msg,
+                                ))),
+                            }
+                        }),
+                )
+            }
+        }
+    }
+}
+
+/// An HTTPS connector that resolves host names itself so that it can order,
+/// filter or race IPv4 and IPv6 addresses according to an `AddressFamily`.
+pub struct DualStackConnector {
+    resolver: CpuPool,
+    handle: Handle,
+    family: AddressFamily,
+    tls: Tls,
+    offline: bool,
+    proxy: ProxyConfig,
+    pins: Vec<CertificatePin>,
+}
+
+impl DualStackConnector {
+    pub fn new(
+        threads: usize,
+        config: &Config,
+        handle: &Handle,
+        l: &Logger,
+    ) -> Result<Self, Error> {
+        config.proxy.check()?;
+        Ok(DualStackConnector {
+            resolver: CpuPool::new(threads),
+            handle: handle.clone(),
+            family: config.address_family,
+            tls: Tls::new(config, l)?,
+            offline: config.offline,
+            proxy: config.proxy.clone(),
+            pins: config.certificate_pins.clone(),
+        })
+    }
+}
+
+fn connect_sequential(addrs: Vec<SocketAddr>, handle: Handle) -> Connecting {
+    Box::new(async_block!{
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no address to connect to");
+        for addr in addrs {
+            match await!(TcpStream::connect(&addr, &handle)) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    })
+}
+
+fn connect_racing(primary: Vec<SocketAddr>, fallback: Vec<SocketAddr>, handle: Handle) -> Connecting {
+    let first = connect_sequential(primary, handle.clone());
+    let delay = Timeout::new(Duration::from_millis(HAPPY_EYEBALLS_DELAY), &handle);
+    let second: Connecting = Box::new(
+        delay
+            .into_future()
+            .flatten()
+            .and_then(move |_| connect_sequential(fallback, handle)),
+    );
+    Box::new(
+        first
+            .select(second)
+            .map(|(stream, _)| stream)
+            .or_else(|(_, remaining)| remaining),
+    )
+}
+
+pub(crate) fn proxy_authorization(proxy: &Proxy) -> Option<ProxyAuthorization<Basic>> {
+    proxy.username.as_ref().map(|username| {
+        ProxyAuthorization(Basic {
+            username: username.clone(),
+            password: proxy.password.clone(),
+        })
+    })
+}
+
+// Ask the proxy connected to by `tcp` for a tunnel to `target`, given as
+// `host:port`, so that TLS is negotiated with the server itself
+fn tunnel(tcp: TcpStream, target: String, proxy: &Proxy) -> Connecting {
+    let mut headers = Headers::new();
+    if let Some(auth) = proxy_authorization(proxy) {
+        headers.set(auth);
+    }
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n{1}\r\n", target, headers);
+    Box::new(async_block!{
+        let (mut tcp, _) = await!(write_all(tcp, request.into_bytes()))?;
+        let mut response = Vec::new();
+        while !response.windows(4).any(|end| end == b"\r\n\r\n") {
+            if response.len() > MAX_TUNNEL_RESPONSE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "proxy response too long"));
+            }
+            let (stream, buf, len) = await!(read(tcp, [0u8; 512]))?;
+            if len == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof, "proxy closed the connection"));
+            }
+            response.extend_from_slice(&buf[..len]);
+            tcp = stream;
+        }
+        let status_line = String::from_utf8_lossy(&response)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("proxy refused to connect to {}: {}", target, status_line)));
+        }
+        Ok(tcp)
+    })
+}
+
+fn connect_addrs(addrs: Vec<SocketAddr>, family: AddressFamily, handle: Handle) -> Connecting {
+    let (v4, v6): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv4);
+    match family {
+        AddressFamily::HappyEyeballs => {
+            // Prefer IPv6, starting IPv4 attempts shortly after
+            if v6.is_empty() || v4.is_empty() {
+                connect_sequential(v6.into_iter().chain(v4).collect(), handle)
+            } else {
+                connect_racing(v6, v4, handle)
+            }
+        }
+        AddressFamily::PreferIpv4 => connect_sequential(v4.into_iter().chain(v6).collect(), handle),
+        AddressFamily::PreferIpv6 => connect_sequential(v6.into_iter().chain(v4).collect(), handle),
+        AddressFamily::Ipv4Only => connect_sequential(v4, handle),
+        AddressFamily::Ipv6Only => connect_sequential(v6, handle),
+    }
+}
+
+impl Service for DualStackConnector {
+    type Request = Uri;
+    type Response = HttpsStream;
+    type Error = io::Error;
+    type Future = Securing;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        let is_https = uri.scheme() == Some("https");
+        let host = match uri.host() {
+            Some(host) => host.to_string(),
+            None => {
+                return Box::new(err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "URI does not contain a host",
+                )))
+            }
+        };
+        // Refused before resolving, so that offline work never waits on the network
+        if self.offline {
+            return Box::new(err(OfflineRefused::new(&host)));
+        }
+        let port = uri.port().unwrap_or(if is_https { 443 } else { 80 });
+        let family = self.family;
+        let handle = self.handle.clone();
+        let tls = self.tls.clone();
+        let pinned = pinned_keys(&self.pins, &host);
+        if !is_https && !pinned.is_empty() {
+            return Box::new(err(TlsFailed::wrap(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has pinned keys, refusing to connect without TLS", host),
+            ))));
+        }
+        // Plain http requests are sent to the proxy as they are, https ones
+        // go through a tunnel
+        let proxy = self.proxy.for_host(is_https, &host).cloned();
+        let (lookup_host, lookup_port) = match proxy {
+            Some(ref proxy) => (proxy.host.clone(), proxy.port),
+            None => (host.clone(), port),
+        };
+        let tunnel_to = match proxy {
+            Some(proxy) if is_https => Some((format!("{}:{}", host, port), proxy)),
+            _ => None,
+        };
+        let resolved = self.resolver.spawn_fn(move || {
+            (lookup_host.as_str(), lookup_port)
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>())
+                .map_err(|e| ResolveFailed::wrap(&lookup_host, e))
+        });
+        Box::new(
+            resolved
+                .and_then(move |addrs| connect_addrs(addrs, family, handle))
+                .and_then(move |tcp| match tunnel_to {
+                    Some((target, proxy)) => Either::A(tunnel(tcp, target, &proxy)),
+                    None => Either::B(ok(tcp)),
+                }).and_then(move |tcp| {
+                    if is_https {
+                        Either::A(tls.secure(host, tcp))
+                    } else {
+                        Either::B(ok(HttpsStream::Http(tcp)))
+                    }
+                }),
+        )
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::PathBuf;
+
+use failure::Error;
+use futures::stream::iter_ok;
+use futures::Stream;
+use hyper::client::Connect;
+use hyper::{Body, Client};
+use slog::Logger;
+use tokio_core::reactor::{Core, Handle};
+
+use pack_index::config::Config;
+use pdsc::Usage;
+
+use connect::DualStackConnector;
+use dl_pack::PackRelease;
+use download::{download_stream, DownloadProgress};
+use history::History;
+use plan::{make_plan, pending_updates, PendingUpdates, UpdatePlan};
+use snapshot::Snapshot;
+use temp::sweep_orphans;
+use {install_inner, update_inner};
+
+use dl_pdsc::update_refs;
+use vidx::pdsc_refs;
+
+/// How the entry points of a `Context` reach the servers.
+pub trait Transport {
+    type Connector: Connect;
+    fn connector(
+        &self,
+        config: &Config,
+        handle: &Handle,
+        logger: &Logger,
+    ) -> Result<Self::Connector, Error>;
+}
+
+/// Connections over IPv4 and IPv6 as configured, with TLS for https.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTransport;
+
+impl Transport for DefaultTransport {
+    type Connector = DualStackConnector;
+    fn connector(
+        &self,
+        config: &Config,
+        handle: &Handle,
+        logger: &Logger,
+    ) -> Result<DualStackConnector, Error> {
+        Ok(DualStackConnector::new(4, config, handle, logger)?)
+    }
+}
+
+/// Everything an update or an install runs with. The free functions of this
+/// crate take a default context and report on stdout; embedders build one to
+/// supply their own transport or progress reporting.
+#[derive(Clone)]
+pub struct Context<'a, T = DefaultTransport, P = ()> {
+    config: &'a Config,
+    logger: &'a Logger,
+    transport: T,
+    progress: P,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(config: &'a Config, logger: &'a Logger) -> Self {
+        Context {
+            config,
+            logger,
+            transport: DefaultTransport,
+            progress: (),
+        }
+    }
+}
+
+impl<'a, T, P> Context<'a, T, P>
+where
+    T: Transport,
+    P: DownloadProgress + Clone,
+{
+    pub fn with_config(self, config: &'a Config) -> Self {
+        Context { config, ..self }
+    }
+
+    pub fn with_logger(self, logger: &'a Logger) -> Self {
+        Context { logger, ..self }
+    }
+
+    pub fn with_transport<U: Transport>(self, transport: U) -> Context<'a, U, P> {
+        Context {
+            config: self.config,
+            logger: self.logger,
+            transport,
+            progress: self.progress,
+        }
+    }
+
+    pub fn with_progress<Q: DownloadProgress + Clone>(self, progress: Q) -> Context<'a, T, Q> {
+        Context {
+            config: self.config,
+            logger: self.logger,
+            transport: self.transport,
+            progress,
+        }
+    }
+
+    pub fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    pub fn logger(&self) -> &'a Logger {
+        self.logger
+    }
+
+    fn client(&self, handle: &Handle) -> Result<Client<T::Connector, Body>, Error> {
+        let connector = self.transport.connector(self.config, handle, self.logger)?;
+        Ok(Client::configure()
+            .keep_alive(true)
+            .connector(connector)
+            .build(handle))
+    }
+
+    /// Flatten a list of Vidx Urls into a list of updated descriptions,
+    /// only downloading the pack versions recorded in `pinned` if given.
+    pub fn update<I>(
+        &self,
+        vidx_list: I,
+        pinned: Option<&Snapshot>,
+    ) -> Result<Vec<PathBuf>, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let (config, logger) = (self.config, self.logger);
+        let sources: Vec<String> = vidx_list.into_iter().collect();
+        sweep_orphans(config, logger);
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let updated = update_inner(
+            config,
+            sources.clone(),
+            pinned,
+            &mut core,
+            &client,
+            logger,
+            &handle,
+            self.progress.clone(),
+        )?;
+        record_update(config, sources, &updated, logger);
+        Ok(updated)
+    }
+
+    /// List the descriptions `update` would download, without downloading them.
+    pub fn plan<I>(&self, vidx_list: I, pinned: Option<&Snapshot>) -> Result<UpdatePlan, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let (config, logger) = (self.config, self.logger);
+        let sources: Vec<String> = vidx_list.into_iter().collect();
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let refs = update_refs(config, sources.clone(), pinned, &client, logger);
+        let refs = core.run(refs.collect())?;
+        Ok(make_plan(config, sources, refs, logger))
+    }
+
+    /// Compare the pack versions the indexes list, as `update` would
+    /// consider them, with the descriptions in the store.
+    pub fn check_updates<I>(&self, vidx_list: I) -> Result<PendingUpdates, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let sources: Vec<String> = vidx_list.into_iter().collect();
+        let refs = update_refs(self.config, sources, None, &client, self.logger);
+        let refs = core.run(refs.collect())?;
+        Ok(pending_updates(self.config, refs))
+    }
+
+    /// Download the descriptions of a plan, in its order.
+    pub fn execute(&self, plan: UpdatePlan) -> Result<Vec<PathBuf>, Error> {
+        let (config, logger) = (self.config, self.logger);
+        let UpdatePlan { sources, downloads } = plan;
+        sweep_orphans(config, logger);
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let downloads = iter_ok(downloads);
+        let progress = self.progress.clone();
+        let updated = core.run(
+            download_stream(config, downloads, &client, logger, &handle, progress).collect(),
+        )?;
+        record_update(config, sources, &updated, logger);
+        Ok(updated)
+    }
+
+    /// Record the pack versions currently listed by the vendor indexes.
+    pub fn take_snapshot<I>(&self, vidx_list: I) -> Result<Snapshot, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let refs = pdsc_refs(self.config, vidx_list, &client, self.logger);
+        let refs = core.run(refs.collect())?;
+        Ok(Snapshot::from_refs(refs))
+    }
+
+    /// Download each release of `pdsc_list`. A pack given by its description
+    /// installs its latest release.
+    pub fn install<'p, I>(&self, pdsc_list: I) -> Result<Vec<PathBuf>, Error>
+    where
+        I: IntoIterator + 'p,
+        I::Item: Into<PackRelease<'p>>,
+    {
+        sweep_orphans(self.config, self.logger);
+        let pdsc_list: Vec<PackRelease> = pdsc_list.into_iter().map(Into::into).collect();
+        let mut core = Core::new()?;
+        let handle = core.handle();
+        let client = self.client(&handle)?;
+        let installed = install_inner(
+            self.config,
+            pdsc_list,
+            &mut core,
+            &client,
+            self.logger,
+            &handle,
+            self.progress.clone(),
+        )?;
+        let recorded = Usage::record(self.config, |usage| {
+            for &(ref id, _) in &installed {
+                usage.use_pack(&id.vendor, &id.name);
+            }
+        });
+        if let Err(e) = recorded {
+            debug!(self.logger, "Could not record the installed packs: {}", e);
+        }
+        Ok(installed.into_iter().map(|(_, path)| path).collect())
+    }
+}
+
+fn record_update(config: &Config, sources: Vec<String>, updated: &[PathBuf], logger: &Logger) {
+    if !updated.is_empty() {
+        if let Err(e) = History::record(config, sources, updated.to_vec()) {
+            warn!(logger, "Could not record the update in the history: {}", e);
+        }
+    }
+}
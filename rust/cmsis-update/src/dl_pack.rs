@@ -1,49 +1,137 @@
+use std::collections::HashMap;
+use std::fs::remove_file;
 use std::path::{Path, PathBuf};
 
-use failure::Error;
+use failure::{err_msg, Error};
+use futures::future::result;
 use futures::prelude::*;
 use futures::stream::iter_ok;
 use hyper::client::Connect;
 use hyper::{Body, Client, Uri};
 use slog::Logger;
+use tokio_core::reactor::Handle;
 
-use pack_index::config::Config;
-use pdsc::Package;
+use pack_index::config::{Config, InstallEvent, PackId};
+use pack_index::SupportLevel;
+use pdsc::{Package, Release};
 
+use blobs::{restore_blob, store_blob};
 use download::{download_stream, DownloadProgress, IntoDownload};
+use install::{extract_pack, is_extracted, pack_archive, pack_dir};
+use integrity::{advertised_sizes, check_download, remove_checksum};
+use signature::{SignatureStatus, Signers};
 
-impl<'a> IntoDownload for &'a Package {
-    fn into_uri(&self, _: &Config) -> Result<Uri, Error> {
+/// A release of a pack, as installed into the pack store.
+#[derive(Debug, Clone, Copy)]
+pub struct PackRelease<'a> {
+    pub pack: &'a Package,
+    pub release: &'a Release,
+}
+
+impl<'a> PackRelease<'a> {
+    pub fn version(&self) -> &'a str {
+        &self.release.version
+    }
+}
+
+/// The latest release of a pack
+impl<'a> From<&'a Package> for PackRelease<'a> {
+    fn from(pack: &'a Package) -> Self {
+        PackRelease {
+            pack,
+            release: pack.releases.latest_release(),
+        }
+    }
+}
+
+impl<'a> IntoDownload for PackRelease<'a> {
+    fn into_uri(&self, config: &Config) -> Result<Uri, Error> {
         let &Package {
             ref name,
             ref vendor,
             ref url,
-            ref releases,
             ..
-        } = *self;
-        let version: &str = releases.latest_release().version.as_ref();
-        let uri = if url.ends_with('/') {
-            format!("{}{}.{}.{}.pack", url, vendor, name, version)
-        } else {
-            format!("{}/{}.{}.{}.pdsc", url, vendor, name, version)
-        }.parse()?;
-        Ok(uri)
+        } = self.pack;
+        let version = self.version();
+        // Older releases may be archived away from the url of the pack
+        let url = match self.release.url {
+            Some(ref url) => url.clone(),
+            None if url.ends_with('/') => format!("{}{}.{}.{}.pack", url, vendor, name, version),
+            None => format!("{}/{}.{}.{}.pack", url, vendor, name, version),
+        };
+        Ok(config.mirror_url(vendor, &url).parse()?)
     }
 
     fn into_fd(&self, config: &Config) -> PathBuf {
         let &Package {
             ref name,
             ref vendor,
-            ref releases,
             ..
-        } = *self;
-        let version: &str = releases.latest_release().version.as_ref();
-        let mut filename = config.pack_store.clone();
-        filename.push(Path::new(vendor));
-        filename.push(Path::new(name));
-        filename.push(format!("{}.pack", version));
-        filename
+        } = self.pack;
+        pack_archive(config, vendor, name, self.version())
+    }
+
+    // Through its link, the cache would keep a discarded archive on disk
+    fn use_http_cache(&self, config: &Config) -> bool {
+        !config.discard_archives
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    // Versions removed from the store since they were downloaded come back
+    // from their blob
+    fn restore_copy(&self, config: &Config, _dest: &Path, l: &Logger) -> bool {
+        let restored = restore_blob(config, &pack_id(self), l);
+        if restored {
+            info!(
+                l,
+                "Restored {}.{} {} without downloading it",
+                self.pack.vendor,
+                self.pack.name,
+                self.version()
+            );
+        }
+        restored
+    }
+
+    fn installed(&self, config: &Config) -> bool {
+        let &Package {
+            ref name,
+            ref vendor,
+            ..
+        } = self.pack;
+        config.discard_archives
+            && pack_dir(config, vendor, name, self.version())
+                .map(|dir| is_extracted(&dir))
+                .unwrap_or(false)
+    }
+}
+
+// Whether the pack at `path` may be installed, which it may not when its
+// signature is invalid or cannot be checked; it is then removed
+fn check_signed(signers: Option<&Signers>, path: &Path, l: &Logger) -> bool {
+    match signers.map(|signers| signers.status(path)) {
+        Some(Ok(SignatureStatus::Invalid(reason))) => {
+            error!(l, "Removing downloaded pack {:?}: {}", path, reason);
+            discard(path);
+            return false;
+        }
+        Some(Ok(status)) => info!(l, "{:?} is {}", path, status),
+        Some(Err(e)) => {
+            error!(l, "Removing downloaded pack {:?}: could not check its signature: {}", path, e);
+            discard(path);
+            return false;
+        }
+        None => (),
     }
+    true
+}
+
+fn discard(path: &Path) {
+    let _ = remove_file(path);
+    remove_checksum(path);
 }
 
 pub fn install_future<'client, 'a: 'client, C, I, P>(
@@ -51,12 +139,132 @@ pub fn install_future<'client, 'a: 'client, C, I, P>(
     pdscs: I,
     client: &'client Client<C, Body>,
     logger: &'a Logger,
+    handle: &'client Handle,
     progress: P,
-) -> impl Future<Item = Vec<PathBuf>, Error = Error> + 'client
+) -> impl Future<Item = Vec<(PackId, PathBuf)>, Error = Error> + 'client
 where
     C: Connect,
-    I: IntoIterator<Item = &'a Package> + 'a,
+    I: IntoIterator<Item = PackRelease<'a>> + 'a,
     P: DownloadProgress + 'client,
 {
-    download_stream(config, iter_ok(pdscs), client, logger, progress).collect()
+    let pdscs: Vec<PackRelease> = pdscs
+        .into_iter()
+        .filter(|&PackRelease { pack: pdsc, .. }| {
+            let allowed = config.pack_filter.allows(&pdsc.vendor, &pdsc.name);
+            if !allowed {
+                warn!(
+                    logger,
+                    "Not installing {}.{}: it is excluded by the pack filter",
+                    pdsc.vendor,
+                    pdsc.name
+                );
+            }
+            allowed
+        }).inspect(|&PackRelease { pack: pdsc, .. }| {
+            if let Some(release) = pdsc.releases.deprecation() {
+                let replacement = release
+                    .replacement
+                    .as_ref()
+                    .map(|by| format!(", replaced by {}", by))
+                    .unwrap_or_default();
+                warn!(
+                    logger,
+                    "{}.{} is deprecated{}",
+                    pdsc.vendor,
+                    pdsc.name,
+                    replacement
+                );
+            }
+        }).filter(|pdsc| {
+            let version = pdsc.version();
+            let supported = config.supports_version(version);
+            if !supported {
+                warn!(
+                    logger,
+                    "Not installing {}.{} {}: it is {:?}",
+                    pdsc.pack.vendor,
+                    pdsc.pack.name,
+                    version,
+                    SupportLevel::from_version(version)
+                );
+            }
+            supported
+        }).collect();
+    let ids: HashMap<PathBuf, PackId> = pdscs
+        .iter()
+        .map(|pdsc| (pdsc.into_fd(config), pack_id(pdsc)))
+        .collect();
+    let sizes = advertised_sizes(config, logger);
+    // Restored and cached copies come through the download stream too, and
+    // are checked the same way
+    result(Signers::from_config(config)).and_then(move |signers| {
+        download_stream(config, iter_ok(pdscs), client, logger, handle, progress)
+            .filter_map(move |path| {
+                // Every download is of one of the requested releases
+                let id = match ids.get(&path) {
+                    Some(id) => id,
+                    None => return None,
+                };
+                let key = (id.vendor.clone(), id.name.clone(), id.version.clone());
+                let recorded = match check_download(id, &path, sizes.get(&key).cloned()) {
+                    Ok(None) => true,
+                    Ok(Some(corrupt)) => {
+                        error!(logger, "Removing downloaded pack: {}", corrupt);
+                        discard(&path);
+                        return None;
+                    }
+                    // Signed packs are only installed once they are known intact
+                    Err(ref e) if signers.is_some() => {
+                        error!(
+                            logger,
+                            "Removing downloaded pack {:?}: could not record its checksum: {}",
+                            path,
+                            e
+                        );
+                        discard(&path);
+                        return None;
+                    }
+                    Err(e) => {
+                        warn!(logger, "Could not record the checksum of {:?}: {}", path, e);
+                        false
+                    }
+                };
+                if !check_signed(signers.as_ref(), &path, logger) {
+                    return None;
+                }
+                if recorded && !config.discard_archives {
+                    if let Err(e) = store_blob(config, id, &path, logger) {
+                        warn!(logger, "Could not store {:?} by its checksum: {}", path, e);
+                    }
+                }
+                let path = match extract_pack(config, id, &path, logger) {
+                    // The extraction is all that is kept of the pack
+                    Ok(installed) if config.discard_archives => {
+                        discard(&path);
+                        installed.path
+                    }
+                    Ok(_) => path,
+                    Err(e) => {
+                        error!(logger, "Could not extract {:?}: {}", path, e);
+                        return Some(Err(path));
+                    }
+                };
+                config.run_install_hooks(InstallEvent::Installed, id, &path);
+                Some(Ok((id.clone(), path)))
+            }).collect()
+            .and_then(|installs: Vec<Result<(PackId, PathBuf), PathBuf>>| {
+                match installs.iter().filter(|install| install.is_err()).count() {
+                    0 => Ok(installs.into_iter().filter_map(Result::ok).collect()),
+                    num => Err(err_msg(format!("{} packs could not be extracted", num))),
+                }
+            })
+    })
+}
+
+fn pack_id(pdsc: &PackRelease) -> PackId {
+    PackId {
+        vendor: pdsc.pack.vendor.clone(),
+        name: pdsc.pack.name.clone(),
+        version: pdsc.version().to_string(),
+    }
 }
@@ -1,31 +1,36 @@
+use std::cmp::Reverse;
 use std::path::PathBuf;
 
 use failure::Error;
 use futures::prelude::*;
+use futures::stream::iter_ok;
 use hyper::client::Connect;
 use hyper::{Body, Client, Uri};
 use slog::Logger;
+use tokio_core::reactor::Handle;
 
 use pack_index::config::Config;
 use pack_index::PdscRef;
+use pdsc::Usage;
 
 use download::{download_stream, DownloadProgress, IntoDownload};
-use vidx::{download_vidx_list, flatmap_pdscs};
+use snapshot::Snapshot;
+use vidx::{cached_pdsc_refs, pdsc_refs};
 
 impl IntoDownload for PdscRef {
-    fn into_uri(&self, _: &Config) -> Result<Uri, Error> {
+    fn into_uri(&self, config: &Config) -> Result<Uri, Error> {
         let &PdscRef {
             ref url,
             ref vendor,
             ref name,
             ..
         } = self;
-        let uri = if url.ends_with('/') {
+        let url = if url.ends_with('/') {
             format!("{}{}.{}.pdsc", url, vendor, name)
         } else {
             format!("{}/{}.{}.pdsc", url, vendor, name)
-        }.parse()?;
-        Ok(uri)
+        };
+        Ok(config.mirror_url(vendor, &url).parse()?)
     }
 
     fn into_fd(&self, config: &Config) -> PathBuf {
@@ -35,19 +40,85 @@ impl IntoDownload for PdscRef {
             ref version,
             ..
         } = self;
-        let mut filename = config.pack_store.clone();
-        let pdscname = format!("{}.{}.{}.pdsc", vendor, name, version);
-        filename.push(pdscname);
-        filename
+        config.pdsc_path(vendor, name, version)
     }
+
+    // A new version in the index does not mean the description changed yet
+    fn revalidates(&self) -> bool {
+        true
+    }
+
+    fn previous_fd(&self, config: &Config) -> Option<PathBuf> {
+        config.cached_pdsc(&self.vendor, &self.name, None)
+    }
+}
+
+/// The description references of the indexes that an update considers:
+/// the supported versions, restricted to the `pinned` snapshot if any.
+pub(crate) fn update_refs<'a, C, I>(
+    config: &'a Config,
+    vidx_list: I,
+    pinned: Option<&'a Snapshot>,
+    client: &'a Client<C, Body>,
+    logger: &'a Logger,
+) -> Box<Stream<Item = PdscRef, Error = Error> + 'a>
+where
+    C: Connect,
+    I: IntoIterator<Item = String> + 'a,
+{
+    // Offline, only the vendor indexes cached by earlier updates are known
+    let refs: Box<Stream<Item = PdscRef, Error = Error> + 'a> = if config.offline {
+        let filter = &config.pack_filter;
+        let cached = cached_pdsc_refs(config, logger)
+            .into_iter()
+            .filter(|pdsc| filter.allows(&pdsc.vendor, &pdsc.name));
+        Box::new(iter_ok(cached.collect::<Vec<_>>()))
+    } else {
+        Box::new(pdsc_refs(config, vidx_list, client, logger))
+    };
+    let refs = refs.filter(move |pdsc| {
+        if !config.supports_version(&pdsc.version) {
+            debug!(
+                logger,
+                "Skipping {}.{} {}: it is {:?}",
+                &*pdsc.vendor,
+                &*pdsc.name,
+                &*pdsc.version,
+                pdsc.support_level()
+            );
+            return false;
+        }
+        pinned
+            .map(|snapshot| snapshot.allows(pdsc, config, logger))
+            .unwrap_or(true)
+    });
+    let usage = Usage::load(config);
+    if usage.is_empty() {
+        return Box::new(refs);
+    }
+    // The packs used most are fetched first; waiting for every index to
+    // order them is worth it once downloads are queued behind each other
+    let sorted = refs.collect().map(move |mut refs| {
+        refs.sort_by_key(|pdsc| {
+            let uses = (
+                usage.pack_uses(&pdsc.vendor, &pdsc.name),
+                usage.vendor_uses(&pdsc.vendor),
+            );
+            Reverse(uses)
+        });
+        iter_ok(refs)
+    });
+    Box::new(sorted.flatten_stream())
 }
 
 /// Create a future of the update command.
 pub fn update_future<'a, C, I, P>(
     config: &'a Config,
     vidx_list: I,
+    pinned: Option<&'a Snapshot>,
     client: &'a Client<C, Body>,
     logger: &'a Logger,
+    handle: &'a Handle,
     progress: P,
 ) -> impl Future<Item = Vec<PathBuf>, Error = Error> + 'a
 where
@@ -55,11 +126,6 @@ where
     I: IntoIterator<Item = String> + 'a,
     P: DownloadProgress + 'a,
 {
-    let parsed_vidx = download_vidx_list(vidx_list, client, logger);
-    let pdsc_list = parsed_vidx
-        .filter_map(move |vidx| match vidx {
-            Ok(v) => Some(flatmap_pdscs(v, client, logger)),
-            Err(_) => None,
-        }).flatten();
-    download_stream(config, pdsc_list, client, logger, progress).collect()
+    let pdsc_list = update_refs(config, vidx_list, pinned, client, logger);
+    download_stream(config, pdsc_list, client, logger, handle, progress).collect()
 }
@@ -1,29 +1,83 @@
-use std::fs::{create_dir_all, rename, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::{ok, Either};
 use futures::prelude::{async_block, async_stream_block, await, stream_yield, Future};
 use futures::Stream;
 use hyper::client::Connect;
-use hyper::{Body, Client, Uri};
+use hyper::header::{ContentLength, Headers};
+use hyper::{Body, Chunk, Client, StatusCode, Uri};
 use pbr::ProgressBar;
 use slog::Logger;
 use std::sync::Arc;
+use tokio_core::reactor::{Handle, Timeout};
 
 use pack_index::config::Config;
+use utils::parse::is_gzip;
+use utils::span::Span;
 
+use error::DownloadError;
+use http_cache::{cached_copy, restore, Freshness, HttpCache};
 use redirect::ClientRedirExt;
+use resume::Partial;
+use revalidate::Validators;
+use temp::TempDir;
+use watchdog::{StalledDownload, Watchdog};
 
 pub(crate) trait IntoDownload {
     fn into_uri(&self, &Config) -> Result<Uri, Error>;
     fn into_fd(&self, &Config) -> PathBuf;
+    /// Whether the download may be served from, and stored in, the HTTP cache
+    fn use_http_cache(&self, _: &Config) -> bool {
+        false
+    }
+    /// Whether an interrupted download continues where it stopped
+    fn resumable(&self) -> bool {
+        false
+    }
+    /// Whether the download is made conditional on the file having changed
+    /// since `previous_fd` was downloaded
+    fn revalidates(&self) -> bool {
+        false
+    }
+    /// An earlier copy of the file, downloaded from the same url
+    fn previous_fd(&self, _: &Config) -> Option<PathBuf> {
+        None
+    }
+    /// Put the file back at `dest` from a copy kept locally, instead of
+    /// downloading it. Returns whether it did.
+    fn restore_copy(&self, _: &Config, _dest: &Path, _: &Logger) -> bool {
+        false
+    }
+    /// Whether what the file is downloaded for is in place already, even
+    /// though the file itself is not
+    fn installed(&self, _: &Config) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DownloadOptions {
+    use_cache: bool,
+    resumable: bool,
+    revalidate: bool,
+    previous: Option<PathBuf>,
+}
+
+// Where a download is written until it is complete
+enum Staging {
+    Temp(TempDir),
+    Partial(Partial),
 }
 
 fn should_download<'a, DL: IntoDownload>(config: &Config, from: &'a DL) -> Option<PathBuf> {
     let dest = from.into_fd(config);
-    if dest.exists() {
+    if dest.exists() || from.installed(config) {
         None
     } else {
         dest.parent().map(create_dir_all);
@@ -33,8 +87,14 @@ fn should_download<'a, DL: IntoDownload>(config: &Config, from: &'a DL) -> Optio
 
 pub trait DownloadProgress: Sync {
     fn size(&self, files: usize);
+    /// A transfer began, or restarted, with the given Content-Length
+    fn started(&self, _length: Option<u64>) {}
     fn progress(&self, bytes: usize);
     fn complete(&self);
+    /// The transfer failed for good. Counts as completed unless overridden.
+    fn failed(&self, _error: &DownloadError) {
+        self.complete()
+    }
     fn for_file(&self, file: &str) -> Self;
 }
 
@@ -66,28 +126,198 @@ impl<'a, W: Write + Send + 'a> DownloadProgress for &'a Mutex<ProgressBar<W>> {
     }
 }
 
-fn download_file<'b, C: Connect, P: DownloadProgress + 'b>(
+enum CacheFile {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl CacheFile {
+    fn create(path: &PathBuf, compress: bool) -> io::Result<Self> {
+        let fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        if compress {
+            Ok(CacheFile::Gzip(GzEncoder::new(fd, Compression::default())))
+        } else {
+            Ok(CacheFile::Plain(fd))
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            CacheFile::Plain(mut fd) => fd.flush(),
+            CacheFile::Gzip(enc) => enc.finish().map(|_| ()),
+        }
+    }
+}
+
+impl Write for CacheFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            CacheFile::Plain(ref mut fd) => fd.write(buf),
+            CacheFile::Gzip(ref mut enc) => enc.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            CacheFile::Plain(ref mut fd) => fd.flush(),
+            CacheFile::Gzip(ref mut enc) => enc.flush(),
+        }
+    }
+}
+
+fn fetch_file<'b, C: Connect, P: DownloadProgress + 'b>(
     source: Uri,
     dest: PathBuf,
+    config: &'b Config,
     client: &'b Client<C, Body>,
     logger: &'b Logger,
+    handle: &'b Handle,
+    options: DownloadOptions,
     spinner: Arc<P>,
-) -> impl Future<Item = PathBuf, Error = Error> + 'b {
+) -> impl Future<Item = Option<PathBuf>, Error = Error> + 'b {
+    let stall_timeout = config.stall_timeout;
+    let cache = if options.use_cache {
+        config.http_cache.as_ref().map(HttpCache::new)
+    } else {
+        None
+    };
     async_block!{
-        let response = await!(client.redirectable(source, logger))?;
-        let temp = dest.with_extension("part");
-        let mut fd = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&temp)?;
+        if let Some(cached) = cached_copy(cache.as_ref(), &source, config.offline)? {
+            slog_debug!(logger, "using cached {:?} for {}", cached, source);
+            restore(&cached, &dest)?;
+            return Ok(Some(dest));
+        }
+        let partial = if options.resumable {
+            Some(Partial::new(&dest))
+        } else {
+            None
+        };
+        let (offset, mut headers) = match partial {
+            Some(ref partial) => partial.resume_headers(&source),
+            None => (0, Headers::new()),
+        };
+        let previous = options.previous.as_ref().and_then(|prev| Validators::load(prev, &source));
+        if let Some(ref previous) = previous {
+            previous.condition(&mut headers);
+        }
+        let mut response = await!(client.redirectable(source.clone(), headers, config, logger))?;
+        if response.status() == StatusCode::RangeNotSatisfiable {
+            // The partial download is not a prefix of the file on the server
+            if let Some(ref partial) = partial {
+                partial.discard();
+            }
+            response = await!(client.redirectable(
+                source.clone(), Headers::new(), config, logger))?;
+        }
+        if response.status() == StatusCode::NotModified && previous.is_some() {
+            // The index lists a version the server does not provide yet
+            slog_debug!(logger, "{} did not change since {:?}", source, options.previous);
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(DownloadError::HttpStatus(response.status().as_u16()).into());
+        }
+        let freshness = Freshness::from_headers(response.headers());
+        let validators = if options.revalidate {
+            Validators::from_headers(&source, response.headers())
+        } else {
+            None
+        };
+        spinner.started(response.headers().get::<ContentLength>().map(|&ContentLength(len)| len));
+        let (mut fd, staging) = match partial {
+            // Failing keeps what was received, for the next attempt to resume
+            Some(partial) => {
+                let fd = partial.open(&source, response.status(), response.headers(), offset)?;
+                if response.status() == StatusCode::PartialContent {
+                    slog_debug!(logger, "resuming {} at byte {}", source, offset);
+                }
+                (CacheFile::Plain(fd), Staging::Partial(partial))
+            }
+            // Dropping this future, or failing, removes the partial download
+            None => {
+                let temp_dir = TempDir::new_in(dest.parent().unwrap_or(&config.pack_store))?;
+                let fd = CacheFile::create(&temp_dir.join("download"), is_gzip(&dest))?;
+                (fd, Staging::Temp(temp_dir))
+            }
+        };
+        let body: Box<Stream<Item = Chunk, Error = Error>> = match stall_timeout {
+            Some(timeout) => Box::new(Watchdog::new(response.body(), timeout, handle)),
+            None => Box::new(response.body().from_err::<Error>()),
+        };
         #[async]
-        for bytes in response.body() {
+        for bytes in body {
             fd.write_all(bytes.as_ref())?;
             spinner.progress(bytes.len());
         }
-        rename(&temp, &dest)?;
-        spinner.complete();
-        Ok(dest)
+        fd.finish()?;
+        match staging {
+            Staging::Temp(temp_dir) => temp_dir.persist("download", &dest)?,
+            Staging::Partial(partial) => partial.finish()?,
+        }
+        if let (Some(cache), Some(freshness)) = (cache, freshness) {
+            if let Err(e) = cache.store(&source, freshness, &dest) {
+                slog_warn!(logger, "could not cache {}: {}", source, e);
+            }
+        }
+        if let Some(validators) = validators {
+            if let Err(e) = validators.store(&dest) {
+                slog_warn!(logger, "could not record the validators of {}: {}", source, e);
+            }
+        }
+        Ok(Some(dest))
+    }
+}
+
+fn download_file<'b, C: Connect, P: DownloadProgress + 'b>(
+    source: Uri,
+    dest: PathBuf,
+    config: &'b Config,
+    client: &'b Client<C, Body>,
+    logger: &'b Logger,
+    handle: &'b Handle,
+    options: DownloadOptions,
+    spinner: Arc<P>,
+) -> impl Future<Item = Option<PathBuf>, Error = Error> + 'b {
+    let stall_retries = config.stall_retries;
+    let policy = &config.retry_policy;
+    async_block!{
+        let _span = Span::enter(logger, "download", &source);
+        let mut stalls = 0;
+        let mut attempt = 1;
+        loop {
+            let fetched = fetch_file(source.clone(), dest.clone(), config, client, logger, handle,
+                                     options.clone(), spinner.clone());
+            match await!(fetched) {
+                Ok(dest) => {
+                    spinner.complete();
+                    return Ok(dest);
+                }
+                Err(e) => {
+                    if stalls < stall_retries && e.downcast_ref::<StalledDownload>().is_some() {
+                        stalls += 1;
+                        slog_warn!(logger, "download of {:?} stalled: {}; restarting ({}/{})",
+                                   source, e, stalls, stall_retries);
+                        continue;
+                    }
+                    let error = DownloadError::classify(&e);
+                    let retried = error.retry_class()
+                        .map(|class| policy.retries(attempt, class))
+                        .unwrap_or(false);
+                    if !retried {
+                        return Err(error.into());
+                    }
+                    let backoff = policy.backoff(attempt);
+                    slog_warn!(logger, "download of {:?} failed: {}; retrying in {}s ({}/{})",
+                               source, error, backoff.as_secs(), attempt, policy.max_attempts - 1);
+                    await!(Timeout::new(backoff, handle)?)?;
+                    attempt += 1;
+                }
+            }
+        }
     }
 }
 
@@ -96,6 +326,7 @@ pub(crate) fn download_stream<'b, 'a: 'b, F, C, P: 'b, DL: 'a>(
     stream: F,
     client: &'b Client<C, Body>,
     logger: &'b Logger,
+    handle: &'b Handle,
     progress: P,
 ) -> Box<Stream<Item = PathBuf, Error = Error> + 'b>
 where
@@ -111,19 +342,35 @@ where
             progress.size(len);
             for from in to_dl {
                 if let Some(dest) = should_download(config, &from) {
+                    // Handed on like a download, to be checked and installed the same way
+                    if from.restore_copy(config, &dest, logger) {
+                        progress.for_file(&dest.to_string_lossy()).complete();
+                        stream_yield!(Either::A(ok(Some(dest))));
+                        continue;
+                    }
                     let source = from.into_uri(config)?;
                     let new_prog = Arc::new(progress.for_file(&dest.to_string_lossy()));
-                    stream_yield!(download_file(source.clone(), dest, client, logger, new_prog.clone())
-                                  .map(Some)
+                    let options = DownloadOptions {
+                        use_cache: from.use_http_cache(config),
+                        resumable: from.resumable(),
+                        revalidate: from.revalidates(),
+                        previous: from.previous_fd(config),
+                    };
+                    stream_yield!(Either::B(download_file(source.clone(), dest, config, client, logger, handle, options, new_prog.clone())
                                   .or_else(
                                       move |e| {
                                           slog_error!(logger, "download of {:?} failed: {}", source, e);
-                                          new_prog.complete();
-                                          Ok(None)
-                                      }))
+                                          let error = DownloadError::classify(&e);
+                                          new_prog.failed(&error);
+                                          match error {
+                                              // Fails the update or install as a whole
+                                              DownloadError::Offline(_) => Err(Error::from(error)),
+                                              _ => Ok(None),
+                                          }
+                                      })))
                 }
             }
             Ok(())
-        ).buffer_unordered(32).filter_map(|x| x)
+        ).buffer_unordered(config.max_connections).filter_map(|x| x)
     )
 }
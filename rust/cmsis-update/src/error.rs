@@ -0,0 +1,271 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use failure::{Error, Fail};
+use hyper;
+
+use pack_index::config::{RetryOn, TrustRoots};
+
+use watchdog::StalledDownload;
+
+/// Why a download failed, coarse enough for a frontend to suggest a fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadError {
+    /// The host name could not be resolved
+    DnsFailure(String),
+    /// The TLS handshake failed, or the certificate was rejected
+    TlsError(String),
+    /// The server answered with a status other than success
+    HttpStatus(u16),
+    /// No data was received for too long
+    Timeout,
+    /// The file was not downloaded before, and the network may not be used
+    Offline(String),
+    /// The connection failed, or the transfer was cut short or garbled
+    Network(String),
+    /// Any other error, such as the pack store being full or read-only
+    Io(String),
+}
+
+impl DownloadError {
+    /// What the user may try to resolve the failure.
+    pub fn hint(&self) -> &'static str {
+        match *self {
+            DownloadError::DnsFailure(_) => "check your network connection and proxy settings",
+            DownloadError::TlsError(_) => {
+                "check the system clock and any proxy intercepting HTTPS traffic"
+            }
+            DownloadError::HttpStatus(code) if code >= 500 => {
+                "the server may be down; try again later"
+            }
+            DownloadError::HttpStatus(_) => {
+                "the pack index may refer to a file that no longer exists"
+            }
+            DownloadError::Timeout => "the connection is too slow or was interrupted; try again",
+            DownloadError::Offline(_) => "download it while online before working offline",
+            DownloadError::Network(_) => "check your network connection and try again",
+            DownloadError::Io(_) => "check that the pack store is writable and has free space",
+        }
+    }
+
+    /// Which failures of the retry policy this is, if a later attempt may
+    /// succeed.
+    pub fn retry_class(&self) -> Option<RetryOn> {
+        match *self {
+            DownloadError::HttpStatus(code) if code >= 500 || code == 429 => {
+                Some(RetryOn::ServerError)
+            }
+            DownloadError::Timeout => Some(RetryOn::Timeout),
+            DownloadError::DnsFailure(_) | DownloadError::Network(_) => Some(RetryOn::Connection),
+            _ => None,
+        }
+    }
+
+    /// Sort an error from a download into one of the categories above.
+    pub fn classify(e: &Error) -> DownloadError {
+        if let Some(known) = e.downcast_ref::<DownloadError>() {
+            return known.clone();
+        }
+        if let Some(no_roots) = e.downcast_ref::<NoTrustRoots>() {
+            return DownloadError::TlsError(no_roots.to_string());
+        }
+        if e.downcast_ref::<StalledDownload>().is_some() {
+            return DownloadError::Timeout;
+        }
+        // hyper only fails on the connection, while a bare I/O error comes
+        // from the file being written, unless its kind tells otherwise
+        match e.downcast_ref::<hyper::Error>() {
+            Some(&hyper::Error::Timeout) => DownloadError::Timeout,
+            Some(&hyper::Error::Io(ref io)) => from_io(io, true),
+            Some(other) => DownloadError::Network(other.to_string()),
+            None => match e.downcast_ref::<io::Error>() {
+                Some(io) => from_io(io, false),
+                None => DownloadError::Io(e.to_string()),
+            },
+        }
+    }
+}
+
+fn from_io(e: &io::Error, network: bool) -> DownloadError {
+    if let Some(inner) = e.get_ref() {
+        if let Some(ResolveFailed(msg)) = inner.downcast_ref::<ResolveFailed>().cloned() {
+            return DownloadError::DnsFailure(msg);
+        }
+        if let Some(TlsFailed(msg)) = inner.downcast_ref::<TlsFailed>().cloned() {
+            return DownloadError::TlsError(msg);
+        }
+        if let Some(OfflineRefused(host)) = inner.downcast_ref::<OfflineRefused>().cloned() {
+            return DownloadError::Offline(host);
+        }
+    }
+    match e.kind() {
+        io::ErrorKind::TimedOut => DownloadError::Timeout,
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof => DownloadError::Network(e.to_string()),
+        _ if network => DownloadError::Network(e.to_string()),
+        _ => DownloadError::Io(e.to_string()),
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DownloadError::DnsFailure(ref msg) => write!(f, "could not resolve host: {}", msg)?,
+            DownloadError::TlsError(ref msg) => write!(f, "TLS error: {}", msg)?,
+            DownloadError::HttpStatus(code) => write!(f, "server responded with status {}", code)?,
+            DownloadError::Timeout => write!(f, "timed out")?,
+            DownloadError::Offline(ref what) => write!(f, "{} is not available offline", what)?,
+            DownloadError::Network(ref msg) => write!(f, "{}", msg)?,
+            DownloadError::Io(ref msg) => write!(f, "{}", msg)?,
+        }
+        write!(f, " ({})", self.hint())
+    }
+}
+
+impl Fail for DownloadError {}
+
+/// No certificate could be trusted for HTTPS connections with the given
+/// source of trust roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoTrustRoots(pub TrustRoots);
+
+impl fmt::Display for NoTrustRoots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "no trusted certificate found with {:?} trust roots; install the CA \
+             certificates of your system or point SSL_CERT_FILE at a bundle",
+            self.0
+        )
+    }
+}
+
+impl Fail for NoTrustRoots {}
+
+// Markers carried inside the `io::Error`s of the connector, so that the
+// cause survives hyper wrapping them.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolveFailed(pub(crate) String);
+
+#[derive(Debug, Clone)]
+pub(crate) struct TlsFailed(pub(crate) String);
+
+#[derive(Debug, Clone)]
+pub(crate) struct OfflineRefused(pub(crate) String);
+
+impl fmt::Display for ResolveFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for TlsFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for OfflineRefused {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not connecting to {} while offline", self.0)
+    }
+}
+
+impl StdError for ResolveFailed {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl StdError for TlsFailed {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl StdError for OfflineRefused {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ResolveFailed {
+    pub(crate) fn wrap(host: &str, e: io::Error) -> io::Error {
+        io::Error::new(e.kind(), ResolveFailed(format!("{}: {}", host, e)))
+    }
+}
+
+impl TlsFailed {
+    pub(crate) fn wrap(e: io::Error) -> io::Error {
+        io::Error::new(e.kind(), TlsFailed(e.to_string()))
+    }
+}
+
+impl OfflineRefused {
+    pub(crate) fn new(host: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotConnected,
+            OfflineRefused(host.to_string()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn io_error(kind: io::ErrorKind) -> io::Error {
+        io::Error::new(kind, "test")
+    }
+
+    #[test]
+    fn retry_only_what_may_succeed_later() {
+        let retry = |e: DownloadError| e.retry_class();
+        assert_eq!(retry(DownloadError::HttpStatus(503)), Some(RetryOn::ServerError));
+        assert_eq!(retry(DownloadError::HttpStatus(429)), Some(RetryOn::ServerError));
+        assert_eq!(retry(DownloadError::HttpStatus(404)), None);
+        assert_eq!(retry(DownloadError::Timeout), Some(RetryOn::Timeout));
+        assert_eq!(retry(DownloadError::DnsFailure(String::new())), Some(RetryOn::Connection));
+        assert_eq!(retry(DownloadError::Network(String::new())), Some(RetryOn::Connection));
+        assert_eq!(retry(DownloadError::TlsError(String::new())), None);
+        assert_eq!(retry(DownloadError::Offline(String::new())), None);
+        assert_eq!(retry(DownloadError::Io(String::new())), None);
+    }
+
+    #[test]
+    fn classify_connection_and_file_errors() {
+        let classify = |e: Error| DownloadError::classify(&e);
+        let from_hyper = |e: io::Error| classify(Error::from(hyper::Error::Io(e)));
+        match from_hyper(io_error(io::ErrorKind::Other)) {
+            DownloadError::Network(_) => (),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(from_hyper(io_error(io::ErrorKind::TimedOut)), DownloadError::Timeout);
+        assert_eq!(
+            from_hyper(OfflineRefused::new("www.keil.com")),
+            DownloadError::Offline(String::from("www.keil.com"))
+        );
+        assert_eq!(classify(Error::from(hyper::Error::Timeout)), DownloadError::Timeout);
+        match classify(Error::from(hyper::Error::Incomplete)) {
+            DownloadError::Network(_) => (),
+            other => panic!("{:?}", other),
+        }
+
+        // Without hyper, the error comes from the file system
+        match classify(Error::from(io_error(io::ErrorKind::PermissionDenied))) {
+            DownloadError::Io(_) => (),
+            other => panic!("{:?}", other),
+        }
+        match classify(Error::from(io_error(io::ErrorKind::ConnectionReset))) {
+            DownloadError::Network(_) => (),
+            other => panic!("{:?}", other),
+        }
+        let known = DownloadError::HttpStatus(404);
+        assert_eq!(classify(Error::from(known.clone())), known);
+    }
+}
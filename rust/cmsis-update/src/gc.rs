@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use std::fs::remove_file;
+use std::path::PathBuf;
+
+use failure::{err_msg, Error};
+use slog::Logger;
+
+use pack_index::config::Config;
+
+use blobs::prune_blobs;
+use install::{installed_packs, pack_archive};
+use integrity::{pack_archives, remove_checksum};
+use revalidate::remove_validators;
+use vidx::cached_pdsc_refs;
+
+/// What a garbage collection of the pack store removed.
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    pub removed: Vec<PathBuf>,
+    /// Bytes the removed files took
+    pub reclaimed: u64,
+}
+
+// Both forms, so that descriptions cached before a change of
+// `compress_pdsc` are kept too
+fn pdsc_paths(config: &Config, vendor: &str, name: &str, version: &str) -> Vec<PathBuf> {
+    let path = config.pdsc_path(vendor, name, version);
+    let other = if config.compress_pdsc {
+        path.with_extension("")
+    } else {
+        path.with_file_name(format!("{}.{}.{}.pdsc.gz", vendor, name, version))
+    };
+    vec![path, other]
+}
+
+/// Remove the descriptions and the pack archives that the cached vendor
+/// indexes no longer reference, then the stored content no archive links to.
+/// Descriptions and archives of extracted packs are kept whatever the
+/// indexes say.
+pub fn gc(config: &Config, l: &Logger) -> Result<GcReport, Error> {
+    let refs = cached_pdsc_refs(config, l);
+    if refs.is_empty() {
+        // Everything would look orphaned
+        return Err(err_msg(
+            "no vendor index is cached, update before collecting garbage",
+        ));
+    }
+    let mut keep_pdsc = HashSet::new();
+    let mut keep_pack = HashSet::new();
+    for pdsc in &refs {
+        keep_pdsc.extend(pdsc_paths(config, &pdsc.vendor, &pdsc.name, &pdsc.version));
+        keep_pack.insert(pack_archive(config, &pdsc.vendor, &pdsc.name, &pdsc.version));
+    }
+    for pack in installed_packs(config) {
+        keep_pdsc.extend(pdsc_paths(config, &pack.vendor, &pack.name, &pack.version));
+        keep_pack.insert(pack_archive(config, &pack.vendor, &pack.name, &pack.version));
+    }
+    let orphans = config
+        .pdsc_files()
+        .into_iter()
+        .filter(|path| !keep_pdsc.contains(path))
+        .chain(
+            pack_archives(config)
+                .into_iter()
+                .map(|(_, path)| path)
+                .filter(|path| !keep_pack.contains(path)),
+        );
+    let mut report = GcReport::default();
+    for path in orphans {
+        let size = path.metadata().map(|meta| meta.len()).unwrap_or(0);
+        match remove_file(&path) {
+            Ok(()) => {
+                debug!(l, "Removed {:?}", path);
+                remove_checksum(&path);
+                remove_validators(&path);
+                report.reclaimed += size;
+                report.removed.push(path);
+            }
+            Err(e) => warn!(l, "Could not remove {:?}: {}", path, e),
+        }
+    }
+    let (blobs, reclaimed) = prune_blobs(config, l)?;
+    report.removed.extend(blobs);
+    report.reclaimed += reclaimed;
+    Ok(report)
+}
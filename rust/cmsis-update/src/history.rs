@@ -0,0 +1,90 @@
+use std::fs::{remove_file, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use slog::Logger;
+use toml;
+
+use pack_index::config::Config;
+
+const HISTORY_FILE: &str = "update_history.toml";
+
+/// One index update: when it happened, which vendor indexes it read, and the
+/// descriptions it added to the pack store.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// Seconds since the epoch
+    pub time: u64,
+    pub sources: Vec<String>,
+    pub added: Vec<PathBuf>,
+}
+
+/// The updates of the pack store, oldest first.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct History {
+    #[serde(default)]
+    pub update: Vec<HistoryEntry>,
+}
+
+impl History {
+    fn path(config: &Config) -> PathBuf {
+        config.vidx_list.with_file_name(HISTORY_FILE)
+    }
+
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        let mut content = String::new();
+        match OpenOptions::new().read(true).open(Self::path(config)) {
+            Ok(mut fd) => fd.read_to_string(&mut content)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(toml::from_str(&content)?)
+    }
+
+    fn save(&self, config: &Config) -> Result<(), Error> {
+        let content = toml::to_string(self)?;
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(Self::path(config))?;
+        fd.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Append an update that read `sources` and added the descriptions `added`.
+    pub fn record(config: &Config, sources: Vec<String>, added: Vec<PathBuf>) -> Result<(), Error> {
+        let mut history = Self::load(config)?;
+        history.update.push(HistoryEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            sources,
+            added,
+        });
+        history.save(config)
+    }
+}
+
+/// Undo the latest recorded update by removing the descriptions it added,
+/// bringing the index back to the state before it. Returns the update that
+/// was undone, if there was any.
+pub fn rollback(config: &Config, l: &Logger) -> Result<Option<HistoryEntry>, Error> {
+    let mut history = History::load(config)?;
+    let last = match history.update.pop() {
+        Some(last) => last,
+        None => return Ok(None),
+    };
+    for path in &last.added {
+        match remove_file(path) {
+            Ok(()) => debug!(l, "Removed {:?}", path),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    history.save(config)?;
+    Ok(Some(last))
+}
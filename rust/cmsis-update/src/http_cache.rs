@@ -0,0 +1,211 @@
+use std::fs::{copy, create_dir_all, hard_link, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use hyper::header::{CacheControl, CacheDirective, Headers};
+use hyper::Uri;
+use sha2::{Digest, Sha256};
+use toml;
+
+use error::DownloadError;
+use temp::TempDir;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// How long a response may be reused for, from its Cache-Control and Age
+/// headers. Responses that must be revalidated before each use are not kept.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Freshness {
+    initial_age: u64,
+    max_age: u64,
+}
+
+impl Freshness {
+    pub(crate) fn from_headers(headers: &Headers) -> Option<Self> {
+        let mut max_age = None;
+        for directive in headers.get::<CacheControl>()?.iter() {
+            match *directive {
+                CacheDirective::NoStore | CacheDirective::NoCache => return None,
+                CacheDirective::MaxAge(secs) => max_age = Some(u64::from(secs)),
+                _ => (),
+            }
+        }
+        let initial_age = headers
+            .get_raw("Age")
+            .and_then(|raw| raw.one())
+            .and_then(|age| str::from_utf8(age).ok())
+            .and_then(|age| age.trim().parse().ok())
+            .unwrap_or(0);
+        match max_age {
+            Some(max_age) if max_age > initial_age => Some(Freshness {
+                initial_age,
+                max_age,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    url: String,
+    /// Seconds since the epoch at which the response was received
+    received: u64,
+    initial_age: u64,
+    max_age: u64,
+}
+
+impl Entry {
+    fn is_fresh(&self) -> bool {
+        let resident = now().saturating_sub(self.received);
+        self.initial_age + resident < self.max_age
+    }
+}
+
+// Packs are only ever replaced by renaming a new file over them, never
+// written in place, so the cache and the pack store may share one copy of a
+// body. Linking keeps a large pack from taking twice its size on disk.
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    hard_link(from, to).or_else(|_| copy(from, to).map(|_| ()))
+}
+
+/// A directory of downloaded response bodies, keyed by the url they were
+/// requested from, each with the metadata needed to tell if it is still fresh.
+pub(crate) struct HttpCache {
+    root: PathBuf,
+}
+
+impl HttpCache {
+    pub(crate) fn new<P: Into<PathBuf>>(root: P) -> Self {
+        HttpCache { root: root.into() }
+    }
+
+    fn paths(&self, uri: &Uri) -> (PathBuf, PathBuf) {
+        let mut hasher = Sha256::default();
+        hasher.input(uri.to_string().as_bytes());
+        let key = format!("{:x}", hasher.result());
+        (
+            self.root.join(format!("{}.toml", key)),
+            self.root.join(format!("{}.body", key)),
+        )
+    }
+
+    /// The cached body of `uri`, if there is one that is still fresh or
+    /// `stale` ones are accepted.
+    pub(crate) fn lookup(&self, uri: &Uri, stale: bool) -> Option<PathBuf> {
+        let (meta, body) = self.paths(uri);
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(&meta)
+            .and_then(|mut fd| fd.read_to_string(&mut content))
+            .ok()?;
+        let entry: Entry = toml::from_str(&content).ok()?;
+        if entry.url == uri.to_string() && (stale || entry.is_fresh()) && body.exists() {
+            Some(body)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn store(&self, uri: &Uri, freshness: Freshness, from: &Path) -> Result<(), Error> {
+        create_dir_all(&self.root)?;
+        let (meta, body) = self.paths(uri);
+        let temp_dir = TempDir::new_in(&self.root)?;
+        link_or_copy(from, &temp_dir.join("body"))?;
+        temp_dir.persist("body", &body)?;
+        let entry = Entry {
+            url: uri.to_string(),
+            received: now(),
+            initial_age: freshness.initial_age,
+            max_age: freshness.max_age,
+        };
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&meta)?;
+        fd.write_all(toml::to_string(&entry)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The cached body of `uri` to use instead of downloading it, if any. Offline,
+/// a stale copy is better than none, and without a copy there is nothing to
+/// use.
+pub(crate) fn cached_copy(
+    cache: Option<&HttpCache>,
+    uri: &Uri,
+    offline: bool,
+) -> Result<Option<PathBuf>, DownloadError> {
+    match cache.and_then(|cache| cache.lookup(uri, offline)) {
+        Some(cached) => Ok(Some(cached)),
+        None if offline => Err(DownloadError::Offline(uri.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Link or copy a cached body to `dest`, so that `dest` never holds a partial
+/// file.
+pub(crate) fn restore(cached: &Path, dest: &Path) -> Result<(), Error> {
+    let temp_dir = TempDir::new_in(dest.parent().unwrap_or_else(|| Path::new(".")))?;
+    link_or_copy(cached, &temp_dir.join("body"))?;
+    temp_dir.persist("body", dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    fn cache_body(cache: &HttpCache, uri: &Uri, max_age: u64) {
+        let temp = tempdir().unwrap();
+        let body = temp.path().join("body");
+        File::create(&body).unwrap().write_all(b"<index/>").unwrap();
+        let freshness = Freshness {
+            initial_age: 0,
+            max_age,
+        };
+        cache.store(uri, freshness, &body).unwrap();
+    }
+
+    #[test]
+    fn use_stale_copies_only_offline() {
+        let temp = tempdir().unwrap();
+        let cache = HttpCache::new(temp.path());
+        let (fresh, stale, missing): (Uri, Uri, Uri) = (
+            "https://www.keil.com/fresh.pdsc".parse().unwrap(),
+            "https://www.keil.com/stale.pdsc".parse().unwrap(),
+            "https://www.keil.com/missing.pdsc".parse().unwrap(),
+        );
+        cache_body(&cache, &fresh, 3600);
+        cache_body(&cache, &stale, 0);
+
+        assert!(cached_copy(Some(&cache), &fresh, false).unwrap().is_some());
+        assert!(cached_copy(Some(&cache), &stale, false).unwrap().is_none());
+        assert!(cached_copy(Some(&cache), &missing, false).unwrap().is_none());
+        assert!(cached_copy(None, &fresh, false).unwrap().is_none());
+
+        assert!(cached_copy(Some(&cache), &fresh, true).unwrap().is_some());
+        assert!(cached_copy(Some(&cache), &stale, true).unwrap().is_some());
+        assert_eq!(
+            cached_copy(Some(&cache), &missing, true),
+            Err(DownloadError::Offline(missing.to_string()))
+        );
+        assert!(cached_copy(None, &fresh, true).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use std::fs::{create_dir_all, remove_dir_all, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::{err_msg, Error};
+use slog::Logger;
+use toml;
+use zip::ZipArchive;
+
+use pack_index::config::{Config, PackId};
+use pdsc::FlashDevice;
+use utils::natural::natural_cmp;
+
+use integrity::{children, lossy, recorded_checksum};
+use temp::TempDir;
+
+// Written last into an extracted pack, so that a directory without it is
+// not a complete installation
+const INSTALLED_MARKER: &str = ".installed.toml";
+
+/// A pack extracted into the pack store.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InstalledPack {
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+    /// The SHA-256 of the archive the pack was extracted from
+    pub sha256: Option<String>,
+    /// When the pack was extracted, in seconds since the epoch
+    pub time: u64,
+    /// The directory the pack was extracted into
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl InstalledPack {
+    pub fn id(&self) -> PackId {
+        PackId {
+            vendor: self.vendor.clone(),
+            name: self.name.clone(),
+            version: self.version.clone(),
+        }
+    }
+
+    fn load(dir: &Path) -> Result<Self, Error> {
+        let mut content = String::new();
+        File::open(dir.join(INSTALLED_MARKER))?.read_to_string(&mut content)?;
+        Ok(toml::from_str(&content)?)
+    }
+}
+
+/// `part` of the id of a pack, when it names a single directory entry
+/// rather than a path that could lead out of the pack store.
+pub(crate) fn plain_name<'a>(what: &str, part: &'a str) -> Result<&'a str, Error> {
+    let mut components = Path::new(part).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if *name == *part && !part.contains('\\') => {
+            Ok(part)
+        }
+        _ => Err(err_msg(format!("{} {:?} is not a plain file name", what, part))),
+    }
+}
+
+/// The directory a pack version is extracted into: `Vendor/Name/version`
+/// within the pack store.
+pub fn pack_dir(
+    config: &Config,
+    vendor: &str,
+    name: &str,
+    version: &str,
+) -> Result<PathBuf, Error> {
+    let mut dir = config.pack_store.clone();
+    dir.push(plain_name("vendor", vendor)?);
+    dir.push(plain_name("name", name)?);
+    dir.push(plain_name("version", version)?);
+    Ok(dir)
+}
+
+/// Where the archive of a pack version is downloaded: `Vendor/Name/version.pack`
+/// within the pack store.
+pub fn pack_archive(config: &Config, vendor: &str, name: &str, version: &str) -> PathBuf {
+    let mut path = config.pack_store.clone();
+    path.push(vendor);
+    path.push(name);
+    path.push(format!("{}.pack", version));
+    path
+}
+
+/// Extract the archive of a pack into its directory in the pack store,
+/// replacing any previous extraction of the same version.
+pub fn extract_pack(
+    config: &Config,
+    id: &PackId,
+    archive: &Path,
+    l: &Logger,
+) -> Result<InstalledPack, Error> {
+    let dest = pack_dir(config, &id.vendor, &id.name, &id.version)?;
+    let parent = dest
+        .parent()
+        .ok_or_else(|| err_msg(format!("{:?} has no parent directory", dest)))?;
+    let temp = TempDir::new_in(parent)?;
+    let root = temp.join("pack");
+    create_dir_all(&root)?;
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        // Entries naming a path out of the archive root are sanitized back in
+        let path = root.join(entry.sanitized_name());
+        if entry.name().ends_with('/') {
+            create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+    }
+    let installed = InstalledPack {
+        vendor: id.vendor.clone(),
+        name: id.name.clone(),
+        version: id.version.clone(),
+        sha256: recorded_checksum(archive),
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        path: dest.clone(),
+    };
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(root.join(INSTALLED_MARKER))?
+        .write_all(toml::to_string(&installed)?.as_bytes())?;
+    if dest.exists() {
+        remove_dir_all(&dest)?;
+    }
+    temp.persist("pack", &dest)?;
+    debug!(l, "Extracted {:?} into {:?}", archive, dest);
+    Ok(installed)
+}
+
+/// Whether `dir` holds a complete extraction of a pack.
+pub(crate) fn is_extracted(dir: &Path) -> bool {
+    dir.join(INSTALLED_MARKER).is_file()
+}
+
+/// Remove the extraction of a pack version, if there is one.
+pub fn remove_extracted(config: &Config, id: &PackId) -> Result<bool, Error> {
+    let dir = pack_dir(config, &id.vendor, &id.name, &id.version)?;
+    if !dir.join(INSTALLED_MARKER).exists() {
+        return Ok(false);
+    }
+    remove_dir_all(&dir)?;
+    Ok(true)
+}
+
+/// Where a file named by the description of an installed pack, such as the
+/// `file_name` of a flash algorithm, was extracted to. Descriptions may
+/// separate directories with backslashes.
+pub fn installed_file(config: &Config, id: &PackId, file: &Path) -> Result<PathBuf, Error> {
+    let dir = pack_dir(config, &id.vendor, &id.name, &id.version)?;
+    if !dir.join(INSTALLED_MARKER).is_file() {
+        return Err(err_msg(format!(
+            "{}.{} {} is not installed",
+            id.vendor, id.name, id.version
+        )));
+    }
+    let name = file.to_string_lossy();
+    let parts: Vec<&str> = name
+        .split(|c: char| c == '/' || c == '\\')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.contains(&"..") {
+        return Err(err_msg(format!("{} is out of the pack", name)));
+    }
+    let path = parts.into_iter().fold(dir, |path, part| path.join(part));
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(err_msg(format!(
+            "{} is not in {}.{} {}",
+            name, id.vendor, id.name, id.version
+        )))
+    }
+}
+
+/// The flash programmed by the algorithm `file` of an installed pack: its
+/// sectors, page size and timeouts.
+pub fn flash_device(config: &Config, id: &PackId, file: &Path) -> Result<FlashDevice, Error> {
+    FlashDevice::from_path(&installed_file(config, id, file)?)
+}
+
+/// Every pack extracted into the pack store, by vendor, name and version.
+pub fn installed_packs(config: &Config) -> Vec<InstalledPack> {
+    let mut packs = Vec::new();
+    for vendor in children(&config.pack_store).into_iter().filter(|p| p.is_dir()) {
+        for name in children(&vendor).into_iter().filter(|p| p.is_dir()) {
+            for dir in children(&name) {
+                if !dir.join(INSTALLED_MARKER).is_file() {
+                    continue;
+                }
+                // A pack moved by hand is named by where it is now
+                let recorded = InstalledPack::load(&dir).ok();
+                packs.push(InstalledPack {
+                    vendor: lossy(vendor.file_name()),
+                    name: lossy(name.file_name()),
+                    version: lossy(dir.file_name()),
+                    sha256: recorded.as_ref().and_then(|rec| rec.sha256.clone()),
+                    time: recorded.map(|rec| rec.time).unwrap_or(0),
+                    path: dir,
+                });
+            }
+        }
+    }
+    packs.sort_by(|a, b| {
+        natural_cmp(&a.vendor, &b.vendor)
+            .then_with(|| natural_cmp(&a.name, &b.name))
+            .then_with(|| natural_cmp(&a.version, &b.version))
+    });
+    packs
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_names_only() {
+        for good in &["ARM", "CMSIS", "5.4.0", "1.0.0-rc.1"] {
+            assert_eq!(plain_name("version", good).unwrap(), *good);
+        }
+        for bad in &["", ".", "..", "../ARM", "ARM/..", "ARM/", "/ARM", "..\\ARM", "a/b"] {
+            assert!(plain_name("vendor", bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{remove_file, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use slog::Logger;
+
+use pack_index::config::{Config, PackId};
+use utils::natural::natural_cmp;
+
+use project::sha256_file;
+use resume::looks_like_zip;
+use vidx::cached_pdsc_refs;
+
+const CHECKSUM_SUFFIX: &str = ".sha256";
+
+pub(crate) fn lossy(name: Option<&OsStr>) -> String {
+    name.map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn checksum_path(pack: &Path) -> PathBuf {
+    pack.with_file_name(format!("{}{}", lossy(pack.file_name()), CHECKSUM_SUFFIX))
+}
+
+/// Record the SHA-256 of a pack next to it, in the format of `sha256sum`,
+/// and return it.
+pub fn record_checksum(pack: &Path) -> Result<String, Error> {
+    let sha256 = sha256_file(pack)?;
+    let name = lossy(pack.file_name());
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(checksum_path(pack))?
+        .write_all(format!("{}  {}\n", sha256, name).as_bytes())?;
+    Ok(sha256)
+}
+
+/// The SHA-256 recorded when the pack was installed.
+pub fn recorded_checksum(pack: &Path) -> Option<String> {
+    let mut content = String::new();
+    OpenOptions::new()
+        .read(true)
+        .open(checksum_path(pack))
+        .and_then(|mut fd| fd.read_to_string(&mut content))
+        .ok()?;
+    content.split_whitespace().next().map(String::from)
+}
+
+pub(crate) fn remove_checksum(pack: &Path) {
+    let _ = remove_file(checksum_path(pack));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// The pack is not a zip archive
+    NotAnArchive,
+    /// The pack is not the size its index advertises
+    Size { advertised: u64, found: u64 },
+    /// The pack changed since its checksum was recorded
+    Checksum { recorded: String, found: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct CorruptPack {
+    pub id: PackId,
+    pub path: PathBuf,
+    pub corruption: Corruption,
+}
+
+impl fmt::Display for CorruptPack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{} {} ", self.id.vendor, self.id.name, self.id.version)?;
+        match self.corruption {
+            Corruption::NotAnArchive => write!(f, "is not a zip archive"),
+            Corruption::Size { advertised, found } => write!(
+                f,
+                "is {} bytes but its index advertises {}",
+                found, advertised
+            ),
+            Corruption::Checksum {
+                ref recorded,
+                ref found,
+            } => write!(
+                f,
+                "has checksum {} but {} was recorded at install",
+                found, recorded
+            ),
+        }
+    }
+}
+
+// Pack sizes given in bytes by the cached vendor indexes. Sizes given in
+// other units are too rough to check against.
+pub(crate) fn advertised_sizes(
+    config: &Config,
+    l: &Logger,
+) -> HashMap<(String, String, String), u64> {
+    cached_pdsc_refs(config, l)
+        .into_iter()
+        .filter_map(|pdsc| {
+            let size = pdsc.size.as_ref()?.trim().parse().ok()?;
+            let key = (
+                String::from(&*pdsc.vendor),
+                String::from(&*pdsc.name),
+                String::from(&*pdsc.version),
+            );
+            Some((key, size))
+        }).collect()
+}
+
+fn check(path: &Path, advertised: Option<u64>) -> Result<Option<Corruption>, Error> {
+    if !looks_like_zip(path)? {
+        return Ok(Some(Corruption::NotAnArchive));
+    }
+    let found = path.metadata()?.len();
+    match advertised {
+        Some(advertised) if advertised != found => {
+            Ok(Some(Corruption::Size { advertised, found }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Check a freshly downloaded pack and record its checksum.
+pub(crate) fn check_download(
+    id: &PackId,
+    path: &Path,
+    advertised: Option<u64>,
+) -> Result<Option<CorruptPack>, Error> {
+    if let Some(corruption) = check(path, advertised)? {
+        return Ok(Some(CorruptPack {
+            id: id.clone(),
+            path: path.to_path_buf(),
+            corruption,
+        }));
+    }
+    record_checksum(path)?;
+    Ok(None)
+}
+
+/// Check every pack in the pack store against the index and against the
+/// checksum recorded when it was installed. Packs installed before checksums
+/// were recorded get one now.
+pub fn verify_packs(config: &Config, l: &Logger) -> Result<Vec<CorruptPack>, Error> {
+    let sizes = advertised_sizes(config, l);
+    let mut corrupt = Vec::new();
+    for (id, path) in pack_archives(config) {
+        let key = (id.vendor.clone(), id.name.clone(), id.version.clone());
+        let corruption = match check(&path, sizes.get(&key).cloned())? {
+            Some(corruption) => Some(corruption),
+            None => {
+                let found = sha256_file(&path)?;
+                match recorded_checksum(&path) {
+                    Some(recorded) if recorded != found => {
+                        Some(Corruption::Checksum { recorded, found })
+                    }
+                    Some(_) => None,
+                    None => {
+                        debug!(l, "Recording the checksum of {:?}", path);
+                        record_checksum(&path)?;
+                        None
+                    }
+                }
+            }
+        };
+        if let Some(corruption) = corruption {
+            corrupt.push(CorruptPack {
+                id,
+                path,
+                corruption,
+            });
+        }
+    }
+    Ok(corrupt)
+}
+
+pub(crate) fn children(dir: &Path) -> Vec<PathBuf> {
+    dir.read_dir()
+        .into_iter()
+        .flat_map(|entries| entries.filter_map(Result::ok))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+// Every pack archive of the store, laid out as `Vendor/Name/version.pack`
+pub(crate) fn pack_archives(config: &Config) -> Vec<(PackId, PathBuf)> {
+    let mut packs = Vec::new();
+    for vendor in children(&config.pack_store).into_iter().filter(|p| p.is_dir()) {
+        for name in children(&vendor).into_iter().filter(|p| p.is_dir()) {
+            for path in children(&name) {
+                if path.extension().map(|ext| ext != "pack").unwrap_or(true) {
+                    continue;
+                }
+                let id = PackId {
+                    vendor: lossy(vendor.file_name()),
+                    name: lossy(name.file_name()),
+                    version: lossy(path.file_stem()),
+                };
+                packs.push((id, path));
+            }
+        }
+    }
+    packs.sort_by(|a, b| {
+        natural_cmp(&a.0.vendor, &b.0.vendor)
+            .then_with(|| natural_cmp(&a.0.name, &b.0.name))
+            .then_with(|| natural_cmp(&a.0.version, &b.0.version))
+    });
+    packs
+}
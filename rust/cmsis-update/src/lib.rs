@@ -1,12 +1,37 @@
 #![feature(generators, libc, proc_macro_non_items, use_extern_macros)]
 
+extern crate base64;
 extern crate clap;
 extern crate failure;
+extern crate flate2;
 extern crate futures_await as futures;
+extern crate futures_cpupool;
 extern crate hyper;
-extern crate hyper_rustls;
 extern crate minidom;
+#[cfg(feature = "native")]
+extern crate native_tls;
+extern crate rustls;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate sha2;
+#[cfg(test)]
+extern crate tempfile;
 extern crate tokio_core;
+extern crate tokio_io;
+extern crate tokio_rustls;
+#[cfg(feature = "native")]
+extern crate tokio_tls;
+extern crate toml;
+extern crate untrusted;
+extern crate webpki;
+extern crate webpki_roots;
+extern crate zip;
+
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate schannel;
 
 #[macro_use]
 extern crate slog;
@@ -16,42 +41,92 @@ extern crate pbr;
 extern crate pdsc;
 extern crate utils;
 
+use std::collections::HashSet;
+use std::fs::remove_file;
+use std::io::Stdout;
 use std::sync::Mutex;
 
 use clap::{App, Arg, ArgMatches, SubCommand};
-use failure::Error;
+use failure::{err_msg, Error};
 use hyper::client::Connect;
 use hyper::{Body, Client};
-use hyper_rustls::HttpsConnector;
 use pbr::ProgressBar;
 use slog::Logger;
 use std::iter::Iterator;
 use std::path::{Path, PathBuf};
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Handle};
 
-use pack_index::config::Config;
+use pack_index::config::{Config, InstallEvent, PackId};
 use pdsc::Package;
+use utils::natural::natural_cmp;
 use utils::parse::FromElem;
+use utils::span::Span;
 
+mod blobs;
+mod connect;
+mod context;
 mod dl_pack;
 mod dl_pdsc;
 mod download;
+mod error;
+mod gc;
+pub mod history;
+mod http_cache;
+pub mod install;
+mod integrity;
+mod pinning;
+pub mod plan;
+mod progress;
+pub mod project;
 mod redirect;
+mod resume;
+mod revalidate;
+mod roots;
+mod signature;
+pub mod snapshot;
+mod temp;
+mod tool;
 pub mod upgrade;
 mod vidx;
+mod watchdog;
 
 use dl_pack::install_future;
 use dl_pdsc::update_future;
-use download::DownloadProgress;
+use history::rollback;
+use install::{is_extracted, pack_archive, pack_dir, plain_name, remove_extracted};
+use integrity::{lossy, remove_checksum};
+use plan::{PendingUpdates, PlanReason, UpdatePlan};
+use project::{
+    install_project, lock_project, verify, Lockfile, Manifest, LOCKFILE_NAME, MANIFEST_NAME,
+};
+use snapshot::Snapshot;
+use vidx::cached_pdsc_refs;
+pub use blobs::{disk_usage, PackUsage};
+pub use context::{Context, DefaultTransport, Transport};
+pub use dl_pack::PackRelease;
+pub use download::DownloadProgress;
+pub use error::{DownloadError, NoTrustRoots};
+pub use gc::{gc, GcReport};
+pub use integrity::{record_checksum, recorded_checksum, verify_packs, CorruptPack, Corruption};
+pub use progress::{
+    AggregateProgress, ProgressCallback, ProgressEvent, ProgressSummary, TransferProgress,
+};
+pub use signature::{pack_signatures, PackSignature, SignatureStatus};
+pub use temp::{sweep_orphans, TempDir};
+pub use tool::{ToolError, ToolOutput, ToolRunner};
+pub use vidx::{flatmap_pdscs, pdsc_refs};
+pub use watchdog::StalledDownload;
 
 // This will "trick" the borrow checker into thinking that the lifetimes for
 // client and core are at least as big as the lifetime for pdscs, which they actually are
 fn update_inner<C, I, P>(
     config: &Config,
     vidx_list: I,
+    pinned: Option<&Snapshot>,
     core: &mut Core,
     client: &Client<C, Body>,
     logger: &Logger,
+    handle: &Handle,
     progress: P,
 ) -> Result<Vec<PathBuf>, Error>
 where
@@ -59,53 +134,250 @@ where
     I: IntoIterator<Item = String>,
     P: DownloadProgress,
 {
-    core.run(update_future(config, vidx_list, client, logger, progress))
+    let _span = Span::enter(logger, "update", config.pack_store.display());
+    core.run(update_future(config, vidx_list, pinned, client, logger, handle, progress))
 }
 
 /// Flatten a list of Vidx Urls into a list of updated CMSIS packs
-pub fn update<I>(config: &Config, vidx_list: I, logger: &Logger) -> Result<Vec<PathBuf>, Error>
+pub fn update<I>(ctx: &Context, vidx_list: I) -> Result<Vec<PathBuf>, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let progress = progress_bar("Downloading Descriptions ");
+    ctx.clone().with_progress(&progress).update(vidx_list, None)
+}
+
+/// Like `update`, reporting to `progress` instead of a progress bar on stdout
+pub fn update_with_progress<I, P>(
+    ctx: &Context,
+    vidx_list: I,
+    progress: P,
+) -> Result<Vec<PathBuf>, Error>
+where
+    I: IntoIterator<Item = String>,
+    P: DownloadProgress + Clone,
+{
+    ctx.clone().with_progress(progress).update(vidx_list, None)
+}
+
+/// Like `update`, but only downloads the pack versions recorded in `snapshot`
+pub fn update_as_of<I>(
+    ctx: &Context,
+    vidx_list: I,
+    snapshot: &Snapshot,
+) -> Result<Vec<PathBuf>, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let progress = progress_bar("Downloading Descriptions ");
+    ctx.clone()
+        .with_progress(&progress)
+        .update(vidx_list, Some(snapshot))
+}
+
+/// Record the pack versions currently listed by the vendor indexes
+pub fn take_snapshot<I>(ctx: &Context, vidx_list: I) -> Result<Snapshot, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    ctx.take_snapshot(vidx_list)
+}
+
+/// List the descriptions `update` would download, without downloading them
+pub fn plan<I>(ctx: &Context, vidx_list: I) -> Result<UpdatePlan, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    ctx.plan(vidx_list, None)
+}
+
+/// Like `plan`, but only plans the pack versions recorded in `snapshot`
+pub fn plan_as_of<I>(ctx: &Context, vidx_list: I, snapshot: &Snapshot) -> Result<UpdatePlan, Error>
 where
     I: IntoIterator<Item = String>,
 {
-    let mut core = Core::new().unwrap();
-    let handle = core.handle();
-    let client: Client<HttpsConnector, _> = Client::configure()
-        .keep_alive(true)
-        .connector(HttpsConnector::new(4, &handle))
-        .build(&handle);
+    ctx.plan(vidx_list, Some(snapshot))
+}
+
+/// Compare the vendor indexes with the descriptions in the store, without
+/// downloading any description
+pub fn check_updates<I>(ctx: &Context, vidx_list: I) -> Result<PendingUpdates, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    ctx.check_updates(vidx_list)
+}
+
+/// Download the descriptions of a plan, in its order
+pub fn execute(ctx: &Context, plan: UpdatePlan) -> Result<Vec<PathBuf>, Error> {
+    execute_with_progress(ctx, plan, &progress_bar("Downloading Descriptions "))
+}
+
+/// Like `execute`, reporting to `progress` instead of a progress bar on stdout
+pub fn execute_with_progress<P>(
+    ctx: &Context,
+    plan: UpdatePlan,
+    progress: P,
+) -> Result<Vec<PathBuf>, Error>
+where
+    P: DownloadProgress + Clone,
+{
+    ctx.clone().with_progress(progress).execute(plan)
+}
+
+fn progress_bar(message: &str) -> Mutex<ProgressBar<Stdout>> {
     let mut progress = ProgressBar::new(363);
     progress.show_speed = false;
     progress.show_time_left = false;
     progress.format("[#> ]");
-    progress.message("Downloading Descriptions ");
-    let progress = Mutex::new(progress);
-    update_inner(config, vidx_list, &mut core, &client, logger, &progress)
+    progress.message(message);
+    Mutex::new(progress)
 }
 
 pub fn update_args<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("update")
         .about("Update CMSIS PDSC files for indexing")
         .version("0.1.0")
+        .arg(
+            Arg::with_name("as-of")
+                .long("as-of")
+                .takes_value(true)
+                .value_name("SNAPSHOT")
+                .help("Only download the pack versions recorded in a snapshot"),
+        ).arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("List the descriptions that would be downloaded, without downloading them"),
+        )
 }
 
-pub fn update_command<'a>(conf: &Config, _: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
-    let vidx_list = conf.read_vidx_list(&logger);
-    for url in &vidx_list {
-        info!(logger, "Updating registry from `{}`", url);
-    }
-    let updated = update(conf, vidx_list, logger)?;
-    let num_updated = updated.iter().map(|_| 1).sum::<u32>();
-    match num_updated {
+fn report_updated(updated: &[PathBuf], logger: &Logger) {
+    match updated.len() {
         0 => {
             info!(logger, "Already up to date");
         }
         1 => {
             info!(logger, "Updated 1 package");
         }
-        _ => {
-            info!(logger, "Updated {} package", num_updated);
+        num_updated => {
+            info!(logger, "Updated {} packages", num_updated);
         }
     }
+}
+
+pub fn update_command<'a>(conf: &Config, args: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    if let Some(old) = conf.moved_pack_root() {
+        warn!(
+            logger,
+            "The pack store moved from {:?} to {:?}; run `migrate` to bring the installed packs along",
+            old,
+            conf.pack_store
+        );
+    }
+    conf.migrate_pdsc_cache(logger)?;
+    let ctx = Context::new(conf, logger);
+    let vidx_list = conf.read_vidx_list(&logger);
+    for url in &vidx_list {
+        info!(logger, "Updating registry from `{}`", url);
+    }
+    if args.is_present("dry-run") {
+        let plan = match args.value_of("as-of") {
+            Some(path) => {
+                let snapshot = Snapshot::from_path(Path::new(path))?;
+                plan_as_of(&ctx, vidx_list, &snapshot)?
+            }
+            None => plan(&ctx, vidx_list)?,
+        };
+        for dl in plan.iter() {
+            match dl.reason {
+                PlanReason::New => println!("{} (new)", dl.url),
+                PlanReason::NewVersion { .. } => println!("{} (new version)", dl.url),
+            }
+        }
+        let (size, unknown) = plan.estimated_size();
+        info!(
+            logger,
+            "Would download {} descriptions, about {} kB with {} of unknown size",
+            plan.len(),
+            size / 1024,
+            unknown
+        );
+        return Ok(());
+    }
+    let updated = match args.value_of("as-of") {
+        Some(path) => {
+            let snapshot = Snapshot::from_path(Path::new(path))?;
+            info!(logger, "Updating as of snapshot `{}`", path);
+            update_as_of(&ctx, vidx_list, &snapshot)?
+        }
+        None => update(&ctx, vidx_list)?,
+    };
+    conf.record_pack_root()?;
+    report_updated(&updated, logger);
+    Ok(())
+}
+
+pub fn snapshot_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("snapshot")
+        .about("Record the pack versions currently listed by the vendor indexes")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("File to write the snapshot to")
+                .required(true)
+                .index(1),
+        )
+}
+
+pub fn snapshot_command<'a>(conf: &Config, args: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    let output = Path::new(args.value_of("OUTPUT").unwrap());
+    let vidx_list = conf.read_vidx_list(&logger);
+    let snapshot = take_snapshot(&Context::new(conf, logger), vidx_list)?;
+    snapshot.write_to(output)?;
+    info!(logger, "Recorded {} packs in {:?}", snapshot.pack.len(), output);
+    Ok(())
+}
+
+pub fn rollback_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("rollback")
+        .about("Undo the latest update of the descriptions")
+        .version("0.1.0")
+}
+
+pub fn rollback_command<'a>(conf: &Config, _: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    match rollback(conf, logger)? {
+        Some(undone) => info!(
+            logger,
+            "Removed {} descriptions added by the update from {}",
+            undone.added.len(),
+            undone.sources.join(", ")
+        ),
+        None => info!(logger, "No update to roll back"),
+    }
+    Ok(())
+}
+
+pub fn migrate_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("migrate")
+        .about("Move installed packs from a previous pack store into the current one")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .takes_value(true)
+                .help("Pack store to migrate from, defaults to the one used last"),
+        )
+}
+
+pub fn migrate_command<'a>(conf: &Config, args: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    let from = match args.value_of("from") {
+        Some(from) => PathBuf::from(from),
+        None => conf
+            .moved_pack_root()
+            .ok_or_else(|| err_msg("No previous pack store found to migrate from"))?,
+    };
+    conf.migrate_pack_root(&from, logger)?;
+    conf.migrate_pdsc_cache(logger)?;
     Ok(())
 }
 
@@ -117,38 +389,39 @@ fn install_inner<'client, 'a: 'client, C, I: 'a, P: 'client>(
     core: &mut Core,
     client: &'client Client<C, Body>,
     logger: &'a Logger,
+    handle: &'client Handle,
     progress: P,
-) -> Result<Vec<PathBuf>, Error>
+) -> Result<Vec<(PackId, PathBuf)>, Error>
 where
     C: Connect,
-    I: IntoIterator<Item = &'a Package>,
+    I: IntoIterator<Item = PackRelease<'a>>,
     P: DownloadProgress,
 {
-    core.run(install_future(config, pdsc_list, client, logger, progress))
+    let _span = Span::enter(logger, "install", config.pack_store.display());
+    core.run(install_future(config, pdsc_list, client, logger, handle, progress))
 }
 
 /// Flatten a list of Vidx Urls into a list of updated CMSIS packs
-pub fn install<'a, I: 'a>(
-    config: &'a Config,
+pub fn install<'a, I: 'a>(ctx: &Context, pdsc_list: I) -> Result<Vec<PathBuf>, Error>
+where
+    I: IntoIterator,
+    I::Item: Into<PackRelease<'a>>,
+{
+    install_with_progress(ctx, pdsc_list, &progress_bar("Downloading Packs "))
+}
+
+/// Like `install`, reporting to `progress` instead of a progress bar on stdout
+pub fn install_with_progress<'a, I: 'a, P>(
+    ctx: &Context,
     pdsc_list: I,
-    logger: &'a Logger,
+    progress: P,
 ) -> Result<Vec<PathBuf>, Error>
 where
-    I: IntoIterator<Item = &'a Package>,
+    I: IntoIterator,
+    I::Item: Into<PackRelease<'a>>,
+    P: DownloadProgress + Clone,
 {
-    let mut core = Core::new().unwrap();
-    let handle = core.handle();
-    let client: Client<HttpsConnector, _> = Client::configure()
-        .keep_alive(true)
-        .connector(HttpsConnector::new(4, &handle))
-        .build(&handle);
-    let mut progress = ProgressBar::new(363);
-    progress.show_speed = false;
-    progress.show_time_left = false;
-    progress.format("[#> ]");
-    progress.message("Downloading Packs ");
-    let progress = Mutex::new(progress);
-    install_inner(config, pdsc_list, &mut core, &client, logger, &progress)
+    ctx.clone().with_progress(progress).install(pdsc_list)
 }
 
 pub fn install_args() -> App<'static, 'static> {
@@ -160,10 +433,26 @@ pub fn install_args() -> App<'static, 'static> {
                 .required(true)
                 .takes_value(true)
                 .index(1)
-                .multiple(true),
+                .multiple(true)
+                .help("Description files, or packs of the cached index as Vendor.Name"),
         )
 }
 
+// The cached description of the pack `Vendor.Name`, when `input` is not a
+// file
+fn cached_pdsc(conf: &Config, input: &str, logger: &Logger) -> Option<PathBuf> {
+    if Path::new(input).exists() {
+        return None;
+    }
+    let mut parts = input.splitn(2, '.');
+    let (vendor, name) = (parts.next()?, parts.next()?);
+    cached_pdsc_refs(conf, logger)
+        .into_iter()
+        .filter(|pdsc| &*pdsc.vendor == vendor && &*pdsc.name == name)
+        .max_by(|a, b| natural_cmp(&a.version, &b.version))
+        .map(|pdsc| conf.pdsc_path(&pdsc.vendor, &pdsc.name, &pdsc.version))
+}
+
 pub fn install_command<'a>(
     conf: &Config,
     args: &ArgMatches<'a>,
@@ -172,20 +461,296 @@ pub fn install_command<'a>(
     let pdsc_list: Vec<_> = args
         .values_of("PDSC")
         .unwrap()
-        .filter_map(|input| Package::from_path(Path::new(input), logger).ok())
-        .collect();
-    let updated = install(conf, pdsc_list.iter(), logger)?;
-    let num_updated = updated.iter().map(|_| 1).sum::<u32>();
-    match num_updated {
-        0 => {
-            info!(logger, "Already up to date");
+        .filter_map(|input| match cached_pdsc(conf, input, logger) {
+            Some(path) => Package::from_path(&path, &conf.parse_options, logger).ok(),
+            None => Package::from_path(Path::new(input), &conf.parse_options, logger).ok(),
+        }).collect();
+    let updated = install(&Context::new(conf, logger), pdsc_list.iter())?;
+    report_updated(&updated, logger);
+    Ok(())
+}
+
+/// Remove one version of a pack from the pack store: its archive, the
+/// checksum recorded for it and its extraction. Returns whether anything
+/// was installed.
+pub fn remove_pack(
+    config: &Config,
+    vendor: &str,
+    name: &str,
+    version: &str,
+    logger: &Logger,
+) -> Result<bool, Error> {
+    let id = PackId {
+        vendor: vendor.to_string(),
+        name: name.to_string(),
+        version: version.to_string(),
+    };
+    // Refuse ids naming anything out of the pack store before touching it
+    pack_dir(config, vendor, name, version)?;
+    let path = pack_archive(config, vendor, name, version);
+    let archived = path.exists();
+    if archived {
+        remove_file(&path)?;
+        remove_checksum(&path);
+        debug!(logger, "Removed {:?}", path);
+    }
+    let extracted = remove_extracted(config, &id)?;
+    if !archived && !extracted {
+        return Ok(false);
+    }
+    config.run_install_hooks(InstallEvent::Uninstalled, &id, &path);
+    Ok(true)
+}
+
+/// Remove an installed pack from the pack store: only `version` when
+/// given, every installed version otherwise.
+pub fn uninstall(
+    config: &Config,
+    vendor: &str,
+    name: &str,
+    version: Option<&str>,
+    logger: &Logger,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut dir = config.pack_store.clone();
+    dir.push(plain_name("vendor", vendor)?);
+    dir.push(plain_name("name", name)?);
+    let mut removed = Vec::new();
+    if !dir.exists() {
+        return Ok(removed);
+    }
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        // Versions whose archive was discarded are only left extracted
+        let installed = if path.extension().map(|ext| ext == "pack").unwrap_or(false) {
+            lossy(path.file_stem())
+        } else if is_extracted(&path) {
+            lossy(path.file_name())
+        } else {
+            continue;
+        };
+        if version.map(|v| v != installed).unwrap_or(false) {
+            continue;
         }
-        1 => {
-            info!(logger, "Updated 1 package");
+        if remove_pack(config, vendor, name, &installed, logger)? {
+            removed.push(path);
         }
+    }
+    Ok(removed)
+}
+
+pub fn uninstall_args() -> App<'static, 'static> {
+    SubCommand::with_name("uninstall")
+        .alias("remove")
+        .about("Remove an installed CMSIS Pack")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("PACK")
+                .required(true)
+                .help("Pack to remove, as Vendor.Name"),
+        ).arg(
+            Arg::with_name("version")
+                .long("version")
+                .takes_value(true)
+                .help("Only remove this version"),
+        )
+}
+
+pub fn uninstall_command<'a>(
+    conf: &Config,
+    args: &ArgMatches<'a>,
+    logger: &Logger,
+) -> Result<(), Error> {
+    let pack = args.value_of("PACK").unwrap();
+    let mut parts = pack.splitn(2, '.');
+    let (vendor, name) = match (parts.next(), parts.next()) {
+        (Some(vendor), Some(name)) => (vendor, name),
         _ => {
-            info!(logger, "Updated {} package", num_updated);
+            return Err(err_msg(format!(
+                "pack \"{}\" is not of the form \"Vendor.Name\"",
+                pack
+            )))
+        }
+    };
+    let removed = uninstall(conf, vendor, name, args.value_of("version"), logger)?;
+    match removed.len() {
+        0 => info!(logger, "{} is not installed", pack),
+        1 => info!(logger, "Removed 1 package"),
+        n => info!(logger, "Removed {} packages", n),
+    }
+    Ok(())
+}
+
+pub fn sync_args() -> App<'static, 'static> {
+    SubCommand::with_name("sync")
+        .about("Install the packs required by a project file")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("manifest")
+                .short("m")
+                .long("manifest")
+                .takes_value(true)
+                .help("Project file to read, defaults to cmsis-packs.toml"),
+        ).arg(
+            Arg::with_name("lockfile")
+                .short("l")
+                .long("lockfile")
+                .takes_value(true)
+                .help("Lockfile to write, defaults to cmsis-packs.lock"),
+        )
+}
+
+pub fn sync_command<'a>(conf: &Config, args: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    let manifest_path = Path::new(args.value_of("manifest").unwrap_or(MANIFEST_NAME));
+    let manifest = Manifest::from_path(manifest_path)?;
+    let updated = install_project(&Context::new(conf, logger), &manifest)?;
+    report_updated(&updated, logger);
+    let lockfile_path = Path::new(args.value_of("lockfile").unwrap_or(LOCKFILE_NAME));
+    lock_project(conf, &manifest, logger)?.write_to(lockfile_path)
+}
+
+pub fn verify_args() -> App<'static, 'static> {
+    SubCommand::with_name("verify")
+        .about("Check that the installed packs match a project lockfile")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("manifest")
+                .short("m")
+                .long("manifest")
+                .takes_value(true)
+                .help("Project file to read, defaults to cmsis-packs.toml"),
+        ).arg(
+            Arg::with_name("lockfile")
+                .short("l")
+                .long("lockfile")
+                .takes_value(true)
+                .help("Lockfile to read, defaults to cmsis-packs.lock"),
+        )
+}
+
+/// Fails when any installed pack deviates from the lockfile.
+pub fn verify_command<'a>(
+    conf: &Config,
+    args: &ArgMatches<'a>,
+    logger: &Logger,
+) -> Result<(), Error> {
+    let manifest_path = Path::new(args.value_of("manifest").unwrap_or(MANIFEST_NAME));
+    let lockfile_path = Path::new(args.value_of("lockfile").unwrap_or(LOCKFILE_NAME));
+    let manifest = Manifest::from_path(manifest_path)?;
+    let lockfile = Lockfile::from_path(lockfile_path)?;
+    let discrepancies = verify(conf, &manifest, &lockfile)?;
+    for discrepancy in &discrepancies {
+        error!(logger, "{}", discrepancy);
+    }
+    match discrepancies.len() {
+        0 => {
+            info!(logger, "{} packs verified", lockfile.pack.len());
+            Ok(())
+        }
+        num => Err(err_msg(format!("{} packs failed verification", num))),
+    }
+}
+
+pub fn gc_args() -> App<'static, 'static> {
+    SubCommand::with_name("gc")
+        .about("Remove the descriptions and packs the vendor indexes no longer reference")
+        .version("0.1.0")
+}
+
+pub fn gc_command<'a>(conf: &Config, _: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    let report = gc(conf, logger)?;
+    info!(
+        logger,
+        "Removed {} files, reclaiming {} bytes",
+        report.removed.len(),
+        report.reclaimed
+    );
+    Ok(())
+}
+
+pub fn usage_args() -> App<'static, 'static> {
+    SubCommand::with_name("usage")
+        .about("Report the disk space taken by each pack archive")
+        .version("0.1.0")
+}
+
+pub fn usage_command<'a>(conf: &Config, _: &ArgMatches<'a>, logger: &Logger) -> Result<(), Error> {
+    let usage = disk_usage(conf);
+    let mut total = 0;
+    // Content shared by several versions takes its space once
+    let mut counted = HashSet::new();
+    for pack in &usage {
+        if pack.sha256.as_ref().map(|sha256| counted.insert(sha256)).unwrap_or(true) {
+            total += pack.size;
+        }
+        let id = &pack.id;
+        match pack.shared_with.first() {
+            Some(other) => info!(
+                logger,
+                "{}.{} {}: {} bytes, the same content as {}",
+                id.vendor,
+                id.name,
+                id.version,
+                pack.size,
+                other.version
+            ),
+            None => info!(logger, "{}.{} {}: {} bytes", id.vendor, id.name, id.version, pack.size),
         }
     }
+    info!(logger, "{} pack archives take {} bytes", usage.len(), total);
     Ok(())
 }
+
+pub fn verify_packs_args() -> App<'static, 'static> {
+    SubCommand::with_name("verify-packs")
+        .about("Check the installed packs against their index and recorded checksums")
+        .version("0.1.0")
+}
+
+/// Fails when any installed pack is corrupted.
+pub fn verify_packs_command<'a>(
+    conf: &Config,
+    _: &ArgMatches<'a>,
+    logger: &Logger,
+) -> Result<(), Error> {
+    let corrupt = verify_packs(conf, logger)?;
+    for pack in &corrupt {
+        error!(logger, "{}", pack);
+    }
+    match corrupt.len() {
+        0 => {
+            info!(logger, "Installed packs verified");
+            Ok(())
+        }
+        num => Err(err_msg(format!("{} packs are corrupted", num))),
+    }
+}
+
+pub fn signatures_args() -> App<'static, 'static> {
+    SubCommand::with_name("signatures")
+        .about("Check the signatures of the installed packs against the trusted signers")
+        .version("0.1.0")
+}
+
+/// Fails when any installed pack has an invalid signature.
+pub fn signatures_command<'a>(
+    conf: &Config,
+    _: &ArgMatches<'a>,
+    logger: &Logger,
+) -> Result<(), Error> {
+    let signatures = pack_signatures(conf)?;
+    let mut invalid = 0;
+    for pack in &signatures {
+        let id = &pack.id;
+        match pack.status {
+            SignatureStatus::Invalid(_) => {
+                invalid += 1;
+                error!(logger, "{}.{} {}: {}", id.vendor, id.name, id.version, pack.status);
+            }
+            _ => info!(logger, "{}.{} {}: {}", id.vendor, id.name, id.version, pack.status),
+        }
+    }
+    match invalid {
+        0 => Ok(()),
+        num => Err(err_msg(format!("{} packs have an invalid signature", num))),
+    }
+}
@@ -0,0 +1,202 @@
+use rustls::{
+    Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError, WebPKIVerifier,
+};
+use sha2::{Digest, Sha256};
+use webpki::DNSNameRef;
+
+use pack_index::config::CertificatePin;
+
+const DER_SEQUENCE: u8 = 0x30;
+// The explicit `[0] version` tag opening a v2 or v3 TBSCertificate
+const DER_VERSION: u8 = 0xA0;
+// Serial number, signature algorithm, issuer, validity and subject
+const FIELDS_BEFORE_KEY: usize = 5;
+
+// The tag, the length of the header and the whole length of the DER element
+// at the start of `der`
+fn der_element(der: &[u8]) -> Option<(u8, usize, usize)> {
+    let tag = *der.get(0)?;
+    let first = *der.get(1)? as usize;
+    let (header, len) = if first < 0x80 {
+        (2, first)
+    } else {
+        let count = first & 0x7F;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = der.get(2..2 + count)?;
+        (2 + count, bytes.iter().fold(0, |len, &b| len << 8 | b as usize))
+    };
+    if der.len() < header + len {
+        return None;
+    }
+    Some((tag, header, header + len))
+}
+
+fn sequence_content(der: &[u8]) -> Option<&[u8]> {
+    match der_element(der)? {
+        (DER_SEQUENCE, header, end) => Some(&der[header..end]),
+        _ => None,
+    }
+}
+
+/// The DER encoded SubjectPublicKeyInfo of a certificate.
+pub(crate) fn public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let tbs = sequence_content(sequence_content(cert)?)?;
+    let mut rest = tbs;
+    if let (DER_VERSION, _, end) = der_element(rest)? {
+        rest = &rest[end..];
+    }
+    for _ in 0..FIELDS_BEFORE_KEY {
+        let (_, _, end) = der_element(rest)?;
+        rest = &rest[end..];
+    }
+    let (_, _, end) = der_element(rest)?;
+    Some(&rest[..end])
+}
+
+/// The keys pinned for `host` by any of `pins`.
+pub(crate) fn pinned_keys(pins: &[CertificatePin], host: &str) -> Vec<[u8; 32]> {
+    pins.iter()
+        .filter(|pin| pin.covers(host))
+        .flat_map(|pin| pin.keys.iter().cloned())
+        .collect()
+}
+
+/// Check that the certificate of the server itself, the first of the
+/// `chain` presented by `host`, carries one of the `pinned` keys. The
+/// others are not looked at, as anyone may append them to their chain.
+/// Nothing is pinned when `pinned` is empty.
+pub(crate) fn check_pins<'a, I>(host: &str, chain: I, pinned: &[[u8; 32]]) -> Result<(), String>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    if pinned.is_empty() {
+        return Ok(());
+    }
+    let matched = chain
+        .into_iter()
+        .next()
+        .and_then(public_key_info)
+        .map(|key| {
+            let digest = Sha256::digest(key);
+            pinned.iter().any(|pin| pin[..] == digest[..])
+        }).unwrap_or(false);
+    if matched {
+        Ok(())
+    } else {
+        Err(format!("{} did not present any of the keys pinned for it", host))
+    }
+}
+
+/// Verifies the certificates of servers as rustls does, then checks the
+/// keys pinned for their host, so that a connection to a pinned host never
+/// completes its handshake with another key.
+pub(crate) struct PinningVerifier {
+    pins: Vec<CertificatePin>,
+    verifier: WebPKIVerifier,
+}
+
+impl PinningVerifier {
+    pub(crate) fn new(pins: Vec<CertificatePin>) -> Self {
+        PinningVerifier {
+            pins,
+            verifier: WebPKIVerifier::new(),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let verified =
+            self.verifier
+                .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)?;
+        let host: &str = dns_name.into();
+        check_pins(
+            host,
+            presented_certs.iter().map(|cert| &cert.0[..]),
+            &pinned_keys(&self.pins, host),
+        ).map_err(TLSError::General)?;
+        Ok(verified)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        if content.len() < 0x80 {
+            element.push(content.len() as u8);
+        } else {
+            element.extend_from_slice(&[0x81, content.len() as u8]);
+        }
+        element.extend_from_slice(content);
+        element
+    }
+
+    fn certificate(key: &[u8]) -> Vec<u8> {
+        let mut tbs = der(DER_VERSION, &[0x02, 0x01, 0x02]);
+        for _ in 0..FIELDS_BEFORE_KEY {
+            tbs.extend(der(DER_SEQUENCE, &[0x05, 0x00]));
+        }
+        tbs.extend_from_slice(key);
+        let mut cert = der(DER_SEQUENCE, &tbs);
+        cert.extend(der(DER_SEQUENCE, &[0x05, 0x00]));
+        der(DER_SEQUENCE, &cert)
+    }
+
+    #[test]
+    fn pinned_key_of_the_chain() {
+        let key = der(DER_SEQUENCE, &[0x03, 0x02, 0x00, 0x2A]);
+        let cert = certificate(&key);
+        assert_eq!(public_key_info(&cert), Some(&key[..]));
+
+        let mut pin = [0u8; 32];
+        pin.copy_from_slice(&Sha256::digest(&key));
+        let chain = vec![&cert[..], &b"not a certificate"[..]];
+        assert!(check_pins("keil.com", chain.clone(), &[pin]).is_ok());
+        assert!(check_pins("keil.com", chain.clone(), &[[0u8; 32]]).is_err());
+        assert!(check_pins("keil.com", chain, &[]).is_ok());
+        assert!(check_pins("keil.com", Vec::new(), &[pin]).is_err());
+    }
+
+    #[test]
+    fn appended_pinned_certificate() {
+        let key = der(DER_SEQUENCE, &[0x03, 0x02, 0x00, 0x2A]);
+        let pinned = certificate(&key);
+        let unpinned = certificate(&der(DER_SEQUENCE, &[0x03, 0x02, 0x00, 0x2B]));
+        let mut pin = [0u8; 32];
+        pin.copy_from_slice(&Sha256::digest(&key));
+        let chain = vec![&unpinned[..], &pinned[..]];
+        assert!(check_pins("keil.com", chain, &[pin]).is_err());
+    }
+
+    #[test]
+    fn keys_pinned_for_the_host() {
+        let pins = vec![
+            CertificatePin {
+                host: String::from("*.keil.com"),
+                keys: vec![[1u8; 32]],
+            },
+            CertificatePin {
+                host: String::from("www.keil.com"),
+                keys: vec![[2u8; 32], [3u8; 32]],
+            },
+        ];
+        assert_eq!(pinned_keys(&pins, "WWW.Keil.com"), vec![[1u8; 32], [2u8; 32], [3u8; 32]]);
+        assert_eq!(pinned_keys(&pins, "download.keil.com"), vec![[1u8; 32]]);
+        assert!(pinned_keys(&pins, "keil.com.example.com").is_empty());
+    }
+}
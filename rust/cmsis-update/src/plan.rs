@@ -0,0 +1,197 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use failure::Error;
+use hyper::Uri;
+use slog::Logger;
+
+use pack_index::config::{Config, PackId};
+use pack_index::PdscRef;
+use utils::natural::natural_cmp;
+
+use download::IntoDownload;
+
+/// Why a description is part of an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanReason {
+    /// No version of the pack is in the store yet
+    New,
+    /// The store holds `previous`, an older version of the pack
+    NewVersion { previous: PathBuf },
+}
+
+/// A description an update intends to download.
+#[derive(Debug, Clone)]
+pub struct PlannedDownload {
+    pub pdsc: PdscRef,
+    pub url: String,
+    pub dest: PathBuf,
+    pub reason: PlanReason,
+    /// Size of the description, estimated from the previous version when the
+    /// store holds it uncompressed
+    pub estimated_size: Option<u64>,
+}
+
+impl PlannedDownload {
+    fn new(pdsc: PdscRef, config: &Config) -> Result<Self, Error> {
+        let url = pdsc.into_uri(config)?.to_string();
+        let dest = pdsc.into_fd(config);
+        let previous = config.cached_pdsc(&pdsc.vendor, &pdsc.name, None);
+        let estimated_size = previous
+            .as_ref()
+            .filter(|path| path.extension().map(|ext| ext == "pdsc").unwrap_or(false))
+            .and_then(|path| path.metadata().ok())
+            .map(|meta| meta.len());
+        let reason = match previous {
+            Some(previous) => PlanReason::NewVersion { previous },
+            None => PlanReason::New,
+        };
+        Ok(PlannedDownload {
+            pdsc,
+            url,
+            dest,
+            reason,
+            estimated_size,
+        })
+    }
+}
+
+impl IntoDownload for PlannedDownload {
+    fn into_uri(&self, _: &Config) -> Result<Uri, Error> {
+        Ok(self.url.parse()?)
+    }
+
+    fn into_fd(&self, _: &Config) -> PathBuf {
+        self.dest.clone()
+    }
+}
+
+/// The downloads an update would make, in the order it makes them. Entries
+/// may be removed or reordered before the plan is executed.
+#[derive(Debug, Clone, Default)]
+pub struct UpdatePlan {
+    /// The vendor indexes the plan was made from
+    pub sources: Vec<String>,
+    pub downloads: Vec<PlannedDownload>,
+}
+
+impl UpdatePlan {
+    pub fn len(&self) -> usize {
+        self.downloads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.downloads.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlannedDownload> {
+        self.downloads.iter()
+    }
+
+    /// Keep only the downloads for which `keep` is true.
+    pub fn retain<F: FnMut(&PlannedDownload) -> bool>(&mut self, keep: F) {
+        self.downloads.retain(keep)
+    }
+
+    /// Sum of the estimated sizes, and how many downloads have none.
+    pub fn estimated_size(&self) -> (u64, usize) {
+        self.downloads
+            .iter()
+            .fold((0, 0), |(size, unknown), dl| match dl.estimated_size {
+                Some(known) => (size + known, unknown),
+                None => (size, unknown + 1),
+            })
+    }
+}
+
+/// Plan the download of the description references found in the indexes,
+/// leaving out the ones already in the store.
+pub(crate) fn make_plan(
+    config: &Config,
+    sources: Vec<String>,
+    refs: Vec<PdscRef>,
+    logger: &Logger,
+) -> UpdatePlan {
+    let downloads = refs
+        .into_iter()
+        .filter(|pdsc| !pdsc.into_fd(config).exists())
+        .filter_map(|pdsc| match PlannedDownload::new(pdsc, config) {
+            Ok(planned) => Some(planned),
+            Err(e) => {
+                warn!(logger, "Leaving a description out of the plan: {}", e);
+                None
+            }
+        }).collect();
+    UpdatePlan { sources, downloads }
+}
+
+/// A pack the indexes list in a newer version than the store describes.
+#[derive(Debug, Clone)]
+pub struct UpgradedPack {
+    pub pdsc: PdscRef,
+    /// The newest version described in the store
+    pub cached: String,
+}
+
+/// How the descriptions in the store differ from the vendor indexes.
+#[derive(Debug, Clone, Default)]
+pub struct PendingUpdates {
+    /// Packs the indexes list and the store does not describe
+    pub new: Vec<PdscRef>,
+    pub upgraded: Vec<UpgradedPack>,
+    /// Packs the store describes and no index lists anymore, in their
+    /// newest cached version
+    pub removed: Vec<PackId>,
+}
+
+impl PendingUpdates {
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.upgraded.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare the description references found in the indexes with the
+/// descriptions in the store.
+pub(crate) fn pending_updates(config: &Config, refs: Vec<PdscRef>) -> PendingUpdates {
+    let cached_ids = config.cached_pdsc_ids();
+    let mut newest: BTreeMap<(String, String), String> = BTreeMap::new();
+    for id in &cached_ids {
+        let key = (id.vendor.clone(), id.name.clone());
+        let replace = newest
+            .get(&key)
+            .map(|version| natural_cmp(version, &id.version) == Ordering::Less)
+            .unwrap_or(true);
+        if replace {
+            newest.insert(key, id.version.clone());
+        }
+    }
+    let mut pending = PendingUpdates::default();
+    let mut listed = BTreeSet::new();
+    for pdsc in refs {
+        let key = (String::from(&*pdsc.vendor), String::from(&*pdsc.name));
+        let cached = cached_ids.iter().any(|id| {
+            id.vendor == key.0 && id.name == key.1 && id.version == &*pdsc.version
+        });
+        match newest.get(&key) {
+            None => pending.new.push(pdsc),
+            Some(version) if !cached && natural_cmp(version, &*pdsc.version) == Ordering::Less => {
+                pending.upgraded.push(UpgradedPack {
+                    cached: version.clone(),
+                    pdsc,
+                })
+            }
+            Some(_) => (),
+        }
+        listed.insert(key);
+    }
+    pending.removed = newest
+        .into_iter()
+        .filter(|key_version| !listed.contains(&key_version.0))
+        .map(|((vendor, name), version)| PackId {
+            vendor,
+            name,
+            version,
+        }).collect();
+    pending
+}
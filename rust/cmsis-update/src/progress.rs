@@ -0,0 +1,226 @@
+use std::sync::{Arc, Mutex};
+
+use download::DownloadProgress;
+use error::DownloadError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub name: String,
+    pub received: u64,
+    /// From the Content-Length of the response, when the server sent one
+    pub length: Option<u64>,
+}
+
+impl TransferProgress {
+    fn fraction(&self) -> f32 {
+        match self.length {
+            Some(0) => 1.0,
+            Some(length) => (self.received as f32 / length as f32).min(1.0),
+            None => 0.0,
+        }
+    }
+}
+
+/// The state of a set of concurrent downloads, ready to be rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressSummary {
+    pub files_total: usize,
+    pub files_done: usize,
+    pub bytes_received: u64,
+    /// Overall completion between 0 and 100. Transfers of unknown length only
+    /// count once they complete.
+    pub percent: f32,
+    /// The largest transfers in progress, largest first
+    pub largest_active: Vec<TransferProgress>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    files_total: usize,
+    files_done: usize,
+    bytes_received: u64,
+    active: Vec<TransferProgress>,
+}
+
+/// A `DownloadProgress` that aggregates every transfer of an update or install
+/// so a frontend can poll `summary` at its own refresh rate, instead of
+/// reacting to each chunk.
+#[derive(Debug, Clone, Default)]
+pub struct AggregateProgress {
+    state: Arc<Mutex<State>>,
+    file: Option<String>,
+}
+
+impl AggregateProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Summarize the downloads, listing at most `largest` active transfers.
+    pub fn summary(&self, largest: usize) -> ProgressSummary {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut active = state.active.clone();
+        active.sort_by(|a, b| b.length.unwrap_or(b.received).cmp(&a.length.unwrap_or(a.received)));
+        active.truncate(largest);
+        let partial: f32 = state.active.iter().map(TransferProgress::fraction).sum();
+        let percent = if state.files_total == 0 {
+            100.0
+        } else {
+            ((state.files_done as f32 + partial) / state.files_total as f32 * 100.0).min(100.0)
+        };
+        ProgressSummary {
+            files_total: state.files_total,
+            files_done: state.files_done,
+            bytes_received: state.bytes_received,
+            percent,
+            largest_active: active,
+        }
+    }
+
+    fn with_transfer<F: FnOnce(&mut State, Option<usize>)>(&self, f: F) {
+        if let Ok(mut state) = self.state.lock() {
+            let index = self
+                .file
+                .as_ref()
+                .and_then(|file| state.active.iter().position(|t| t.name == *file));
+            f(&mut state, index);
+        }
+    }
+}
+
+impl DownloadProgress for AggregateProgress {
+    fn size(&self, files: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.files_total = files;
+        }
+    }
+
+    fn started(&self, length: Option<u64>) {
+        let name = match self.file {
+            Some(ref name) => name.clone(),
+            None => return,
+        };
+        self.with_transfer(|state, index| {
+            let transfer = TransferProgress {
+                name,
+                received: 0,
+                length,
+            };
+            match index {
+                // A restarted transfer begins from scratch
+                Some(index) => {
+                    let old = state.active[index].received;
+                    state.bytes_received -= old;
+                    state.active[index] = transfer;
+                }
+                None => state.active.push(transfer),
+            }
+        });
+    }
+
+    fn progress(&self, bytes: usize) {
+        self.with_transfer(|state, index| {
+            state.bytes_received += bytes as u64;
+            if let Some(index) = index {
+                state.active[index].received += bytes as u64;
+            }
+        });
+    }
+
+    fn complete(&self) {
+        self.with_transfer(|state, index| {
+            state.files_done += 1;
+            if let Some(index) = index {
+                state.active.swap_remove(index);
+            }
+        });
+    }
+
+    fn for_file(&self, file: &str) -> Self {
+        AggregateProgress {
+            state: self.state.clone(),
+            file: Some(file.to_string()),
+        }
+    }
+}
+
+/// What happened to a download, as reported to a `ProgressCallback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The number of files about to be downloaded
+    Planned { files: usize },
+    /// A transfer began, or restarted, with the announced size if any
+    Started { file: String, length: Option<u64> },
+    /// Bytes were received for a transfer
+    Received { file: String, bytes: usize },
+    Finished { file: String },
+    Failed { file: String, error: DownloadError },
+}
+
+/// A `DownloadProgress` calling a function on each event, for frontends that
+/// would rather be told than poll.
+pub struct ProgressCallback<F> {
+    callback: Arc<F>,
+    file: String,
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressCallback<F> {
+    pub fn new(callback: F) -> Self {
+        ProgressCallback {
+            callback: Arc::new(callback),
+            file: String::new(),
+        }
+    }
+}
+
+impl<F> Clone for ProgressCallback<F> {
+    fn clone(&self) -> Self {
+        ProgressCallback {
+            callback: self.callback.clone(),
+            file: self.file.clone(),
+        }
+    }
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> DownloadProgress for ProgressCallback<F> {
+    fn size(&self, files: usize) {
+        (self.callback)(ProgressEvent::Planned { files })
+    }
+
+    fn started(&self, length: Option<u64>) {
+        (self.callback)(ProgressEvent::Started {
+            file: self.file.clone(),
+            length,
+        })
+    }
+
+    fn progress(&self, bytes: usize) {
+        (self.callback)(ProgressEvent::Received {
+            file: self.file.clone(),
+            bytes,
+        })
+    }
+
+    fn complete(&self) {
+        (self.callback)(ProgressEvent::Finished {
+            file: self.file.clone(),
+        })
+    }
+
+    fn failed(&self, error: &DownloadError) {
+        (self.callback)(ProgressEvent::Failed {
+            file: self.file.clone(),
+            error: error.clone(),
+        })
+    }
+
+    fn for_file(&self, file: &str) -> Self {
+        ProgressCallback {
+            callback: self.callback.clone(),
+            file: file.to_string(),
+        }
+    }
+}
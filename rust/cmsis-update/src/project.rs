@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{err_msg, Error};
+use sha2::{Digest, Sha256};
+use slog::Logger;
+use toml;
+
+use pack_index::config::Config;
+use pdsc::{parse_packages, Package, Release};
+
+use dl_pack::PackRelease;
+use download::IntoDownload;
+use {install, Context};
+
+pub const MANIFEST_NAME: &str = "cmsis-packs.toml";
+pub const LOCKFILE_NAME: &str = "cmsis-packs.lock";
+
+/// The contents of a `cmsis-packs.toml` project file, for example:
+///
+/// ```toml
+/// devices = ["nRF52832_xxAA"]
+///
+/// [packs]
+/// "Keil.STM32F4xx_DFP" = "2.13.0"
+/// "ARM.CMSIS" = "*"
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub packs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub devices: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackRequirement {
+    pub vendor: String,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl PackRequirement {
+    fn new(id: &str, version: &str) -> Result<Self, Error> {
+        let mut parts = id.splitn(2, '.');
+        match (parts.next(), parts.next()) {
+            (Some(vendor), Some(name)) if !vendor.is_empty() && !name.is_empty() => Ok(Self {
+                vendor: vendor.to_string(),
+                name: name.to_string(),
+                version: match version {
+                    "*" | "" => None,
+                    v => Some(v.to_string()),
+                },
+            }),
+            _ => Err(err_msg(format!(
+                "pack \"{}\" is not of the form \"Vendor.Name\"",
+                id
+            ))),
+        }
+    }
+
+    /// The release of `pack` satisfying this requirement: the pinned one,
+    /// however old, or the latest.
+    pub fn release<'a>(&self, pack: &'a Package) -> Option<&'a Release> {
+        if pack.vendor != self.vendor || pack.name != self.name {
+            return None;
+        }
+        match self.version {
+            Some(ref version) => pack.releases.get(version),
+            None => Some(pack.releases.latest_release()),
+        }
+    }
+
+    pub fn matches(&self, pack: &Package) -> bool {
+        self.release(pack).is_some()
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Error;
+    fn from_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+impl Manifest {
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(path)?
+            .read_to_string(&mut content)?;
+        content.parse()
+    }
+
+    pub fn requirements(&self) -> Result<Vec<PackRequirement>, Error> {
+        self.packs
+            .iter()
+            .map(|(id, version)| PackRequirement::new(id, version))
+            .collect()
+    }
+}
+
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_digit(10))
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Parse every description file in the pack store.
+pub fn read_pack_store(config: &Config, l: &Logger) -> Vec<Package> {
+    parse_packages(config.pdsc_files(), config, l)
+}
+
+/// Select the releases from `available` that satisfy `manifest`. A pack
+/// without a pinned version resolves to the newest release available, and
+/// each required device pulls in the newest pack that describes it.
+pub fn resolve<'a>(
+    manifest: &Manifest,
+    available: &'a [Package],
+) -> Result<Vec<PackRelease<'a>>, Error> {
+    let newest = |candidates: Vec<PackRelease<'a>>| {
+        candidates
+            .into_iter()
+            .max_by_key(|candidate| version_key(candidate.version()))
+    };
+    let mut resolved: Vec<PackRelease> = Vec::new();
+    for req in manifest.requirements()? {
+        let candidates = available
+            .iter()
+            .filter_map(|pack| req.release(pack).map(|release| PackRelease { pack, release }))
+            .collect();
+        let found = newest(candidates).ok_or_else(|| {
+            err_msg(format!(
+                "no pack matching {}.{} {} found",
+                req.vendor,
+                req.name,
+                req.version.as_ref().map(String::as_str).unwrap_or("*")
+            ))
+        })?;
+        resolved.push(found);
+    }
+    for device in &manifest.devices {
+        if resolved.iter().any(|r| r.pack.devices().get(device).is_some()) {
+            continue;
+        }
+        let found = newest(
+            available
+                .iter()
+                .filter(|p| p.devices().get(device).is_some())
+                .map(PackRelease::from)
+                .collect(),
+        ).ok_or_else(|| err_msg(format!("no pack describing device {} found", device)))?;
+        resolved.push(found);
+    }
+    Ok(resolved)
+}
+
+/// Download every pack required by `manifest` that is not already installed.
+pub fn install_project(ctx: &Context, manifest: &Manifest) -> Result<Vec<PathBuf>, Error> {
+    let available = read_pack_store(ctx.config(), ctx.logger());
+    let resolved = resolve(manifest, &available)?;
+    install(ctx, resolved)
+}
+
+/// List the packs required by `manifest` that are not installed.
+pub fn missing_packs(config: &Config, manifest: &Manifest, l: &Logger) -> Result<Vec<PathBuf>, Error> {
+    let available = read_pack_store(config, l);
+    let resolved = resolve(manifest, &available)?;
+    Ok(resolved
+        .into_iter()
+        .map(|pack| pack.into_fd(config))
+        .filter(|path| !path.exists())
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LockedPack {
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+    pub sha256: String,
+}
+
+/// The exact packs a project was last synchronized with.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub pack: Vec<LockedPack>,
+}
+
+impl Lockfile {
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(path)?
+            .read_to_string(&mut content)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        let content = toml::to_string(self)?;
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        fd.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, Error> {
+    let mut fd = OpenOptions::new().read(true).open(path)?;
+    let mut hasher = Sha256::default();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = fd.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.result()))
+}
+
+/// Record the installed packs resolved for `manifest` in a lockfile.
+pub fn lock_project(config: &Config, manifest: &Manifest, l: &Logger) -> Result<Lockfile, Error> {
+    let available = read_pack_store(config, l);
+    let resolved = resolve(manifest, &available)?;
+    let pack = resolved
+        .into_iter()
+        .map(|resolved| {
+            Ok(LockedPack {
+                vendor: resolved.pack.vendor.clone(),
+                name: resolved.pack.name.clone(),
+                version: resolved.version().to_string(),
+                sha256: sha256_file(&resolved.into_fd(config))?,
+            })
+        }).collect::<Result<Vec<_>, Error>>()?;
+    Ok(Lockfile { pack })
+}
+
+#[derive(Debug)]
+pub enum Discrepancy {
+    /// Required by the manifest, but absent from the lockfile
+    NotLocked(PackRequirement),
+    Missing(LockedPack),
+    VersionMismatch {
+        locked: LockedPack,
+        installed: Vec<String>,
+    },
+    ChecksumMismatch {
+        locked: LockedPack,
+        found: String,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Discrepancy::NotLocked(ref req) => write!(
+                f,
+                "{}.{} is required but not locked; run sync",
+                req.vendor, req.name
+            ),
+            Discrepancy::Missing(ref locked) => write!(
+                f,
+                "{}.{} {} is not installed",
+                locked.vendor, locked.name, locked.version
+            ),
+            Discrepancy::VersionMismatch {
+                ref locked,
+                ref installed,
+            } => write!(
+                f,
+                "{}.{} is locked at {} but {} is installed",
+                locked.vendor,
+                locked.name,
+                locked.version,
+                installed.join(", ")
+            ),
+            Discrepancy::ChecksumMismatch {
+                ref locked,
+                ref found,
+            } => write!(
+                f,
+                "{}.{} {} has checksum {}, expected {}",
+                locked.vendor, locked.name, locked.version, found, locked.sha256
+            ),
+        }
+    }
+}
+
+fn installed_versions(config: &Config, vendor: &str, name: &str) -> Vec<String> {
+    let mut dir = config.pack_store.clone();
+    dir.push(vendor);
+    dir.push(name);
+    dir.read_dir()
+        .map(|rd| {
+            rd.flat_map(|dirent| dirent.into_iter().map(|d| d.path()))
+                .filter(|path| path.extension().map(|ext| ext == "pack").unwrap_or(false))
+                .filter_map(|path| {
+                    path.file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                }).collect()
+        }).unwrap_or_default()
+}
+
+/// Compare the installed packs against `lockfile`, and the lockfile against
+/// `manifest`.
+pub fn verify(config: &Config, manifest: &Manifest, lockfile: &Lockfile) -> Result<Vec<Discrepancy>, Error> {
+    let mut found = Vec::new();
+    for req in manifest.requirements()? {
+        let locked = lockfile.pack.iter().any(|p| {
+            p.vendor == req.vendor
+                && p.name == req.name
+                && req.version.as_ref().map(|v| *v == p.version).unwrap_or(true)
+        });
+        if !locked {
+            found.push(Discrepancy::NotLocked(req));
+        }
+    }
+    for locked in &lockfile.pack {
+        let mut path = config.pack_store.clone();
+        path.push(&locked.vendor);
+        path.push(&locked.name);
+        path.push(format!("{}.pack", locked.version));
+        if !path.exists() {
+            let installed = installed_versions(config, &locked.vendor, &locked.name);
+            if installed.is_empty() {
+                found.push(Discrepancy::Missing(locked.clone()));
+            } else {
+                found.push(Discrepancy::VersionMismatch {
+                    locked: locked.clone(),
+                    installed,
+                });
+            }
+            continue;
+        }
+        let sha256 = sha256_file(&path)?;
+        if sha256 != locked.sha256 {
+            found.push(Discrepancy::ChecksumMismatch {
+                locked: locked.clone(),
+                found: sha256,
+            });
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+    use utils::parse::{FromElem, ParseOptions};
+
+    fn pack() -> Package {
+        let l = Logger::root(Discard, o!());
+        Package::from_string(
+            r#"<package>
+              <vendor>Vendor</vendor>
+              <name>Pack</name>
+              <description>Several releases</description>
+              <url>http://example.com/packs/</url>
+              <releases>
+                <release version="1.2.0">Latest</release>
+                <release version="1.1.0" url="http://example.com/archive/Vendor.Pack.1.1.0.pack">
+                  Archived
+                </release>
+              </releases>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap()
+    }
+
+    #[test]
+    fn resolve_older_release() {
+        let available = vec![pack()];
+        let manifest: Manifest = "[packs]\n\"Vendor.Pack\" = \"1.1.0\"".parse().unwrap();
+        let resolved = resolve(&manifest, &available).unwrap();
+        assert_eq!(resolved[0].version(), "1.1.0");
+        assert_eq!(
+            resolved[0].release.url.as_ref().map(String::as_str),
+            Some("http://example.com/archive/Vendor.Pack.1.1.0.pack")
+        );
+        let latest: Manifest = "[packs]\n\"Vendor.Pack\" = \"*\"".parse().unwrap();
+        assert_eq!(resolve(&latest, &available).unwrap()[0].version(), "1.2.0");
+        let unknown: Manifest = "[packs]\n\"Vendor.Pack\" = \"1.0.0\"".parse().unwrap();
+        assert!(resolve(&unknown, &available).is_err());
+    }
+}
@@ -1,9 +1,15 @@
+use std::io;
+
 use futures::prelude::{async_block, await, Future};
 use hyper::client::Connect;
-use hyper::header::Location;
-use hyper::{Body, Client, Error, Response, StatusCode, Uri};
+use hyper::header::{Headers, Location};
+use hyper::{Body, Client, Error, Method, Request, Response, StatusCode, Uri};
 use slog::Logger;
 
+use pack_index::config::{Config, OutgoingRequest, ProxyConfig, RequestHook};
+
+use connect::proxy_authorization;
+
 pub(crate) trait ClientRedirExt<C>
 where
     C: Connect,
@@ -11,46 +17,180 @@ where
     fn redirectable<'a>(
         &'a self,
         uri: Uri,
+        headers: Headers,
+        config: &'a Config,
         logger: &'a Logger,
     ) -> Box<Future<Item = Response, Error = Error> + 'a>;
 }
 
+fn request_error(msg: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::Other, msg))
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    match status {
+        StatusCode::MovedPermanently
+        | StatusCode::Found
+        | StatusCode::SeeOther
+        | StatusCode::TemporaryRedirect
+        | StatusCode::PermanentRedirect => true,
+        _ => false,
+    }
+}
+
+fn apply_hook(hook: &RequestHook, uri: &Uri, headers: &Headers) -> Result<Request, Error> {
+    let mut outgoing = OutgoingRequest {
+        url: uri.to_string(),
+        headers: Vec::new(),
+    };
+    hook.before_request(&mut outgoing)
+        .map_err(|e| request_error(format!("request hook failed for {}: {}", uri, e)))?;
+    let mut request = Request::new(Method::Get, outgoing.url.parse()?);
+    *request.headers_mut() = headers.clone();
+    for (name, value) in outgoing.headers {
+        request.headers_mut().set_raw(name, value);
+    }
+    Ok(request)
+}
+
+/// Where a redirect to `location` leads from `uri`, a location without a
+/// host being on the host of `uri`.
+fn redirect_target(uri: &Uri, location: &str) -> Result<Uri, Error> {
+    let target: Uri = location.parse()?;
+    match (target.authority(), uri.scheme(), uri.authority()) {
+        (None, Some(scheme), Some(authority)) => {
+            Ok(format!("{}://{}{}", scheme, authority, target).parse()?)
+        }
+        _ => Ok(target),
+    }
+}
+
+/// Drop the credentials in `headers` when a redirect from `from` to `to`
+/// leaves the host they were meant for. Host names are compared ignoring
+/// case.
+fn strip_auth(headers: &mut Headers, from: &Uri, to: &Uri) {
+    let same_host = match (from.host(), to.host()) {
+        (Some(from), Some(to)) => from.eq_ignore_ascii_case(to),
+        (from, to) => from == to,
+    };
+    if !same_host {
+        headers.remove_raw("Authorization");
+        headers.remove_raw("Proxy-Authorization");
+        headers.remove_raw("Cookie");
+    }
+}
+
+// Plain http requests name the whole url when sent to a proxy
+fn through_proxy(request: &mut Request, proxies: &ProxyConfig) {
+    let proxy = match (request.uri().scheme(), request.uri().host()) {
+        (Some("http"), Some(host)) => proxies.for_host(false, host).cloned(),
+        _ => None,
+    };
+    if let Some(proxy) = proxy {
+        request.set_proxy(true);
+        if let Some(auth) = proxy_authorization(&proxy) {
+            request.headers_mut().set(auth);
+        }
+    }
+}
+
 impl<C: Connect> ClientRedirExt<C> for Client<C, Body> {
     fn redirectable<'a>(
         &'a self,
         mut uri: Uri,
+        mut headers: Headers,
+        config: &'a Config,
         logger: &'a Logger,
     ) -> Box<Future<Item = Response, Error = Error> + 'a> {
+        let policy = &config.redirect_policy;
         Box::new(async_block!{
-            let mut urls = Vec::new();
+            let mut redirects = 0;
             loop {
-                urls.push(uri.clone());
-                let res = await!(self.get(uri))?;
-                match res.status() {
-                    StatusCode::MovedPermanently |
-                    StatusCode::Found |
-                    StatusCode::SeeOther |
-                    StatusCode::TemporaryRedirect |
-                    StatusCode::PermanentRedirect => {
-                        let mut new_uri: Uri = res.headers()
-                            .get::<Location>()
-                            .unwrap_or(&Location::new(""))
-                            .parse()?;
-                        if let Some(ref old_uri) = urls.last() {
-                            if new_uri.authority().is_none() {
-                                if let Some(authority) = old_uri.authority() {
-                                    new_uri = format!("{}{}", authority, old_uri).parse()?
-                                }
-                            }
-                            debug!(logger, "Redirecting from {} to {}", old_uri, new_uri);
-                        }
-                        uri = new_uri;
+                let mut request = match config.request_hook {
+                    Some(ref hook) => apply_hook(&**hook, &uri, &headers)?,
+                    None => {
+                        let mut request = Request::new(Method::Get, uri.clone());
+                        *request.headers_mut() = headers.clone();
+                        request
                     }
-                    _ => {
-                        return Ok(res);
+                };
+                through_proxy(&mut request, &config.proxy);
+                let res = await!(self.request(request))?;
+                if !policy.follow || !is_redirect(res.status()) {
+                    return Ok(res);
+                }
+                if redirects >= policy.max_redirects {
+                    return Err(request_error(format!(
+                        "more than {} redirects while fetching {}", policy.max_redirects, uri)));
+                }
+                redirects += 1;
+                let location = res.headers()
+                    .get::<Location>()
+                    .map(|location| location.to_string())
+                    .unwrap_or_default();
+                let new_uri = redirect_target(&uri, &location)?;
+                match new_uri.scheme() {
+                    Some(scheme) if policy.allows_scheme(scheme) => (),
+                    scheme => {
+                        return Err(request_error(format!(
+                            "refusing to redirect from {} to {} with scheme {}",
+                            uri, new_uri, scheme.unwrap_or("<none>"))));
                     }
                 }
+                if policy.strip_auth_across_hosts {
+                    strip_auth(&mut headers, &uri, &new_uri);
+                }
+                debug!(logger, "Redirecting from {} to {}", uri, new_uri);
+                uri = new_uri;
             }
         })
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+
+    fn credentials() -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("Authorization", "Basic dXNlcjpwYXNz");
+        headers.set_raw("Proxy-Authorization", "Basic cHJveHk6cGFzcw==");
+        headers.set_raw("Cookie", "session=1");
+        headers
+    }
+
+    #[test]
+    fn resolve_relative_locations() {
+        let uri: Uri = "https://www.keil.com/pack/index.pidx".parse().unwrap();
+        assert_eq!(
+            redirect_target(&uri, "/pack/Keil.pdsc").unwrap().to_string(),
+            "https://www.keil.com/pack/Keil.pdsc"
+        );
+        assert_eq!(
+            redirect_target(&uri, "http://mirror.example.com/Keil.pdsc").unwrap().to_string(),
+            "http://mirror.example.com/Keil.pdsc"
+        );
+        assert!(redirect_target(&uri, "").is_err());
+    }
+
+    #[test]
+    fn strip_credentials_across_hosts() {
+        let from: Uri = "https://www.keil.com/pack/index.pidx".parse().unwrap();
+
+        let mut headers = credentials();
+        strip_auth(&mut headers, &from, &"https://WWW.Keil.com/Keil.pdsc".parse().unwrap());
+        assert!(headers.get_raw("Authorization").is_some());
+        assert!(headers.get_raw("Proxy-Authorization").is_some());
+        assert!(headers.get_raw("Cookie").is_some());
+
+        let mut headers = credentials();
+        strip_auth(&mut headers, &from, &"https://keil.com.example.com/".parse().unwrap());
+        assert!(headers.get_raw("Authorization").is_none());
+        assert!(headers.get_raw("Proxy-Authorization").is_none());
+        assert!(headers.get_raw("Cookie").is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs::{remove_file, rename, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use failure::Error;
+use hyper::header::Headers;
+use hyper::{StatusCode, Uri};
+use toml;
+
+use error::DownloadError;
+
+pub(crate) const PARTIAL_META_SUFFIX: &str = ".part.toml";
+
+// The end of central directory record of a zip archive, and how far from the
+// end of the file it may be found
+const ZIP_END_SIGNATURE: &[u8] = b"PK\x05\x06";
+const ZIP_END_SEARCH: u64 = 22 + 0xFFFF;
+
+/// What identifies the file a partial download is a prefix of.
+#[derive(Debug, Deserialize, Serialize)]
+struct Validator {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Size of the complete file, when the server announced it
+    length: Option<u64>,
+}
+
+fn raw_header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers
+        .get_raw(name)
+        .and_then(|raw| raw.one())
+        .and_then(|value| str::from_utf8(value).ok())
+        .map(str::trim)
+}
+
+// The first byte and the total size from a `bytes first-last/total` range
+fn content_range(headers: &Headers) -> Option<(u64, Option<u64>)> {
+    let range = raw_header(headers, "Content-Range")?;
+    let mut parts = range.trim_left_matches("bytes").trim().splitn(2, '/');
+    let first = parts.next()?.split('-').next()?.parse().ok()?;
+    let total = parts.next().and_then(|total| total.parse().ok());
+    Some((first, total))
+}
+
+pub(crate) fn looks_like_zip(path: &Path) -> Result<bool, Error> {
+    let mut fd = File::open(path)?;
+    let len = fd.metadata()?.len();
+    fd.seek(SeekFrom::Start(len.saturating_sub(ZIP_END_SEARCH)))?;
+    let mut tail = Vec::new();
+    fd.read_to_end(&mut tail)?;
+    Ok(tail
+        .windows(ZIP_END_SIGNATURE.len())
+        .any(|window| window == ZIP_END_SIGNATURE))
+}
+
+/// A download kept next to its destination between attempts, so that an
+/// interrupted transfer continues where it stopped rather than from zero.
+pub(crate) struct Partial {
+    dest: PathBuf,
+    data: PathBuf,
+    meta: PathBuf,
+}
+
+impl Partial {
+    pub(crate) fn new(dest: &Path) -> Self {
+        let name = dest
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Partial {
+            dest: dest.to_path_buf(),
+            data: dest.with_file_name(format!("{}.part", name)),
+            meta: dest.with_file_name(format!("{}{}", name, PARTIAL_META_SUFFIX)),
+        }
+    }
+
+    fn validator(&self) -> Option<Validator> {
+        let mut content = String::new();
+        File::open(&self.meta)
+            .and_then(|mut fd| fd.read_to_string(&mut content))
+            .ok()?;
+        toml::from_str(&content).ok()
+    }
+
+    /// The headers requesting the rest of the partial download of `uri`,
+    /// and the offset they resume at. Without a partial download, or without
+    /// a way to tell that the file on the server did not change, the
+    /// download starts over.
+    pub(crate) fn resume_headers(&self, uri: &Uri) -> (u64, Headers) {
+        let mut headers = Headers::new();
+        let offset = self.data.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let validator = match self.validator() {
+            Some(ref validator) if validator.url != uri.to_string() => return (0, headers),
+            Some(validator) => validator,
+            None => return (0, headers),
+        };
+        // Weak entity tags may not be used to resume
+        let if_range = validator
+            .etag
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(validator.last_modified);
+        match if_range {
+            Some(if_range) if offset > 0 => {
+                headers.set_raw("Range", format!("bytes={}-", offset));
+                headers.set_raw("If-Range", if_range);
+                (offset, headers)
+            }
+            _ => (0, headers),
+        }
+    }
+
+    /// Open the partial download to write the body of a response to a
+    /// request made with `resume_headers`. The file is appended to when the
+    /// server resumed at `offset`, and truncated when it sent everything.
+    pub(crate) fn open(
+        &self,
+        uri: &Uri,
+        status: StatusCode,
+        headers: &Headers,
+        offset: u64,
+    ) -> Result<File, Error> {
+        let (resumed, length) = if status == StatusCode::PartialContent {
+            match content_range(headers) {
+                Some((first, total)) if first == offset => (true, total),
+                _ => {
+                    self.discard();
+                    return Err(DownloadError::Network(format!(
+                        "{} resumed at an unexpected offset",
+                        uri
+                    )).into());
+                }
+            }
+        } else {
+            let length = raw_header(headers, "Content-Length").and_then(|len| len.parse().ok());
+            (false, length)
+        };
+        let validator = Validator {
+            url: uri.to_string(),
+            etag: raw_header(headers, "ETag").map(String::from),
+            last_modified: raw_header(headers, "Last-Modified").map(String::from),
+            length,
+        };
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.meta)?
+            .write_all(toml::to_string(&validator)?.as_bytes())?;
+        let fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&self.data)?;
+        Ok(fd)
+    }
+
+    /// Check the completed download and move it to its destination. A pack
+    /// that is not the announced size, or is not a zip archive, is dropped.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        let len = self.data.metadata()?.len();
+        let sized = self
+            .validator()
+            .and_then(|validator| validator.length)
+            .map(|length| length == len)
+            .unwrap_or(true);
+        let is_pack = self.dest.extension().map(|ext| ext == "pack").unwrap_or(false);
+        if !sized || (is_pack && !looks_like_zip(&self.data)?) {
+            self.discard();
+            return Err(DownloadError::Network(format!(
+                "downloaded {:?} is corrupt",
+                self.dest
+            )).into());
+        }
+        rename(&self.data, &self.dest)?;
+        let _ = remove_file(&self.meta);
+        Ok(())
+    }
+
+    pub(crate) fn discard(&self) {
+        let _ = remove_file(&self.data);
+        let _ = remove_file(&self.meta);
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs::{remove_file, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use failure::Error;
+use hyper::header::Headers;
+use hyper::Uri;
+use toml;
+
+const VALIDATORS_SUFFIX: &str = ".validators.toml";
+
+fn validators_path(file: &Path) -> PathBuf {
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    file.with_file_name(format!("{}{}", name, VALIDATORS_SUFFIX))
+}
+
+fn raw_header(headers: &Headers, name: &str) -> Option<String> {
+    headers
+        .get_raw(name)
+        .and_then(|raw| raw.one())
+        .and_then(|value| str::from_utf8(value).ok())
+        .map(|value| value.trim().to_string())
+}
+
+/// What the server said identifies the version of a downloaded file, kept
+/// next to it so that the next download of the same url may be conditional.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Validators {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn from_headers(uri: &Uri, headers: &Headers) -> Option<Self> {
+        let etag = raw_header(headers, "ETag");
+        let last_modified = raw_header(headers, "Last-Modified");
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Validators {
+            url: uri.to_string(),
+            etag,
+            last_modified,
+        })
+    }
+
+    /// The validators recorded for `file`, if it was downloaded from `uri`.
+    pub(crate) fn load(file: &Path, uri: &Uri) -> Option<Self> {
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(validators_path(file))
+            .and_then(|mut fd| fd.read_to_string(&mut content))
+            .ok()?;
+        let validators: Validators = toml::from_str(&content).ok()?;
+        if validators.url == uri.to_string() && file.exists() {
+            Some(validators)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn store(&self, file: &Path) -> Result<(), Error> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(validators_path(file))?
+            .write_all(toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Make `headers` ask for the body only if it changed since these
+    /// validators were received.
+    pub(crate) fn condition(&self, headers: &mut Headers) {
+        if let Some(ref etag) = self.etag {
+            headers.set_raw("If-None-Match", etag.clone());
+        }
+        if let Some(ref last_modified) = self.last_modified {
+            headers.set_raw("If-Modified-Since", last_modified.clone());
+        }
+    }
+}
+
+pub(crate) fn remove_validators(file: &Path) {
+    let _ = remove_file(validators_path(file));
+}
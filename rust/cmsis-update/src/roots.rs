@@ -0,0 +1,140 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use rustls::RootCertStore;
+use slog::Logger;
+use webpki_roots;
+
+use pack_index::config::TrustRoots;
+
+use error::NoTrustRoots;
+
+// Where distributions install their CA bundle, in the order openssl-probe
+// searches them
+#[cfg(unix)]
+const CERT_FILES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/pki/tls/cacert.pem",
+    "/etc/ssl/cert.pem",
+    "/usr/local/share/certs/ca-root-nss.crt",
+    "/usr/local/etc/openssl/cert.pem",
+];
+
+#[cfg(unix)]
+const CERT_DIRS: &[&str] = &[
+    "/etc/ssl/certs",
+    "/etc/pki/tls/certs",
+    "/system/etc/security/cacerts",
+];
+
+fn add_pem_file(store: &mut RootCertStore, path: &Path, l: &Logger) -> usize {
+    let fd = match File::open(path) {
+        Ok(fd) => fd,
+        Err(_) => return 0,
+    };
+    match store.add_pem_file(&mut BufReader::new(fd)) {
+        Ok((added, skipped)) => {
+            if skipped > 0 {
+                debug!(l, "Skipped {} unusable certificates in {:?}", skipped, path);
+            }
+            added
+        }
+        Err(()) => {
+            warn!(l, "Could not read certificates from {:?}", path);
+            0
+        }
+    }
+}
+
+fn add_pem_dir(store: &mut RootCertStore, dir: &Path, l: &Logger) -> usize {
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext == "pem" || ext == "crt")
+                .unwrap_or(false)
+        }).map(|path| add_pem_file(store, &path, l))
+        .sum()
+}
+
+// SSL_CERT_FILE and SSL_CERT_DIR take precedence over the platform, as
+// they do for OpenSSL
+fn env_roots(store: &mut RootCertStore, l: &Logger) -> usize {
+    let mut added = 0;
+    if let Some(file) = env::var_os("SSL_CERT_FILE") {
+        added += add_pem_file(store, Path::new(&file), l);
+    }
+    if let Some(dir) = env::var_os("SSL_CERT_DIR") {
+        added += add_pem_dir(store, Path::new(&dir), l);
+    }
+    added
+}
+
+#[cfg(unix)]
+fn system_roots(store: &mut RootCertStore, l: &Logger) -> usize {
+    for file in CERT_FILES {
+        let added = add_pem_file(store, Path::new(file), l);
+        if added > 0 {
+            return added;
+        }
+    }
+    for dir in CERT_DIRS {
+        let added = add_pem_dir(store, Path::new(dir), l);
+        if added > 0 {
+            return added;
+        }
+    }
+    0
+}
+
+#[cfg(windows)]
+fn system_roots(store: &mut RootCertStore, l: &Logger) -> usize {
+    use rustls::Certificate;
+    use schannel::cert_store::CertStore;
+
+    let certs = match CertStore::open_current_user("ROOT") {
+        Ok(certs) => certs,
+        Err(e) => {
+            warn!(l, "Could not open the certificate store: {}", e);
+            return 0;
+        }
+    };
+    certs
+        .certs()
+        .filter(|cert| store.add(&Certificate(cert.to_der().to_vec())).is_ok())
+        .count()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn system_roots(_: &mut RootCertStore, _: &Logger) -> usize {
+    0
+}
+
+/// The certificates trusted for HTTPS connections, according to `which`.
+pub(crate) fn trust_roots(which: TrustRoots, l: &Logger) -> Result<RootCertStore, NoTrustRoots> {
+    let mut store = RootCertStore::empty();
+    if which != TrustRoots::Bundled {
+        let added = match env_roots(&mut store, l) {
+            0 => system_roots(&mut store, l),
+            added => added,
+        };
+        debug!(l, "Trusting {} certificates from the system", added);
+    }
+    if store.roots.is_empty() && which != TrustRoots::System {
+        store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    }
+    if store.roots.is_empty() {
+        Err(NoTrustRoots(which))
+    } else {
+        Ok(store)
+    }
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use base64;
+use failure::{err_msg, Error};
+use rustls::internal::pemfile;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use untrusted::Input;
+use webpki::trust_anchor_util::cert_der_as_trust_anchor;
+use webpki::{self, EndEntityCert, SignatureAlgorithm, TLSClientTrustAnchors, Time};
+
+use pack_index::config::{Config, PackId};
+
+use integrity::{children, lossy, pack_archives};
+
+// A detached CMS signature is stored next to the archive, as
+// `version.pack.p7s`
+const SIGNATURE_SUFFIX: &str = ".p7s";
+
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const CONTEXT_0: u8 = 0xA0;
+const CONTEXT_1: u8 = 0xA1;
+
+const SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+const CONTENT_TYPE: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x03];
+const MESSAGE_DIGEST: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x04];
+const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+// webpki refuses the algorithms that do not suit the key of the signer, so
+// each digest lists those of every kind of key
+static SHA256_ALGORITHMS: &[&SignatureAlgorithm] = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P384_SHA256,
+];
+static SHA384_ALGORITHMS: &[&SignatureAlgorithm] = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ECDSA_P256_SHA384,
+];
+static SHA512_ALGORITHMS: &[&SignatureAlgorithm] = &[&webpki::RSA_PKCS1_2048_8192_SHA512];
+// Those the certificates of a chain may be signed with
+static CHAIN_ALGORITHMS: &[&SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// Whether a pack archive carries a signature made by one of the trusted
+/// signers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Verified,
+    Unsigned,
+    /// The signature does not match the archive, or its signer is not trusted
+    Invalid(String),
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignatureStatus::Verified => write!(f, "verified"),
+            SignatureStatus::Unsigned => write!(f, "unsigned"),
+            SignatureStatus::Invalid(ref reason) => write!(f, "invalid: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PackSignature {
+    pub id: PackId,
+    pub path: PathBuf,
+    pub status: SignatureStatus,
+}
+
+fn malformed() -> Error {
+    err_msg("malformed signature")
+}
+
+// One DER element
+#[derive(Debug, Clone, Copy)]
+struct Der<'a> {
+    tag: u8,
+    value: &'a [u8],
+    // The whole element, with its tag and length
+    raw: &'a [u8],
+}
+
+// Take the next element off `input`
+fn next<'a>(input: &mut &'a [u8]) -> Result<Der<'a>, Error> {
+    let data = *input;
+    // Tags that do not fit a byte are not used by certificates or CMS
+    if data.len() < 2 || data[0] & 0x1F == 0x1F {
+        return Err(malformed());
+    }
+    let (len, header) = match data[1] {
+        len @ 0x00...0x7F => (len as usize, 2),
+        0x81...0x84 => {
+            let count = (data[1] & 0x7F) as usize;
+            let bytes = data.get(2..2 + count).ok_or_else(malformed)?;
+            (bytes.iter().fold(0, |len, &b| len << 8 | b as usize), 2 + count)
+        }
+        _ => return Err(malformed()),
+    };
+    if len > data.len() - header {
+        return Err(malformed());
+    }
+    *input = &data[header + len..];
+    Ok(Der {
+        tag: data[0],
+        value: &data[header..header + len],
+        raw: &data[..header + len],
+    })
+}
+
+fn expect<'a>(input: &mut &'a [u8], tag: u8) -> Result<Der<'a>, Error> {
+    let der = next(input)?;
+    if der.tag == tag {
+        Ok(der)
+    } else {
+        Err(malformed())
+    }
+}
+
+// The next element when it has `tag`, leaving `input` as it is otherwise
+fn optional<'a>(input: &mut &'a [u8], tag: u8) -> Result<Option<Der<'a>>, Error> {
+    if input.first() == Some(&tag) {
+        next(input).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// The object identifier of an AlgorithmIdentifier
+fn algorithm<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let mut algorithm = expect(input, SEQUENCE)?.value;
+    Ok(expect(&mut algorithm, OID)?.value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+fn hash_file<D: Digest + Default>(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut fd = File::open(path)?;
+    let mut hasher = D::default();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = fd.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+    }
+    Ok(hasher.result().to_vec())
+}
+
+impl HashAlgorithm {
+    fn from_digest(oid: &[u8]) -> Option<Self> {
+        match oid {
+            oid if oid == SHA256 => Some(HashAlgorithm::Sha256),
+            oid if oid == SHA384 => Some(HashAlgorithm::Sha384),
+            oid if oid == SHA512 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn algorithms(self) -> &'static [&'static SignatureAlgorithm] {
+        match self {
+            HashAlgorithm::Sha256 => SHA256_ALGORITHMS,
+            HashAlgorithm::Sha384 => SHA384_ALGORITHMS,
+            HashAlgorithm::Sha512 => SHA512_ALGORITHMS,
+        }
+    }
+
+    // Read as a stream, so that large packs are not held in memory
+    fn hash_file(self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self {
+            HashAlgorithm::Sha256 => hash_file::<Sha256>(path),
+            HashAlgorithm::Sha384 => hash_file::<Sha384>(path),
+            HashAlgorithm::Sha512 => hash_file::<Sha512>(path),
+        }
+    }
+}
+
+// Whether `signature` of `message` was made with the key of the certificate
+// `signer`
+fn signed_by(signer: &[u8], hash: HashAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+    EndEntityCert::from(Input::from(signer))
+        .map(|cert| {
+            hash.algorithms().iter().any(|alg| {
+                cert.verify_signature(alg, Input::from(message), Input::from(signature))
+                    .is_ok()
+            })
+        }).unwrap_or(false)
+}
+
+// A CMS SignedData (RFC 5652) of detached content, by a single signer
+#[derive(Debug)]
+struct SignedData<'a> {
+    // The certificate of the signer, and the intermediate certificates up
+    // to a trust anchor
+    certificates: Vec<&'a [u8]>,
+    hash: HashAlgorithm,
+    // The signed attributes, which is what the signature covers
+    attributes: &'a [u8],
+    message_digest: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> SignedData<'a> {
+    fn parse(der: &'a [u8]) -> Result<Self, Error> {
+        let mut input = der;
+        let mut content_info = expect(&mut input, SEQUENCE)?.value;
+        if expect(&mut content_info, OID)?.value != SIGNED_DATA {
+            return Err(err_msg("not a CMS signature"));
+        }
+        let mut content = expect(&mut content_info, CONTEXT_0)?.value;
+        let mut signed_data = expect(&mut content, SEQUENCE)?.value;
+        expect(&mut signed_data, INTEGER)?;
+        expect(&mut signed_data, SET)?;
+        let mut encapsulated = expect(&mut signed_data, SEQUENCE)?.value;
+        let content_type = expect(&mut encapsulated, OID)?.value;
+        if !encapsulated.is_empty() {
+            return Err(err_msg("the signature is not detached"));
+        }
+        let mut certificates = Vec::new();
+        if let Some(set) = optional(&mut signed_data, CONTEXT_0)? {
+            let mut set = set.value;
+            while !set.is_empty() {
+                // Other kinds of certificates are of no use to a chain
+                let choice = next(&mut set)?;
+                if choice.tag == SEQUENCE {
+                    certificates.push(choice.raw);
+                }
+            }
+        }
+        optional(&mut signed_data, CONTEXT_1)?;
+        let mut signer_infos = expect(&mut signed_data, SET)?.value;
+        let mut signer_info = expect(&mut signer_infos, SEQUENCE)?.value;
+        if !signer_infos.is_empty() {
+            return Err(err_msg("the signature has several signers"));
+        }
+        expect(&mut signer_info, INTEGER)?;
+        // The signer is found by its signature instead of this identifier
+        next(&mut signer_info)?;
+        let hash = HashAlgorithm::from_digest(algorithm(&mut signer_info)?)
+            .ok_or_else(|| err_msg("unsupported digest algorithm"))?;
+        let attributes = optional(&mut signer_info, CONTEXT_0)?
+            .ok_or_else(|| err_msg("the signature has no signed attributes"))?;
+        algorithm(&mut signer_info)?;
+        let signature = expect(&mut signer_info, OCTET_STRING)?.value;
+        let (mut message_digest, mut signed_type) = (None, None);
+        let mut list = attributes.value;
+        while !list.is_empty() {
+            let mut attribute = expect(&mut list, SEQUENCE)?.value;
+            let id = expect(&mut attribute, OID)?.value;
+            let mut values = expect(&mut attribute, SET)?.value;
+            if id == MESSAGE_DIGEST {
+                message_digest = Some(expect(&mut values, OCTET_STRING)?.value);
+            } else if id == CONTENT_TYPE {
+                signed_type = Some(expect(&mut values, OID)?.value);
+            }
+        }
+        if signed_type != Some(content_type) {
+            return Err(err_msg("the content type is not signed"));
+        }
+        Ok(SignedData {
+            certificates,
+            hash,
+            attributes: attributes.raw,
+            message_digest: message_digest
+                .ok_or_else(|| err_msg("the signature has no message digest"))?,
+            signature,
+        })
+    }
+}
+
+// The DER of a PEM or DER encoded CMS signature
+fn signature_der(contents: Vec<u8>) -> Option<Vec<u8>> {
+    if contents.first() == Some(&SEQUENCE) {
+        return Some(contents);
+    }
+    let text = String::from_utf8_lossy(&contents);
+    pem_block(&text, "CMS").or_else(|| pem_block(&text, "PKCS7"))
+}
+
+fn pem_block(text: &str, label: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let start = text.find(&begin)? + begin.len();
+    let stop = start + text[start..].find(&end)?;
+    let encoded: String = text[start..stop].split_whitespace().collect();
+    base64::decode(&encoded).ok()
+}
+
+/// The trust anchors that pack signatures are checked against: the
+/// certificates of the CAs that issued those of the signers.
+pub(crate) struct Signers {
+    anchors: Vec<Vec<u8>>,
+}
+
+impl Signers {
+    /// Read the certificates of the PEM file at `path`, or of the `.pem` and
+    /// `.crt` files of the directory at `path`.
+    pub(crate) fn load(path: &Path) -> Result<Self, Error> {
+        let files = if path.is_dir() {
+            children(path)
+                .into_iter()
+                .filter(|file| {
+                    file.extension()
+                        .map(|ext| ext == "pem" || ext == "crt")
+                        .unwrap_or(false)
+                }).collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let mut anchors = Vec::new();
+        for file in files {
+            let certs = pemfile::certs(&mut BufReader::new(File::open(&file)?))
+                .map_err(|()| err_msg(format!("could not read certificates from {:?}", file)))?;
+            for cert in certs {
+                cert_der_as_trust_anchor(Input::from(&cert.0[..])).map_err(|e| {
+                    err_msg(format!("could not read a certificate of {:?}: {:?}", file, e))
+                })?;
+                anchors.push(cert.0);
+            }
+        }
+        if anchors.is_empty() {
+            return Err(err_msg(format!("no signer certificates in {:?}", path)));
+        }
+        Ok(Signers { anchors })
+    }
+
+    /// The signers of `config`, or `None` when packs are not checked.
+    pub(crate) fn from_config(config: &Config) -> Result<Option<Self>, Error> {
+        match config.signers {
+            Some(ref signers) => Signers::load(signers).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The signature of an archive is a detached CMS SignedData (RFC 5652),
+    /// in DER or PEM, in `version.pack.p7s` next to it. It must have signed
+    /// attributes, as `openssl cms -sign` adds by default, and a signer whose
+    /// certificate chains up to one of the anchors.
+    ///
+    /// The chain is checked by webpki as that of a TLS client certificate:
+    /// the certificate of the signer must not be a CA, and when it lists
+    /// extended key usages, they must include client authentication.
+    pub(crate) fn status(&self, archive: &Path) -> Result<SignatureStatus, Error> {
+        let now = Time::try_from(SystemTime::now())
+            .map_err(|_| err_msg("the system clock is set before 1970"))?;
+        self.status_at(archive, now)
+    }
+
+    fn status_at(&self, archive: &Path, time: Time) -> Result<SignatureStatus, Error> {
+        let detached = archive.with_file_name(format!(
+            "{}{}",
+            lossy(archive.file_name()),
+            SIGNATURE_SUFFIX
+        ));
+        if !detached.is_file() {
+            return Ok(SignatureStatus::Unsigned);
+        }
+        let mut contents = Vec::new();
+        File::open(&detached)?.read_to_end(&mut contents)?;
+        let der = match signature_der(contents) {
+            Some(der) => der,
+            None => return Ok(SignatureStatus::Invalid(malformed().to_string())),
+        };
+        let signed = match SignedData::parse(&der) {
+            Ok(signed) => signed,
+            Err(e) => return Ok(SignatureStatus::Invalid(e.to_string())),
+        };
+        let digest = signed.hash.hash_file(archive)?;
+        Ok(match self.verify(&signed, &digest, time) {
+            Ok(()) => SignatureStatus::Verified,
+            Err(e) => SignatureStatus::Invalid(e.to_string()),
+        })
+    }
+
+    fn verify(&self, signed: &SignedData, digest: &[u8], time: Time) -> Result<(), Error> {
+        if signed.message_digest != digest {
+            return Err(err_msg("the signature does not match the archive"));
+        }
+        // The signature covers the signed attributes, encoded as a SET
+        let mut message = signed.attributes.to_vec();
+        message[0] = SET;
+        let signer = *signed
+            .certificates
+            .iter()
+            .find(|cert| signed_by(cert, signed.hash, &message, signed.signature))
+            .ok_or_else(|| err_msg("the signature was not made by any of its certificates"))?;
+        let anchors: Vec<_> = self
+            .anchors
+            .iter()
+            .filter_map(|der| cert_der_as_trust_anchor(Input::from(&der[..])).ok())
+            .collect();
+        let intermediates: Vec<Input> = signed
+            .certificates
+            .iter()
+            .filter(|cert| **cert != signer)
+            .map(|cert| Input::from(*cert))
+            .collect();
+        EndEntityCert::from(Input::from(signer))
+            .and_then(|cert| {
+                cert.verify_is_valid_tls_client_cert(
+                    CHAIN_ALGORITHMS,
+                    &TLSClientTrustAnchors(&anchors),
+                    &intermediates,
+                    time,
+                )
+            }).map_err(|e| err_msg(format!("its signer is not trusted: {:?}", e)))
+    }
+}
+
+/// The signature status of every pack archive of the store.
+pub fn pack_signatures(config: &Config) -> Result<Vec<PackSignature>, Error> {
+    let signers = Signers::from_config(config)?
+        .ok_or_else(|| err_msg("no signers to check the packs against"))?;
+    pack_archives(config)
+        .into_iter()
+        .map(|(id, path)| {
+            let status = signers.status(&path)?;
+            Ok(PackSignature { id, path, status })
+        }).collect()
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    // A pack signed with `openssl cms -sign -binary -md sha256` by a
+    // certificate issued by PACK_CA; both are valid from 2020 to 2120
+    const ARCHIVE: &[u8] = b"PK not really an archive\n";
+    const SIGNATURE: &str = "-----BEGIN CMS-----
+MIIDKAYJKoZIhvcNAQcCoIIDGTCCAxUCAQExDTALBglghkgBZQMEAgEwCwYJKoZI
+hvcNAQcBoIIBcTCCAW0wggEToAMCAQICFH2Y3GHGMFzRlQOGzZzlIH0q7L+GMAoG
+CCqGSM49BAMCMBIxEDAOBgNVBAMMB1BhY2sgQ0EwIBcNMjAwMTAxMDAwMDAwWhgP
+MjEyMDAxMDEwMDAwMDBaMBYxFDASBgNVBAMMC1BhY2sgVmVuZG9yMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAEcPovEgt6R9e6cj8i9Wp32G7fIMmlJxZHoJhq1oFH
+jLuzzvlmAxUZxoLRLf36LHycgmTylaT7ZtemcvJbojQQB6NBMD8wDAYDVR0TAQH/
+BAIwADAOBgNVHQ8BAf8EBAMCB4AwHwYDVR0jBBgwFoAUdxltg8G0/ri7SaJO88ql
+VDkyu8wwCgYIKoZIzj0EAwIDSAAwRQIgKQS/qQgPEDANpF3CVRej6u59PFK6enGY
+xzS9B4Pes1ECIQCHsT52EPrhEOep7wr/zWK8J2gtGdwTAQcJxifd3t7kRzGCAX0w
+ggF5AgEBMCowEjEQMA4GA1UEAwwHUGFjayBDQQIUfZjcYcYwXNGVA4bNnOUgfSrs
+v4YwCwYJYIZIAWUDBAIBoIHkMBgGCSqGSIb3DQEJAzELBgkqhkiG9w0BBwEwHAYJ
+KoZIhvcNAQkFMQ8XDTI2MTAxNjE0NDMwMVowLwYJKoZIhvcNAQkEMSIEIKbMuYmi
+s9nNzigV1+kMpO0Hp2oVKyl4fJ0RDGOFXDpwMHkGCSqGSIb3DQEJDzFsMGowCwYJ
+YIZIAWUDBAEqMAsGCWCGSAFlAwQBFjALBglghkgBZQMEAQIwCgYIKoZIhvcNAwcw
+DgYIKoZIhvcNAwICAgCAMA0GCCqGSIb3DQMCAgFAMAcGBSsOAwIHMA0GCCqGSIb3
+DQMCAgEoMAoGCCqGSM49BAMCBEgwRgIhAKURgljXYwfsmZCHRLC/dPM7GW2+CpOR
+jYp5/QRdc84wAiEAzNMt0Xkhihn+GTQJxCLPaEPJit64XhfYx6+FQyyADpA=
+-----END CMS-----
+";
+    const PACK_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBajCCARCgAwIBAgIUGgDQVXh/LODB/t1UjMZlaw/ABXYwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHUGFjayBDQTAgFw0yMDAxMDEwMDAwMDBaGA8yMTIwMDEwMTAw
+MDAwMFowEjEQMA4GA1UEAwwHUGFjayBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABF13znC3ByhJ+fZBh+G7iz2ANkZp59V17BNccXR4fktUIBADH1pZDXqOC4Ca
+XfWjna9eb4q5xy0AZqnTWUdxP/6jQjBAMA8GA1UdEwEB/wQFMAMBAf8wDgYDVR0P
+AQH/BAQDAgEGMB0GA1UdDgQWBBR3GW2DwbT+uLtJok7zyqVUOTK7zDAKBggqhkjO
+PQQDAgNIADBFAiEAsdM+SonTpF06fay16qbqjaLULV1bmLhNSpy56epCR0ECIGIp
+fxKuJGuE98qS/hbr6l9Aj7Aaoo4KQ6mHxFtJPvCS
+-----END CERTIFICATE-----
+";
+    const OTHER_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBbDCCARKgAwIBAgIUJZUlSxuIqfaZhG7CgXxk/4xHMvUwCgYIKoZIzj0EAwIw
+EzERMA8GA1UEAwwIT3RoZXIgQ0EwIBcNMjAwMTAxMDAwMDAwWhgPMjEyMDAxMDEw
+MDAwMDBaMBMxETAPBgNVBAMMCE90aGVyIENBMFkwEwYHKoZIzj0CAQYIKoZIzj0D
+AQcDQgAEzAaehLgN5XmMHdODPfTVDdfFCKOzWSxC3fv0MZj6i9w4btC1+yNBdePC
+pn9k3YaqhzX0aK1CsHGnWDOmy4wmlqNCMEAwDwYDVR0TAQH/BAUwAwEB/zAOBgNV
+HQ8BAf8EBAMCAQYwHQYDVR0OBBYEFNLiS0C/pVFq8fhDgqybpY2VUtb7MAoGCCqG
+SM49BAMCA0gAMEUCICrsErqF/9oWszfiVTcEc/3fenAIK9XwROMpoBEsF7QFAiEA
+n6bTcT0y+zyPCkATZ2bsctoDELiquf8x+elW6CgGcSA=
+-----END CERTIFICATE-----
+";
+    // 2024-01-01 and 2121-01-01
+    const SIGNED_AT: u64 = 1_704_067_200;
+    const EXPIRED_AT: u64 = 4_765_132_800;
+
+    fn write(path: &Path, contents: &[u8]) {
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    fn is_invalid(status: SignatureStatus) -> bool {
+        match status {
+            SignatureStatus::Invalid(_) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn chain_up_to_the_ca() {
+        let temp = tempdir().unwrap();
+        let dir = temp.path();
+        let (archive, signature) = (dir.join("1.0.0.pack"), dir.join("1.0.0.pack.p7s"));
+        let (pack_ca, other_ca) = (dir.join("pack-ca.pem"), dir.join("other-ca.pem"));
+        write(&pack_ca, PACK_CA.as_bytes());
+        write(&other_ca, OTHER_CA.as_bytes());
+        let trusted = Signers::load(&pack_ca).unwrap();
+        let untrusted = Signers::load(&other_ca).unwrap();
+        let time = Time::from_seconds_since_unix_epoch(SIGNED_AT);
+
+        write(&archive, ARCHIVE);
+        assert_eq!(trusted.status_at(&archive, time).unwrap(), SignatureStatus::Unsigned);
+        write(&signature, SIGNATURE.as_bytes());
+        assert_eq!(trusted.status_at(&archive, time).unwrap(), SignatureStatus::Verified);
+        assert!(is_invalid(untrusted.status_at(&archive, time).unwrap()));
+        let expired = Time::from_seconds_since_unix_epoch(EXPIRED_AT);
+        assert!(is_invalid(trusted.status_at(&archive, expired).unwrap()));
+        write(&archive, b"PK tampered with\n");
+        assert!(is_invalid(trusted.status_at(&archive, time).unwrap()));
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use slog::Logger;
+use toml;
+
+use pack_index::config::Config;
+use pack_index::PdscRef;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SnapshotPack {
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+    /// Release date from the index, when the index provides one
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+/// Every pack version seen in the vendor indexes at one point in time.
+/// Updating "as of" a snapshot never picks a version it did not record.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// Seconds since the epoch at which the snapshot was taken
+    pub taken: u64,
+    #[serde(default)]
+    pub pack: Vec<SnapshotPack>,
+}
+
+impl Snapshot {
+    pub fn from_refs<I: IntoIterator<Item = PdscRef>>(refs: I) -> Self {
+        let mut pack: Vec<SnapshotPack> = refs
+            .into_iter()
+            .map(|r| SnapshotPack {
+                vendor: String::from(&*r.vendor),
+                name: String::from(&*r.name),
+                version: String::from(&*r.version),
+                date: r.date,
+            }).collect();
+        pack.sort_by(|a, b| (&a.vendor, &a.name).cmp(&(&b.vendor, &b.name)));
+        pack.dedup_by(|a, b| a.vendor == b.vendor && a.name == b.name);
+        Snapshot {
+            taken: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            pack,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(path)?
+            .read_to_string(&mut content)?;
+        let mut snapshot: Snapshot = toml::from_str(&content)?;
+        snapshot
+            .pack
+            .sort_by(|a, b| (&a.vendor, &a.name).cmp(&(&b.vendor, &b.name)));
+        Ok(snapshot)
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        let content = toml::to_string(self)?;
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        fd.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn pinned(&self, vendor: &str, name: &str) -> Option<&str> {
+        self.pack
+            .binary_search_by(|p| (p.vendor.as_str(), p.name.as_str()).cmp(&(vendor, name)))
+            .ok()
+            .map(|index| self.pack[index].version.as_str())
+    }
+
+    /// Whether the description `pdsc` from the index may be downloaded. Packs
+    /// that are newer than the snapshot, or absent from it, are skipped.
+    pub(crate) fn allows(&self, pdsc: &PdscRef, config: &Config, l: &Logger) -> bool {
+        let (vendor, name) = (&*pdsc.vendor, &*pdsc.name);
+        match self.pinned(vendor, name) {
+            Some(version) if version == &*pdsc.version => true,
+            Some(version) => {
+                if !config.pdsc_path(vendor, name, version).exists() {
+                    warn!(
+                        l,
+                        "{}.{} is pinned at {}, but the index only provides {}",
+                        vendor,
+                        name,
+                        version,
+                        &*pdsc.version
+                    );
+                }
+                false
+            }
+            None => {
+                debug!(l, "{}.{} is not in the snapshot", vendor, name);
+                false
+            }
+        }
+    }
+}
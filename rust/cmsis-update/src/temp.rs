@@ -0,0 +1,152 @@
+use std::fs::{create_dir, create_dir_all, remove_dir_all, remove_file, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::{Duration, SystemTime};
+
+use slog::Logger;
+
+use pack_index::config::Config;
+
+use resume::PARTIAL_META_SUFFIX;
+
+const TEMP_SUFFIX: &str = ".tmp";
+
+// Partial downloads kept next to their destination to be resumed, and the
+// ones written there by older versions
+const PARTIAL_SUFFIX: &str = ".part";
+
+static NEXT_TEMP: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// A scratch directory for a download or an extraction in progress. It is
+/// removed with everything left in it when dropped, so that an operation
+/// that fails, or a future that is cancelled, leaves nothing behind.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a temporary directory within `parent`, which should be on the
+    /// same file system as the files eventually persisted from it.
+    pub fn new_in(parent: &Path) -> io::Result<Self> {
+        create_dir_all(parent)?;
+        let name = format!(
+            ".{}-{}{}",
+            process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::SeqCst),
+            TEMP_SUFFIX
+        );
+        let path = parent.join(name);
+        create_dir(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Move `name` out of the temporary directory to `dest`.
+    pub fn persist<P: AsRef<Path>>(&self, name: P, dest: &Path) -> io::Result<()> {
+        rename(self.path.join(name), dest)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}
+
+// The process that created a temporary directory, from its name
+fn owner(name: &str) -> Option<u32> {
+    name.trim_left_matches('.')
+        .split('-')
+        .next()
+        .and_then(|pid| pid.parse().ok())
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> Option<bool> {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_running(_: u32) -> Option<bool> {
+    None
+}
+
+fn is_older_than(path: &Path, age: Duration) -> bool {
+    path.symlink_metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|elapsed| elapsed > age)
+        .unwrap_or(false)
+}
+
+fn sweep_dir(dir: &Path, age: Duration, l: &Logger) -> usize {
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    let mut removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        let orphaned = if is_dir && name.ends_with(TEMP_SUFFIX) {
+            // Without a way to tell whether the owner still runs, only
+            // directories that have not been touched for a while are orphans
+            match owner(&name) {
+                Some(pid) if pid == process::id() => false,
+                Some(pid) => is_running(pid)
+                    .map(|running| !running)
+                    .unwrap_or_else(|| is_older_than(&path, age)),
+                None => is_older_than(&path, age),
+            }
+        } else if !is_dir
+            && (name.ends_with(PARTIAL_SUFFIX) || name.ends_with(PARTIAL_META_SUFFIX))
+        {
+            is_older_than(&path, age)
+        } else {
+            if is_dir {
+                removed += sweep_dir(&path, age, l);
+            }
+            false
+        };
+        if orphaned {
+            let result = if is_dir {
+                remove_dir_all(&path)
+            } else {
+                remove_file(&path)
+            };
+            match result {
+                Ok(()) => removed += 1,
+                Err(e) => warn!(l, "Could not remove {:?}: {}", path, e),
+            }
+        }
+    }
+    removed
+}
+
+/// Remove the temporary directories and partial downloads left behind by
+/// processes that crashed, returning how many were removed.
+pub fn sweep_orphans(config: &Config, l: &Logger) -> usize {
+    let age = Duration::from_secs(24 * 60 * 60);
+    let mut removed = sweep_dir(&config.pack_store, age, l);
+    if let Some(ref http_cache) = config.http_cache {
+        if !http_cache.starts_with(&config.pack_store) {
+            removed += sweep_dir(http_cache, age, l);
+        }
+    }
+    if removed > 0 {
+        info!(l, "Removed {} leftovers of interrupted downloads", removed);
+    }
+    removed
+}
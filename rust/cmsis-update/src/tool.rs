@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::{Error, Fail};
+
+const DEFAULT_TIMEOUT: u64 = 5 * 60;
+const POLL_INTERVAL: u64 = 50;
+
+/// Why a tool described by a pack was not run, or was stopped.
+#[derive(Debug)]
+pub enum ToolError {
+    /// The path leads out of the directory it must be in
+    Escapes(PathBuf),
+    /// The program is not a file of the pack
+    NotInPack(PathBuf),
+    /// An argument template uses a variable the runner does not define
+    UnknownVariable(String),
+    /// The tool ran longer than the timeout and was killed
+    TimedOut(Duration),
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ToolError::Escapes(ref path) => write!(f, "{:?} is out of its directory", path),
+            ToolError::NotInPack(ref path) => write!(f, "{:?} is not a file of the pack", path),
+            ToolError::UnknownVariable(ref name) => write!(f, "unknown variable ${}", name),
+            ToolError::TimedOut(timeout) => {
+                write!(f, "killed after running for {} seconds", timeout.as_secs())
+            }
+        }
+    }
+}
+
+impl Fail for ToolError {}
+
+/// What a tool printed, and how it exited.
+#[derive(Debug)]
+pub struct ToolOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Runs tools named by pack metadata, such as generators. The program must
+/// be a file of the pack, it starts in a directory of the workspace with an
+/// empty environment, its arguments are expanded from templates, and it is
+/// killed once it runs too long.
+///
+/// This is not an isolation boundary: the tool runs with the rights of the
+/// user and can reach anything they can. On Unix, the tool and whatever it
+/// started are killed together, as a process group; elsewhere only the tool
+/// itself is.
+#[derive(Debug, Clone)]
+pub struct ToolRunner {
+    pack: PathBuf,
+    workspace: PathBuf,
+    timeout: Duration,
+    variables: BTreeMap<String, String>,
+}
+
+// Resolve `.` and `..` without touching the file system, so that paths
+// that do not exist yet can be checked too
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other.as_os_str()),
+        }
+    }
+    normal
+}
+
+// `path`, relative to `root` unless absolute, when it does not lead out of
+// `root` through `..` or a symbolic link
+fn confine(root: &Path, path: &Path) -> Result<PathBuf, ToolError> {
+    let path = normalize(&root.join(path));
+    let resolved = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if resolved.starts_with(root) {
+        Ok(resolved)
+    } else {
+        Err(ToolError::Escapes(path))
+    }
+}
+
+#[cfg(unix)]
+fn own_process_group(command: &mut Command) {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    command.before_exec(|| {
+        if unsafe { ::libc::setpgid(0, 0) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn own_process_group(_: &mut Command) {}
+
+// The tool leads its own process group, so this reaches what it started too
+#[cfg(unix)]
+fn kill_all(child: &mut Child) {
+    unsafe {
+        ::libc::kill(-(child.id() as ::libc::pid_t), ::libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill_all(child: &mut Child) {
+    let _ = child.kill();
+}
+
+impl ToolRunner {
+    /// Run the tools of the pack extracted in `pack` on the files of
+    /// `workspace`.
+    pub fn new<P: AsRef<Path>, W: AsRef<Path>>(pack: P, workspace: W) -> Result<Self, Error> {
+        Ok(ToolRunner {
+            pack: pack.as_ref().canonicalize()?,
+            workspace: workspace.as_ref().canonicalize()?,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            variables: BTreeMap::new(),
+        })
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        ToolRunner { timeout, ..self }
+    }
+
+    /// Define `$name` for the argument templates.
+    pub fn with_variable<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn pack(&self) -> &Path {
+        &self.pack
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Resolve `path`, relative to the workspace unless absolute, refusing
+    /// any path out of it, through `..` or a symbolic link.
+    pub fn confine<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ToolError> {
+        confine(&self.workspace, path.as_ref())
+    }
+
+    /// Resolve the program `path` within the pack, refusing anything but a
+    /// file of the pack.
+    pub fn program<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ToolError> {
+        let program = confine(&self.pack, path.as_ref())?;
+        if program.is_file() {
+            Ok(program)
+        } else {
+            Err(ToolError::NotInPack(program))
+        }
+    }
+
+    /// Replace the variables of a template, written `$name` or `${name}`.
+    /// `$$` stands for a single `$`.
+    pub fn expand(&self, template: &str) -> Result<String, ToolError> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('$') {
+            expanded.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            if rest.starts_with('$') {
+                expanded.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+            let (name, after) = if rest.starts_with('{') {
+                match rest.find('}') {
+                    Some(end) => (&rest[1..end], &rest[end + 1..]),
+                    None => return Err(ToolError::UnknownVariable(rest.to_string())),
+                }
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or_else(|| rest.len());
+                (&rest[..end], &rest[end..])
+            };
+            match self.variables.get(name) {
+                Some(value) => expanded.push_str(value),
+                None => return Err(ToolError::UnknownVariable(name.to_string())),
+            }
+            rest = after;
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Run `program`, a file of the pack, from `working_dir`, a directory of
+    /// the workspace, with the expansion of each argument template. Anything
+    /// the tool leaves running once it exits is killed.
+    pub fn run<P, W>(&self, program: P, args: &[&str], working_dir: W) -> Result<ToolOutput, Error>
+    where
+        P: AsRef<Path>,
+        W: AsRef<Path>,
+    {
+        let program = self.program(program)?;
+        let working_dir = self.confine(working_dir)?;
+        let args = args
+            .iter()
+            .map(|arg| self.expand(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut command = Command::new(&program);
+        command
+            .args(&args)
+            .current_dir(&working_dir)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        own_process_group(&mut command);
+        let mut child = command.spawn()?;
+        // Drained from threads, so that a tool filling a pipe does not block
+        let drain = |pipe: Option<Box<Read + Send>>| {
+            thread::spawn(move || {
+                let mut content = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_end(&mut content);
+                }
+                content
+            })
+        };
+        let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<Read + Send>));
+        let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<Read + Send>));
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                kill_all(&mut child);
+                let _ = child.wait();
+                return Err(ToolError::TimedOut(self.timeout).into());
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL));
+        };
+        // What it started in the background would hold the pipes open
+        kill_all(&mut child);
+        Ok(ToolOutput {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use std::fs::{create_dir_all, File};
+    use tempfile::tempdir;
+
+    #[test]
+    fn confine_programs_to_the_pack() {
+        let root = tempdir().unwrap();
+        let (pack, workspace) = (root.path().join("pack"), root.path().join("workspace"));
+        create_dir_all(pack.join("bin")).unwrap();
+        create_dir_all(&workspace).unwrap();
+        File::create(pack.join("bin").join("gen")).unwrap();
+        File::create(workspace.join("tool")).unwrap();
+        let runner = ToolRunner::new(&pack, &workspace).unwrap();
+        assert!(runner.program("bin/gen").is_ok());
+        assert!(runner.program("bin").is_err());
+        assert!(runner.program("../workspace/tool").is_err());
+        assert!(runner.program(workspace.join("tool")).is_err());
+        assert!(runner.confine("../pack").is_err());
+    }
+}
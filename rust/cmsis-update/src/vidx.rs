@@ -1,18 +1,25 @@
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
 use failure::Error;
 use futures::prelude::{async_block, await, Future};
-use futures::stream::{futures_unordered, iter_ok};
+use futures::stream::iter_ok;
 use futures::Stream;
 use hyper::client::Connect;
+use hyper::header::Headers;
 use hyper::{self, Body, Chunk, Client, Response};
 use minidom;
 use slog::Logger;
 
-use pack_index::{PdscRef, Pidx, Vidx};
-use utils::parse::FromElem;
+use pack_index::config::Config;
+use pack_index::{PdscRef, PidxDelta, Pidx, Vidx};
+use utils::parse::{FromElem, ParseOptions};
 
 use redirect::ClientRedirExt;
 
 fn download_vidx<'a, C: Connect, I: Into<String>>(
+    config: &'a Config,
     client: &'a Client<C, Body>,
     vidx_ref: I,
     logger: &'a Logger,
@@ -21,15 +28,16 @@ fn download_vidx<'a, C: Connect, I: Into<String>>(
     async_block!{
         let uri = vidx.parse()?;
         let body = await!(
-            client.redirectable(uri, logger)
+            client.redirectable(uri, Headers::new(), config, logger)
                 .map(Response::body)
                 .flatten_stream()
                 .concat2())?;
-        Ok(parse_vidx(&body, logger))
+        Ok(parse_vidx(&body, &config.parse_options, logger))
     }
 }
 
 pub(crate) fn download_vidx_list<'a, C, I>(
+    config: &'a Config,
     list: I,
     client: &'a Client<C, Body>,
     logger: &'a Logger,
@@ -39,22 +47,132 @@ where
     I: IntoIterator + 'a,
     <I as IntoIterator>::Item: Into<String>,
 {
-    futures_unordered(
-        list.into_iter()
-            .map(|vidx_ref| download_vidx(client, vidx_ref, logger)),
-    )
+    iter_ok(list)
+        .map(move |vidx_ref| download_vidx(config, client, vidx_ref, logger))
+        .buffer_unordered(config.max_connections)
 }
 
-fn parse_vidx(body: &Chunk, logger: &Logger) -> Result<Vidx, minidom::Error> {
-    let string = String::from_utf8_lossy(body);
-    Vidx::from_string(&string, logger)
+fn parse_vidx(
+    body: &Chunk,
+    opts: &ParseOptions,
+    logger: &Logger,
+) -> Result<Vidx, minidom::Error> {
+    Vidx::from_bytes(body, opts, logger)
 }
 
-fn into_uri(Pidx { url, vendor, .. }: Pidx) -> String {
+fn into_uri(&Pidx { ref url, ref vendor, .. }: &Pidx) -> String {
     format!("{}{}.pidx", url, vendor)
 }
 
-pub(crate) fn flatmap_pdscs<'a, C>(
+fn into_delta_uri(&Pidx { ref url, ref vendor, .. }: &Pidx) -> String {
+    format!("{}{}.delta.pidx", url, vendor)
+}
+
+// The last full version of each vendor index, for deltas to apply to
+fn cached_index_path(config: &Config, url: &str) -> PathBuf {
+    let name: String = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' })
+        .collect();
+    config.vidx_list.with_file_name("index_cache").join(name)
+}
+
+/// The description references of every vendor index cached by previous
+/// updates, without downloading anything.
+pub(crate) fn cached_pdsc_refs(config: &Config, logger: &Logger) -> Vec<PdscRef> {
+    let dir = config.vidx_list.with_file_name("index_cache");
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| read_cached_index(&entry.path(), &config.parse_options, logger))
+        .flat_map(|vidx| vidx.pdsc_index)
+        .collect()
+}
+
+fn read_cached_index(path: &PathBuf, opts: &ParseOptions, logger: &Logger) -> Option<Vidx> {
+    let mut content = String::new();
+    OpenOptions::new()
+        .read(true)
+        .open(path)
+        .and_then(|mut fd| fd.read_to_string(&mut content))
+        .ok()?;
+    Vidx::from_string(&content, opts, logger).ok()
+}
+
+fn write_cached_index(path: &PathBuf, vidx: &Vidx, logger: &Logger) {
+    let written = path
+        .parent()
+        .map(create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+        }).and_then(|mut fd| fd.write_all(vidx.to_xml().as_bytes()));
+    if let Err(e) = written {
+        warn!(logger, "Could not cache index {:?}: {}", path, e);
+    }
+}
+
+/// Fetch a vendor index, applying the vendor's delta to the cached copy when
+/// one is published and was made against that copy, and downloading the
+/// index in full otherwise.
+fn download_pidx<'a, C: Connect>(
+    config: &'a Config,
+    client: &'a Client<C, Body>,
+    pidx: Pidx,
+    logger: &'a Logger,
+) -> impl Future<Item = Result<Vidx, minidom::Error>, Error = hyper::Error> + 'a {
+    let url = into_uri(&pidx);
+    let delta_url = into_delta_uri(&pidx);
+    let cache_path = cached_index_path(config, &url);
+    async_block!{
+        if let Some(mut cached) = read_cached_index(&cache_path, &config.parse_options, logger) {
+            let uri = delta_url.parse()?;
+            let response = await!(client.redirectable(uri, Headers::new(), config, logger));
+            if let Ok(response) = response {
+                let body = if response.status().is_success() {
+                    await!(response.body().concat2()).ok()
+                } else {
+                    None
+                };
+                if let Some(body) = body {
+                    let applied = PidxDelta::from_bytes(&body, &config.parse_options, logger)
+                        .and_then(|delta| cached.apply_delta(delta));
+                    match applied {
+                        Ok(()) => {
+                            slog_debug!(logger, "Updated {} from its delta", url);
+                            write_cached_index(&cache_path, &cached, logger);
+                            return Ok(Ok(cached));
+                        }
+                        Err(e) => slog_debug!(logger, "Not using the delta of {}: {}", url, e),
+                    }
+                }
+            }
+        }
+        let vidx = await!(download_vidx(config, client, url, logger))?;
+        if let Ok(ref vidx) = vidx {
+            write_cached_index(&cache_path, vidx, logger);
+        }
+        Ok(vidx)
+    }
+}
+
+/// Flatten a parsed Vidx into the `PdscRef`s it lists directly, followed by
+/// the ones listed in each of its vendor indexes. Packs left out by the pack
+/// filter of `config` are dropped, and the indexes of vendors it excludes are
+/// not fetched.
+///
+/// The returned stream is lazy: vendor indexes are only downloaded while it is
+/// polled, and no more than `config.max_connections` of them at once, so a
+/// slow consumer holds back further downloads.
+pub fn flatmap_pdscs<'a, C>(
+    config: &'a Config,
     Vidx {
         vendor_index,
         pdsc_index,
@@ -66,11 +184,44 @@ pub(crate) fn flatmap_pdscs<'a, C>(
 where
     C: Connect,
 {
-    let pidx_urls = vendor_index.into_iter().map(into_uri);
-    let job = download_vidx_list(pidx_urls, client, logger)
+    let filter = &config.pack_filter;
+    let job = iter_ok(vendor_index)
+        .filter(move |pidx| filter.allows_vendor(&pidx.vendor))
+        .map(move |pidx| download_pidx(config, client, pidx, logger))
+        .buffer_unordered(config.max_connections)
         .filter_map(|vidx| match vidx {
             Ok(v) => Some(iter_ok(v.pdsc_index.into_iter())),
             Err(_) => None,
         }).flatten();
-    iter_ok(pdsc_index.into_iter()).chain(job)
+    iter_ok(pdsc_index.into_iter())
+        .chain(job)
+        .filter(move |pdsc| filter.allows(&pdsc.vendor, &pdsc.name))
+}
+
+/// Stream every `PdscRef` reachable from the Vidx urls of `vidx_list`, in no
+/// particular order. Indexes that fail to parse are skipped with a warning.
+///
+/// This is the index traversal used by `update`, for consumers that want to
+/// select or download descriptions themselves. As with `flatmap_pdscs`, the
+/// stream only makes progress while it is polled.
+pub fn pdsc_refs<'a, C, I>(
+    config: &'a Config,
+    vidx_list: I,
+    client: &'a Client<C, Body>,
+    logger: &'a Logger,
+) -> impl Stream<Item = PdscRef, Error = Error> + 'a
+where
+    C: Connect,
+    I: IntoIterator + 'a,
+    <I as IntoIterator>::Item: Into<String>,
+{
+    download_vidx_list(config, vidx_list, client, logger)
+        .from_err()
+        .filter_map(move |vidx| match vidx {
+            Ok(v) => Some(flatmap_pdscs(config, v, client, logger)),
+            Err(e) => {
+                warn!(logger, "Could not parse index: {}", e);
+                None
+            }
+        }).flatten()
 }
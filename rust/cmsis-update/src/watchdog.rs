@@ -0,0 +1,66 @@
+use std::fmt;
+use std::time::Duration;
+
+use failure::{Error, Fail};
+use futures::{Async, Future, Poll, Stream};
+use tokio_core::reactor::{Handle, Timeout};
+
+#[derive(Debug)]
+pub struct StalledDownload(pub Duration);
+
+impl fmt::Display for StalledDownload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no data received for {} seconds", self.0.as_secs())
+    }
+}
+
+impl Fail for StalledDownload {}
+
+/// Wraps a stream of body chunks, erroring with `StalledDownload` when no
+/// chunk arrives within `timeout`. The timer restarts on every chunk, so
+/// only a transfer whose throughput drops to zero trips the watchdog.
+pub(crate) struct Watchdog<S> {
+    inner: S,
+    timeout: Duration,
+    handle: Handle,
+    timer: Option<Timeout>,
+}
+
+impl<S> Watchdog<S> {
+    pub(crate) fn new(inner: S, timeout: Duration, handle: &Handle) -> Self {
+        Watchdog {
+            inner,
+            timeout,
+            handle: handle.clone(),
+            timer: None,
+        }
+    }
+}
+
+impl<S> Stream for Watchdog<S>
+where
+    S: Stream,
+    S::Error: Into<Error>,
+{
+    type Item = S::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, Error> {
+        match self.inner.poll().map_err(Into::into)? {
+            Async::Ready(item) => {
+                self.timer = None;
+                Ok(Async::Ready(item))
+            }
+            Async::NotReady => {
+                if self.timer.is_none() {
+                    self.timer = Some(Timeout::new(self.timeout, &self.handle)?);
+                }
+                match self.timer.as_mut().map(Future::poll) {
+                    Some(Ok(Async::Ready(()))) => Err(StalledDownload(self.timeout).into()),
+                    Some(Err(e)) => Err(e.into()),
+                    _ => Ok(Async::NotReady),
+                }
+            }
+        }
+    }
+}
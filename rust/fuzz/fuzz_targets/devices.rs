@@ -0,0 +1,116 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum Node {
+    Family(Vec<Node>),
+    SubFamily(Vec<Node>),
+    Device(Option<String>, Vec<Node>),
+    Variant(Option<String>),
+    Processor {
+        pname: Option<String>,
+        core: Option<String>,
+        fpu: Option<String>,
+        mpu: Option<String>,
+        units: Option<u8>,
+    },
+    Memory {
+        id: String,
+        start: u32,
+        size: u32,
+    },
+    Algorithm {
+        name: String,
+        start: u32,
+        size: u32,
+    },
+    Debug(Option<String>),
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+fn attr(out: &mut String, name: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+    }
+}
+
+fn render(node: &Node, out: &mut String) {
+    match node {
+        Node::Family(children) => {
+            out.push_str("<family Dfamily=\"F\" Dvendor=\"V:0\">");
+            children.iter().for_each(|c| render(c, out));
+            out.push_str("</family>");
+        }
+        Node::SubFamily(children) => {
+            out.push_str("<subFamily DsubFamily=\"S\">");
+            children.iter().for_each(|c| render(c, out));
+            out.push_str("</subFamily>");
+        }
+        Node::Device(name, children) => {
+            out.push_str("<device");
+            attr(out, "Dname", name.as_ref().map(String::as_str));
+            out.push('>');
+            children.iter().for_each(|c| render(c, out));
+            out.push_str("</device>");
+        }
+        Node::Variant(name) => {
+            out.push_str("<variant");
+            attr(out, "Dvariant", name.as_ref().map(String::as_str));
+            out.push_str("/>");
+        }
+        Node::Processor {
+            pname,
+            core,
+            fpu,
+            mpu,
+            units,
+        } => {
+            out.push_str("<processor");
+            attr(out, "Pname", pname.as_ref().map(String::as_str));
+            attr(out, "Dcore", core.as_ref().map(String::as_str));
+            attr(out, "Dfpu", fpu.as_ref().map(String::as_str));
+            attr(out, "Dmpu", mpu.as_ref().map(String::as_str));
+            attr(
+                out,
+                "Punits",
+                units.map(|u| u.to_string()).as_ref().map(String::as_str),
+            );
+            out.push_str("/>");
+        }
+        Node::Memory { id, start, size } => {
+            out.push_str(&format!(
+                "<memory id=\"{}\" start=\"0x{:X}\" size=\"0x{:X}\"/>",
+                escape(id),
+                start,
+                size
+            ));
+        }
+        Node::Algorithm { name, start, size } => {
+            out.push_str(&format!(
+                "<algorithm name=\"{}\" start=\"0x{:X}\" size=\"0x{:X}\"/>",
+                escape(name),
+                start,
+                size
+            ));
+        }
+        Node::Debug(svd) => {
+            out.push_str("<debug");
+            attr(out, "svd", svd.as_ref().map(String::as_str));
+            out.push_str("/>");
+        }
+    }
+}
+
+fuzz_target!(|families: Vec<Node>| {
+    let mut xml = String::from("<devices>");
+    families.iter().for_each(|f| render(f, &mut xml));
+    xml.push_str("</devices>");
+    let _ = pdsc::fuzz::devices(xml.as_bytes());
+});
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = pack_index::fuzz::vidx(data);
+    let _ = pack_index::fuzz::pidx(data);
+    let _ = pack_index::fuzz::pdsc_ref(data);
+});
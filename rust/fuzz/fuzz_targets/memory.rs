@@ -0,0 +1,41 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+// Attribute values are mostly well formed so that the fuzzer spends its time
+// past the XML parser.
+#[derive(Arbitrary, Debug)]
+struct Memory {
+    id: Option<String>,
+    name: Option<String>,
+    access: Option<String>,
+    start: Option<String>,
+    size: Option<String>,
+    startup: Option<String>,
+    default: Option<String>,
+    pname: Option<String>,
+}
+
+fn attr(out: &mut String, name: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        let escaped = value
+            .replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;");
+        out.push_str(&format!(" {}=\"{}\"", name, escaped));
+    }
+}
+
+fuzz_target!(|mem: Memory| {
+    let mut xml = String::from("<memory");
+    attr(&mut xml, "id", &mem.id);
+    attr(&mut xml, "name", &mem.name);
+    attr(&mut xml, "access", &mem.access);
+    attr(&mut xml, "start", &mem.start);
+    attr(&mut xml, "size", &mem.size);
+    attr(&mut xml, "startup", &mem.startup);
+    attr(&mut xml, "default", &mem.default);
+    attr(&mut xml, "Pname", &mem.pname);
+    xml.push_str("/>");
+    let _ = pdsc::fuzz::memory(xml.as_bytes());
+});
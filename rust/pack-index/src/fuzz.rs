@@ -0,0 +1,26 @@
+//! Entry points for the fuzz targets: parse raw bytes without any logger or
+//! file system setup.
+
+use minidom::Error;
+use slog::{Discard, Logger};
+
+use utils::parse::{FromElem, ParseOptions};
+
+use {PdscRef, Pidx, Vidx};
+
+fn parse<T: FromElem>(data: &[u8]) -> Result<T, Error> {
+    let l = Logger::root(Discard, o!());
+    T::from_bytes(data, &ParseOptions::default(), &l)
+}
+
+pub fn vidx(data: &[u8]) -> Result<Vidx, Error> {
+    parse(data)
+}
+
+pub fn pidx(data: &[u8]) -> Result<Pidx, Error> {
+    parse(data)
+}
+
+pub fn pdsc_ref(data: &[u8]) -> Result<PdscRef, Error> {
+    parse(data)
+}
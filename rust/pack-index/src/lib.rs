@@ -1,18 +1,31 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
 extern crate app_dirs;
+extern crate flate2;
 extern crate minidom;
 extern crate quick_xml;
 extern crate smallstring;
+#[macro_use]
 extern crate utils;
 #[macro_use]
 extern crate slog;
 extern crate failure;
 
 pub mod config;
+#[doc(hidden)]
+pub mod fuzz;
+
+use std::str::FromStr;
 
-use minidom::{Element, Error};
+use minidom::{Element, Error, ErrorKind};
 use slog::Logger;
 use smallstring::SmallString;
-use utils::parse::{assert_root_name, attr_map, child_text, get_child_no_ns, FromElem};
+use utils::parse::{
+    assert_root_name, attr_map, child_text, get_child_no_ns, FromElem, ParseOptions,
+};
 
 #[derive(Debug, Clone)]
 pub struct PdscRef {
@@ -26,6 +39,58 @@ pub struct PdscRef {
     pub size: Option<String>,
 }
 
+/// How mature a pack is. Ordered from least to most mature, so that a
+/// minimum level can be compared against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SupportLevel {
+    Alpha,
+    Beta,
+    Production,
+}
+
+impl SupportLevel {
+    /// Read the maturity of a pack from its version: pre-release versions
+    /// such as `1.2.0-alpha` or `2.0.0-rc1`, and `0.x` releases, are not
+    /// production ready.
+    pub fn from_version(version: &str) -> Self {
+        let (release, pre) = match version.find(|c: char| c == '-' || c == '+') {
+            Some(i) if version[i..].starts_with('-') => (&version[..i], Some(&version[i + 1..])),
+            Some(i) => (&version[..i], None),
+            None => (version, None),
+        };
+        match pre.map(str::to_lowercase) {
+            Some(ref pre)
+                if ["alpha", "dev", "prototype", "preview", "snapshot"]
+                    .iter()
+                    .any(|tag| pre.contains(tag)) =>
+            {
+                SupportLevel::Alpha
+            }
+            Some(_) => SupportLevel::Beta,
+            None if release.split('.').next() == Some("0") => SupportLevel::Beta,
+            None => SupportLevel::Production,
+        }
+    }
+}
+
+impl FromStr for SupportLevel {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from.to_lowercase().as_str() {
+            "alpha" => Ok(SupportLevel::Alpha),
+            "beta" => Ok(SupportLevel::Beta),
+            "production" => Ok(SupportLevel::Production),
+            unknown => Err(err_msg!("Unknown support level {}", unknown)),
+        }
+    }
+}
+
+impl PdscRef {
+    pub fn support_level(&self) -> SupportLevel {
+        SupportLevel::from_version(&self.version)
+    }
+}
+
 #[derive(Debug)]
 pub struct Pidx {
     pub url: String,
@@ -43,8 +108,8 @@ pub struct Vidx {
 }
 
 impl FromElem for PdscRef {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "pdsc")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "pdsc", opts)?;
         Ok(Self {
             url: attr_map(e, "url", "pdsc")?,
             vendor: attr_map(e, "vendor", "pdsc")?,
@@ -59,8 +124,8 @@ impl FromElem for PdscRef {
 }
 
 impl FromElem for Pidx {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "pidx")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "pidx", opts)?;
         Ok(Self {
             url: attr_map(e, "url", "pidx")?,
             vendor: attr_map(e, "vendor", "pidx")?,
@@ -70,29 +135,200 @@ impl FromElem for Pidx {
 }
 
 impl FromElem for Vidx {
-    fn from_elem(root: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(root, "index")?;
-        let vendor = child_text(root, "vendor", "index")?;
-        let url = child_text(root, "url", "index")?;
+    fn from_elem(root: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(root, "index", opts)?;
+        let vendor = child_text(root, "vendor", "index", opts)?;
+        let url = child_text(root, "url", "index", opts)?;
         Ok(Vidx {
             vendor,
             url,
-            timestamp: get_child_no_ns(root, "timestamp").map(Element::text),
-            vendor_index: get_child_no_ns(root, "vindex")
-                .map(|e| Pidx::vec_from_children(e.children(), &l))
+            timestamp: get_child_no_ns(root, "timestamp", opts).map(Element::text),
+            vendor_index: get_child_no_ns(root, "vindex", opts)
+                .map(|e| Pidx::vec_from_children(e.children(), opts, &l))
+                .unwrap_or_default(),
+            pdsc_index: get_child_no_ns(root, "pindex", opts)
+                .map(|e| PdscRef::vec_from_children(e.children(), opts, &l))
                 .unwrap_or_default(),
-            pdsc_index: get_child_no_ns(root, "pindex")
-                .map(|e| PdscRef::vec_from_children(e.children(), &l))
+        })
+    }
+}
+
+/// Changes to a vendor index since the full index stamped `base`. Some
+/// vendors publish one as `<vendor>.delta.pidx` beside `<vendor>.pidx`: an
+/// `index` document holding a `<delta base="..."/>` element, the new
+/// `timestamp`, the added or updated descriptions in `pindex`, and the
+/// removed ones in `premoved`.
+#[derive(Debug)]
+pub struct PidxDelta {
+    pub base: String,
+    pub timestamp: Option<String>,
+    pub updated: Vec<PdscRef>,
+    /// Vendor and name of each description that is no longer listed
+    pub removed: Vec<(String, String)>,
+}
+
+impl FromElem for PidxDelta {
+    fn from_elem(root: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(root, "index", opts)?;
+        let delta =
+            get_child_no_ns(root, "delta", opts).ok_or_else(|| err_msg!("index is not a delta"))?;
+        Ok(PidxDelta {
+            base: attr_map(delta, "base", "delta")?,
+            timestamp: get_child_no_ns(root, "timestamp", opts).map(Element::text),
+            updated: get_child_no_ns(root, "pindex", opts)
+                .map(|e| PdscRef::vec_from_children(e.children(), opts, &l))
                 .unwrap_or_default(),
+            removed: get_child_no_ns(root, "premoved", opts)
+                .map(|e| {
+                    e.children()
+                        .filter_map(|pdsc| {
+                            let vendor = attr_map(pdsc, "vendor", "pdsc").ok()?;
+                            let name = attr_map(pdsc, "name", "pdsc").ok()?;
+                            Some((vendor, name))
+                        }).collect()
+                }).unwrap_or_default(),
         })
     }
 }
 
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Vidx {
+    /// Apply a delta made against this index. Fails, leaving the index
+    /// untouched, when the delta was made against another version of it.
+    pub fn apply_delta(&mut self, delta: PidxDelta) -> Result<(), Error> {
+        if self.timestamp.as_ref() != Some(&delta.base) {
+            return Err(err_msg!(
+                "delta applies to the index of {}, not of {}",
+                delta.base,
+                self.timestamp
+                    .as_ref()
+                    .map(String::as_str)
+                    .unwrap_or("an unknown date")
+            ));
+        }
+        let same = |a: &PdscRef, vendor: &str, name: &str| &*a.vendor == vendor && &*a.name == name;
+        self.pdsc_index.retain(|pdsc| {
+            !delta
+                .removed
+                .iter()
+                .any(|&(ref vendor, ref name)| same(pdsc, vendor, name))
+        });
+        for pdsc in delta.updated {
+            let existing = self
+                .pdsc_index
+                .iter()
+                .position(|old| same(old, &*pdsc.vendor, &*pdsc.name));
+            match existing {
+                Some(index) => self.pdsc_index[index] = pdsc,
+                None => self.pdsc_index.push(pdsc),
+            }
+        }
+        self.timestamp = delta.timestamp;
+        Ok(())
+    }
+
+    /// Render the index as an `index` document that `from_string` reads back.
+    pub fn to_xml(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<index>\n");
+        out.push_str(&format!("  <vendor>{}</vendor>\n", escape(&self.vendor)));
+        out.push_str(&format!("  <url>{}</url>\n", escape(&self.url)));
+        if let Some(ref timestamp) = self.timestamp {
+            out.push_str(&format!("  <timestamp>{}</timestamp>\n", escape(timestamp)));
+        }
+        out.push_str("  <vindex>\n");
+        for pidx in &self.vendor_index {
+            out.push_str(&format!(
+                "    <pidx url=\"{}\" vendor=\"{}\"",
+                escape(&pidx.url),
+                escape(&pidx.vendor)
+            ));
+            if let Some(ref date) = pidx.date {
+                out.push_str(&format!(" date=\"{}\"", escape(date)));
+            }
+            out.push_str("/>\n");
+        }
+        out.push_str("  </vindex>\n  <pindex>\n");
+        for pdsc in &self.pdsc_index {
+            out.push_str(&format!(
+                "    <pdsc url=\"{}\" vendor=\"{}\" name=\"{}\" version=\"{}\"",
+                escape(&pdsc.url),
+                escape(&pdsc.vendor),
+                escape(&pdsc.name),
+                escape(&pdsc.version)
+            ));
+            let optional = [
+                ("date", &pdsc.date),
+                ("deprecated", &pdsc.deprecated),
+                ("replacement", &pdsc.replacement),
+                ("size", &pdsc.size),
+            ];
+            for &(name, value) in optional.iter() {
+                if let Some(ref value) = *value {
+                    out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+                }
+            }
+            out.push_str("/>\n");
+        }
+        out.push_str("  </pindex>\n</index>\n");
+        out
+    }
+}
+
 #[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
 mod test {
     use super::*;
     use slog::{Discard, Logger};
 
+    #[test]
+    fn apply_pidx_delta() {
+        let log = Logger::root(Discard, o!());
+        let full = "<index><vendor>Vendor</vendor><url>http://example.com/</url>\
+                    <timestamp>2018-01-01</timestamp><pindex>\
+                    <pdsc url=\"http://example.com/\" vendor=\"Vendor\" name=\"A\" version=\"1.0.0\"/>\
+                    <pdsc url=\"http://example.com/\" vendor=\"Vendor\" name=\"B\" version=\"1.0.0\"/>\
+                    </pindex></index>";
+        let delta = "<index><vendor>Vendor</vendor><url>http://example.com/</url>\
+                     <timestamp>2018-02-01</timestamp><delta base=\"2018-01-01\"/><pindex>\
+                     <pdsc url=\"http://example.com/\" vendor=\"Vendor\" name=\"A\" version=\"1.1.0\"/>\
+                     </pindex><premoved><pdsc vendor=\"Vendor\" name=\"B\"/></premoved></index>";
+        let mut vidx = Vidx::from_string(full, &ParseOptions::default(), &log).unwrap();
+        vidx.apply_delta(PidxDelta::from_string(delta, &ParseOptions::default(), &log).unwrap())
+            .unwrap();
+        assert_eq!(vidx.timestamp, Some("2018-02-01".to_string()));
+        assert_eq!(vidx.pdsc_index.len(), 1);
+        assert_eq!(&*vidx.pdsc_index[0].version, "1.1.0");
+        let stale = PidxDelta::from_string(delta, &ParseOptions::default(), &log).unwrap();
+        assert!(vidx.apply_delta(stale).is_err());
+        let reread = Vidx::from_string(&vidx.to_xml(), &ParseOptions::default(), &log).unwrap();
+        assert_eq!(reread.pdsc_index.len(), 1);
+    }
+
+    #[test]
+    fn support_level_from_version() {
+        let cases = vec![
+            ("1.2.0", SupportLevel::Production),
+            ("1.2.0+build3", SupportLevel::Production),
+            ("0.9.1", SupportLevel::Beta),
+            ("2.0.0-rc1", SupportLevel::Beta),
+            ("4.0.0-2.alpha", SupportLevel::Alpha),
+            ("0.0.1-prototype2", SupportLevel::Alpha),
+        ];
+        for (version, level) in cases {
+            assert_eq!(SupportLevel::from_version(version), level, "{}", version);
+        }
+    }
+
     #[test]
     fn pdscref_missing_attr() {
         let log = Logger::root(Discard, o!());
@@ -104,7 +340,7 @@ mod test {
             "<pdsc vendor=\"Vendor\" name=\"Name\" version=\"1.2.3-alpha\">",
         ];
         for bad_string in erroring_strings {
-            assert!(PdscRef::from_string(bad_string, &log).is_err());
+            assert!(PdscRef::from_string(bad_string, &ParseOptions::default(), &log).is_err());
         }
     }
 
@@ -113,7 +349,7 @@ mod test {
         let log = Logger::root(Discard, o!());
         let bad_string =
             "<notPdsc vendor=\"Vendor\" url=\"Url\" name=\"name\" version=\"1.2.3-alpha\">";
-        assert!(PdscRef::from_string(bad_string, &log).is_err())
+        assert!(PdscRef::from_string(bad_string, &ParseOptions::default(), &log).is_err())
     }
 
     #[test]
@@ -121,7 +357,7 @@ mod test {
         let log = Logger::root(Discard, o!());
         let good_string =
             "<pdsc vendor=\"Vendor\" url=\"Url\" name=\"Name\" version=\"1.2.3-alpha\">";
-        let response = PdscRef::from_string(good_string, &log).unwrap();
+        let response = PdscRef::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.vendor, SmallString::from("Vendor"));
         assert_eq!(response.url, "Url");
         assert_eq!(response.name, SmallString::from("Name"));
@@ -129,7 +365,7 @@ mod test {
         let good_string =
             "<pdsc vendor=\"Vendor\" url=\"Url\" name=\"Name\" version=\"1.2.3-alpha\"
                 date=\"A-Date\" deprecated=\"true\" replacement=\"Other\" size=\"8MB\">";
-        let response = PdscRef::from_string(good_string, &log).unwrap();
+        let response = PdscRef::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.date, Some(String::from("A-Date")));
         assert_eq!(response.deprecated, Some(String::from("true")));
         assert_eq!(response.replacement, Some(String::from("Other")));
@@ -145,7 +381,7 @@ mod test {
             "<pidx url=\"Url\"/>",
         ];
         for bad_string in erroring_strings {
-            assert!(Pidx::from_string(bad_string, &log).is_err());
+            assert!(Pidx::from_string(bad_string, &ParseOptions::default(), &log).is_err());
         }
     }
 
@@ -153,20 +389,20 @@ mod test {
     fn pidx_wrong_elem() {
         let log = Logger::root(Discard, o!());
         let bad_string = "<notpidx url=\"Url\" vendor=\"Vendor\"/>";
-        assert!(Pidx::from_string(bad_string, &log).is_err())
+        assert!(Pidx::from_string(bad_string, &ParseOptions::default(), &log).is_err())
     }
 
     #[test]
     fn pidx_optionals() {
         let log = Logger::root(Discard, o!());
         let good_string = "<pidx vendor=\"Vendor\" url=\"Url\"/>";
-        let response = Pidx::from_string(good_string, &log).unwrap();
+        let response = Pidx::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.vendor, SmallString::from("Vendor"));
         assert_eq!(response.url, "Url");
 
         let good_string =
             "<pidx vendor=\"Vendor\" url=\"Url\" date=\"Fri Sep  1 11:21:06 CDT 2017\"/>";
-        let response = Pidx::from_string(good_string, &log).unwrap();
+        let response = Pidx::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.vendor, SmallString::from("Vendor"));
         assert_eq!(response.url, "Url");
         assert_eq!(
@@ -189,7 +425,7 @@ mod test {
              </index>",
         ];
         for bad_string in erroring_strings {
-            assert!(Vidx::from_string(bad_string, &log).is_err());
+            assert!(Vidx::from_string(bad_string, &ParseOptions::default(), &log).is_err());
         }
     }
 
@@ -200,7 +436,7 @@ mod test {
                <vendor>Vendor</vendor>
                <url>Url</url>
              </notindex>";
-        assert!(Vidx::from_string(bad_string, &log).is_err())
+        assert!(Vidx::from_string(bad_string, &ParseOptions::default(), &log).is_err())
     }
 
     #[test]
@@ -210,7 +446,7 @@ mod test {
                <vendor>Vendor</vendor>
                <url>Url</url>
              </index>";
-        let response = Vidx::from_string(good_string, &log).unwrap();
+        let response = Vidx::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.vendor, String::from("Vendor"));
         assert_eq!(response.url, "Url");
 
@@ -219,7 +455,7 @@ mod test {
                <url>Url</url>
                <timestamp>Fri Sep  1 13:26:41 CDT 2017</timestamp>
              </index>";
-        let response = Vidx::from_string(good_string, &log).unwrap();
+        let response = Vidx::from_string(good_string, &ParseOptions::default(), &log).unwrap();
         assert_eq!(response.vendor, String::from("Vendor"));
         assert_eq!(response.url, "Url");
     }
@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate criterion;
+#[macro_use]
+extern crate slog;
+extern crate pdsc;
+
+use criterion::Criterion;
+use slog::{Discard, Logger};
+
+use pdsc::bench::{build_index, parse_pdscs, query_device, synthetic_pdscs};
+
+const PACKS: usize = 16;
+const DEVICES: usize = 32;
+
+fn parse(c: &mut Criterion) {
+    let pdscs = synthetic_pdscs(PACKS, DEVICES);
+    c.bench_function("parse 16 PDSCs", move |b| {
+        let log = Logger::root(Discard, o!());
+        b.iter(|| parse_pdscs(&pdscs, &log))
+    });
+}
+
+fn index(c: &mut Criterion) {
+    let log = Logger::root(Discard, o!());
+    let packs = parse_pdscs(&synthetic_pdscs(PACKS, DEVICES), &log);
+    c.bench_function("build index", move |b| b.iter(|| build_index(&packs).len()));
+}
+
+fn query(c: &mut Criterion) {
+    let log = Logger::root(Discard, o!());
+    let packs = parse_pdscs(&synthetic_pdscs(PACKS, DEVICES), &log);
+    c.bench_function("query device", move |b| {
+        b.iter(|| query_device(&packs, "SYN00017").len())
+    });
+}
+
+criterion_group!(benches, parse, index, query);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
+use device::Device;
+use Package;
+
+/// The result of looking a device up by name. A device found under a former
+/// name carries the name it is known by now, so callers can redirect to it.
+#[derive(Debug)]
+pub enum DeviceMatch<'a> {
+    Exact(&'a Device),
+    Renamed { former: String, device: &'a Device },
+}
+
+impl<'a> DeviceMatch<'a> {
+    pub fn device(&self) -> &'a Device {
+        match *self {
+            DeviceMatch::Exact(device) => device,
+            DeviceMatch::Renamed { device, .. } => device,
+        }
+    }
+}
+
+fn trim_word(word: &str) -> &str {
+    word.trim_matches(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+}
+
+/// Find device renames in release notes, written as either
+/// "renamed OLD to NEW" or "OLD renamed to NEW".
+pub(crate) fn renames_from_notes(text: &str) -> Vec<(String, String)> {
+    let words: Vec<&str> = text.split_whitespace().map(trim_word).collect();
+    let is_to = |i: usize| {
+        words
+            .get(i)
+            .map(|w| w.eq_ignore_ascii_case("to") || w.eq_ignore_ascii_case("as"))
+            .unwrap_or(false)
+    };
+    let mut renames = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if !word.eq_ignore_ascii_case("renamed") {
+            continue;
+        }
+        let found = if is_to(i + 2) {
+            words.get(i + 1).and_then(|old| words.get(i + 3).map(|new| (old, new)))
+        } else if i > 0 && is_to(i + 1) {
+            words.get(i + 2).map(|new| (&words[i - 1], new))
+        } else {
+            None
+        };
+        if let Some((old, new)) = found {
+            if !old.is_empty() && !new.is_empty() {
+                renames.push((old.to_string(), new.to_string()));
+            }
+        }
+    }
+    renames
+}
+
+impl Package {
+    /// Look a device up by its current name, one of its `Dalias` names or a
+    /// former name mentioned in the release notes.
+    pub fn find_device(&self, name: &str) -> Option<DeviceMatch> {
+        if let Some(device) = self.devices.get(name) {
+            return Some(DeviceMatch::Exact(device));
+        }
+        let renamed = |device| DeviceMatch::Renamed {
+            former: name.to_string(),
+            device,
+        };
+        if let Some(device) = self
+            .devices
+            .iter()
+            .find(|d| d.aliases.iter().any(|alias| alias == name))
+        {
+            return Some(renamed(device));
+        }
+        // Follow chains of renames through the releases, newest first
+        let renames: Vec<(String, String)> = self
+            .releases
+            .0
+            .iter()
+            .flat_map(|release| renames_from_notes(&release.text))
+            .collect();
+        let mut current = name;
+        for _ in 0..renames.len() {
+            match renames.iter().find(|&&(ref old, _)| old == current) {
+                Some(&(_, ref new)) => {
+                    if let Some(device) = self.devices.get(new) {
+                        return Some(renamed(device));
+                    }
+                    current = new;
+                }
+                None => break,
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,97 @@
+//! Entry points for measuring the performance of the pack database. Their
+//! signatures are kept stable, so that measurements taken with different
+//! releases, or different configurations, remain comparable.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use slog::Logger;
+
+use pack_index::config::Config;
+use utils::parse::{FromElem, ParseOptions};
+use utils::ResultLogExt;
+
+use lookup::{devices_by_name, DeviceCandidate};
+use pool::parse_packages;
+use synthetic::SyntheticPack;
+use view::device_refs;
+use {DumpDevice, Package};
+
+/// `count` distinct synthetic descriptions of `devices` devices each.
+pub fn synthetic_pdscs(count: usize, devices: usize) -> Vec<String> {
+    (0..count)
+        .map(|index| {
+            SyntheticPack {
+                vendor: format!("Synthetic{}", index),
+                name: format!("Synthetic{}_DFP", index),
+                families: 1,
+                sub_families: 0,
+                devices,
+                variants: 0,
+                seed: index as u64,
+            }.to_pdsc()
+        }).collect()
+}
+
+/// Parse descriptions held in memory, skipping the ones that fail.
+pub fn parse_pdscs(pdscs: &[String], l: &Logger) -> Vec<Package> {
+    pdscs
+        .iter()
+        .filter_map(|pdsc| Package::from_string(pdsc, &ParseOptions::default(), l).ok_warn(l))
+        .collect()
+}
+
+/// Parse the descriptions of the pack store with the threads and niceness
+/// of `config`.
+pub fn parse_store(config: &Config, l: &Logger) -> Vec<Package> {
+    parse_packages(config.pdsc_files(), config, l)
+}
+
+/// Build the device index written by `dump-devices`, keyed by device name.
+pub fn build_index(packs: &[Package]) -> BTreeMap<&str, DumpDevice> {
+    device_refs(packs)
+        .map(|dev| (dev.name(), dev.to_dump()))
+        .collect()
+}
+
+/// Look a device up by name across `packs`.
+pub fn query_device<'a>(packs: &'a [Package], name: &str) -> Vec<DeviceCandidate<'a>> {
+    devices_by_name(packs, name)
+}
+
+/// Time spent in each of the entry points above over one corpus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Baseline {
+    pub packs: usize,
+    pub devices: usize,
+    pub parse: Duration,
+    pub index: Duration,
+    /// Time to look every device up once
+    pub query: Duration,
+}
+
+impl Baseline {
+    pub fn measure(pdscs: &[String], l: &Logger) -> Self {
+        let start = Instant::now();
+        let packs = parse_pdscs(pdscs, l);
+        let parse = start.elapsed();
+
+        let start = Instant::now();
+        let index = build_index(&packs);
+        let index_time = start.elapsed();
+
+        let start = Instant::now();
+        for name in index.keys() {
+            query_device(&packs, name);
+        }
+        let query = start.elapsed();
+
+        Baseline {
+            packs: packs.len(),
+            devices: index.len(),
+            parse,
+            index: index_time,
+            query,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use minidom::{Element, Error};
+use slog::Logger;
+
+use utils::parse::{
+    assert_root_name, attr_map, child_text, element_name, FromElem, ParseOptions,
+};
+
+/// A device, or a family of devices, a board can carry in place of the one
+/// mounted on it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CompatibleDevice {
+    pub vendor: Option<String>,
+    pub family: Option<String>,
+    pub sub_family: Option<String>,
+    pub name: Option<String>,
+}
+
+impl CompatibleDevice {
+    /// Whether `device`, from the family `family`, is compatible.
+    pub fn matches(&self, device: &str, family: Option<&str>) -> bool {
+        match (&self.name, &self.sub_family, &self.family) {
+            (Some(name), _, _) => name == device,
+            (None, Some(sub_family), _) => family == Some(sub_family.as_str()),
+            (None, None, Some(fam)) => family == Some(fam.as_str()),
+            (None, None, None) => false,
+        }
+    }
+}
+
+/// A debug adapter on the board itself.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DebugProbe {
+    pub name: String,
+    /// How the probe talks to the device, such as "swd" or "jtag"
+    pub debug_link: Option<String>,
+}
+
+/// A connector an external debug adapter may be plugged into.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DebugInterface {
+    pub adapter: String,
+    pub connector: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Board {
+    pub name: String,
+    /// Names of the devices mounted on the board
+    pub mounted_devices: Vec<String>,
+    #[serde(default)]
+    pub vendor: Option<String>,
+    #[serde(default)]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub compatible_devices: Vec<CompatibleDevice>,
+    #[serde(default)]
+    pub debug_probes: Vec<DebugProbe>,
+    #[serde(default)]
+    pub debug_interfaces: Vec<DebugInterface>,
+}
+
+impl FromElem for Board {
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "board", opts)?;
+        let mut board = Self {
+            name: attr_map(e, "name", "board")?,
+            mounted_devices: Vec::new(),
+            vendor: attr_map(e, "vendor", "board").ok(),
+            revision: attr_map(e, "revision", "board").ok(),
+            description: child_text(e, "description", "board", opts).ok(),
+            compatible_devices: Vec::new(),
+            debug_probes: Vec::new(),
+            debug_interfaces: Vec::new(),
+        };
+        for child in e.children() {
+            match element_name(child, opts) {
+                "mountedDevice" => {
+                    if let Ok(name) = attr_map(child, "Dname", "mountedDevice") {
+                        board.mounted_devices.push(name);
+                    }
+                }
+                "compatibleDevice" => board.compatible_devices.push(CompatibleDevice {
+                    vendor: attr_map(child, "Dvendor", "compatibleDevice").ok(),
+                    family: attr_map(child, "Dfamily", "compatibleDevice").ok(),
+                    sub_family: attr_map(child, "DsubFamily", "compatibleDevice").ok(),
+                    name: attr_map(child, "Dname", "compatibleDevice").ok(),
+                }),
+                "debugProbe" => {
+                    if let Ok(name) = attr_map(child, "name", "debugProbe") {
+                        board.debug_probes.push(DebugProbe {
+                            name,
+                            debug_link: attr_map(child, "debugLink", "debugProbe").ok(),
+                        });
+                    }
+                }
+                "debugInterface" => {
+                    if let Ok(adapter) = attr_map(child, "adapter", "debugInterface") {
+                        board.debug_interfaces.push(DebugInterface {
+                            adapter,
+                            connector: attr_map(child, "connector", "debugInterface").ok(),
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(board)
+    }
+}
+
+/// The boards described by a pack.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Boards(pub(crate) Vec<Board>);
+
+impl Boards {
+    pub fn get(&self, name: &str) -> Option<&Board> {
+        self.0.iter().find(|board| board.name == name)
+    }
+
+    /// Iterate over the boards in the order they are described in the PDSC.
+    pub fn iter(&self) -> impl Iterator<Item = &Board> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The boards the device `name` is mounted on.
+    pub fn with_device<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Board> + 'a {
+        self.0
+            .iter()
+            .filter(move |board| board.mounted_devices.iter().any(|dev| dev == name))
+    }
+}
+
+impl FromElem for Boards {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "boards", opts)?;
+        Ok(Boards(Board::vec_from_children(e.children(), opts, l)))
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use failure::Error as FailError;
+use serde_json;
+use slog::Logger;
+
+use pack_index::config::Config;
+use utils::parse::FromElem;
+
+use Package;
+
+/// A description file in the pack store, with the outcome of parsing it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CachedPdsc {
+    pub path: PathBuf,
+    /// Why the file could not be parsed, `None` when it parsed
+    pub error: Option<String>,
+}
+
+impl CachedPdsc {
+    pub fn is_parsed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Parse every description in the pack store, reporting failures instead of
+/// skipping them, so that tools can audit the cache.
+pub fn cached_pdscs(config: &Config, l: &Logger) -> Vec<CachedPdsc> {
+    let mut paths = config.pdsc_files();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let error = Package::from_path(&path, &config.parse_options, l)
+                .err()
+                .map(|e| e.to_string());
+            CachedPdsc { path, error }
+        }).collect()
+}
+
+pub fn dumps_cached_pdscs(config: &Config, l: &Logger) -> Result<String, FailError> {
+    Ok(serde_json::to_string(&cached_pdscs(config, l))?)
+}
@@ -0,0 +1,61 @@
+use device::{Device, Processors};
+
+/// Which parts of a device description a debugger needs are present. An
+/// incomplete device can still be used, but flashing or debugging it will
+/// need some manual configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completeness {
+    pub memories: bool,
+    pub algorithm: bool,
+    /// An SVD file is referenced from a `debug` element
+    pub debug: bool,
+    /// Every processor states its FPU and MPU
+    pub processor: bool,
+}
+
+impl Completeness {
+    /// Percentage of the criteria above that are met.
+    pub fn score(&self) -> u8 {
+        let met = [self.memories, self.algorithm, self.debug, self.processor]
+            .iter()
+            .filter(|&&met| met)
+            .count();
+        (met * 25) as u8
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    /// Names of the criteria that are not met.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.memories {
+            missing.push("memories");
+        }
+        if !self.algorithm {
+            missing.push("flash algorithm");
+        }
+        if !self.debug {
+            missing.push("SVD");
+        }
+        if !self.processor {
+            missing.push("processor details");
+        }
+        missing
+    }
+}
+
+impl Device {
+    pub fn completeness(&self) -> Completeness {
+        Completeness {
+            memories: !self.memories.0.is_empty(),
+            algorithm: !self.algorithms.is_empty(),
+            debug: self.svd.is_some(),
+            processor: match self.processor {
+                Processors::Symmetric(ref prc) => prc.described,
+                Processors::Asymmetric(ref map) => map.values().all(|prc| prc.described),
+            },
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::fs::{rename, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{Read, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(feature = "fs")]
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "fs")]
+use failure::Error;
+#[cfg(feature = "fs")]
+use serde_json;
+#[cfg(feature = "fs")]
+use slog::Logger;
+
+#[cfg(feature = "fs")]
+use utils::parse::{FromElem, ParseOptions};
+
+use device::Device;
+use roles::MemoryRole;
+#[cfg(feature = "fs")]
+use Package;
+
+/// Format of the completion data, described by `COMPLETIONS_SCHEMA`.
+pub const COMPLETIONS_SCHEMA: &str = include_str!("../schema/completions.schema.json");
+
+/// Bumped on every incompatible change to `COMPLETIONS_SCHEMA`.
+pub const COMPLETIONS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionDevice {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Display names of the cores, one per processor description
+    pub cores: Vec<String>,
+    /// Total flash, in bytes
+    pub flash: u64,
+    /// Total RAM, in bytes
+    pub ram: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionPack {
+    /// File name of the description in the pack store
+    pub source: String,
+    /// Modification time and size of `source` when it was read
+    pub modified: u64,
+    pub size: u64,
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+    pub devices: Vec<CompletionDevice>,
+}
+
+/// Devices and packs, in a compact form meant for editor completion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completions {
+    pub version: u32,
+    pub packs: Vec<CompletionPack>,
+}
+
+fn total_size(dev: &Device, roles: &BTreeMap<String, MemoryRole>, wanted: &[MemoryRole]) -> u64 {
+    dev.memories
+        .0
+        .iter()
+        .filter(|&(name, _)| {
+            roles
+                .get(name.as_str())
+                .map(|r| wanted.contains(r))
+                .unwrap_or(false)
+        }).map(|(_, mem)| mem.size)
+        .sum()
+}
+
+impl CompletionDevice {
+    /// The completion entry of `dev`.
+    pub fn new(dev: &Device) -> Self {
+        let roles = dev.memory_roles();
+        CompletionDevice {
+            name: dev.name.clone(),
+            aliases: dev.aliases.clone(),
+            cores: dev
+                .topology()
+                .iter()
+                .map(|core| core.core.to_string())
+                .collect(),
+            flash: total_size(
+                dev,
+                &roles,
+                &[MemoryRole::PrimaryFlash, MemoryRole::SecondaryFlash],
+            ),
+            ram: total_size(
+                dev,
+                &roles,
+                &[
+                    MemoryRole::PrimaryRam,
+                    MemoryRole::SecondaryRam,
+                    MemoryRole::BackupRam,
+                ],
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+pub(crate) fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = path.metadata().ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((modified.as_secs(), meta.len()))
+}
+
+#[cfg(feature = "fs")]
+fn read_completions(path: &Path) -> Result<Completions, Error> {
+    let mut contents = Vec::new();
+    OpenOptions::new()
+        .read(true)
+        .open(path)?
+        .read_to_end(&mut contents)?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// Bring the completion data in `dest` up to date with the descriptions at
+/// `paths`. Packs whose description did not change since `dest` was written
+/// are not parsed again, and packs no longer in `paths` are dropped.
+#[cfg(feature = "fs")]
+pub fn update_completions<I, P>(
+    paths: I,
+    dest: &Path,
+    opts: &ParseOptions,
+    l: &Logger,
+) -> Result<Completions, Error>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut previous: BTreeMap<String, CompletionPack> = match read_completions(dest) {
+        Ok(ref old) if old.version != COMPLETIONS_VERSION => BTreeMap::new(),
+        Ok(old) => old
+            .packs
+            .into_iter()
+            .map(|pack| (pack.source.clone(), pack))
+            .collect(),
+        Err(_) => BTreeMap::new(),
+    };
+    let mut packs = Vec::new();
+    let mut parsed = 0;
+    for path in paths {
+        let path = path.as_ref();
+        let (source, (modified, size)) = match (path.file_name(), stamp(path)) {
+            (Some(name), Some(stamp)) => (name.to_string_lossy().into_owned(), stamp),
+            _ => continue,
+        };
+        match previous.remove(&source) {
+            Some(ref old) if old.modified == modified && old.size == size => {
+                packs.push(old.clone());
+                continue;
+            }
+            _ => (),
+        }
+        parsed += 1;
+        let pack = match Package::from_path(path, opts, l) {
+            Ok(pack) => pack,
+            Err(e) => {
+                warn!(l, "parsing {:?}: {}", path, e);
+                continue;
+            }
+        };
+        packs.push(CompletionPack {
+            source,
+            modified,
+            size,
+            vendor: pack.vendor.clone(),
+            name: pack.name.clone(),
+            version: pack.releases.latest_release().version.clone(),
+            devices: pack.devices.iter().map(CompletionDevice::new).collect(),
+        });
+    }
+    packs.sort_by(|a, b| a.source.cmp(&b.source));
+    debug!(l, "Updated completions for {} of {} packs", parsed, packs.len());
+    let completions = Completions {
+        version: COMPLETIONS_VERSION,
+        packs,
+    };
+    let temp = dest.with_extension("part");
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp)?
+        .write_all(&serde_json::to_vec(&completions)?)?;
+    rename(&temp, dest)?;
+    Ok(completions)
+}
@@ -1,10 +1,19 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use minidom::{Element, Error, ErrorKind};
+use minidom::{Element, Error};
 use slog::Logger;
 
-use utils::parse::{assert_root_name, attr_map, attr_parse, child_text, get_child_no_ns, FromElem};
+use utils::error::PdscError;
+use utils::parse::{
+    assert_root_name, attr_map, attr_parse, attr_parse_bool, child_text, element_name,
+    get_child_no_ns, FromElem, ParseOptions,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub enum FileCategory {
@@ -40,7 +49,10 @@ impl FromStr for FileCategory {
             "utility" => Ok(FileCategory::Utility),
             "image" => Ok(FileCategory::Image),
             "other" => Ok(FileCategory::Other),
-            unknown => Err(err_msg!("Unknown file catogory {}", unknown)),
+            unknown => Err(PdscError::Unknown {
+                what: "file category",
+                value: unknown.to_string(),
+            }.into()),
         }
     }
 }
@@ -57,7 +69,10 @@ impl FromStr for FileAttribute {
         match from {
             "config" => Ok(FileAttribute::Config),
             "template" => Ok(FileAttribute::Template),
-            unknown => Err(err_msg!("Unknown file attribute {}", unknown)),
+            unknown => Err(PdscError::Unknown {
+                what: "file attribute",
+                value: unknown.to_string(),
+            }.into()),
         }
     }
 }
@@ -65,17 +80,17 @@ impl FromStr for FileAttribute {
 #[derive(Debug, Clone, Serialize)]
 pub struct FileRef {
     pub path: PathBuf,
-    category: FileCategory,
-    attr: Option<FileAttribute>,
+    pub category: FileCategory,
+    pub attr: Option<FileAttribute>,
     pub condition: Option<String>,
-    select: Option<String>,
-    src: Option<String>,
-    version: Option<String>,
+    pub select: Option<String>,
+    pub src: Option<String>,
+    pub version: Option<String>,
 }
 
 impl FromElem for FileRef {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "file")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "file", opts)?;
         Ok(Self {
             path: attr_map(e, "name", "file")?,
             category: attr_parse(e, "category", "file")?,
@@ -94,6 +109,8 @@ pub struct ComponentBuilder {
     pub class: Option<String>,
     pub group: Option<String>,
     pub sub_group: Option<String>,
+    /// The bundle the component was described in, if any
+    pub bundle: Option<String>,
     pub variant: Option<String>,
     pub version: Option<String>,
     pub api_version: Option<String>,
@@ -107,8 +124,8 @@ pub struct ComponentBuilder {
 }
 
 impl FromElem for ComponentBuilder {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "component")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "component", opts)?;
         let mut l = l.new(o!("in" => "Component"));
         let vendor: Option<String> = attr_map(e, "Cvendor", "component").ok();
         if let Some(v) = vendor.clone() {
@@ -123,28 +140,30 @@ impl FromElem for ComponentBuilder {
             l = l.new(o!("Group" => g));
         }
         let sub_group: Option<String> = attr_map(e, "Csub", "component").ok();
-        if let Some(s) = vendor.clone() {
+        if let Some(s) = sub_group.clone() {
             l = l.new(o!("SubGroup" => s));
         }
-        let files = get_child_no_ns(e, "files")
-            .map(move |child| FileRef::vec_from_children(child.children(), &l))
+        let files = get_child_no_ns(e, "files", opts)
+            .map(|child| FileRef::vec_from_children(child.children(), opts, &l))
             .unwrap_or_default();
         Ok(Self {
             vendor,
             class,
             group,
             sub_group,
+            bundle: None,
             version: attr_map(e, "Cversion", "component").ok(),
             variant: attr_map(e, "Cvariant", "component").ok(),
             api_version: attr_map(e, "Capiversion", "component").ok(),
             condition: attr_map(e, "condition", "component").ok(),
             max_instances: attr_parse(e, "maxInstances", "component").ok(),
-            is_default: attr_parse(e, "isDefaultVariant", "component").unwrap_or(true),
-            description: child_text(e, "description", "component")?,
-            deprecated: child_text(e, "deprecated", "component")
+            is_default: attr_parse_bool(e, "isDefaultVariant", "component", opts, &l)
+                .unwrap_or(true),
+            description: child_text(e, "description", "component", opts)?,
+            deprecated: child_text(e, "deprecated", "component", opts)
                 .map(|s| s.parse().unwrap_or(false))
                 .unwrap_or(false),
-            rte_addition: child_text(e, "RTE_components_h", "component").unwrap_or_default(),
+            rte_addition: child_text(e, "RTE_components_h", "component", opts).unwrap_or_default(),
             files,
         })
     }
@@ -166,6 +185,7 @@ impl Bundle {
         let class = self.class;
         let version = self.version;
         let vendor = self.vendor;
+        let name = self.name;
         if self.components.is_empty() {
             let mut l = l.new(o!("in" => "Bundle",
                                  "Class" => class.clone()));
@@ -180,14 +200,15 @@ impl Bundle {
                 class: comp.class.or_else(|| Some(class.clone())),
                 version: comp.version.or_else(|| Some(version.clone())),
                 vendor: comp.vendor.or_else(|| vendor.clone()),
+                bundle: Some(name.clone()),
                 ..comp
             }).collect()
     }
 }
 
 impl FromElem for Bundle {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "bundle")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "bundle", opts)?;
         let name: String = attr_map(e, "Cbundle", "bundle")?;
         let class: String = attr_map(e, "Cclass", "bundle")?;
         let version: String = attr_map(e, "Cversion", "bundle")?;
@@ -197,8 +218,8 @@ impl FromElem for Bundle {
         let components = e
             .children()
             .filter_map(move |chld| {
-                if chld.name() == "component" {
-                    ComponentBuilder::from_elem(chld, &l).ok()
+                if element_name(chld, opts) == "component" {
+                    ComponentBuilder::from_elem(chld, opts, &l).ok()
                 } else {
                     None
                 }
@@ -208,8 +229,8 @@ impl FromElem for Bundle {
             class,
             version,
             vendor: attr_map(e, "Cvendor", "bundle").ok(),
-            description: child_text(e, "description", "bundle")?,
-            doc: child_text(e, "doc", "bundle")?,
+            description: child_text(e, "description", "bundle", opts)?,
+            doc: child_text(e, "doc", "bundle", opts)?,
             components,
         })
     }
@@ -217,21 +238,22 @@ impl FromElem for Bundle {
 
 fn child_to_component_iter(
     e: &Element,
+    opts: &ParseOptions,
     l: &Logger,
 ) -> Result<Box<Iterator<Item = ComponentBuilder>>, Error> {
-    match e.name() {
+    match element_name(e, opts) {
         "bundle" => {
-            let bundle = Bundle::from_elem(e, l)?;
+            let bundle = Bundle::from_elem(e, opts, l)?;
             Ok(Box::new(bundle.into_components(l).into_iter()))
         }
         "component" => {
-            let component = ComponentBuilder::from_elem(e, l)?;
+            let component = ComponentBuilder::from_elem(e, opts, l)?;
             Ok(Box::new(Some(component).into_iter()))
         }
-        _ => Err(Error::from_kind(ErrorKind::Msg(format!(
-            "element of name {} is not allowed as a descendant of components",
-            e.name()
-        )))),
+        _ => Err(PdscError::UnexpectedElement {
+            expected: String::from("component or bundle"),
+            found: e.name().to_string(),
+        }.into()),
     }
 }
 
@@ -239,11 +261,11 @@ fn child_to_component_iter(
 pub struct ComponentBuilders(pub(crate) Vec<ComponentBuilder>);
 
 impl FromElem for ComponentBuilders {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "components")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "components", opts)?;
         Ok(ComponentBuilders(
             e.children()
-                .flat_map(move |c| match child_to_component_iter(c, l) {
+                .flat_map(move |c| match child_to_component_iter(c, opts, l) {
                     Ok(iter) => iter,
                     Err(e) => {
                         error!(l, "when trying to parse component: {}", e);
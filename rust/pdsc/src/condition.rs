@@ -1,54 +1,101 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
 use minidom::{Element, Error};
 use slog::Logger;
 
-use utils::parse::{assert_root_name, attr_map, FromElem};
+use utils::parse::{assert_root_name, attr_map, element_name, FromElem, ParseOptions};
 use utils::ResultLogExt;
 
+/// One `accept`, `deny` or `require` expression of a condition. Every
+/// attribute given must match; the ones left out match anything.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ConditionComponent {
     pub device_family: Option<String>,
     pub device_sub_family: Option<String>,
     pub device_variant: Option<String>,
     pub device_vendor: Option<String>,
     pub device_name: Option<String>,
+    pub device_core: Option<String>,
+    pub device_fpu: Option<String>,
+    pub device_mpu: Option<String>,
+    pub device_endian: Option<String>,
+    pub processor: Option<String>,
+    pub compiler: Option<String>,
+    pub compiler_options: Option<String>,
+    pub component_vendor: Option<String>,
+    pub component_bundle: Option<String>,
+    pub component_class: Option<String>,
+    pub component_group: Option<String>,
+    pub component_sub_group: Option<String>,
+    pub component_variant: Option<String>,
+    pub component_version: Option<String>,
+    pub component_api_version: Option<String>,
+    /// Another condition this expression refers to
+    pub condition: Option<String>,
 }
 
 impl FromElem for ConditionComponent {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
+    fn from_elem(e: &Element, _: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        let attr = |name: &str| -> Option<String> { attr_map(e, name, "condition").ok() };
         Ok(ConditionComponent {
-            device_family: attr_map(e, "Dfamily", "condition").ok(),
-            device_sub_family: attr_map(e, "Dsubfamily", "condition").ok(),
-            device_variant: attr_map(e, "Dvariant", "condition").ok(),
-            device_vendor: attr_map(e, "Dvendor", "condition").ok(),
-            device_name: attr_map(e, "Dname", "condition").ok(),
+            device_family: attr("Dfamily"),
+            device_sub_family: attr("DsubFamily").or_else(|| attr("Dsubfamily")),
+            device_variant: attr("Dvariant"),
+            device_vendor: attr("Dvendor"),
+            device_name: attr("Dname"),
+            device_core: attr("Dcore"),
+            device_fpu: attr("Dfpu"),
+            device_mpu: attr("Dmpu"),
+            device_endian: attr("Dendian"),
+            processor: attr("Pname"),
+            compiler: attr("Tcompiler"),
+            compiler_options: attr("Toptions"),
+            component_vendor: attr("Cvendor"),
+            component_bundle: attr("Cbundle"),
+            component_class: attr("Cclass"),
+            component_group: attr("Cgroup"),
+            component_sub_group: attr("Csub"),
+            component_variant: attr("Cvariant"),
+            component_version: attr("Cversion"),
+            component_api_version: attr("Capiversion"),
+            condition: attr("condition"),
         })
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
 pub struct Condition {
     pub id: String,
+    pub description: Option<String>,
     pub accept: Vec<ConditionComponent>,
     pub deny: Vec<ConditionComponent>,
     pub require: Vec<ConditionComponent>,
 }
 
 impl FromElem for Condition {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "condition")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "condition", opts)?;
         let mut accept = Vec::new();
         let mut deny = Vec::new();
         let mut require = Vec::new();
+        let mut description = None;
         for elem in e.children() {
-            match elem.name() {
+            match element_name(elem, opts) {
                 "accept" => {
-                    accept.push(ConditionComponent::from_elem(e, l)?);
+                    accept.push(ConditionComponent::from_elem(elem, opts, l)?);
                 }
                 "deny" => {
-                    deny.push(ConditionComponent::from_elem(e, l)?);
+                    deny.push(ConditionComponent::from_elem(elem, opts, l)?);
                 }
                 "require" => {
-                    require.push(ConditionComponent::from_elem(e, l)?);
+                    require.push(ConditionComponent::from_elem(elem, opts, l)?);
+                }
+                "description" => {
+                    description = Some(elem.text());
                 }
-                "description" => {}
                 _ => {
                     warn!(l, "Found unkonwn element {} in components", elem.name());
                 }
@@ -56,6 +103,7 @@ impl FromElem for Condition {
         }
         Ok(Condition {
             id: attr_map(e, "id", "condition")?,
+            description,
             accept,
             deny,
             require,
@@ -63,15 +111,34 @@ impl FromElem for Condition {
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct Conditions(pub(crate) Vec<Condition>);
 
+impl Conditions {
+    pub fn get(&self, id: &str) -> Option<&Condition> {
+        self.0.iter().find(|cond| cond.id == id)
+    }
+
+    /// Iterate over the conditions in the order they are described in the PDSC.
+    pub fn iter(&self) -> impl Iterator<Item = &Condition> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 impl FromElem for Conditions {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "conditions")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "conditions", opts)?;
         Ok(Conditions(
             e.children()
-                .flat_map(|c| Condition::from_elem(c, l).ok_warn(l))
+                .flat_map(|c| Condition::from_elem(c, opts, l).ok_warn(l))
                 .collect(),
         ))
     }
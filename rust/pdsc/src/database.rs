@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fs::{rename, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use bincode;
+use failure::{err_msg, Error};
+use slog::Logger;
+
+use pack_index::config::Config;
+use utils::parse::{FromElem, ParseOptions};
+
+use completions::stamp;
+use device::{Device, Devices, Processors};
+use Package;
+
+/// Bumped on every incompatible change to the devices or to the layout of
+/// the cache; a cache of another version is parsed again from scratch.
+pub const DEVICE_DATABASE_VERSION: u32 = 4;
+
+const MAGIC: &[u8] = b"CMSISDDB";
+const DEFAULT_CACHE: &str = "devices.cache";
+
+/// The devices of one description, and the state of the file they were
+/// parsed from.
+#[derive(Serialize, Deserialize)]
+pub struct CachedPack {
+    pub source: String,
+    modified: u64,
+    size: u64,
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+    pub devices: Devices,
+    // Whether each processor was fully described, which devices do not
+    // serialize
+    described: BTreeMap<String, Vec<bool>>,
+}
+
+fn described_flags(device: &Device) -> Vec<bool> {
+    match device.processor {
+        Processors::Symmetric(ref prc) => vec![prc.described],
+        Processors::Asymmetric(ref map) => map.values().map(|prc| prc.described).collect(),
+    }
+}
+
+fn restore_flags(device: &mut Device, flags: &[bool]) {
+    match device.processor {
+        Processors::Symmetric(ref mut prc) => {
+            prc.described = flags.first().cloned().unwrap_or(false);
+        }
+        Processors::Asymmetric(ref mut map) => {
+            for (prc, &flag) in map.values_mut().zip(flags) {
+                prc.described = flag;
+            }
+        }
+    }
+}
+
+impl CachedPack {
+    fn parse(
+        path: &Path,
+        source: String,
+        stamp: (u64, u64),
+        opts: &ParseOptions,
+        l: &Logger,
+    ) -> Result<Self, Error> {
+        let pack = Package::from_path(path, opts, l)?;
+        let described = pack
+            .devices
+            .iter()
+            .map(|dev| (dev.name.clone(), described_flags(dev)))
+            .collect();
+        Ok(CachedPack {
+            source,
+            modified: stamp.0,
+            size: stamp.1,
+            version: pack.releases.latest_release().version.clone(),
+            vendor: pack.vendor,
+            name: pack.name,
+            devices: pack.devices,
+            described,
+        })
+    }
+
+    fn restore(mut self) -> Self {
+        for (name, flags) in &self.described {
+            if let Some(dev) = self.devices.0.get_mut(name) {
+                restore_flags(dev, flags);
+            }
+        }
+        self
+    }
+}
+
+/// The devices of every description in the pack store, kept in a binary
+/// cache so that only the descriptions that changed are parsed again.
+#[derive(Default, Serialize, Deserialize)]
+pub struct DeviceDatabase {
+    packs: Vec<CachedPack>,
+}
+
+fn read_cache(path: &Path) -> Result<DeviceDatabase, Error> {
+    let mut contents = Vec::new();
+    OpenOptions::new()
+        .read(true)
+        .open(path)?
+        .read_to_end(&mut contents)?;
+    let header = MAGIC.len() + 4;
+    if contents.len() < header || &contents[..MAGIC.len()] != MAGIC {
+        return Err(err_msg("not a device database"));
+    }
+    let version = contents[MAGIC.len()..header]
+        .iter()
+        .rev()
+        .fold(0u32, |version, &byte| version << 8 | u32::from(byte));
+    if version != DEVICE_DATABASE_VERSION {
+        return Err(err_msg("device database of another version"));
+    }
+    Ok(bincode::deserialize(&contents[header..])?)
+}
+
+fn write_cache(path: &Path, database: &DeviceDatabase) -> Result<(), Error> {
+    let temp = path.with_extension("part");
+    let mut fd = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp)?;
+    fd.write_all(MAGIC)?;
+    let version: Vec<u8> = (0..4)
+        .map(|i| (DEVICE_DATABASE_VERSION >> (8 * i)) as u8)
+        .collect();
+    fd.write_all(&version)?;
+    fd.write_all(&bincode::serialize(database)?)?;
+    drop(fd);
+    rename(&temp, path)?;
+    Ok(())
+}
+
+impl DeviceDatabase {
+    /// The devices of the pack store of `config`, cached within it.
+    pub fn open(config: &Config, l: &Logger) -> Result<Self, Error> {
+        Self::load(
+            config.pdsc_files(),
+            &config.pack_store.join(DEFAULT_CACHE),
+            &config.parse_options,
+            l,
+        )
+    }
+
+    /// The devices of the descriptions at `paths`, reusing the ones cached
+    /// in `cache` for the files that did not change since, and updating the
+    /// cache when any did.
+    pub fn load<I, P>(
+        paths: I,
+        cache: &Path,
+        opts: &ParseOptions,
+        l: &Logger,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut previous: BTreeMap<String, CachedPack> = match read_cache(cache) {
+            Ok(old) => old
+                .packs
+                .into_iter()
+                .map(|pack| (pack.source.clone(), pack))
+                .collect(),
+            Err(e) => {
+                debug!(l, "Parsing every description: {}", e);
+                BTreeMap::new()
+            }
+        };
+        let before = previous.len();
+        let mut packs = Vec::new();
+        let mut parsed = 0;
+        for path in paths {
+            let path = path.as_ref();
+            let stamp = match stamp(path) {
+                Some(stamp) => stamp,
+                None => continue,
+            };
+            let source = path.to_string_lossy().into_owned();
+            match previous.remove(&source) {
+                Some(old) if (old.modified, old.size) == stamp => {
+                    packs.push(old.restore());
+                    continue;
+                }
+                _ => (),
+            }
+            parsed += 1;
+            match CachedPack::parse(path, source, stamp, opts, l) {
+                Ok(pack) => packs.push(pack),
+                Err(e) => warn!(l, "parsing {:?}: {}", path, e),
+            }
+        }
+        packs.sort_by(|a, b| a.source.cmp(&b.source));
+        let database = DeviceDatabase { packs };
+        // Descriptions removed from `paths` change the cache as well
+        if parsed > 0 || before != database.packs.len() {
+            debug!(l, "Parsed {} of {} descriptions", parsed, database.packs.len());
+            write_cache(cache, &database)?;
+        }
+        Ok(database)
+    }
+
+    pub fn packs(&self) -> &[CachedPack] {
+        &self.packs
+    }
+
+    /// Every device, along with the pack describing it.
+    pub fn devices(&self) -> impl Iterator<Item = (&CachedPack, &Device)> {
+        self.packs
+            .iter()
+            .flat_map(|pack| pack.devices.iter().map(move |dev| (pack, dev)))
+    }
+}
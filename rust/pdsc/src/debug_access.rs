@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use minidom::{Element, Error};
+use slog::Logger;
+
+use utils::error::PdscError;
+use utils::parse::{
+    assert_root_name, attr_map, attr_parse_bool, attr_parse_hex, element_name, get_child_no_ns,
+    FromElem, ParseOptions,
+};
+use utils::ResultLogExt;
+
+use device::for_processor;
+use intern::Name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebugProtocol {
+    Swd,
+    Jtag,
+}
+
+impl FromStr for DebugProtocol {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from.to_lowercase().as_str() {
+            "swd" => Ok(DebugProtocol::Swd),
+            "jtag" => Ok(DebugProtocol::Jtag),
+            _ => Err(PdscError::Unknown {
+                what: "debug protocol",
+                value: from.to_string(),
+            }.into()),
+        }
+    }
+}
+
+/// Default debug settings, from a `debugconfig` element
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebugConfig {
+    pub default: Option<DebugProtocol>,
+    /// Debug clock, in Hz
+    pub clock: Option<u64>,
+    /// Whether the port can switch between SWD and JTAG
+    pub swj: Option<bool>,
+    /// Whether the port must be woken from the dormant state
+    pub dormant: Option<bool>,
+    /// System Description File of the debug topology
+    pub sdf: Option<PathBuf>,
+}
+
+impl DebugConfig {
+    // Attributes given by a device replace the ones of its family
+    fn or(self, parent: &Self) -> Self {
+        DebugConfig {
+            default: self.default.or(parent.default),
+            clock: self.clock.or(parent.clock),
+            swj: self.swj.or(parent.swj),
+            dormant: self.dormant.or(parent.dormant),
+            sdf: self.sdf.or_else(|| parent.sdf.clone()),
+        }
+    }
+}
+
+impl FromElem for DebugConfig {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "debugconfig", opts)?;
+        Ok(DebugConfig {
+            default: e
+                .attr("default")
+                .map(DebugProtocol::from_str)
+                .and_then(|p| p.ok_warn(l)),
+            clock: attr_parse_hex(e, "clock", "debugconfig").ok(),
+            swj: attr_parse_bool(e, "swj", "debugconfig", opts, l).ok(),
+            dormant: attr_parse_bool(e, "dormant", "debugconfig", opts, l).ok(),
+            sdf: attr_map(e, "sdf", "debugconfig").ok(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SwdPort {
+    pub idcode: Option<u64>,
+    /// Selects the target on a multi-drop SWD bus
+    pub targetsel: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JtagPort {
+    /// Position of the TAP in the scan chain
+    pub tapindex: Option<u64>,
+    pub idcode: Option<u64>,
+    /// Instruction register length of the TAP
+    pub irlen: Option<u64>,
+}
+
+/// A debug port of the device, from a `debugport` element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugPort {
+    /// The `__dp` the `debug` elements refer to this port by
+    pub id: u64,
+    pub swd: Option<SwdPort>,
+    pub jtag: Option<JtagPort>,
+}
+
+impl FromElem for DebugPort {
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "debugport", opts)?;
+        Ok(DebugPort {
+            id: attr_parse_hex(e, "__dp", "debugport").unwrap_or_default(),
+            swd: get_child_no_ns(e, "swd", opts).map(|swd| SwdPort {
+                idcode: attr_parse_hex(swd, "idcode", "swd").ok(),
+                targetsel: attr_parse_hex(swd, "targetsel", "swd").ok(),
+            }),
+            jtag: get_child_no_ns(e, "jtag", opts).map(|jtag| JtagPort {
+                tapindex: attr_parse_hex(jtag, "tapindex", "jtag").ok(),
+                idcode: attr_parse_hex(jtag, "idcode", "jtag").ok(),
+                irlen: attr_parse_hex(jtag, "irlen", "jtag").ok(),
+            }),
+        })
+    }
+}
+
+/// A step of a debug access sequence. The code and the conditions are kept
+/// as written, in the expression language of the CMSIS pack standard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceItem {
+    Block {
+        /// Run without interruption by other debugger accesses
+        atomic: bool,
+        info: Option<String>,
+        code: String,
+    },
+    Control {
+        /// Run `items` only when this is true
+        condition: Option<String>,
+        /// Repeat `items` as long as this is true
+        repeat_while: Option<String>,
+        /// Bound of a `repeat_while` loop, in microseconds
+        timeout: Option<u64>,
+        info: Option<String>,
+        items: Vec<SequenceItem>,
+    },
+}
+
+impl FromElem for SequenceItem {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        let info = attr_map(e, "info", "sequence").ok();
+        match element_name(e, opts) {
+            "block" => Ok(SequenceItem::Block {
+                atomic: attr_parse_bool(e, "atomic", "block", opts, l).unwrap_or_default(),
+                info,
+                code: e.text(),
+            }),
+            "control" => Ok(SequenceItem::Control {
+                condition: attr_map(e, "if", "control").ok(),
+                repeat_while: attr_map(e, "while", "control").ok(),
+                timeout: attr_parse_hex(e, "timeout", "control").ok(),
+                info,
+                items: SequenceItem::vec_from_children(e.children(), opts, l),
+            }),
+            other => Err(PdscError::Unknown {
+                what: "sequence element",
+                value: other.to_string(),
+            }.into()),
+        }
+    }
+}
+
+/// A debug access sequence, replacing the debugger's built-in sequence of
+/// the same name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    /// The processor the sequence applies to on an asymmetric device
+    pub pname: Option<Name>,
+    /// The built-in sequence must not be run, and nothing replaces it
+    pub disable: bool,
+    pub info: Option<String>,
+    pub items: Vec<SequenceItem>,
+}
+
+impl FromElem for Sequence {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "sequence", opts)?;
+        Ok(Sequence {
+            name: attr_map(e, "name", "sequence")?,
+            pname: attr_map(e, "Pname", "sequence").ok(),
+            disable: attr_parse_bool(e, "disable", "sequence", opts, l).unwrap_or_default(),
+            info: attr_map(e, "info", "sequence").ok(),
+            items: SequenceItem::vec_from_children(e.children(), opts, l),
+        })
+    }
+}
+
+/// How a debugger connects to the device: its default settings, debug ports
+/// and the sequences overriding the debugger's own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebugAccess {
+    pub config: Option<DebugConfig>,
+    pub ports: Vec<DebugPort>,
+    pub sequences: Vec<Sequence>,
+}
+
+impl DebugAccess {
+    pub fn is_empty(&self) -> bool {
+        self.config.is_none() && self.ports.is_empty() && self.sequences.is_empty()
+    }
+
+    pub fn port(&self, id: u64) -> Option<&DebugPort> {
+        self.ports.iter().find(|port| port.id == id)
+    }
+
+    /// The sequence `name` for processor `pname`, falling back to the one
+    /// shared by all processors.
+    pub fn sequence(&self, name: &str, pname: Option<&str>) -> Option<&Sequence> {
+        let named = || self.sequences.iter().filter(move |seq| seq.name == name);
+        named()
+            .find(|seq| seq.pname.as_ref().map(Name::as_str) == pname)
+            .or_else(|| named().find(|seq| for_processor(&seq.pname, pname)))
+    }
+
+    /// Add a `debugconfig`, `debugport` or `sequences` element.
+    pub(crate) fn add(&mut self, e: &Element, opts: &ParseOptions, l: &Logger) {
+        match element_name(e, opts) {
+            "debugconfig" => {
+                if let Some(config) = DebugConfig::from_elem(e, opts, l).ok_warn(l) {
+                    self.config = Some(match self.config.take() {
+                        Some(old) => config.or(&old),
+                        None => config,
+                    });
+                }
+            }
+            "debugport" => {
+                if let Some(port) = DebugPort::from_elem(e, opts, l).ok_warn(l) {
+                    self.ports.retain(|old| old.id != port.id);
+                    self.ports.push(port);
+                }
+            }
+            "sequences" => {
+                for seq in Sequence::vec_from_children(e.children(), opts, l) {
+                    self.sequences
+                        .retain(|old| old.name != seq.name || old.pname != seq.pname);
+                    self.sequences.push(seq);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Combine with the debug access of a parent family, keeping what this
+    /// one describes.
+    pub(crate) fn merge(mut self, parent: &Self) -> Self {
+        self.config = match (self.config, parent.config.as_ref()) {
+            (Some(mine), Some(theirs)) => Some(mine.or(theirs)),
+            (mine, theirs) => mine.or_else(|| theirs.cloned()),
+        };
+        for port in &parent.ports {
+            if self.port(port.id).is_none() {
+                self.ports.push(port.clone());
+            }
+        }
+        for seq in &parent.sequences {
+            let overridden = self
+                .sequences
+                .iter()
+                .any(|mine| mine.name == seq.name && mine.pname == seq.pname);
+            if !overridden {
+                self.sequences.push(seq.clone());
+            }
+        }
+        self
+    }
+}
@@ -1,15 +1,43 @@
-use std::collections::{BTreeMap, HashMap};
-use std::path::PathBuf;
+// Parsing must not panic on hostile input; return errors instead
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use minidom::{Element, Error, ErrorKind};
+use indexmap::IndexMap;
+use minidom::{Element, Error};
 use slog::Logger;
 
-use utils::parse::{attr_map, attr_parse, attr_parse_hex, FromElem};
-use utils::ResultLogExt;
+use utils::error::PdscError;
+use utils::parse::{
+    attr_map, attr_parse, attr_parse_bool, attr_parse_hex, element_name, get_child_no_ns,
+    DuplicateDevicePolicy, FromElem, MemoryMergePolicy, ParseOptions,
+};
+
+use debug_access::DebugAccess;
+use intern::Name;
+use report::ParseReport;
+
+fn unknown_value(what: &'static str, value: &str) -> Error {
+    PdscError::Unknown {
+        what,
+        value: value.to_string(),
+    }.into()
+}
+
+fn incomplete(elem: &str, missing: &'static str) -> Error {
+    PdscError::Incomplete {
+        elem: elem.to_string(),
+        missing,
+    }.into()
+}
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-enum Core {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Core {
     CortexM0,
     CortexM0Plus,
     CortexM1,
@@ -72,12 +100,12 @@ impl FromStr for Core {
             "Cortex-A57" => Ok(Core::CortexA57),
             "Cortex-A72" => Ok(Core::CortexA72),
             "Cortex-A73" => Ok(Core::CortexA73),
-            unknown => Err(err_msg!("Unknown core {}", unknown)),
+            unknown => Err(PdscError::UnknownCore(unknown.to_string()).into()),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FPU {
     None,
     SinglePrecision,
@@ -95,12 +123,12 @@ impl FromStr for FPU {
             "0" => Ok(FPU::None),
             "DP_FPU" => Ok(FPU::DoublePrecision),
             "2" => Ok(FPU::DoublePrecision),
-            unknown => Err(err_msg!("Unknown fpu {}", unknown)),
+            unknown => Err(unknown_value("fpu", unknown)),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MPU {
     NotPresent,
     Present,
@@ -114,7 +142,93 @@ impl FromStr for MPU {
             "1" => Ok(MPU::Present),
             "None" => Ok(MPU::NotPresent),
             "0" => Ok(MPU::NotPresent),
-            unknown => Err(err_msg!("Unknown fpu {}", unknown)),
+            unknown => Err(unknown_value("mpu", unknown)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Endian {
+    Little,
+    Big,
+    /// Selected by the application
+    Configurable,
+}
+
+impl FromStr for Endian {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from {
+            "Little-endian" => Ok(Endian::Little),
+            "Big-endian" => Ok(Endian::Big),
+            "Configurable" | "*" => Ok(Endian::Configurable),
+            unknown => Err(unknown_value("endianness", unknown)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DSP {
+    NotPresent,
+    Present,
+}
+
+impl Default for DSP {
+    fn default() -> Self {
+        DSP::NotPresent
+    }
+}
+
+impl FromStr for DSP {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from {
+            "DSP" | "1" => Ok(DSP::Present),
+            "NO_DSP" | "0" => Ok(DSP::NotPresent),
+            unknown => Err(unknown_value("dsp", unknown)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrustZone {
+    NotPresent,
+    Present,
+}
+
+impl Default for TrustZone {
+    fn default() -> Self {
+        TrustZone::NotPresent
+    }
+}
+
+impl FromStr for TrustZone {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from {
+            "TZ" | "1" => Ok(TrustZone::Present),
+            "NO_TZ" | "0" => Ok(TrustZone::NotPresent),
+            unknown => Err(unknown_value("trustzone", unknown)),
+        }
+    }
+}
+
+/// The security state a processor with TrustZone runs the application in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecureMode {
+    Secure,
+    NonSecure,
+    TrustZoneDisabled,
+}
+
+impl FromStr for SecureMode {
+    type Err = Error;
+    fn from_str(from: &str) -> Result<Self, Error> {
+        match from {
+            "Secure" => Ok(SecureMode::Secure),
+            "Non-secure" => Ok(SecureMode::NonSecure),
+            "TZ-disabled" => Ok(SecureMode::TrustZoneDisabled),
+            unknown => Err(unknown_value("secure mode", unknown)),
         }
     }
 }
@@ -125,6 +239,58 @@ pub struct Processor {
     core: Core,
     fpu: FPU,
     mpu: MPU,
+    #[serde(default)]
+    clock: Option<u64>,
+    #[serde(default)]
+    endian: Option<Endian>,
+    #[serde(default)]
+    dsp: DSP,
+    #[serde(default)]
+    trustzone: TrustZone,
+    #[serde(default)]
+    secure: Option<SecureMode>,
+    /// Whether the FPU and MPU were stated rather than assumed absent
+    #[serde(skip)]
+    pub(crate) described: bool,
+}
+
+impl Processor {
+    pub fn core(&self) -> Core {
+        self.core
+    }
+
+    pub fn units(&self) -> u8 {
+        self.units
+    }
+
+    pub fn fpu(&self) -> FPU {
+        self.fpu
+    }
+
+    pub fn mpu(&self) -> MPU {
+        self.mpu
+    }
+
+    /// The maximum clock frequency, in Hz
+    pub fn clock(&self) -> Option<u64> {
+        self.clock
+    }
+
+    pub fn endian(&self) -> Option<Endian> {
+        self.endian
+    }
+
+    pub fn dsp(&self) -> DSP {
+        self.dsp
+    }
+
+    pub fn trustzone(&self) -> TrustZone {
+        self.trustzone
+    }
+
+    pub fn secure(&self) -> Option<SecureMode> {
+        self.secure
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -133,6 +299,11 @@ struct ProcessorBuilder {
     units: Option<u8>,
     fpu: Option<FPU>,
     mpu: Option<MPU>,
+    clock: Option<u64>,
+    endian: Option<Endian>,
+    dsp: Option<DSP>,
+    trustzone: Option<TrustZone>,
+    secure: Option<SecureMode>,
 }
 
 impl ProcessorBuilder {
@@ -142,26 +313,42 @@ impl ProcessorBuilder {
             units: self.units.or(parent.units),
             fpu: self.fpu.or(parent.fpu),
             mpu: self.mpu.or(parent.mpu),
+            clock: self.clock.or(parent.clock),
+            endian: self.endian.or(parent.endian),
+            dsp: self.dsp.or(parent.dsp),
+            trustzone: self.trustzone.or(parent.trustzone),
+            secure: self.secure.or(parent.secure),
         }
     }
 
     fn build(self) -> Result<Processor, Error> {
         Ok(Processor {
-            core: self.core.ok_or_else(|| err_msg!("No Core found!"))?,
+            core: self.core.ok_or_else(|| incomplete("processor", "core"))?,
             units: self.units.unwrap_or(1u8),
             fpu: self.fpu.unwrap_or(FPU::None),
             mpu: self.mpu.unwrap_or(MPU::NotPresent),
+            clock: self.clock,
+            endian: self.endian,
+            dsp: self.dsp.unwrap_or_default(),
+            trustzone: self.trustzone.unwrap_or_default(),
+            secure: self.secure,
+            described: self.fpu.is_some() && self.mpu.is_some(),
         })
     }
 }
 
 impl FromElem for ProcessorBuilder {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
         Ok(ProcessorBuilder {
             core: attr_parse(e, "Dcore", "processor").ok(),
             units: attr_parse(e, "Punits", "processor").ok(),
             fpu: attr_parse(e, "Dfpu", "processor").ok(),
             mpu: attr_parse(e, "Dmpu", "processor").ok(),
+            clock: attr_parse(e, "Dclock", "processor").ok(),
+            endian: attr_parse(e, "Dendian", "processor").ok(),
+            dsp: attr_parse(e, "Ddsp", "processor").ok(),
+            trustzone: attr_parse(e, "Dtz", "processor").ok(),
+            secure: attr_parse(e, "Dsecure", "processor").ok(),
         })
     }
 }
@@ -185,15 +372,15 @@ impl ProcessorsBuilder {
                 Some(ProcessorsBuilder::Symmetric(ref single_core)) => {
                     Ok(ProcessorsBuilder::Symmetric(me.merge(single_core)))
                 }
-                Some(ProcessorsBuilder::Asymmetric(_)) => Err(err_msg!(
-                    "Tried to merge symmetric and asymmetric processors"
-                )),
+                Some(ProcessorsBuilder::Asymmetric(_)) => Err(PdscError::Conflict(String::from(
+                    "Tried to merge symmetric and asymmetric processors",
+                )).into()),
                 None => Ok(ProcessorsBuilder::Symmetric(me)),
             },
             ProcessorsBuilder::Asymmetric(mut me) => match *parent {
-                Some(ProcessorsBuilder::Symmetric(_)) => Err(err_msg!(
-                    "Tried to merge asymmetric and symmetric processors"
-                )),
+                Some(ProcessorsBuilder::Symmetric(_)) => Err(PdscError::Conflict(String::from(
+                    "Tried to merge asymmetric and symmetric processors",
+                )).into()),
                 Some(ProcessorsBuilder::Asymmetric(ref par_map)) => {
                     me.extend(par_map.iter().map(|(k, v)| (k.clone(), v.clone())));
                     Ok(ProcessorsBuilder::Asymmetric(me))
@@ -230,27 +417,27 @@ impl ProcessorsBuilder {
 }
 
 impl FromElem for ProcessorsBuilder {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
         Ok(match e.attr("Pname") {
             Some(name) => ProcessorsBuilder::Asymmetric(
-                Some((name.to_string(), ProcessorBuilder::from_elem(e, l)?))
+                Some((name.to_string(), ProcessorBuilder::from_elem(e, opts, l)?))
                     .into_iter()
                     .collect(),
             ),
-            None => ProcessorsBuilder::Symmetric(ProcessorBuilder::from_elem(e, l)?),
+            None => ProcessorsBuilder::Symmetric(ProcessorBuilder::from_elem(e, opts, l)?),
         })
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MemoryPermissions {
-    read: bool,
-    write: bool,
-    execute: bool,
-    peripheral: bool,
-    secure: bool,
-    non_secure: bool,
-    non_secure_callable: bool,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryPermissions {
+    pub(crate) read: bool,
+    pub(crate) write: bool,
+    pub(crate) execute: bool,
+    pub(crate) peripheral: bool,
+    pub(crate) secure: bool,
+    pub(crate) non_secure: bool,
+    pub(crate) non_secure_callable: bool,
 }
 
 impl MemoryPermissions {
@@ -278,108 +465,282 @@ impl MemoryPermissions {
         }
         ret
     }
+
+    pub fn read(&self) -> bool {
+        self.read
+    }
+
+    pub fn write(&self) -> bool {
+        self.write
+    }
+
+    pub fn execute(&self) -> bool {
+        self.execute
+    }
+
+    pub fn peripheral(&self) -> bool {
+        self.peripheral
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn non_secure(&self) -> bool {
+        self.non_secure
+    }
+
+    pub fn non_secure_callable(&self) -> bool {
+        self.non_secure_callable
+    }
 }
 
-enum NumberBool {
-    False,
-    True,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Memory {
+    pub(crate) access: MemoryPermissions,
+    pub(crate) start: u64,
+    pub(crate) size: u64,
+    pub(crate) startup: bool,
+    pub(crate) default: bool,
+    pub(crate) pname: Option<Name>,
+    #[serde(default)]
+    pub(crate) alias: Option<Name>,
+    #[serde(default)]
+    pub(crate) uninit: bool,
 }
 
-impl Into<bool> for NumberBool {
-    fn into(self) -> bool {
-        match self {
-            NumberBool::True => true,
-            NumberBool::False => false,
-        }
+impl Memory {
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn permissions(&self) -> &MemoryPermissions {
+        &self.access
+    }
+
+    /// Whether the device boots from this memory
+    pub fn startup(&self) -> bool {
+        self.startup
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    /// The processor this memory is private to, if any
+    pub fn pname(&self) -> Option<&str> {
+        self.pname.as_ref().map(Name::as_str)
+    }
+
+    /// The memory this one is another view of, such as the same RAM at a
+    /// different address
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_ref().map(Name::as_str)
+    }
+
+    /// Whether this memory must not be zeroed or loaded at startup
+    pub fn uninit(&self) -> bool {
+        self.uninit
     }
 }
 
-impl FromStr for NumberBool {
-    type Err = Error;
-    fn from_str(from: &str) -> Result<Self, Error> {
-        match from {
-            "true" => Ok(NumberBool::True),
-            "1" => Ok(NumberBool::True),
-            "false" => Ok(NumberBool::False),
-            "0" => Ok(NumberBool::False),
-            unknown => Err(err_msg!(
-                "unkown boolean found in merory startup {}",
-                unknown
-            )),
+/// A memory as described at one level of the device tree. Devices refine the
+/// memories of their family, so any attribute may be left for a parent to give.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct MemoryBuilder {
+    access: Option<MemoryPermissions>,
+    /// Access implied by an `id` such as "IROM1", used when no level gives one
+    implied_access: Option<MemoryPermissions>,
+    start: Option<u64>,
+    size: Option<u64>,
+    startup: Option<bool>,
+    default: Option<bool>,
+    pname: Option<Name>,
+    alias: Option<Name>,
+    uninit: Option<bool>,
+}
+
+impl MemoryBuilder {
+    /// Fill the attributes this memory leaves out from `parent`.
+    fn refine(self, parent: &Self) -> Self {
+        MemoryBuilder {
+            access: self.access.or_else(|| parent.access.clone()),
+            implied_access: self
+                .implied_access
+                .or_else(|| parent.implied_access.clone()),
+            start: self.start.or(parent.start),
+            size: self.size.or(parent.size),
+            startup: self.startup.or(parent.startup),
+            default: self.default.or(parent.default),
+            pname: self.pname.or_else(|| parent.pname.clone()),
+            alias: self.alias.or_else(|| parent.alias.clone()),
+            uninit: self.uninit.or(parent.uninit),
         }
     }
+
+    // Only a different placement is a conflict; other attributes are refinements
+    fn conflicts_with(&self, parent: &Self) -> bool {
+        let differs = |mine: Option<u64>, theirs: Option<u64>| match (mine, theirs) {
+            (Some(mine), Some(theirs)) => mine != theirs,
+            _ => false,
+        };
+        differs(self.start, parent.start) || differs(self.size, parent.size)
+    }
+
+    fn build(self) -> Result<Memory, Error> {
+        Ok(Memory {
+            access: self
+                .access
+                .or(self.implied_access)
+                .ok_or_else(|| incomplete("memory", "access or id"))?,
+            start: self.start.ok_or_else(|| incomplete("memory", "start"))?,
+            size: self.size.ok_or_else(|| incomplete("memory", "size"))?,
+            startup: self.startup.unwrap_or_default(),
+            default: self.default.unwrap_or_default(),
+            pname: self.pname,
+            alias: self.alias,
+            uninit: self.uninit.unwrap_or_default(),
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Memory {
-    access: MemoryPermissions,
-    start: u64,
-    size: u64,
-    startup: bool,
-    default: bool,
+fn optional_hex(e: &Element, name: &str) -> Result<Option<u64>, Error> {
+    match e.attr(name) {
+        Some(_) => attr_parse_hex(e, name, "memory").map(Some),
+        None => Ok(None),
+    }
 }
 
-struct MemElem(String, Memory);
+pub(crate) struct MemElem(pub(crate) Name, pub(crate) MemoryBuilder);
 
 impl FromElem for MemElem {
-    fn from_elem(e: &Element, _l: &Logger) -> Result<Self, Error> {
-        let access = e
-            .attr("access")
-            .or_else(|| {
-                e.attr("id").map(|memtype| {
-                    if memtype.contains("ROM") {
-                        "rx"
-                    } else if memtype.contains("RAM") {
-                        "rw"
-                    } else {
-                        ""
-                    }
-                })
-            }).map(|memtype| MemoryPermissions::from_str(memtype))
-            .unwrap();
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        let implied_access = e.attr("id").map(|memtype| {
+            if memtype.contains("ROM") {
+                MemoryPermissions::from_str("rx")
+            } else if memtype.contains("RAM") {
+                MemoryPermissions::from_str("rw")
+            } else {
+                MemoryPermissions::from_str("")
+            }
+        });
         let name = e
             .attr("id")
             .or_else(|| e.attr("name"))
-            .map(|s| s.to_string())
-            .ok_or_else(|| err_msg!("No name found for memory"))?;
-        let start = attr_parse_hex(e, "start", "memory")?;
-        let size = attr_parse_hex(e, "size", "memory")?;
-        let startup = attr_parse(e, "startup", "memory")
-            .map(|nb: NumberBool| nb.into())
-            .unwrap_or_default();
-        let default = attr_parse(e, "default", "memory")
-            .map(|nb: NumberBool| nb.into())
-            .unwrap_or_default();
+            .map(Name::new)
+            .ok_or_else(|| incomplete("memory", "name"))?;
         Ok(MemElem(
             name,
-            Memory {
-                access,
-                start,
-                size,
-                startup,
-                default,
+            MemoryBuilder {
+                access: e.attr("access").map(MemoryPermissions::from_str),
+                implied_access,
+                start: optional_hex(e, "start")?,
+                size: optional_hex(e, "size")?,
+                startup: attr_parse_bool(e, "startup", "memory", opts, l).ok(),
+                default: attr_parse_bool(e, "default", "memory", opts, l).ok(),
+                pname: attr_map(e, "Pname", "memory").ok(),
+                alias: attr_map(e, "alias", "memory").ok(),
+                uninit: attr_parse_bool(e, "uninit", "memory", opts, l).ok(),
             },
         ))
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Memories(HashMap<String, Memory>);
-
-fn merge_memories(lhs: Memories, rhs: &Memories) -> Memories {
-    let rhs: Vec<_> = rhs
-        .0
-        .iter()
-        .filter_map(|(k, v)| {
-            if lhs.0.contains_key(k) {
-                None
-            } else {
-                Some((k.clone(), v.clone()))
-            }
-        }).collect();
+pub struct Memories(pub(crate) HashMap<Name, Memory>);
+
+impl Memories {
+    pub fn get(&self, name: &str) -> Option<&Memory> {
+        self.0.get(name)
+    }
+
+    /// Iterate over the memories and their names, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Memory)> {
+        self.0.iter().map(|(name, mem)| (name.as_str(), mem))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+type MemoryBuilders = HashMap<Name, MemoryBuilder>;
+
+fn merge_memories(
+    device: Option<&str>,
+    lhs: MemoryBuilders,
+    rhs: &MemoryBuilders,
+    policy: MemoryMergePolicy,
+    l: &Logger,
+) -> Result<MemoryBuilders, Error> {
     let mut lhs = lhs;
-    lhs.0.extend(rhs);
-    lhs
+    for (k, v) in rhs {
+        let mine = match lhs.remove(k) {
+            None => {
+                lhs.insert(k.clone(), v.clone());
+                continue;
+            }
+            Some(mine) => mine,
+        };
+        if !mine.conflicts_with(v) {
+            lhs.insert(k.clone(), mine.refine(v));
+            continue;
+        }
+        warn!(
+            l, "Memory {} of device {} differs from the one of its parent",
+            k, device.unwrap_or("<unnamed>");
+            "memory" => k.as_str(), "policy" => format!("{:?}", policy)
+        );
+        match policy {
+            MemoryMergePolicy::ChildWins => {
+                lhs.insert(k.clone(), mine.refine(v));
+            }
+            MemoryMergePolicy::ParentWins => {
+                lhs.insert(k.clone(), v.clone().refine(&mine));
+            }
+            MemoryMergePolicy::Error => {
+                return Err(PdscError::Conflict(format!(
+                    "Memory {} is described differently by the device and its parent",
+                    k
+                )).into())
+            }
+            MemoryMergePolicy::RenameWithSuffix => {
+                lhs.insert(k.clone(), mine);
+                let renamed = (1..)
+                    .map(|n| format!("{}_{}", k, n))
+                    .find(|name| {
+                        !lhs.contains_key(name.as_str()) && !rhs.contains_key(name.as_str())
+                    }).map(|name| Name::new(&name))
+                    .unwrap_or_else(|| k.clone());
+                lhs.insert(renamed, v.clone());
+            }
+        }
+    }
+    Ok(lhs)
+}
+
+// Memories still missing their placement once every level is merged are
+// dropped rather than the whole device
+fn build_memories(device: &str, memories: MemoryBuilders, l: &Logger) -> Memories {
+    Memories(
+        memories
+            .into_iter()
+            .filter_map(|(name, mem)| match mem.build() {
+                Ok(mem) => Some((name, mem)),
+                Err(e) => {
+                    warn!(l, "Dropping memory {} of device {}: {}", name, device, e);
+                    None
+                }
+            }).collect(),
+    )
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -390,17 +751,97 @@ pub struct Algorithm {
     default: bool,
     ram_start: Option<u64>,
     ram_size: Option<u64>,
+    pname: Option<Name>,
+}
+
+impl Algorithm {
+    pub fn file_name(&self) -> &Path {
+        &self.file_name
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    pub fn ram_start(&self) -> Option<u64> {
+        self.ram_start
+    }
+
+    pub fn ram_size(&self) -> Option<u64> {
+        self.ram_size
+    }
+
+    pub fn pname(&self) -> Option<&str> {
+        self.pname.as_ref().map(Name::as_str)
+    }
 }
 
 impl FromElem for Algorithm {
-    fn from_elem(e: &Element, _l: &Logger) -> Result<Self, Error> {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
         Ok(Self {
             file_name: attr_map(e, "name", "algorithm")?,
             start: attr_parse_hex(e, "start", "algorithm")?,
             size: attr_parse_hex(e, "size", "algorithm")?,
             ram_start: attr_parse_hex(e, "RAMstart", "algorithm").ok(),
             ram_size: attr_parse_hex(e, "RAMsize", "algorithm").ok(),
-            default: attr_parse(e, "default", "algorithm").unwrap_or_default(),
+            default: attr_parse_bool(e, "default", "algorithm", opts, l).unwrap_or_default(),
+            pname: attr_map(e, "Pname", "algorithm").ok(),
+        })
+    }
+}
+
+/// A capability of a device, from a `feature` element, such as two CAN
+/// controllers given as `type="CAN" n="2"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feature {
+    kind: String,
+    n: Option<f64>,
+    m: Option<f64>,
+    name: Option<String>,
+    pname: Option<Name>,
+}
+
+impl Feature {
+    /// The `type` of the feature, such as "CAN" or "USBHS"
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// A count, or the lower bound of a range such as an operating voltage
+    pub fn n(&self) -> Option<f64> {
+        self.n
+    }
+
+    /// The upper bound of a range
+    pub fn m(&self) -> Option<f64> {
+        self.m
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    pub fn pname(&self) -> Option<&str> {
+        self.pname.as_ref().map(Name::as_str)
+    }
+}
+
+impl FromElem for Feature {
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        Ok(Self {
+            kind: attr_map(e, "type", "feature")?,
+            n: attr_parse(e, "n", "feature").ok(),
+            m: attr_parse(e, "m", "feature").ok(),
+            name: attr_map(e, "name", "feature").ok(),
+            pname: attr_map(e, "Pname", "feature").ok(),
         })
     }
 }
@@ -408,56 +849,341 @@ impl FromElem for Algorithm {
 #[derive(Debug)]
 struct DeviceBuilder<'dom> {
     name: Option<&'dom str>,
+    family: Option<&'dom str>,
+    sub_family: Option<&'dom str>,
+    vendor: Option<&'dom str>,
+    description: Option<String>,
+    aliases: Vec<String>,
     algorithms: Vec<Algorithm>,
-    memories: Memories,
+    features: Vec<Feature>,
+    memories: MemoryBuilders,
     processor: Option<ProcessorsBuilder>,
+    /// `svd` attributes of the `debug` elements, with their `Pname`
+    debugs: Vec<(Option<&'dom str>, &'dom str)>,
+    debug_access: DebugAccess,
+}
+
+/// A System View Description of the device, from one of its `debug` elements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugDescription {
+    pub svd: PathBuf,
+    /// The processor the description applies to on an asymmetric device
+    pub pname: Option<Name>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Device {
     pub name: String,
+    /// Other names this device is known by, from `Dalias` attributes
+    pub aliases: Vec<String>,
+    /// The `Dfamily` the device is described in
+    #[serde(default)]
+    pub family: String,
+    #[serde(default)]
+    pub sub_family: Option<String>,
+    /// The silicon vendor, from `Dvendor` without its numeric id
+    #[serde(default)]
+    pub vendor: String,
+    /// The numeric id that follows the vendor name in `Dvendor`
+    #[serde(default)]
+    pub vendor_id: Option<u32>,
+    #[serde(default)]
+    pub description: Option<String>,
     pub memories: Memories,
     pub algorithms: Vec<Algorithm>,
+    #[serde(default)]
+    pub features: Vec<Feature>,
     pub processor: Processors,
+    /// System View Description of the device, from its `debug` element
+    pub svd: Option<PathBuf>,
+    /// Every System View Description, one per processor on devices that
+    /// describe their processors separately
+    pub debug: Vec<DebugDescription>,
+    /// Debug settings, ports and access sequences
+    pub debug_access: DebugAccess,
+}
+
+// Elements without a Pname are shared by all processors, and asking for no
+// processor in particular matches everything
+pub(crate) fn for_processor<S: AsRef<str>>(elem_pname: &Option<S>, pname: Option<&str>) -> bool {
+    match (elem_pname.as_ref(), pname) {
+        (Some(elem), Some(pname)) => elem.as_ref() == pname,
+        _ => true,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RamRegion<'a> {
+    pub name: &'a str,
+    pub start: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedAlgorithm<'a> {
+    pub file_name: &'a Path,
+    pub start: u64,
+    pub size: u64,
+    pub default: bool,
+    pub ram_start: Option<u64>,
+    pub ram_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreInfo<'a> {
+    /// The `Pname` of the processor on an asymmetric device
+    pub name: Option<&'a str>,
+    pub core: Core,
+    pub units: u8,
+    pub fpu: FPU,
+    pub mpu: MPU,
+}
+
+impl<'a> CoreInfo<'a> {
+    fn new(name: Option<&'a str>, prc: &Processor) -> Self {
+        CoreInfo {
+            name,
+            core: prc.core,
+            units: prc.units,
+            fpu: prc.fpu,
+            mpu: prc.mpu,
+        }
+    }
+}
+
+impl Device {
+    pub fn memories(&self) -> &Memories {
+        &self.memories
+    }
+
+    pub fn algorithms(&self) -> &[Algorithm] {
+        &self.algorithms
+    }
+
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    /// How many of `kind` the device has, such as CAN controllers, adding
+    /// up the `n` of its features of that type. A feature without one counts
+    /// once.
+    pub fn feature_count(&self, kind: &str) -> u32 {
+        self.features
+            .iter()
+            .filter(|feature| feature.kind == kind)
+            .map(|feature| feature.n.map(|n| n as u32).unwrap_or(1))
+            .sum()
+    }
+
+    /// One entry per processor description; a description may cover several
+    /// identical cores through its `units`.
+    pub fn topology(&self) -> Vec<CoreInfo> {
+        match self.processor {
+            Processors::Symmetric(ref prc) => vec![CoreInfo::new(None, prc)],
+            Processors::Asymmetric(ref map) => map
+                .iter()
+                .map(|(name, prc)| CoreInfo::new(Some(name), prc))
+                .collect(),
+        }
+    }
+
+    pub fn core_count(&self) -> usize {
+        self.topology().iter().map(|core| core.units as usize).sum()
+    }
+
+    pub fn is_multicore(&self) -> bool {
+        self.core_count() > 1
+    }
+
+    /// The names of the processors of an asymmetric multi-core device; empty
+    /// for a symmetric device.
+    pub fn processor_names(&self) -> Vec<&str> {
+        match self.processor {
+            Processors::Symmetric(_) => Vec::new(),
+            Processors::Asymmetric(ref map) => map.keys().map(String::as_str).collect(),
+        }
+    }
+
+    /// The RAM a flash algorithm runs from on processor `pname`: the default
+    /// writable region visible to it, falling back to the lowest one.
+    pub fn default_ram(&self, pname: Option<&str>) -> Option<RamRegion> {
+        let mut candidates: Vec<_> = self
+            .memories
+            .0
+            .iter()
+            .filter(|&(_, mem)| {
+                mem.access.write && !mem.access.peripheral && for_processor(&mem.pname, pname)
+            }).collect();
+        candidates.sort_by_key(|&(name, mem)| (!mem.default, mem.start, name.clone()));
+        candidates.first().map(|&(name, mem)| RamRegion {
+            name: name.as_str(),
+            start: mem.start,
+            size: mem.size,
+        })
+    }
+
+    /// The flash algorithms usable from processor `pname`, each paired with
+    /// the RAM it should be loaded to.
+    pub fn algorithms_for(&self, pname: Option<&str>) -> Vec<ResolvedAlgorithm> {
+        let ram = self.default_ram(pname);
+        self.algorithms
+            .iter()
+            .filter(|alg| for_processor(&alg.pname, pname))
+            .map(|alg| ResolvedAlgorithm {
+                file_name: &alg.file_name,
+                start: alg.start,
+                size: alg.size,
+                default: alg.default,
+                ram_start: alg.ram_start.or_else(|| ram.map(|r| r.start)),
+                ram_size: alg.ram_size.or_else(|| ram.map(|r| r.size)),
+            }).collect()
+    }
+
+    /// The System View Description for processor `pname`, falling back to
+    /// the one shared by all processors.
+    pub fn svd_for(&self, pname: Option<&str>) -> Option<&Path> {
+        self.debug
+            .iter()
+            .find(|d| d.pname.as_ref().map(Name::as_str) == pname)
+            .or_else(|| self.debug.iter().find(|d| for_processor(&d.pname, pname)))
+            .map(|d| d.svd.as_path())
+    }
+
+    /// The flash algorithm marked as default for processor `pname`, or the
+    /// only one usable from it when none is marked.
+    pub fn default_algorithm(&self, pname: Option<&str>) -> Option<ResolvedAlgorithm> {
+        let mut algorithms = self.algorithms_for(pname);
+        match algorithms.iter().position(|alg| alg.default) {
+            Some(index) => Some(algorithms.swap_remove(index)),
+            None if algorithms.len() == 1 => algorithms.pop(),
+            None => None,
+        }
+    }
+}
+
+// A `Dvendor` is the name of the vendor and its id, as in "STMicroelectronics:13"
+fn split_vendor(dvendor: &str) -> (String, Option<u32>) {
+    match dvendor.rfind(':') {
+        Some(colon) => (
+            dvendor[..colon].trim().to_string(),
+            dvendor[colon + 1..].trim().parse().ok(),
+        ),
+        None => (dvendor.trim().to_string(), None),
+    }
 }
 
 impl<'dom> DeviceBuilder<'dom> {
-    fn from_elem(e: &'dom Element) -> Self {
-        let memories = Memories(HashMap::new());
+    fn from_elem(e: &'dom Element, opts: &ParseOptions) -> Self {
         DeviceBuilder {
             name: e.attr("Dname").or_else(|| e.attr("Dvariant")),
-            memories,
+            family: e.attr("Dfamily"),
+            sub_family: e.attr("DsubFamily"),
+            vendor: e.attr("Dvendor"),
+            description: get_child_no_ns(e, "description", opts)
+                .map(|desc| desc.text().trim().to_string()),
+            aliases: e
+                .attr("Dalias")
+                .map(|aliases| {
+                    aliases
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|alias| !alias.is_empty())
+                        .map(String::from)
+                        .collect()
+                }).unwrap_or_default(),
+            memories: HashMap::new(),
             algorithms: Vec::new(),
+            features: Vec::new(),
             processor: None,
+            debugs: Vec::new(),
+            debug_access: DebugAccess::default(),
         }
     }
 
-    fn build(self) -> Result<Device, Error> {
-        let name = self
+    fn build(self, l: &Logger) -> Result<Device, Error> {
+        let name: String = self
             .name
             .map(|s| s.into())
-            .ok_or_else(|| err_msg!("Device found without a name"))?;
+            .ok_or_else(|| incomplete("device", "name"))?;
+        let (vendor, vendor_id) = self.vendor.map(split_vendor).unwrap_or_default();
         Ok(Device {
             processor: match self.processor {
                 Some(pb) => pb.build()?,
-                None => return Err(err_msg!("Device found without a processor {}", name)),
+                None => return Err(incomplete(&format!("device {}", name), "processor")),
             },
+            memories: build_memories(&name, self.memories, l),
             name,
-            memories: self.memories,
+            aliases: self.aliases,
+            family: self.family.map(String::from).unwrap_or_default(),
+            sub_family: self.sub_family.map(String::from),
+            vendor,
+            vendor_id,
+            description: self.description,
             algorithms: self.algorithms,
+            features: self.features,
+            svd: self
+                .debugs
+                .iter()
+                .find(|&&(pname, _)| pname.is_none())
+                .or_else(|| self.debugs.first())
+                .map(|&(_, svd)| PathBuf::from(svd)),
+            debug: self
+                .debugs
+                .iter()
+                .map(|&(pname, svd)| DebugDescription {
+                    svd: PathBuf::from(svd),
+                    pname: pname.map(Name::new),
+                }).collect(),
+            debug_access: self.debug_access,
         })
     }
 
-    fn add_parent(mut self, parent: &Self) -> Result<Self, Error> {
+    fn add_parent(
+        mut self,
+        parent: &Self,
+        opts: &ParseOptions,
+        l: &Logger,
+    ) -> Result<Self, Error> {
         self.algorithms.extend_from_slice(&parent.algorithms);
+        // A feature of the same type and processor replaces the one of a parent
+        let inherited: Vec<Feature> = parent
+            .features
+            .iter()
+            .filter(|theirs| {
+                self.features
+                    .iter()
+                    .all(|mine| mine.kind != theirs.kind || mine.pname != theirs.pname)
+            }).cloned()
+            .collect();
+        self.features.extend(inherited);
+        for &(pname, svd) in &parent.debugs {
+            if self.debugs.iter().all(|&(mine, _)| mine != pname) {
+                self.debugs.push((pname, svd));
+            }
+        }
+        let name = self.name.or(parent.name);
         Ok(Self {
-            name: self.name.or(parent.name),
+            name,
+            family: self.family.or(parent.family),
+            sub_family: self.sub_family.or(parent.sub_family),
+            vendor: self.vendor.or(parent.vendor),
+            description: self.description.or_else(|| parent.description.clone()),
+            aliases: self.aliases,
             algorithms: self.algorithms,
-            memories: merge_memories(self.memories, &parent.memories),
+            features: self.features,
+            memories: merge_memories(
+                name,
+                self.memories,
+                &parent.memories,
+                opts.memory_merge,
+                l,
+            )?,
             processor: match self.processor {
                 Some(old_proc) => Some(old_proc.merge(&parent.processor)?),
                 None => parent.processor.clone(),
             },
+            debugs: self.debugs,
+            debug_access: self.debug_access.merge(&parent.debug_access),
         })
     }
 
@@ -470,7 +1196,7 @@ impl<'dom> DeviceBuilder<'dom> {
     }
 
     fn add_memory(&mut self, MemElem(name, mem): MemElem) -> &mut Self {
-        self.memories.0.insert(name, mem);
+        self.memories.insert(name, mem);
         self
     }
 
@@ -478,32 +1204,95 @@ impl<'dom> DeviceBuilder<'dom> {
         self.algorithms.push(alg);
         self
     }
+
+    fn add_feature(&mut self, feature: Feature) -> &mut Self {
+        self.features.push(feature);
+        self
+    }
+
+    fn add_debug(&mut self, e: &'dom Element) -> &mut Self {
+        if let Some(svd) = e.attr("svd") {
+            let pname = e.attr("Pname");
+            match self.debugs.iter_mut().find(|debug| debug.0 == pname) {
+                Some(debug) => debug.1 = svd,
+                None => self.debugs.push((pname, svd)),
+            }
+        }
+        self
+    }
+}
+
+/// A device described in a PDSC that could not be built, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedDevice {
+    pub name: String,
+    pub reason: String,
+}
+
+fn keep_or_drop<T>(
+    name: Option<&str>,
+    res: Result<T, Error>,
+    report: &mut ParseReport,
+    l: &Logger,
+) -> Option<T> {
+    match res {
+        Ok(kept) => Some(kept),
+        Err(e) => {
+            let name = name.unwrap_or("<unnamed>").to_string();
+            warn!(l, "Dropping device {}: {}", name, e);
+            report.dropped_devices.push(DroppedDevice {
+                name,
+                reason: e.to_string(),
+            });
+            None
+        }
+    }
 }
 
-fn parse_device<'dom>(e: &'dom Element, l: &Logger) -> Vec<DeviceBuilder<'dom>> {
-    let mut device = DeviceBuilder::from_elem(e);
+fn parse_device<'dom>(
+    e: &'dom Element,
+    opts: &ParseOptions,
+    l: &Logger,
+    report: &mut ParseReport,
+) -> Vec<DeviceBuilder<'dom>> {
+    let parent = e.attr("Dname");
+    let mut device = DeviceBuilder::from_elem(e, opts);
     let variants = e
         .children()
-        .filter_map(|child| match child.name() {
-            "variant" => Some(DeviceBuilder::from_elem(child)),
+        .filter_map(|child| match element_name(child, opts) {
+            "variant" => Some(DeviceBuilder::from_elem(child, opts)),
             "memory" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|mem| device.add_memory(mem));
                 None
             }
             "algorithm" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|alg| device.add_algorithm(alg));
                 None
             }
+            "feature" => {
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
+                    .map(|feature| device.add_feature(feature));
+                None
+            }
             "processor" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|prc| device.add_processor(prc));
                 None
             }
+            "debug" => {
+                device.add_debug(child);
+                None
+            }
+            "debugconfig" | "debugport" | "sequences" => {
+                device.debug_access.add(child, opts, l);
+                None
+            }
             _ => None,
         }).collect::<Vec<_>>();
     if variants.is_empty() {
@@ -511,92 +1300,368 @@ fn parse_device<'dom>(e: &'dom Element, l: &Logger) -> Vec<DeviceBuilder<'dom>>
     } else {
         variants
             .into_iter()
-            .flat_map(|bld| bld.add_parent(&device).ok_warn(l))
-            .collect()
+            .flat_map(|bld| {
+                let name = bld.name.or(device.name);
+                keep_or_drop(name, bld.add_parent(&device, opts, l), report, l)
+            }).collect()
     }
 }
 
-fn parse_sub_family<'dom>(e: &'dom Element, l: &Logger) -> Vec<DeviceBuilder<'dom>> {
-    let mut sub_family_device = DeviceBuilder::from_elem(e);
+fn parse_sub_family<'dom>(
+    e: &'dom Element,
+    opts: &ParseOptions,
+    l: &Logger,
+    report: &mut ParseReport,
+) -> Vec<DeviceBuilder<'dom>> {
+    let parent = e.attr("DsubFamily");
+    let mut sub_family_device = DeviceBuilder::from_elem(e, opts);
     let devices = e
         .children()
-        .flat_map(|child| match child.name() {
-            "device" => parse_device(child, l),
+        .flat_map(|child| match element_name(child, opts) {
+            "device" => parse_device(child, opts, l, report),
             "memory" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|mem| sub_family_device.add_memory(mem));
                 Vec::new()
             }
             "algorithm" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|alg| sub_family_device.add_algorithm(alg));
                 Vec::new()
             }
+            "feature" => {
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
+                    .map(|feature| sub_family_device.add_feature(feature));
+                Vec::new()
+            }
             "processor" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|prc| sub_family_device.add_processor(prc));
                 Vec::new()
             }
+            "debug" => {
+                sub_family_device.add_debug(child);
+                Vec::new()
+            }
+            "debugconfig" | "debugport" | "sequences" => {
+                sub_family_device.debug_access.add(child, opts, l);
+                Vec::new()
+            }
             _ => Vec::new(),
         }).collect::<Vec<_>>();
     devices
         .into_iter()
-        .flat_map(|bldr| bldr.add_parent(&sub_family_device).ok_warn(l))
-        .collect()
+        .flat_map(|bldr| {
+            let name = bldr.name;
+            let built = bldr.add_parent(&sub_family_device, opts, l);
+            keep_or_drop(name, built, report, l)
+        }).collect()
 }
 
-fn parse_family(e: &Element, l: &Logger) -> Result<Vec<Device>, Error> {
-    let mut family_device = DeviceBuilder::from_elem(e);
+fn parse_family(
+    e: &Element,
+    opts: &ParseOptions,
+    l: &Logger,
+    report: &mut ParseReport,
+) -> Vec<Device> {
+    let parent = e.attr("Dfamily");
+    let mut family_device = DeviceBuilder::from_elem(e, opts);
     let all_devices = e
         .children()
-        .flat_map(|child| match child.name() {
-            "subFamily" => parse_sub_family(child, &l),
-            "device" => parse_device(child, &l),
+        .flat_map(|child| match element_name(child, opts) {
+            "subFamily" => parse_sub_family(child, opts, l, report),
+            "device" => parse_device(child, opts, l, report),
             "memory" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|mem| family_device.add_memory(mem));
                 Vec::new()
             }
             "algorithm" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|alg| family_device.add_algorithm(alg));
                 Vec::new()
             }
+            "feature" => {
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
+                    .map(|feature| family_device.add_feature(feature));
+                Vec::new()
+            }
             "processor" => {
-                FromElem::from_elem(child, l)
-                    .ok_warn(l)
+                report
+                    .keep_or_skip(parent, child, FromElem::from_elem(child, opts, l), l)
                     .map(|prc| family_device.add_processor(prc));
                 Vec::new()
             }
+            "debug" => {
+                family_device.add_debug(child);
+                Vec::new()
+            }
+            "debugconfig" | "debugport" | "sequences" => {
+                family_device.debug_access.add(child, opts, l);
+                Vec::new()
+            }
             _ => Vec::new(),
         }).collect::<Vec<_>>();
     all_devices
         .into_iter()
-        .map(|bldr| bldr.add_parent(&family_device).and_then(|dev| dev.build()))
-        .collect()
+        .flat_map(|bldr| {
+            let name = bldr.name;
+            let built = bldr
+                .add_parent(&family_device, opts, l)
+                .and_then(|dev| dev.build(l));
+            keep_or_drop(name, built, report, l)
+        }).collect()
 }
 
-#[derive(Default, Serialize)]
-pub struct Devices(pub(crate) HashMap<String, Device>);
+#[derive(Default, Serialize, Deserialize)]
+pub struct Devices(pub(crate) IndexMap<String, Device>);
 
-impl FromElem for Devices {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        e.children()
-            .fold(Ok(HashMap::new()), |res, c| {
-                match (res, parse_family(c, l)) {
-                    (Ok(mut devs), Ok(add_this)) => {
-                        devs.extend(add_this.into_iter().map(|dev| (dev.name.clone(), dev)));
-                        Ok(devs)
+impl Devices {
+    pub fn get(&self, name: &str) -> Option<&Device> {
+        self.0.get(name)
+    }
+
+    /// Iterate over the devices in the order they are described in the PDSC.
+    pub fn iter(&self) -> impl Iterator<Item = &Device> {
+        self.0.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Devices {
+    /// Parse a `devices` element, returning the devices that could be built
+    /// beside a report of the devices and elements that could not.
+    pub fn from_elem_with_report(
+        e: &Element,
+        opts: &ParseOptions,
+        l: &Logger,
+    ) -> (Self, ParseReport) {
+        let policy = opts.duplicate_devices;
+        let mut report = ParseReport::default();
+        let mut devs = IndexMap::new();
+        let mut rejected = HashSet::new();
+        for c in e.children() {
+            for dev in parse_family(c, opts, l, &mut report) {
+                let name = dev.name.clone();
+                if !devs.contains_key(&name) && !rejected.contains(&name) {
+                    devs.insert(name, dev);
+                    continue;
+                }
+                warn!(
+                    l, "Device {} is described more than once", name;
+                    "policy" => format!("{:?}", policy)
+                );
+                match policy {
+                    DuplicateDevicePolicy::LastWins => {
+                        devs.insert(name.clone(), dev);
+                    }
+                    DuplicateDevicePolicy::FirstWins => (),
+                    DuplicateDevicePolicy::Reject => {
+                        rejected.insert(name.clone());
                     }
-                    (Ok(_), Err(e)) => Err(e),
-                    (Err(e), Ok(_)) => Err(e),
-                    (Err(e), Err(_)) => Err(e),
                 }
-            }).map(Devices)
+                report.dropped_devices.push(DroppedDevice {
+                    name,
+                    reason: "described more than once".to_string(),
+                });
+            }
+        }
+        devs.retain(|name, _| !rejected.contains(name));
+        (Devices(devs), report)
+    }
+}
+
+impl FromElem for Devices {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        Ok(Devices::from_elem_with_report(e, opts, l).0)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+    use Package;
+
+    fn memories(elems: &[&str], l: &Logger) -> MemoryBuilders {
+        elems
+            .iter()
+            .map(|elem| {
+                let MemElem(name, mem) =
+                    MemElem::from_bytes(elem.as_bytes(), &ParseOptions::default(), l).unwrap();
+                (name, mem)
+            }).collect()
+    }
+
+    #[test]
+    fn inherit_access() {
+        let l = Logger::root(Discard, o!());
+        let family = memories(
+            &[
+                r#"<memory id="IROM1" access="rxs" start="0x0" size="0x1000"/>"#,
+                r#"<memory id="IRAM1" start="0x20000000" size="0x400"/>"#,
+            ],
+            &l,
+        );
+        let device = memories(
+            &[
+                r#"<memory id="IROM1" startup="1"/>"#,
+                r#"<memory id="IRAM1" access="rwn"/>"#,
+            ],
+            &l,
+        );
+        let merged =
+            merge_memories(None, device, &family, MemoryMergePolicy::ChildWins, &l).unwrap();
+        let built = build_memories("dev", merged, &l);
+        let rom = built.get("IROM1").unwrap();
+        assert_eq!(rom.permissions(), &MemoryPermissions::from_str("rxs"));
+        assert_eq!((rom.start(), rom.size(), rom.startup()), (0, 0x1000, true));
+        let ram = built.get("IRAM1").unwrap();
+        assert_eq!(ram.permissions(), &MemoryPermissions::from_str("rwn"));
+        assert_eq!(ram.start(), 0x2000_0000);
+    }
+
+    #[test]
+    fn alias_uninit_and_pname() {
+        let l = Logger::root(Discard, o!());
+        let family = memories(
+            &[r#"<memory name="SRAM_M4" access="rw" start="0x10000000" size="0x8000"
+                   uninit="1" alias="SRAM_M0" Pname="M4"/>"#],
+            &l,
+        );
+        let device = memories(&[r#"<memory name="SRAM_M4" default="1"/>"#], &l);
+        let merged =
+            merge_memories(None, device, &family, MemoryMergePolicy::ChildWins, &l).unwrap();
+        let built = build_memories("dev", merged, &l);
+        let sram = built.get("SRAM_M4").unwrap();
+        assert!(sram.uninit() && sram.is_default());
+        assert_eq!(sram.alias(), Some("SRAM_M0"));
+        assert_eq!(sram.pname(), Some("M4"));
+    }
+
+    #[test]
+    fn report_broken_parts() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Broken families</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Broken Series" Dvendor="ARM:82">
+                  <device Dname="NoCore">
+                    <memory id="IROM1" start="0x0" size="0x1000"/>
+                  </device>
+                </family>
+                <family Dfamily="Good Series" Dvendor="ARM:82">
+                  <processor Dcore="Cortex-M0"/>
+                  <memory id="IROM1" start="0x0" size="huge"/>
+                  <device Dname="Good">
+                    <memory id="IRAM1" start="0x20000000" size="0x400"/>
+                  </device>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let (devices, report) = (pack.devices(), pack.report());
+        assert!(devices.get("Good").is_some());
+        assert_eq!(report.dropped_devices[0].name, "NoCore");
+        assert_eq!(report.skipped_elements[0].parent, "Good Series");
+        assert_eq!(report.skipped_elements[0].element, "memory");
+    }
+
+    #[test]
+    fn vendor_and_description() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Descriptions</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Test Series" Dvendor="STMicroelectronics:13">
+                  <processor Dcore="Cortex-M0"/>
+                  <description>
+                    A test family
+                  </description>
+                  <device Dname="Plain"/>
+                  <device Dname="Described">
+                    <description>A test device</description>
+                  </device>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let plain = pack.devices().get("Plain").unwrap();
+        assert_eq!((plain.vendor.as_str(), plain.vendor_id), ("STMicroelectronics", Some(13)));
+        assert_eq!(plain.description, Some("A test family".to_string()));
+        let described = pack.devices().get("Described").unwrap();
+        assert_eq!(described.description, Some("A test device".to_string()));
+        assert_eq!(split_vendor("Generic"), ("Generic".to_string(), None));
+    }
+
+    #[test]
+    fn inherit_features() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Features</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Test Series" Dvendor="ARM:82">
+                  <processor Dcore="Cortex-M4"/>
+                  <feature type="CAN" n="1"/>
+                  <feature type="VCC" n="1.8" m="3.6"/>
+                  <subFamily DsubFamily="Connected">
+                    <feature type="USBHS" n="1" name="USB High Speed"/>
+                    <device Dname="Dev">
+                      <feature type="CAN" n="2"/>
+                    </device>
+                  </subFamily>
+                  <device Dname="Plain"/>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let dev = pack.devices().get("Dev").unwrap();
+        assert_eq!(dev.features().len(), 3);
+        assert_eq!(dev.feature_count("CAN"), 2);
+        assert_eq!(dev.feature_count("USBHS"), 1);
+        let vcc = dev.features().iter().find(|f| f.kind() == "VCC").unwrap();
+        assert_eq!((vcc.n(), vcc.m()), (Some(1.8), Some(3.6)));
+        let plain = pack.devices().get("Plain").unwrap();
+        assert_eq!(plain.feature_count("CAN"), 1);
+        assert_eq!(plain.feature_count("USBHS"), 0);
     }
 }
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure::Error as FailError;
+use serde_json;
+use slog::Logger;
+
+use pack_index::config::Config;
+
+use device::{Core, Device, FPU, MPU};
+use lookup::resolve_used_device;
+use pool::parse_packages;
+use roles::MemoryRole;
+
+/// One side of a comparison is `None` when the element only exists on the
+/// other device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Difference<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MemoryExtent {
+    pub start: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CoreSummary {
+    pub core: Core,
+    pub units: u8,
+    pub fpu: FPU,
+    pub mpu: MPU,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AlgorithmExtent {
+    pub start: u64,
+    pub size: u64,
+    pub default: bool,
+}
+
+/// What changes when moving a design from one device to another. Only the
+/// elements that differ are listed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeviceDiff {
+    pub memories: BTreeMap<String, Difference<MemoryExtent>>,
+    /// Keyed by processor name; symmetric devices use an empty name
+    pub cores: BTreeMap<String, Difference<CoreSummary>>,
+    /// Keyed by algorithm file name
+    pub algorithms: BTreeMap<String, Difference<AlgorithmExtent>>,
+    /// Change in total flash size, in bytes
+    pub flash_delta: i64,
+    /// Change in total RAM size, in bytes
+    pub ram_delta: i64,
+}
+
+impl DeviceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.memories.is_empty()
+            && self.cores.is_empty()
+            && self.algorithms.is_empty()
+            && self.flash_delta == 0
+            && self.ram_delta == 0
+    }
+}
+
+fn compare<T: PartialEq>(
+    before: BTreeMap<String, T>,
+    mut after: BTreeMap<String, T>,
+) -> BTreeMap<String, Difference<T>> {
+    let mut differences = BTreeMap::new();
+    for (key, old) in before {
+        let new = after.remove(&key);
+        if new.as_ref() != Some(&old) {
+            differences.insert(
+                key,
+                Difference {
+                    before: Some(old),
+                    after: new,
+                },
+            );
+        }
+    }
+    for (key, new) in after {
+        differences.insert(
+            key,
+            Difference {
+                before: None,
+                after: Some(new),
+            },
+        );
+    }
+    differences
+}
+
+fn memories(dev: &Device) -> BTreeMap<String, MemoryExtent> {
+    dev.memories
+        .0
+        .iter()
+        .map(|(name, mem)| {
+            (
+                name.to_string(),
+                MemoryExtent {
+                    start: mem.start,
+                    size: mem.size,
+                },
+            )
+        }).collect()
+}
+
+fn cores(dev: &Device) -> BTreeMap<String, CoreSummary> {
+    dev.topology()
+        .into_iter()
+        .map(|info| {
+            (
+                info.name.unwrap_or_default().to_string(),
+                CoreSummary {
+                    core: info.core,
+                    units: info.units,
+                    fpu: info.fpu,
+                    mpu: info.mpu,
+                },
+            )
+        }).collect()
+}
+
+fn algorithms(dev: &Device) -> BTreeMap<String, AlgorithmExtent> {
+    dev.algorithms_for(None)
+        .into_iter()
+        .map(|alg| {
+            (
+                alg.file_name.to_string_lossy().into_owned(),
+                AlgorithmExtent {
+                    start: alg.start,
+                    size: alg.size,
+                    default: alg.default,
+                },
+            )
+        }).collect()
+}
+
+fn total_size(dev: &Device, roles: &[MemoryRole]) -> i64 {
+    let memory_roles = dev.memory_roles();
+    dev.memories
+        .0
+        .iter()
+        .filter(|&(name, _)| {
+            memory_roles
+                .get(name.as_str())
+                .map(|role| roles.contains(role))
+                .unwrap_or(false)
+        }).map(|(_, mem)| mem.size as i64)
+        .sum()
+}
+
+impl Device {
+    /// Compare this device with `other`, seen as the device being migrated to.
+    pub fn diff(&self, other: &Device) -> DeviceDiff {
+        let flash = [MemoryRole::PrimaryFlash, MemoryRole::SecondaryFlash];
+        let ram = [
+            MemoryRole::PrimaryRam,
+            MemoryRole::SecondaryRam,
+            MemoryRole::BackupRam,
+        ];
+        DeviceDiff {
+            memories: compare(memories(self), memories(other)),
+            cores: compare(cores(self), cores(other)),
+            algorithms: compare(algorithms(self), algorithms(other)),
+            flash_delta: total_size(other, &flash) - total_size(self, &flash),
+            ram_delta: total_size(other, &ram) - total_size(self, &ram),
+        }
+    }
+}
+
+pub fn diff_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("diff")
+        .about("Compare two devices from the pack store")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("FROM")
+                .help("Device the design currently targets, as NAME or VENDOR::NAME")
+                .required(true)
+                .index(1),
+        ).arg(
+            Arg::with_name("TO")
+                .help("Device to compare it with, as NAME or VENDOR::NAME")
+                .required(true)
+                .index(2),
+        )
+}
+
+pub fn diff_command<'a>(c: &Config, args: &ArgMatches<'a>, l: &Logger) -> Result<(), FailError> {
+    let from = args.value_of("FROM").unwrap();
+    let to = args.value_of("TO").unwrap();
+    let packs = parse_packages(c.pdsc_files(), c, l);
+    let from = resolve_used_device(c, &packs, from, l)?.device;
+    let to = resolve_used_device(c, &packs, to, l)?.device;
+    let diff = from.diff(to);
+    println!("{}", serde_json::to_string_pretty(&diff)?);
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+#[cfg(feature = "fs")]
+use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
+use std::io::{Read, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use failure::Error;
+use indexmap::IndexMap;
+use serde_cbor;
+use serde_json;
+#[cfg(feature = "fs")]
+use slog::Logger;
+
+use debug_access::DebugAccess;
+use device::{Device, Devices, Processors};
+use {device_database, DumpDevice, Package};
+
+/// How a device database is written. Both hold the same values, laid out as
+/// described by `DEVICES_SCHEMA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Json,
+    /// CBOR (RFC 7049), a fraction of the size of the JSON
+    Cbor,
+}
+
+impl Default for DumpFormat {
+    fn default() -> Self {
+        DumpFormat::Json
+    }
+}
+
+/// Every device of `pdscs` in `format`, as a map keyed by device name in
+/// natural order.
+pub fn dumps_device_database<'a, I>(pdscs: I, format: DumpFormat) -> Result<Vec<u8>, Error>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    let pdscs: Vec<&Package> = pdscs.into_iter().collect();
+    let devices = device_database(&pdscs);
+    Ok(match format {
+        DumpFormat::Json => serde_json::to_vec_pretty(&devices)?,
+        DumpFormat::Cbor => serde_cbor::to_vec(&devices)?,
+    })
+}
+
+/// Write every device of `pdscs` to `dest` in `format`, replacing it, as
+/// a map keyed by device name in natural order.
+#[cfg(feature = "fs")]
+pub fn dump_device_database<'a, P, I>(
+    pdscs: I,
+    dest: P,
+    format: DumpFormat,
+    l: &Logger,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a Package>,
+{
+    let contents = dumps_device_database(pdscs, format)?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dest.as_ref())?
+        .write_all(&contents)?;
+    debug!(
+        l,
+        "Wrote {} bytes to {:?} as {:?}",
+        contents.len(),
+        dest.as_ref(),
+        format
+    );
+    Ok(())
+}
+
+impl<'a> DumpDevice<'a> {
+    // What a dump leaves out of a device, such as its debug settings, is
+    // left empty
+    fn into_device(self) -> Device {
+        let mut processor = self.processor.into_owned();
+        if let Some(completeness) = self.completeness {
+            match processor {
+                Processors::Symmetric(ref mut prc) => prc.described = completeness.processor,
+                Processors::Asymmetric(ref mut map) => {
+                    for prc in map.values_mut() {
+                        prc.described = completeness.processor;
+                    }
+                }
+            }
+        }
+        Device {
+            name: self.name.into_owned(),
+            aliases: Vec::new(),
+            family: self.family.into_owned(),
+            sub_family: self.sub_family.map(Cow::into_owned),
+            vendor: self.vendor.into_owned(),
+            vendor_id: self.vendor_id,
+            description: self.description.map(Cow::into_owned),
+            memories: self.memories.into_owned(),
+            algorithms: self.algorithms.into_owned(),
+            features: self.features.into_owned(),
+            processor,
+            svd: None,
+            debug: Vec::new(),
+            debug_access: DebugAccess::default(),
+        }
+    }
+}
+
+/// Write every device of `pdscs` to `dest`, replacing it, as an object
+/// keyed by device name in natural order. The layout is described by
+/// `DEVICES_SCHEMA` and only changes along with `DEVICES_SCHEMA_VERSION`.
+#[cfg(feature = "fs")]
+pub fn dump_devices_json<'a, P, I>(pdscs: I, dest: P, l: &Logger) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a Package>,
+{
+    dump_device_database(pdscs, dest, DumpFormat::Json, l)
+}
+
+/// Read back a device database written by `dump_device_database` in either
+/// format.
+#[cfg(feature = "fs")]
+pub fn load_devices<P: AsRef<Path>>(path: P) -> Result<Devices, Error> {
+    let mut contents = Vec::new();
+    OpenOptions::new()
+        .read(true)
+        .open(path.as_ref())?
+        .read_to_end(&mut contents)?;
+    loads_devices(&contents)
+}
+
+/// Read back a device database returned by `dumps_device_database` in
+/// either format.
+pub fn loads_devices(contents: &[u8]) -> Result<Devices, Error> {
+    // A JSON object opens with a brace, which no CBOR map does
+    let is_json = contents
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map(|&b| b == b'{')
+        .unwrap_or(true);
+    // Dumps are in natural order, which is kept
+    let dumped: IndexMap<String, DumpDevice> = if is_json {
+        serde_json::from_slice(contents)?
+    } else {
+        serde_cbor::from_slice(contents)?
+    };
+    Ok(Devices(
+        dumped
+            .into_iter()
+            .map(|(name, dev)| (name, dev.into_device()))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+    use utils::parse::{FromElem, ParseOptions};
+
+    #[test]
+    fn cbor_and_json_load_the_same() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Dump</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Test Series" Dvendor="ARM:82">
+                  <processor Dcore="Cortex-M4" DcoreVersion="r0p1" Dfpu="SP_FPU" Dmpu="MPU"/>
+                  <device Dname="Dev10"><memory id="IROM1" start="0x0" size="0x1000"/></device>
+                  <device Dname="Dev2"><memory id="IROM1" start="0x0" size="0x2000"/></device>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let cbor = dumps_device_database(vec![&pack], DumpFormat::Cbor).unwrap();
+        let json = dumps_device_database(vec![&pack], DumpFormat::Json).unwrap();
+        assert!(cbor.len() < json.len());
+        for dump in &[cbor, json] {
+            let loaded = loads_devices(dump).unwrap();
+            let names: Vec<&str> = loaded.iter().map(|dev| dev.name.as_str()).collect();
+            assert_eq!(names, vec!["Dev2", "Dev10"]);
+            assert_eq!(loaded.get("Dev10").unwrap().memories.get("IROM1").unwrap().size, 0x1000);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use minidom::{Element, Error};
+use slog::Logger;
+
+use utils::parse::{
+    assert_root_name, attr_map, child_text, element_name, get_child_no_ns, FromElem,
+    ParseOptions,
+};
+
+/// A board an example runs on.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExampleBoard {
+    pub name: String,
+    pub vendor: Option<String>,
+}
+
+/// A project file of an example, for one development environment.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExampleProject {
+    /// The environment, such as "uv" or "iar"
+    pub environment: String,
+    /// Path of the project file, relative to the folder of the example
+    pub load: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Example {
+    pub name: String,
+    /// Folder of the example, relative to the root of the pack
+    pub folder: String,
+    /// Path of the documentation, relative to the folder of the example
+    pub doc: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub boards: Vec<ExampleBoard>,
+    #[serde(default)]
+    pub projects: Vec<ExampleProject>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl FromElem for Example {
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "example", opts)?;
+        let mut example = Self {
+            name: attr_map(e, "name", "example")?,
+            folder: attr_map(e, "folder", "example")?,
+            doc: attr_map(e, "doc", "example")?,
+            version: attr_map(e, "version", "example").ok(),
+            description: child_text(e, "description", "example", opts).ok(),
+            boards: Vec::new(),
+            projects: Vec::new(),
+            keywords: Vec::new(),
+            categories: Vec::new(),
+        };
+        for child in e.children() {
+            match element_name(child, opts) {
+                "board" => {
+                    if let Ok(name) = attr_map(child, "name", "board") {
+                        example.boards.push(ExampleBoard {
+                            name,
+                            vendor: attr_map(child, "vendor", "board").ok(),
+                        });
+                    }
+                }
+                "project" => {
+                    for env in child.children().filter(|c| element_name(c, opts) == "environment") {
+                        if let (Ok(environment), Ok(load)) = (
+                            attr_map(env, "name", "environment"),
+                            attr_map(env, "load", "environment"),
+                        ) {
+                            example.projects.push(ExampleProject { environment, load });
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        if let Some(attributes) = get_child_no_ns(e, "attributes", opts) {
+            for attr in attributes.children() {
+                let text = attr.text().trim().to_string();
+                match element_name(attr, opts) {
+                    "keyword" if !text.is_empty() => example.keywords.push(text),
+                    "category" if !text.is_empty() => example.categories.push(text),
+                    _ => (),
+                }
+            }
+        }
+        Ok(example)
+    }
+}
+
+/// The examples shipped in a pack.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Examples(pub(crate) Vec<Example>);
+
+impl Examples {
+    pub fn get(&self, name: &str) -> Option<&Example> {
+        self.0.iter().find(|example| example.name == name)
+    }
+
+    /// Iterate over the examples in the order they are described in the PDSC.
+    pub fn iter(&self) -> impl Iterator<Item = &Example> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The examples that run on the board `name`.
+    pub fn for_board<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Example> + 'a {
+        self.0
+            .iter()
+            .filter(move |example| example.boards.iter().any(|board| board.name == name))
+    }
+}
+
+impl FromElem for Examples {
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "examples", opts)?;
+        Ok(Examples(Example::vec_from_children(e.children(), opts, l)))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+
+    #[test]
+    fn board_projects_and_keywords() {
+        let l = Logger::root(Discard, o!());
+        let example = Example::from_bytes(
+            br#"<example name="Blinky" doc="Abstract.txt" folder="Boards/MCB1800/Blinky">
+                <description>Blinks the LEDs</description>
+                <board name="MCB1800" vendor="Keil"/>
+                <project>
+                  <environment name="uv" load="Blinky.uvprojx"/>
+                </project>
+                <attributes>
+                  <keyword>Blinky</keyword>
+                  <category>Getting Started</category>
+                </attributes>
+              </example>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        assert_eq!(example.description.as_ref().unwrap(), "Blinks the LEDs");
+        assert_eq!(example.boards[0].vendor.as_ref().unwrap(), "Keil");
+        assert_eq!(example.projects[0].load, "Blinky.uvprojx");
+        assert_eq!(example.keywords, vec![String::from("Blinky")]);
+        assert_eq!(example.categories, vec![String::from("Getting Started")]);
+    }
+}
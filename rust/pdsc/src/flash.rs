@@ -0,0 +1,272 @@
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::Read;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use failure::Error;
+use failure::Fail;
+
+const FLASH_DEVICE_SYMBOL: &str = "FlashDevice";
+const SHT_SYMTAB: u32 = 2;
+const SECTION_HEADER_LEN: usize = 40;
+const SYMBOL_LEN: usize = 16;
+// Offsets within `struct FlashDevice` of FlashOS.h, as laid out by armcc
+const DEV_NAME_LEN: usize = 128;
+const SECTORS_OFFSET: usize = 160;
+const SECTOR_END: u32 = 0xFFFF_FFFF;
+
+/// Why a flash algorithm could not be read.
+#[derive(Debug)]
+pub enum FlmError {
+    /// The file is not a little-endian, 32-bit ELF file
+    NotElf,
+    /// A header or a table points past the end of the file
+    Truncated,
+    /// The file has no `FlashDevice` symbol
+    NoFlashDevice,
+}
+
+impl fmt::Display for FlmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlmError::NotElf => write!(f, "not a 32-bit little-endian ELF file"),
+            FlmError::Truncated => write!(f, "truncated ELF file"),
+            FlmError::NoFlashDevice => write!(f, "no {} symbol", FLASH_DEVICE_SYMBOL),
+        }
+    }
+}
+
+impl Fail for FlmError {}
+
+fn bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], FlmError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(FlmError::Truncated)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, FlmError> {
+    let b = bytes(data, offset, 2)?;
+    Ok(u16::from(b[0]) | u16::from(b[1]) << 8)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, FlmError> {
+    let b = bytes(data, offset, 4)?;
+    Ok(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24)
+}
+
+fn c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+struct Section {
+    addr: u32,
+    offset: u32,
+    size: u32,
+    kind: u32,
+    link: u32,
+}
+
+fn sections(elf: &[u8]) -> Result<Vec<Section>, FlmError> {
+    let ident = bytes(elf, 0, 6)?;
+    if ident[..4] != b"\x7fELF"[..] || ident[4] != 1 || ident[5] != 1 {
+        return Err(FlmError::NotElf);
+    }
+    let table = u32_at(elf, 0x20)? as usize;
+    let count = u16_at(elf, 0x30)? as usize;
+    (0..count)
+        .map(|i| {
+            let header = table + i * SECTION_HEADER_LEN;
+            Ok(Section {
+                kind: u32_at(elf, header + 4)?,
+                addr: u32_at(elf, header + 12)?,
+                offset: u32_at(elf, header + 16)?,
+                size: u32_at(elf, header + 20)?,
+                link: u32_at(elf, header + 24)?,
+            })
+        }).collect()
+}
+
+// Where in the file the `FlashDevice` structure starts
+fn flash_device_offset(elf: &[u8]) -> Result<usize, FlmError> {
+    let sections = sections(elf)?;
+    for symtab in sections.iter().filter(|s| s.kind == SHT_SYMTAB) {
+        let strtab = sections.get(symtab.link as usize).ok_or(FlmError::Truncated)?;
+        for i in 0..symtab.size as usize / SYMBOL_LEN {
+            let symbol = symtab.offset as usize + i * SYMBOL_LEN;
+            let name_at = strtab.offset as usize + u32_at(elf, symbol)? as usize;
+            let wanted = bytes(elf, name_at, FLASH_DEVICE_SYMBOL.len() + 1)
+                .map(|name| name == b"FlashDevice\0")
+                .unwrap_or(false);
+            if !wanted {
+                continue;
+            }
+            let value = u32_at(elf, symbol + 4)?;
+            let section = sections
+                .get(u16_at(elf, symbol + 14)? as usize)
+                .ok_or(FlmError::Truncated)?;
+            return value
+                .checked_sub(section.addr)
+                .and_then(|within| section.offset.checked_add(within))
+                .map(|offset| offset as usize)
+                .ok_or(FlmError::Truncated);
+        }
+    }
+    Err(FlmError::NoFlashDevice)
+}
+
+/// A run of sectors of the same size, from `address` up to the next run or
+/// the end of the device. Addresses are relative to the start of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FlashSectors {
+    pub size: u32,
+    pub address: u32,
+}
+
+/// The description of the flash a `.FLM` algorithm programs, from its
+/// `FlashDevice` structure. Timeouts are in milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FlashDevice {
+    pub version: u16,
+    pub name: String,
+    pub device_type: u16,
+    pub address: u32,
+    pub size: u32,
+    pub page_size: u32,
+    /// Value of the bytes of an erased sector
+    pub erased_value: u8,
+    pub program_timeout: u32,
+    pub erase_timeout: u32,
+    pub sectors: Vec<FlashSectors>,
+}
+
+impl FlashDevice {
+    /// Read the `FlashDevice` structure of a flash algorithm.
+    pub fn from_elf(elf: &[u8]) -> Result<Self, FlmError> {
+        let dev = flash_device_offset(elf)?;
+        let mut sectors = Vec::new();
+        let mut at = dev + SECTORS_OFFSET;
+        loop {
+            let size = u32_at(elf, at)?;
+            let address = u32_at(elf, at + 4)?;
+            if size == SECTOR_END && address == SECTOR_END {
+                break;
+            }
+            sectors.push(FlashSectors { size, address });
+            at += 8;
+        }
+        Ok(FlashDevice {
+            version: u16_at(elf, dev)?,
+            name: c_string(bytes(elf, dev + 2, DEV_NAME_LEN)?),
+            device_type: u16_at(elf, dev + 130)?,
+            address: u32_at(elf, dev + 132)?,
+            size: u32_at(elf, dev + 136)?,
+            page_size: u32_at(elf, dev + 140)?,
+            erased_value: bytes(elf, dev + 148, 1)?[0],
+            program_timeout: u32_at(elf, dev + 152)?,
+            erase_timeout: u32_at(elf, dev + 156)?,
+            sectors,
+        })
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let mut elf = Vec::new();
+        File::open(path)?.read_to_end(&mut elf)?;
+        Self::from_elf(&elf).map_err(|e| e.context(format!("reading {:?}", path)).into())
+    }
+
+    /// The size of the sector at `address`, an absolute address.
+    pub fn sector_size(&self, address: u64) -> Option<u32> {
+        let start = u64::from(self.address);
+        if address < start || address >= start + u64::from(self.size) {
+            return None;
+        }
+        self.sectors
+            .iter()
+            .take_while(|sectors| u64::from(sectors.address) <= address - start)
+            .last()
+            .map(|sectors| sectors.size)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+
+    fn put(elf: &mut Vec<u8>, at: usize, value: u32, len: usize) {
+        if elf.len() < at + len {
+            elf.resize(at + len, 0);
+        }
+        for i in 0..len {
+            elf[at + i] = (value >> (8 * i)) as u8;
+        }
+    }
+
+    // A FlashDevice in a data section, found through the symbol table
+    fn flm() -> Vec<u8> {
+        let (data, strtab, symtab, headers) = (64, 248, 264, 296);
+        let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+        put(&mut elf, 0x20, headers, 4);
+        put(&mut elf, 0x30, 4, 2);
+        put(&mut elf, data, 0x0101, 2);
+        put(&mut elf, data + 132, 0x0800_0000, 4);
+        put(&mut elf, data + 136, 0x10_0000, 4);
+        put(&mut elf, data + 140, 0x400, 4);
+        put(&mut elf, data + 148, 0xFF, 1);
+        put(&mut elf, data + 152, 100, 4);
+        put(&mut elf, data + 156, 3000, 4);
+        put(&mut elf, data + 160, 0x4000, 4);
+        put(&mut elf, data + 164, 0, 4);
+        put(&mut elf, data + 168, 0x2_0000, 4);
+        put(&mut elf, data + 172, 0x2_0000, 4);
+        put(&mut elf, data + 176, SECTOR_END, 4);
+        put(&mut elf, data + 180, SECTOR_END, 4);
+        elf[data + 2..data + 10].copy_from_slice(b"Test 1MB");
+        elf.resize(strtab, 0);
+        elf.extend_from_slice(b"\0FlashDevice\0");
+        put(&mut elf, symtab + 16, 1, 4);
+        put(&mut elf, symtab + 30, 3, 2);
+        // kind, offset, size and link of the symbol table, strings and data
+        let sections = [(2, symtab, 32, 2), (3, strtab, 13, 0), (1, data, 184, 0)];
+        for (i, &(kind, offset, size, link)) in sections.iter().enumerate() {
+            let header = headers as usize + (i + 1) * SECTION_HEADER_LEN;
+            put(&mut elf, header + 4, kind, 4);
+            put(&mut elf, header + 16, offset as u32, 4);
+            put(&mut elf, header + 20, size, 4);
+            put(&mut elf, header + 24, link, 4);
+        }
+        elf
+    }
+
+    #[test]
+    fn sectors_and_timeouts() {
+        let dev = FlashDevice::from_elf(&flm()).unwrap();
+        assert_eq!(dev.name, "Test 1MB");
+        assert_eq!(dev.page_size, 0x400);
+        assert_eq!(dev.erased_value, 0xFF);
+        assert_eq!(dev.erase_timeout, 3000);
+        assert_eq!(dev.sectors.len(), 2);
+        assert_eq!(dev.sector_size(0x0800_1000), Some(0x4000));
+        assert_eq!(dev.sector_size(0x0804_0000), Some(0x2_0000));
+        assert_eq!(dev.sector_size(0x0900_0000), None);
+    }
+
+    #[test]
+    fn not_an_algorithm() {
+        let mut elf = flm();
+        // The first letter of the symbol name
+        elf[249] = b'X';
+        assert!(FlashDevice::from_elf(&elf).is_err());
+        assert!(FlashDevice::from_elf(b"\x7fELF").is_err());
+    }
+}
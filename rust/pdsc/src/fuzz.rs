@@ -0,0 +1,34 @@
+//! Entry points for the fuzz targets: parse raw bytes without any logger or
+//! file system setup.
+
+use minidom::Error;
+use slog::{Discard, Logger};
+
+use utils::parse::{FromElem, ParseOptions};
+
+use device::{Devices, MemElem};
+use flash::{FlashDevice, FlmError};
+use Package;
+
+fn discard() -> Logger {
+    Logger::root(Discard, o!())
+}
+
+pub fn package(data: &[u8]) -> Result<Package, Error> {
+    Package::from_bytes(data, &ParseOptions::default(), &discard())
+}
+
+pub fn devices(data: &[u8]) -> Result<Devices, Error> {
+    Devices::from_bytes(data, &ParseOptions::default(), &discard())
+}
+
+/// Parse a single `memory` element, returning its name.
+pub fn memory(data: &[u8]) -> Result<String, Error> {
+    MemElem::from_bytes(data, &ParseOptions::default(), &discard())
+        .map(|MemElem(name, _)| name.to_string())
+}
+
+/// Read the `FlashDevice` structure of a flash algorithm.
+pub fn flash_device(data: &[u8]) -> Result<FlashDevice, FlmError> {
+    FlashDevice::from_elf(data)
+}
@@ -0,0 +1,154 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use failure::{err_msg, Error};
+use slog::Logger;
+
+use pack_index::config::{Config, PackId};
+use utils::natural::natural_cmp;
+use utils::parse::FromElem;
+
+use pool::parse_packages;
+use view::DeviceRef;
+use {Devices, Package};
+
+type PackKey = (String, String);
+
+fn pack_key(pack: &Package) -> PackKey {
+    (pack.vendor.clone(), pack.name.clone())
+}
+
+/// How `Index::refresh_pack` changed the devices of the index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Refreshed {
+    /// Devices the new version of the pack describes and the old one did not
+    pub added: Vec<String>,
+    /// Devices the old version of the pack described and the new one does not
+    pub removed: Vec<String>,
+}
+
+/// The parsed descriptions of the pack store, keeping one version of each
+/// pack, along with which packs describe each device.
+#[derive(Default)]
+pub struct Index {
+    packs: BTreeMap<PackKey, Package>,
+    by_device: HashMap<String, BTreeSet<PackKey>>,
+}
+
+impl Index {
+    /// Parse every description of the pack store.
+    pub fn load(config: &Config, l: &Logger) -> Self {
+        let mut index = Index::default();
+        for pack in parse_packages(config.pdsc_files(), config, l) {
+            index.insert(pack);
+        }
+        index
+    }
+
+    pub fn packs(&self) -> impl Iterator<Item = &Package> {
+        self.packs.values()
+    }
+
+    pub fn pack(&self, vendor: &str, name: &str) -> Option<&Package> {
+        self.packs.get(&(vendor.to_string(), name.to_string()))
+    }
+
+    /// The devices described by one pack.
+    pub fn devices_of(&self, vendor: &str, name: &str) -> Option<&Devices> {
+        self.pack(vendor, name).map(Package::devices)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = DeviceRef> {
+        self.packs.values().flat_map(Package::device_refs)
+    }
+
+    /// Every description of the device `name`, one per pack describing it.
+    pub fn device(&self, name: &str) -> Vec<DeviceRef> {
+        self.by_device
+            .get(name)
+            .into_iter()
+            .flat_map(|keys| keys.iter())
+            .filter_map(|key| self.packs.get(key))
+            .filter_map(|pack| pack.devices.get(name).map(|dev| DeviceRef::new(pack, dev)))
+            .collect()
+    }
+
+    fn register(&mut self, key: &PackKey) -> BTreeSet<String> {
+        let names: BTreeSet<String> = self.packs[key]
+            .devices
+            .iter()
+            .map(|dev| dev.name.clone())
+            .collect();
+        for name in &names {
+            self.by_device
+                .entry(name.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(key.clone());
+        }
+        names
+    }
+
+    // Keeps the newest version when the store holds several
+    fn insert(&mut self, pack: Package) {
+        let key = pack_key(&pack);
+        if let Some(old) = self.packs.get(&key) {
+            let old_version = &old.releases.latest_release().version;
+            let new_version = &pack.releases.latest_release().version;
+            if natural_cmp(old_version, new_version) != Ordering::Less {
+                return;
+            }
+        }
+        self.remove(&key);
+        self.packs.insert(key.clone(), pack);
+        self.register(&key);
+    }
+
+    fn remove(&mut self, key: &PackKey) -> BTreeSet<String> {
+        let old = match self.packs.remove(key) {
+            Some(old) => old,
+            None => return BTreeSet::new(),
+        };
+        let names: BTreeSet<String> = old.devices.iter().map(|dev| dev.name.clone()).collect();
+        for name in &names {
+            let now_empty = match self.by_device.get_mut(name) {
+                Some(keys) => {
+                    keys.remove(key);
+                    keys.is_empty()
+                }
+                None => false,
+            };
+            if now_empty {
+                self.by_device.remove(name);
+            }
+        }
+        names
+    }
+
+    /// Re-parse the description of one pack after it was updated, replacing
+    /// the devices it provided before, instead of reloading the whole store.
+    pub fn refresh_pack(
+        &mut self,
+        pack: &PackId,
+        config: &Config,
+        l: &Logger,
+    ) -> Result<Refreshed, Error> {
+        let path = config
+            .cached_pdsc(&pack.vendor, &pack.name, Some(&pack.version))
+            .ok_or_else(|| {
+                err_msg(format!(
+                    "no description of {}.{} {} in the pack store",
+                    pack.vendor, pack.name, pack.version
+                ))
+            })?;
+        let parsed = Package::from_path(&path, &config.parse_options, l)
+            .map_err(|e| err_msg(format!("parsing {:?}: {}", path, e)))?;
+        let key = pack_key(&parsed);
+        let before = self.remove(&key);
+        self.packs.insert(key.clone(), parsed);
+        let after = self.register(&key);
+        Ok(Refreshed {
+            added: after.difference(&before).cloned().collect(),
+            removed: before.difference(&after).cloned().collect(),
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
+use std::io::Write;
+use std::path::Path;
+
+use failure::Error;
+use serde_json;
+#[cfg(feature = "fs")]
+use slog::Logger;
+
+use device::Device;
+use Package;
+
+// The layout of the `index.json` written by the Python cmsis-pack-manager,
+// which pyOCD reads. Nothing may be added or renamed here.
+
+#[derive(Debug, Serialize)]
+struct Access {
+    read: bool,
+    write: bool,
+    execute: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Memory {
+    access: Access,
+    start: u64,
+    size: u64,
+    startup: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Algorithm<'a> {
+    file_name: &'a Path,
+    start: u64,
+    size: u64,
+    default: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FromPack<'a> {
+    vendor: &'a str,
+    pack: &'a str,
+    version: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexDevice<'a> {
+    name: &'a str,
+    memories: BTreeMap<&'a str, Memory>,
+    algorithms: Vec<Algorithm<'a>>,
+    from_pack: FromPack<'a>,
+}
+
+impl<'a> IndexDevice<'a> {
+    fn new(dev: &'a Device, pack: &'a Package) -> Self {
+        IndexDevice {
+            name: &dev.name,
+            memories: dev
+                .memories
+                .0
+                .iter()
+                .map(|(name, mem)| {
+                    (
+                        name.as_str(),
+                        Memory {
+                            access: Access {
+                                read: mem.access.read,
+                                write: mem.access.write,
+                                execute: mem.access.execute,
+                            },
+                            start: mem.start,
+                            size: mem.size,
+                            startup: mem.startup,
+                        },
+                    )
+                }).collect(),
+            algorithms: dev
+                .algorithms_for(None)
+                .into_iter()
+                .map(|alg| Algorithm {
+                    file_name: alg.file_name,
+                    start: alg.start,
+                    size: alg.size,
+                    default: alg.default,
+                }).collect(),
+            from_pack: FromPack {
+                vendor: &pack.vendor,
+                pack: &pack.name,
+                version: &pack.releases.latest_release().version,
+            },
+        }
+    }
+}
+
+/// The devices of `pdscs` in the `index.json` format of the Python
+/// cmsis-pack-manager, which tools built on it, such as pyOCD, read.
+pub fn dumps_index_json<'a, I>(pdscs: I) -> Result<String, Error>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    let mut index = BTreeMap::new();
+    for pack in pdscs {
+        for dev in pack.devices().iter() {
+            index.insert(dev.name.as_str(), IndexDevice::new(dev, pack));
+        }
+    }
+    Ok(serde_json::to_string(&index)?)
+}
+
+/// Write the devices of `pdscs` to `dest` in the `index.json` format of the
+/// Python cmsis-pack-manager, so that tools built on it, such as pyOCD, can
+/// use this cache.
+#[cfg(feature = "fs")]
+pub fn dump_index_json<'a, P, I>(pdscs: I, dest: P, l: &Logger) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a Package>,
+{
+    let index = dumps_index_json(pdscs)?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dest.as_ref())?
+        .write_all(index.as_bytes())?;
+    debug!(l, "Wrote the index of {:?}", dest.as_ref());
+    Ok(())
+}
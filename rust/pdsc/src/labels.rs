@@ -0,0 +1,151 @@
+use std::fmt;
+
+use device::{Core, FPU, MPU};
+
+/// A display name for values that serialize under a stable, machine oriented
+/// name. `from_label` accepts whatever `label` returns.
+pub trait Label: Sized {
+    fn label(&self) -> &'static str;
+    fn from_label(label: &str) -> Option<Self>;
+}
+
+const CORES: &[(Core, &str)] = &[
+    (Core::CortexM0, "Cortex-M0"),
+    (Core::CortexM0Plus, "Cortex-M0+"),
+    (Core::CortexM1, "Cortex-M1"),
+    (Core::CortexM3, "Cortex-M3"),
+    (Core::CortexM4, "Cortex-M4"),
+    (Core::CortexM7, "Cortex-M7"),
+    (Core::CortexM23, "Cortex-M23"),
+    (Core::CortexM33, "Cortex-M33"),
+    (Core::SC000, "SecurCore SC000"),
+    (Core::SC300, "SecurCore SC300"),
+    (Core::ARMV8MBL, "ARMv8-M Baseline"),
+    (Core::ARMV8MML, "ARMv8-M Mainline"),
+    (Core::CortexR4, "Cortex-R4"),
+    (Core::CortexR5, "Cortex-R5"),
+    (Core::CortexR7, "Cortex-R7"),
+    (Core::CortexR8, "Cortex-R8"),
+    (Core::CortexA5, "Cortex-A5"),
+    (Core::CortexA7, "Cortex-A7"),
+    (Core::CortexA8, "Cortex-A8"),
+    (Core::CortexA9, "Cortex-A9"),
+    (Core::CortexA15, "Cortex-A15"),
+    (Core::CortexA17, "Cortex-A17"),
+    (Core::CortexA32, "Cortex-A32"),
+    (Core::CortexA35, "Cortex-A35"),
+    (Core::CortexA53, "Cortex-A53"),
+    (Core::CortexA57, "Cortex-A57"),
+    (Core::CortexA72, "Cortex-A72"),
+    (Core::CortexA73, "Cortex-A73"),
+];
+
+const FPUS: &[(FPU, &str)] = &[
+    (FPU::None, "no FPU"),
+    (FPU::SinglePrecision, "single precision FPU"),
+    (FPU::DoublePrecision, "double precision FPU"),
+];
+
+const MPUS: &[(MPU, &str)] = &[(MPU::NotPresent, "no MPU"), (MPU::Present, "MPU")];
+
+fn label_of<T: PartialEq>(table: &[(T, &'static str)], value: &T) -> &'static str {
+    table
+        .iter()
+        .find(|&&(ref v, _)| v == value)
+        .map(|&(_, label)| label)
+        .unwrap_or("")
+}
+
+fn from_label_in<T: Copy>(table: &[(T, &str)], label: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|&&(_, l)| l == label)
+        .map(|&(value, _)| value)
+}
+
+macro_rules! labelled {
+    ($ty:ty, $table:ident) => {
+        impl Label for $ty {
+            fn label(&self) -> &'static str {
+                label_of($table, self)
+            }
+
+            fn from_label(label: &str) -> Option<Self> {
+                from_label_in($table, label)
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.label())
+            }
+        }
+    };
+}
+
+labelled!(Core, CORES);
+labelled!(FPU, FPUS);
+labelled!(MPU, MPUS);
+
+/// The label of a core given the name it is serialized under, as found in
+/// dumped devices.
+pub fn core_label(serialized: &str) -> Option<&'static str> {
+    CORES
+        .iter()
+        .find(|&&(core, _)| format!("{:?}", core) == serialized)
+        .map(|&(_, label)| label)
+}
+
+/// Display names of cores and vendors, with per-application overrides taking
+/// precedence over the built in labels.
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    cores: Vec<(Core, String)>,
+    vendors: Vec<(String, String)>,
+}
+
+impl Labels {
+    pub fn with_core<S: Into<String>>(mut self, core: Core, label: S) -> Self {
+        self.cores.push((core, label.into()));
+        self
+    }
+
+    /// Display `vendor`, with or without its `:id` suffix, as `label`.
+    pub fn with_vendor<V: Into<String>, S: Into<String>>(mut self, vendor: V, label: S) -> Self {
+        self.vendors.push((vendor.into(), label.into()));
+        self
+    }
+
+    pub fn core(&self, core: Core) -> &str {
+        self.cores
+            .iter()
+            .rev()
+            .find(|&&(c, _)| c == core)
+            .map(|&(_, ref label)| label.as_str())
+            .unwrap_or_else(|| core.label())
+    }
+
+    pub fn parse_core(&self, label: &str) -> Option<Core> {
+        self.cores
+            .iter()
+            .rev()
+            .find(|&&(_, ref l)| l == label)
+            .map(|&(core, _)| core)
+            .or_else(|| Core::from_label(label))
+    }
+
+    /// Vendors are written as `name:id` in device descriptions; the id is
+    /// dropped unless an override says otherwise.
+    pub fn vendor<'a>(&'a self, vendor: &'a str) -> &'a str {
+        let name = match vendor.rfind(':') {
+            Some(index) => &vendor[..index],
+            None => vendor,
+        };
+        self.vendors
+            .iter()
+            .rev()
+            .find(|&&(ref v, _)| v == vendor || v == name)
+            .map(|&(_, ref label)| label.as_str())
+            .unwrap_or(name)
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+use failure::{err_msg, Error as FailError};
+
+use device::{for_processor, Device, Memory};
+use intern::Name;
+use roles::MemoryRole;
+use scaffold::region_name;
+
+/// How to lay the memories of a device out for a linker.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutOptions {
+    /// Processor of a multi-core device to lay the memories out for
+    pub pname: Option<String>,
+    /// Memory to link code into, instead of the primary flash
+    pub flash: Option<String>,
+    /// Memory to link data into, instead of the primary RAM
+    pub ram: Option<String>,
+    /// Leave out the memories the PDSC does not mark as default
+    pub default_only: bool,
+    /// Bytes at the top of the RAM set apart as a `STACK` region
+    pub stack_size: u64,
+}
+
+struct Region<'a> {
+    name: String,
+    memory: &'a Memory,
+    origin: u64,
+    length: u64,
+}
+
+impl<'a> Region<'a> {
+    fn attributes(&self) -> String {
+        let mut attributes = String::new();
+        if self.memory.access.read {
+            attributes.push('r');
+        }
+        if self.memory.access.write {
+            attributes.push('w');
+        }
+        if self.memory.access.execute {
+            attributes.push('x');
+        }
+        attributes
+    }
+}
+
+/// The memories of a device as a linker sees them, with one region chosen
+/// for code and one for data.
+pub struct MemoryLayout<'a> {
+    device: &'a str,
+    regions: Vec<Region<'a>>,
+    flash: usize,
+    ram: usize,
+    stack: Option<(u64, u64)>,
+}
+
+impl<'a> MemoryLayout<'a> {
+    pub fn new(device: &'a Device, options: &LayoutOptions) -> Result<Self, FailError> {
+        let pname = options.pname.as_ref().map(String::as_str);
+        let mut memories: Vec<(&Name, &Memory)> = device
+            .memories
+            .0
+            .iter()
+            .filter(|&(_, mem)| {
+                !mem.access.peripheral
+                    && for_processor(&mem.pname, pname)
+                    && (mem.default || !options.default_only)
+            }).collect();
+        memories.sort_by_key(|&(id, mem)| (mem.start, id.clone()));
+        let roles = device.memory_roles();
+        let (flash, ram) = {
+            let pick = |chosen: &Option<String>, role: MemoryRole| match *chosen {
+                Some(ref chosen) => memories
+                    .iter()
+                    .position(|&(id, _)| id.as_str() == chosen.as_str())
+                    .ok_or_else(|| err_msg(format!("{} has no memory {}", device.name, chosen))),
+                None => memories
+                    .iter()
+                    .position(|&(id, mem)| roles.get(id.as_str()) == Some(&role) && !mem.uninit)
+                    .ok_or_else(|| err_msg(format!("{} has no {:?} memory", device.name, role))),
+            };
+            (
+                pick(&options.flash, MemoryRole::PrimaryFlash)?,
+                pick(&options.ram, MemoryRole::PrimaryRam)?,
+            )
+        };
+        let mut regions: Vec<Region> = memories
+            .into_iter()
+            .map(|(id, memory)| Region {
+                name: region_name(id),
+                memory,
+                origin: memory.start,
+                length: memory.size,
+            }).collect();
+        let stack = if options.stack_size == 0 {
+            None
+        } else {
+            let ram = &mut regions[ram];
+            if options.stack_size >= ram.length {
+                return Err(err_msg(format!(
+                    "a stack of 0x{:X} bytes does not fit in {}",
+                    options.stack_size, ram.name
+                )));
+            }
+            ram.length -= options.stack_size;
+            Some((ram.origin + ram.length, options.stack_size))
+        };
+        Ok(MemoryLayout {
+            device: &device.name,
+            regions,
+            flash,
+            ram,
+            stack,
+        })
+    }
+
+    /// A GNU ld `MEMORY` command, followed by the `REGION_ALIAS`es `FLASH`
+    /// and `RAM` for the regions chosen for code and data.
+    pub fn gnu_ld(&self) -> String {
+        let mut script = format!("/* Memories of {} */\n\nMEMORY\n{{\n", self.device);
+        for region in &self.regions {
+            let _ = writeln!(
+                script,
+                "  {} ({}) : ORIGIN = 0x{:08X}, LENGTH = 0x{:X}",
+                region.name,
+                region.attributes(),
+                region.origin,
+                region.length
+            );
+        }
+        if let Some((origin, length)) = self.stack {
+            let _ = writeln!(
+                script,
+                "  STACK (rw) : ORIGIN = 0x{:08X}, LENGTH = 0x{:X}",
+                origin, length
+            );
+        }
+        script.push_str("}\n\n");
+        for &(alias, i) in &[("FLASH", self.flash), ("RAM", self.ram)] {
+            if self.regions[i].name != alias {
+                let _ = writeln!(script, "REGION_ALIAS(\"{}\", {});", alias, self.regions[i].name);
+            }
+        }
+        if self.stack.is_some() {
+            script.push_str("\n__StackLimit = ORIGIN(STACK);\n");
+            script.push_str("__StackTop = ORIGIN(STACK) + LENGTH(STACK);\n");
+        }
+        script
+    }
+
+    /// A `memory.x` for `cortex-m-rt`, where code and data go to the regions
+    /// named `FLASH` and `RAM`.
+    pub fn memory_x(&self) -> String {
+        let mut script = format!("/* Memories of {} */\n\nMEMORY\n{{\n", self.device);
+        for (i, region) in self.regions.iter().enumerate() {
+            let name = match i {
+                i if i == self.flash => "FLASH",
+                i if i == self.ram => "RAM",
+                _ => region.name.as_str(),
+            };
+            let _ = writeln!(
+                script,
+                "  {} : ORIGIN = 0x{:08X}, LENGTH = 0x{:X}",
+                name, region.origin, region.length
+            );
+        }
+        if let Some((origin, length)) = self.stack {
+            let _ = writeln!(
+                script,
+                "  STACK : ORIGIN = 0x{:08X}, LENGTH = 0x{:X}",
+                origin, length
+            );
+        }
+        script.push_str("}\n");
+        if self.stack.is_some() {
+            script.push_str("\n_stack_start = ORIGIN(STACK) + LENGTH(STACK);\n");
+        }
+        script
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use device::Devices;
+    use slog::{Discard, Logger};
+    use utils::parse::{FromElem, ParseOptions};
+
+    #[test]
+    fn stack_at_the_top_of_ram() {
+        let l = Logger::root(Discard, o!());
+        let devices = Devices::from_bytes(
+            br#"<devices>
+                  <family Dfamily="Test Series" Dvendor="ARM:82">
+                    <processor Dcore="Cortex-M4" DcoreVersion="r0p1" Dfpu="SP_FPU" Dmpu="MPU"/>
+                    <device Dname="Dev">
+                      <memory id="IROM1" start="0x08000000" size="0x10000" startup="1"/>
+                      <memory id="IRAM1" start="0x20000000" size="0x4000"/>
+                    </device>
+                  </family>
+                </devices>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let device = devices.get("Dev").unwrap();
+        let options = LayoutOptions {
+            stack_size: 0x400,
+            ..LayoutOptions::default()
+        };
+        let layout = MemoryLayout::new(device, &options).unwrap();
+        let memory_x = layout.memory_x();
+        assert!(memory_x.contains("FLASH : ORIGIN = 0x08000000, LENGTH = 0x10000"));
+        assert!(memory_x.contains("RAM : ORIGIN = 0x20000000, LENGTH = 0x3C00"));
+        assert!(memory_x.contains("STACK : ORIGIN = 0x20003C00, LENGTH = 0x400"));
+        assert!(layout.gnu_ld().contains("REGION_ALIAS(\"RAM\", IRAM1);"));
+    }
+}
@@ -1,70 +1,251 @@
-#[macro_use]
 extern crate utils;
 #[macro_use]
 extern crate slog;
 #[macro_use]
 extern crate serde_derive;
+extern crate bincode;
 extern crate failure;
+extern crate indexmap;
+extern crate serde;
+extern crate serde_cbor;
 extern crate serde_json;
+#[cfg(test)]
+extern crate tempfile;
+#[cfg(all(feature = "fs", target_os = "linux"))]
+extern crate libc;
 
+#[cfg(feature = "fs")]
 extern crate clap;
 extern crate minidom;
+#[cfg(feature = "fs")]
 extern crate pack_index;
 
+#[cfg(feature = "fs")]
 use clap::{App, Arg, ArgMatches, SubCommand};
-use minidom::{Element, Error, ErrorKind};
+use minidom::{Element, Error};
 use slog::Logger;
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
 use std::io::Read;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 use failure::Error as FailError;
+#[cfg(feature = "fs")]
 use pack_index::config::Config;
-use utils::parse::{assert_root_name, attr_map, child_text, get_child_no_ns, FromElem};
+use utils::error::PdscError;
+use utils::natural::Natural;
+use utils::parse::{
+    assert_root_name, attr_map, child_text, element_name, get_child_no_ns, FromElem,
+};
+pub use utils::parse::{DuplicateDevicePolicy, MemoryMergePolicy, ParseOptions};
+use utils::span::Span;
 use utils::ResultLogExt;
 
+mod alias;
+#[cfg(feature = "fs")]
+pub mod bench;
+mod board;
+#[cfg(feature = "fs")]
+mod cache;
+mod completeness;
+mod completions;
 mod component;
+#[cfg(feature = "fs")]
+mod database;
 mod condition;
+mod debug_access;
 mod device;
-pub use component::{ComponentBuilders, FileRef};
-pub use condition::{Condition, Conditions};
-pub use device::{Algorithm, Device, Devices, Memories, Processors};
+#[cfg(feature = "fs")]
+mod diff;
+mod dump;
+mod example;
+mod flash;
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "fs")]
+mod index;
+mod index_json;
+mod intern;
+mod labels;
+#[cfg(feature = "linker")]
+mod layout;
+#[cfg(feature = "fs")]
+mod list;
+mod lookup;
+#[cfg(feature = "fs")]
+mod pool;
+mod report;
+mod roles;
+#[cfg(feature = "fs")]
+mod scaffold;
+mod schema;
+#[cfg(feature = "fs")]
+mod shard;
+mod stats;
+#[cfg(feature = "fs")]
+mod store;
+mod synthetic;
+mod tree;
+mod usage;
+mod validate;
+mod view;
+pub use alias::DeviceMatch;
+pub use board::{Board, Boards, CompatibleDevice, DebugInterface, DebugProbe};
+#[cfg(feature = "fs")]
+pub use cache::{cached_pdscs, dumps_cached_pdscs, CachedPdsc};
+pub use completeness::Completeness;
+#[cfg(feature = "fs")]
+pub use completions::update_completions;
+pub use completions::{
+    CompletionDevice, CompletionPack, Completions, COMPLETIONS_SCHEMA, COMPLETIONS_VERSION,
+};
+pub use component::{ComponentBuilders, FileAttribute, FileCategory, FileRef};
+pub use condition::{Condition, ConditionComponent, Conditions};
+#[cfg(feature = "fs")]
+pub use database::{CachedPack, DeviceDatabase, DEVICE_DATABASE_VERSION};
+pub use debug_access::{
+    DebugAccess, DebugConfig, DebugPort, DebugProtocol, JtagPort, Sequence, SequenceItem, SwdPort,
+};
+pub use device::{
+    Algorithm, Core, CoreInfo, DebugDescription, Device, Devices, DroppedDevice, Endian, Feature,
+    Memories, Memory, MemoryPermissions, Processor, Processors, RamRegion, ResolvedAlgorithm,
+    SecureMode, TrustZone, DSP, FPU, MPU,
+};
+#[cfg(feature = "fs")]
+pub use diff::{
+    diff_args, diff_command, AlgorithmExtent, CoreSummary, DeviceDiff, Difference, MemoryExtent,
+};
+#[cfg(feature = "fs")]
+pub use dump::{dump_device_database, dump_devices_json, load_devices};
+pub use dump::{dumps_device_database, loads_devices, DumpFormat};
+pub use example::{Example, ExampleBoard, ExampleProject, Examples};
+pub use flash::{FlashDevice, FlashSectors, FlmError};
+#[cfg(feature = "fs")]
+pub use index::{Index, Refreshed};
+#[cfg(feature = "fs")]
+pub use index_json::dump_index_json;
+pub use index_json::dumps_index_json;
+pub use intern::{interned_names, Name};
+pub use labels::{core_label, Label, Labels};
+#[cfg(feature = "linker")]
+pub use layout::{LayoutOptions, MemoryLayout};
+#[cfg(feature = "fs")]
+pub use list::{
+    dump_json_args, dump_json_command, find_args, find_command, list_devices_args,
+    list_devices_command,
+};
+pub use lookup::{
+    board_devices, device_by_name_and_vendor, devices_by_name, find_devices, nearest_device,
+    resolve_device, resolve_device_ranked, DeviceCandidate,
+};
+#[cfg(feature = "fs")]
+pub use pool::{parse_packages, parse_packages_with_report};
+pub use report::{FailedFile, ParseReport, SkippedElement};
+pub use roles::{MemoryRole, RoleHeuristics};
+#[cfg(feature = "fs")]
+pub use scaffold::{new_project_args, new_project_command, ProjectSkeleton};
+#[cfg(feature = "fs")]
+pub use schema::{schema_args, schema_command};
+pub use schema::{validate_devices, SchemaViolation, DEVICES_SCHEMA, DEVICES_SCHEMA_VERSION};
+#[cfg(feature = "fs")]
+pub use shard::{dump_device_shards, DeviceShards};
+#[cfg(feature = "fs")]
+pub use stats::{stats_args, stats_command};
+pub use stats::{coverage_report, CoverageReport, VendorStats};
+#[cfg(feature = "fs")]
+pub use store::{dump_device_store, DeviceStore};
+pub use synthetic::SyntheticPack;
+pub use tree::{DeviceTree, FamilyNode, SubFamilyNode};
+pub use usage::Usage;
+#[cfg(feature = "fs")]
+pub use validate::{validate_args, validate_command};
+pub use validate::{validate_file, validate_tree, FileReport, Outcome, ValidationReport};
+pub use view::{device_refs, sorted_device_refs, DeviceRef};
+pub use utils::error::PdscError;
 
+/// One entry of the release history of a pack.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Release {
     pub version: String,
+    pub date: Option<String>,
+    /// Date from which the pack is deprecated
+    pub deprecated: Option<String>,
+    /// The pack, as `Vendor.Name`, replacing this one
+    pub replacement: Option<String>,
+    /// Where this version may be downloaded from, when not from the url of
+    /// the pack
+    pub url: Option<String>,
+    /// What changed in this version
     pub text: String,
 }
 
 impl FromElem for Release {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "release")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, _: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "release", opts)?;
         Ok(Self {
             version: attr_map(e, "version", "release")?,
-            text: e.text(),
+            date: attr_map(e, "date", "release").ok(),
+            deprecated: attr_map(e, "deprecated", "release").ok(),
+            replacement: attr_map(e, "replacement", "release").ok(),
+            url: attr_map(e, "url", "release").ok(),
+            text: e.text().trim().to_string(),
         })
     }
 }
 
-#[derive(Default)]
+/// The release history of a pack, newest first.
+/// Never empty: parsing fails when there is no valid release.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Releases(Vec<Release>);
 
 impl Releases {
     pub fn latest_release(&self) -> &Release {
         &self.0[0]
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Release> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, version: &str) -> Option<&Release> {
+        self.0.iter().find(|release| release.version == version)
+    }
+
+    /// The latest release, when it marks the pack as deprecated. Packs are
+    /// deprecated by their last release, so older ones are not looked at.
+    pub fn deprecation(&self) -> Option<&Release> {
+        Some(self.latest_release()).filter(|release| {
+            release.deprecated.is_some() || release.replacement.is_some()
+        })
+    }
 }
 
 impl FromElem for Releases {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "releases")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "releases", opts)?;
         let to_ret: Vec<_> = e
             .children()
-            .flat_map(|c| Release::from_elem(c, l).ok_warn(l))
+            .flat_map(|c| Release::from_elem(c, opts, l).ok_warn(l))
             .collect();
         if to_ret.is_empty() {
-            Err(err_msg!("There must be at least one release!"))
+            Err(PdscError::MissingChild {
+                elem: String::from("releases"),
+                child: String::from("release"),
+            }.into())
         } else {
             Ok(Releases(to_ret))
         }
@@ -72,29 +253,47 @@ impl FromElem for Releases {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct DumpDevice<'a> {
-    name: &'a str,
-    memories: Cow<'a, Memories>,
-    algorithms: Cow<'a, Vec<Algorithm>>,
-    processor: Cow<'a, Processors>,
-    from_pack: FromPack<'a>,
+pub struct DumpDevice<'a> {
+    pub name: Cow<'a, str>,
+    /// Empty in dumps made before families were recorded
+    #[serde(default)]
+    pub family: Cow<'a, str>,
+    #[serde(default)]
+    pub sub_family: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub vendor: Cow<'a, str>,
+    #[serde(default)]
+    pub vendor_id: Option<u32>,
+    #[serde(default)]
+    pub description: Option<Cow<'a, str>>,
+    pub memories: Cow<'a, Memories>,
+    pub algorithms: Cow<'a, Vec<Algorithm>>,
+    #[serde(default)]
+    pub features: Cow<'a, Vec<Feature>>,
+    pub processor: Cow<'a, Processors>,
+    #[serde(default)]
+    pub memory_roles: BTreeMap<String, MemoryRole>,
+    /// Absent from dumps made before completeness was recorded
+    #[serde(default)]
+    pub completeness: Option<Completeness>,
+    pub from_pack: FromPack<'a>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-struct FromPack<'a> {
-    vendor: &'a str,
-    pack: &'a str,
-    version: &'a str,
-    url: &'a str,
+pub struct FromPack<'a> {
+    pub vendor: Cow<'a, str>,
+    pub pack: Cow<'a, str>,
+    pub version: Cow<'a, str>,
+    pub url: Cow<'a, str>,
 }
 
 impl<'a> FromPack<'a> {
     fn new(vendor: &'a str, pack: &'a str, version: &'a str, url: &'a str) -> Self {
         Self {
-            vendor,
-            pack,
-            version,
-            url,
+            vendor: Cow::Borrowed(vendor),
+            pack: Cow::Borrowed(pack),
+            version: Cow::Borrowed(version),
+            url: Cow::Borrowed(url),
         }
     }
 }
@@ -102,15 +301,25 @@ impl<'a> FromPack<'a> {
 impl<'a> DumpDevice<'a> {
     fn from_device(dev: &'a Device, from_pack: FromPack<'a>) -> Self {
         Self {
-            name: &dev.name,
+            name: Cow::Borrowed(&dev.name),
+            family: Cow::Borrowed(&dev.family),
+            sub_family: dev.sub_family.as_ref().map(|sub| Cow::Borrowed(sub.as_str())),
+            vendor: Cow::Borrowed(&dev.vendor),
+            vendor_id: dev.vendor_id,
+            description: dev.description.as_ref().map(|desc| Cow::Borrowed(desc.as_str())),
             memories: Cow::Borrowed(&dev.memories),
             algorithms: Cow::Borrowed(&dev.algorithms),
+            features: Cow::Borrowed(&dev.features),
             processor: Cow::Borrowed(&dev.processor),
+            memory_roles: dev.memory_roles(),
+            completeness: Some(dev.completeness()),
             from_pack,
         }
     }
 }
 
+/// Every section of a PDSC, parsed in a single traversal of its document;
+/// `Package::from_path` reads one from a file.
 pub struct Package {
     pub name: String,
     pub description: String,
@@ -121,33 +330,47 @@ pub struct Package {
     pub releases: Releases,
     conditions: Conditions,
     devices: Devices,
-    pub boards: Vec<Board>,
+    report: ParseReport,
+    pub boards: Boards,
+    pub examples: Examples,
 }
 
 impl FromElem for Package {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error> {
-        assert_root_name(e, "package")?;
-        let name: String = child_text(e, "name", "package")?;
-        let description: String = child_text(e, "description", "package")?;
-        let vendor: String = child_text(e, "vendor", "package")?;
-        let url: String = child_text(e, "url", "package")?;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        assert_root_name(e, "package", opts)?;
+        let name: String = child_text(e, "name", "package", opts)?;
+        let description: String = child_text(e, "description", "package", opts)?;
+        let vendor: String = child_text(e, "vendor", "package", opts)?;
+        let url: String = child_text(e, "url", "package", opts)?;
         let l = l.new(o!("Vendor" => vendor.clone(),
                          "Package" => name.clone()
         ));
-        let components = get_child_no_ns(e, "components")
-            .and_then(|c| ComponentBuilders::from_elem(c, &l).ok_warn(&l))
+        let span = Span::enter(&l, "components", &name);
+        let components = get_child_no_ns(e, "components", opts)
+            .and_then(|c| ComponentBuilders::from_elem(c, opts, &l).ok_warn(&l))
             .unwrap_or_default();
-        let releases = get_child_no_ns(e, "releases")
-            .and_then(|c| Releases::from_elem(c, &l).ok_warn(&l))
+        drop(span);
+        let releases = get_child_no_ns(e, "releases", opts)
+            .ok_or_else(|| {
+                PdscError::MissingChild {
+                    elem: String::from("package"),
+                    child: String::from("releases"),
+                }.into()
+            })
+            .and_then(|c| Releases::from_elem(c, opts, &l))?;
+        let conditions = get_child_no_ns(e, "conditions", opts)
+            .and_then(|c| Conditions::from_elem(c, opts, &l).ok_warn(&l))
             .unwrap_or_default();
-        let conditions = get_child_no_ns(e, "conditions")
-            .and_then(|c| Conditions::from_elem(c, &l).ok_warn(&l))
+        let span = Span::enter(&l, "devices", &name);
+        let (devices, report) = get_child_no_ns(e, "devices", opts)
+            .map(|c| Devices::from_elem_with_report(c, opts, &l))
             .unwrap_or_default();
-        let devices = get_child_no_ns(e, "devices")
-            .and_then(|c| Devices::from_elem(c, &l).ok_warn(&l))
+        drop(span);
+        let boards = get_child_no_ns(e, "boards", opts)
+            .and_then(|c| Boards::from_elem(c, opts, &l).ok_warn(&l))
             .unwrap_or_default();
-        let boards = get_child_no_ns(e, "boards")
-            .map(|c| Board::vec_from_children(c.children(), &l))
+        let examples = get_child_no_ns(e, "examples", opts)
+            .and_then(|c| Examples::from_elem(c, opts, &l).ok_warn(&l))
             .unwrap_or_default();
         Ok(Self {
             name,
@@ -155,66 +378,81 @@ impl FromElem for Package {
             vendor,
             url,
             components,
-            license: child_text(e, "license", "package").ok(),
+            license: child_text(e, "license", "package", opts).ok(),
             releases,
             conditions,
             devices,
+            report,
             boards,
+            examples,
         })
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Board {
-    name: String,
-    mounted_devices: Vec<String>,
-}
-
-impl FromElem for Board {
-    fn from_elem(e: &Element, _: &Logger) -> Result<Self, Error> {
-        Ok(Self {
-            name: attr_map(e, "name", "board")?,
-            mounted_devices: e
-                .children()
-                .flat_map(|c| match c.name() {
-                    "mountedDevice" => attr_map(c, "Dname", "mountedDevice").ok(),
-                    _ => None,
-                }).collect(),
-        })
-    }
-}
-
-#[derive(Debug, Serialize)]
+/// A software component of a pack, with the defaults of its bundle and of
+/// the pack applied.
+#[derive(Debug, Clone, Serialize)]
 pub struct Component {
-    vendor: String,
-    class: String,
-    group: String,
-    sub_group: Option<String>,
-    variant: Option<String>,
-    version: String,
-    api_version: Option<String>,
-    condition: Option<String>,
-    max_instances: Option<u8>,
-    is_default: bool,
-    deprecated: bool,
-    description: String,
-    rte_addition: String,
-    files: Vec<FileRef>,
+    pub vendor: String,
+    pub class: String,
+    pub group: String,
+    pub sub_group: Option<String>,
+    pub bundle: Option<String>,
+    pub variant: Option<String>,
+    pub version: String,
+    pub api_version: Option<String>,
+    /// The id of the condition the component requires
+    pub condition: Option<String>,
+    pub max_instances: Option<u8>,
+    pub is_default: bool,
+    pub deprecated: bool,
+    pub description: String,
+    pub rte_addition: String,
+    pub files: Vec<FileRef>,
 }
 
 type Components = Vec<Component>;
 
 impl Package {
+    pub fn devices(&self) -> &Devices {
+        &self.devices
+    }
+
+    /// Devices described in the PDSC that could not be built
+    pub fn dropped_devices(&self) -> &[DroppedDevice] {
+        &self.report.dropped_devices
+    }
+
+    /// The devices and the elements of the PDSC that could not be parsed
+    pub fn report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    /// The software components of the pack. Components described without a
+    /// class or a group are left out.
+    pub fn components(&self) -> Vec<Component> {
+        self.make_components()
+    }
+
+    pub fn conditions(&self) -> &Conditions {
+        &self.conditions
+    }
+
+    // Components without a class or a group can not be identified
     fn make_components(&self) -> Components {
         self.components
             .0
             .clone()
             .into_iter()
-            .map(|comp| Component {
+            .filter_map(|comp| match (comp.class.clone(), comp.group.clone()) {
+                (Some(class), Some(group)) => Some((class, group, comp)),
+                _ => None,
+            }).map(|(class, group, comp)| Component {
                 vendor: comp.vendor.unwrap_or_else(|| self.vendor.clone()),
-                class: comp.class.unwrap(),
-                group: comp.group.unwrap(),
+                class,
+                group,
                 sub_group: comp.sub_group,
+                bundle: comp.bundle,
                 variant: comp.variant,
                 version: comp
                     .version
@@ -230,6 +468,7 @@ impl Package {
             }).collect()
     }
 
+    #[cfg(feature = "fs")]
     fn make_condition_lookup<'a>(&'a self, l: &Logger) -> HashMap<&'a str, &'a Condition> {
         let mut map = HashMap::with_capacity(self.conditions.0.iter().count());
         for cond in &self.conditions.0 {
@@ -241,20 +480,13 @@ impl Package {
     }
 
     fn make_dump_devices<'a>(&'a self) -> Vec<(&'a str, DumpDevice<'a>)> {
-        let from_pack = FromPack::new(
-            &self.vendor,
-            &self.name,
-            &self.releases.latest_release().version,
-            &self.url,
-        );
-        self.devices
-            .0
-            .iter()
-            .map(|(name, d)| (name.as_str(), DumpDevice::from_device(d, from_pack.clone())))
+        self.device_refs()
+            .map(|dev| (dev.name(), dev.to_dump()))
             .collect()
     }
 }
 
+#[cfg(feature = "fs")]
 pub fn check_args<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("check")
         .about("Check a project or pack for correct usage of the CMSIS standard")
@@ -267,9 +499,11 @@ pub fn check_args<'a, 'b>() -> App<'a, 'b> {
         )
 }
 
+#[cfg(feature = "fs")]
 pub fn check_command<'a>(_: &Config, args: &ArgMatches<'a>, l: &Logger) -> Result<(), FailError> {
     let filename = args.value_of("INPUT").unwrap();
-    match Package::from_path(Path::new(filename), &l) {
+    let parsed = Package::from_path(Path::new(filename), &ParseOptions::strict(), &l);
+    match parsed {
         Ok(c) => {
             info!(l, "Parsing succedded");
             info!(l, "{} Valid Conditions", c.conditions.0.iter().count());
@@ -318,6 +552,20 @@ pub fn check_command<'a>(_: &Config, args: &ArgMatches<'a>, l: &Logger) -> Resul
                 }
             }
             info!(l, "{} Valid Devices", c.devices.0.len());
+            if !c.report.dropped_devices.is_empty() {
+                warn!(l, "{} Dropped Devices", c.report.dropped_devices.len());
+            }
+            for dev in c.devices.iter() {
+                let completeness = dev.completeness();
+                if !completeness.is_complete() {
+                    warn!(
+                        l,
+                        "Device {} is missing {}",
+                        dev.name,
+                        completeness.missing().join(", ")
+                    );
+                }
+            }
             info!(l, "{} Valid Software Components", num_components);
             info!(l, "{} Valid Files References", num_files);
         }
@@ -329,6 +577,7 @@ pub fn check_command<'a>(_: &Config, args: &ArgMatches<'a>, l: &Logger) -> Resul
     Ok(())
 }
 
+#[cfg(feature = "fs")]
 pub fn dump_devices_args<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("dump-devices")
         .about("Dump devices as json")
@@ -343,6 +592,46 @@ pub fn dump_devices_args<'a, 'b>() -> App<'a, 'b> {
                 .short("b")
                 .takes_value(true)
                 .help("Dump JSON in the specified file"),
+        ).arg(
+            Arg::with_name("shards")
+                .short("s")
+                .long("shards")
+                .takes_value(true)
+                .help("Dump JSON sharded by vendor into the specified directory"),
+        ).arg(
+            Arg::with_name("store")
+                .long("store")
+                .takes_value(true)
+                .conflicts_with("shards")
+                .help("Dump devices into a keyed store in the specified directory"),
+        ).arg(
+            Arg::with_name("index-json")
+                .long("index-json")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["shards", "store"])
+                .help("Dump devices in the index.json format read by pyOCD"),
+        ).arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["shards", "store", "index-json", "devices"])
+                .help("Dump every device into the specified file, replacing it"),
+        ).arg(
+            Arg::with_name("cbor")
+                .long("cbor")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["shards", "store", "index-json", "devices", "json"])
+                .help("Dump every device into the specified file as CBOR, replacing it"),
+        ).arg(
+            Arg::with_name("completions")
+                .long("completions")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["shards", "store", "index-json", "json", "cbor"])
+                .help("Update the completion data for editors in the specified file"),
         ).arg(
             Arg::with_name("INPUT")
                 .help("Input file to dump devices from")
@@ -350,6 +639,18 @@ pub fn dump_devices_args<'a, 'b>() -> App<'a, 'b> {
         )
 }
 
+// Every device of `pdscs`, keyed by name in natural order
+pub(crate) fn device_database<'a>(
+    pdscs: &[&'a Package],
+) -> BTreeMap<Natural<&'a str>, DumpDevice<'a>> {
+    pdscs
+        .iter()
+        .flat_map(|pdsc| pdsc.make_dump_devices().into_iter())
+        .map(|(name, dev)| (Natural(name), dev))
+        .collect()
+}
+
+#[cfg(feature = "fs")]
 pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
     pdscs: I,
     device_dest: Option<P>,
@@ -357,10 +658,7 @@ pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
     _: &Logger,
 ) -> Result<(), FailError> {
     let pdscs: Vec<&Package> = pdscs.into_iter().collect();
-    let devices = pdscs
-        .iter()
-        .flat_map(|pdsc| pdsc.make_dump_devices().into_iter())
-        .collect::<HashMap<_, _>>();
+    let devices = device_database(&pdscs);
     match device_dest {
         Some(to_file) => {
             if !devices.is_empty() {
@@ -371,8 +669,8 @@ pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
                     old_devices = serde_json::from_slice(&file_contents).unwrap_or_default();
                 }
                 let mut all_devices = BTreeMap::new();
-                all_devices.extend(old_devices.iter());
-                all_devices.extend(devices.iter());
+                all_devices.extend(old_devices.iter().map(|(k, v)| (Natural(*k), v)));
+                all_devices.extend(devices.iter().map(|(k, v)| (*k, v)));
                 let mut options = OpenOptions::new();
                 options.write(true);
                 options.create(true);
@@ -389,7 +687,7 @@ pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
     let boards = pdscs
         .iter()
         .flat_map(|pdsc| pdsc.boards.iter())
-        .map(|b| (&b.name, b))
+        .map(|b| (b.name.as_str(), b))
         .collect::<HashMap<_, _>>();
     match board_dest {
         Some(to_file) => {
@@ -400,8 +698,8 @@ pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
                 old_boards = serde_json::from_slice(&file_contents).unwrap_or_default();
             }
             let mut all_boards = BTreeMap::new();
-            all_boards.extend(old_boards.iter());
-            all_boards.extend(boards.iter());
+            all_boards.extend(old_boards.iter().map(|(k, v)| (Natural(k.as_str()), v)));
+            all_boards.extend(boards.iter().map(|(k, v)| (Natural(k.as_str()), *v)));
             let mut options = OpenOptions::new();
             options.write(true);
             options.create(true);
@@ -417,6 +715,7 @@ pub fn dump_devices<'a, P: AsRef<Path>, I: IntoIterator<Item = &'a Package>>(
     Ok(())
 }
 
+#[cfg(feature = "fs")]
 pub fn dump_devices_command<'a>(
     c: &Config,
     args: &ArgMatches<'a>,
@@ -425,23 +724,30 @@ pub fn dump_devices_command<'a>(
     let files = args
         .value_of("INPUT")
         .map(|input| vec![Box::new(Path::new(input)).to_path_buf()]);
-    let filenames = files
-        .or_else(|| {
-            c.pack_store.read_dir().ok().map(|rd| {
-                rd.flat_map(|dirent| dirent.into_iter().map(|p| p.path()))
-                    .collect()
-            })
-        }).unwrap();
-    let pdscs = filenames
-        .into_iter()
-        .flat_map(|filename| match Package::from_path(&filename, &l) {
-            Ok(c) => Some(c),
-            Err(e) => {
-                error!(l, "parsing {:?}: {}", filename, e);
-                None
-            }
-        }).collect::<Vec<Package>>();
-    let to_ret = dump_devices(&pdscs, args.value_of("devices"), args.value_of("boards"), l);
+    let filenames = files.unwrap_or_else(|| c.pdsc_files());
+    // Only the descriptions that changed since the last update are parsed
+    if let Some(dest) = args.value_of("completions") {
+        return update_completions(filenames, Path::new(dest), &c.parse_options, l).map(|_| ());
+    }
+    let pdscs = parse_packages(filenames, c, l);
+    let to_ret = match (
+        args.value_of("shards"),
+        args.value_of("store"),
+        args.value_of("index-json"),
+        args.value_of("json"),
+        args.value_of("cbor"),
+    ) {
+        (Some(shard_dir), _, _, _, _) => dump_device_shards(&pdscs, shard_dir, l),
+        (None, Some(store_dir), _, _, _) => dump_device_store(&pdscs, store_dir, l),
+        (None, None, Some(index), _, _) => dump_index_json(&pdscs, index, l),
+        (None, None, None, Some(dest), _) => dump_devices_json(&pdscs, dest, l),
+        (None, None, None, None, Some(dest)) => {
+            dump_device_database(&pdscs, dest, DumpFormat::Cbor, l)
+        }
+        (None, None, None, None, None) => {
+            dump_devices(&pdscs, args.value_of("devices"), args.value_of("boards"), l)
+        }
+    };
     debug!(l, "exiting");
     to_ret
 }
@@ -0,0 +1,97 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure::{err_msg, Error as FailError};
+use slog::Logger;
+
+use pack_index::config::Config;
+
+use dump::dump_devices_json;
+use lookup::{find_devices, nearest_device};
+use pool::parse_packages;
+use view::{sorted_device_refs, DeviceRef};
+
+fn print_device(dev: &DeviceRef) {
+    println!(
+        "{:<32} {}.{} {}",
+        dev.name(),
+        dev.vendor(),
+        dev.pack_name(),
+        dev.version()
+    );
+}
+
+pub fn list_devices_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("list-devices")
+        .about("List the devices of the cached descriptions and the packs describing them")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("vendor")
+                .long("vendor")
+                .takes_value(true)
+                .help("Only list the devices of packs from this vendor"),
+        )
+}
+
+pub fn list_devices_command<'a>(
+    c: &Config,
+    args: &ArgMatches<'a>,
+    l: &Logger,
+) -> Result<(), FailError> {
+    let pdscs = parse_packages(c.pdsc_files(), c, l);
+    let vendor = args.value_of("vendor");
+    for dev in sorted_device_refs(&pdscs) {
+        if vendor.map(|v| v.eq_ignore_ascii_case(dev.vendor())).unwrap_or(true) {
+            print_device(&dev);
+        }
+    }
+    Ok(())
+}
+
+pub fn find_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("find")
+        .about("Find the devices whose name or alias matches a pattern")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("PATTERN")
+                .help("Device name, where * and ? match like in a shell glob")
+                .required(true)
+                .index(1),
+        )
+}
+
+/// Fails when no device matches, suggesting a name close to the pattern.
+pub fn find_command<'a>(c: &Config, args: &ArgMatches<'a>, l: &Logger) -> Result<(), FailError> {
+    let pattern = args.value_of("PATTERN").unwrap();
+    let pdscs = parse_packages(c.pdsc_files(), c, l);
+    let found = find_devices(&pdscs, pattern);
+    if found.is_empty() {
+        let nearest = nearest_device(&pdscs, pattern)
+            .map(|dev| format!(", did you mean {}?", dev.name()))
+            .unwrap_or_default();
+        return Err(err_msg(format!("no device matches {}{}", pattern, nearest)));
+    }
+    for dev in &found {
+        print_device(dev);
+    }
+    Ok(())
+}
+
+pub fn dump_json_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dump-json")
+        .about("Dump every device of the cached descriptions into a JSON file")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("File to write, replacing it")
+                .required(true)
+                .index(1),
+        )
+}
+
+pub fn dump_json_command<'a>(
+    c: &Config,
+    args: &ArgMatches<'a>,
+    l: &Logger,
+) -> Result<(), FailError> {
+    let pdscs = parse_packages(c.pdsc_files(), c, l);
+    dump_devices_json(&pdscs, args.value_of("OUTPUT").unwrap(), l)
+}
@@ -0,0 +1,286 @@
+use std::cmp::Reverse;
+
+use failure::{err_msg, Error as FailError};
+#[cfg(feature = "fs")]
+use slog::Logger;
+
+#[cfg(feature = "fs")]
+use pack_index::config::Config;
+
+use utils::glob::{glob_match, lowercase_chars};
+use utils::natural::natural_cmp;
+
+use usage::Usage;
+use device::{Device, Devices};
+use view::DeviceRef;
+use Package;
+
+/// A device along with the pack that describes it.
+pub type DeviceCandidate<'a> = DeviceRef<'a>;
+
+/// Every device named `name` in `packs`. Several vendors may describe parts
+/// under the same name, so more than one candidate may be returned.
+pub fn devices_by_name<'a, I>(packs: I, name: &str) -> Vec<DeviceCandidate<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    packs
+        .into_iter()
+        .filter_map(|pack| {
+            pack.devices
+                .get(name)
+                .map(|device| DeviceRef::new(pack, device))
+        }).collect()
+}
+
+/// Every device of `packs` whose name or alias matches `pattern`, a glob
+/// ignoring case, by name.
+pub fn find_devices<'a, I>(packs: I, pattern: &str) -> Vec<DeviceCandidate<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    let mut found: Vec<DeviceCandidate> = packs
+        .into_iter()
+        .flat_map(|pack| {
+            pack.devices
+                .find(pattern)
+                .into_iter()
+                .map(move |device| DeviceRef::new(pack, device))
+        }).collect();
+    found.sort_by(|a, b| natural_cmp(a.name(), b.name()));
+    found
+}
+
+/// The device of `packs` with the name closest to `name`, to suggest when
+/// nothing matched it.
+pub fn nearest_device<'a, I>(packs: I, name: &str) -> Option<DeviceCandidate<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    let wanted = lowercase_chars(name);
+    packs
+        .into_iter()
+        .filter_map(|pack| {
+            pack.devices
+                .nearest(name)
+                .map(|device| DeviceRef::new(pack, device))
+        }).min_by_key(|dev| edit_distance(&wanted, &lowercase_chars(dev.name())))
+}
+
+/// The names of the devices mounted on the board `board`, from the first
+/// pack describing it.
+pub fn board_devices<'a, I>(packs: I, board: &str) -> Option<&'a [String]>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    packs
+        .into_iter()
+        .filter_map(|pack| pack.boards.get(board))
+        .map(|board| board.mounted_devices.as_slice())
+        .next()
+}
+
+/// The device named `name` in a pack from `vendor`, compared ignoring case.
+pub fn device_by_name_and_vendor<'a, I>(
+    packs: I,
+    name: &str,
+    vendor: &str,
+) -> Option<DeviceCandidate<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    devices_by_name(packs, name)
+        .into_iter()
+        .find(|candidate| candidate.vendor().eq_ignore_ascii_case(vendor))
+}
+
+/// Resolve a device written either as `NAME` or as `VENDOR::NAME`. A plain
+/// name described by more than one vendor is an error listing them.
+pub fn resolve_device<'a>(
+    packs: &'a [Package],
+    query: &str,
+) -> Result<DeviceCandidate<'a>, FailError> {
+    resolve_device_ranked(packs, query, &Usage::default())
+}
+
+/// Like `resolve_device`, listing the vendors of an ambiguous name by how
+/// often their device was used.
+pub fn resolve_device_ranked<'a>(
+    packs: &'a [Package],
+    query: &str,
+    usage: &Usage,
+) -> Result<DeviceCandidate<'a>, FailError> {
+    let (vendor, name) = match query.find("::") {
+        Some(index) => (Some(&query[..index]), &query[index + 2..]),
+        None => (None, query),
+    };
+    if let Some(vendor) = vendor {
+        return device_by_name_and_vendor(packs, name, vendor).ok_or_else(|| {
+            err_msg(format!("Device {} from {} not found in the pack store", name, vendor))
+        });
+    }
+    let mut candidates = devices_by_name(packs, name);
+    candidates.sort_by(|a, b| natural_cmp(a.vendor(), b.vendor()));
+    candidates.dedup_by(|a, b| a.vendor() == b.vendor());
+    candidates.sort_by_key(|c| {
+        let uses = (usage.device_uses(c.vendor(), name), usage.vendor_uses(c.vendor()));
+        Reverse(uses)
+    });
+    match candidates.len() {
+        0 => Err(err_msg(match nearest_device(packs, name) {
+            Some(dev) => format!(
+                "Device {} not found in the pack store, did you mean {}?",
+                name,
+                dev.name()
+            ),
+            None => format!("Device {} not found in the pack store", name),
+        })),
+        1 => Ok(candidates[0]),
+        _ => {
+            let vendors: Vec<String> = candidates
+                .iter()
+                .map(|c| format!("{}::{}", c.vendor(), name))
+                .collect();
+            Err(err_msg(format!(
+                "Device {} is described by several vendors, use one of {}",
+                name,
+                vendors.join(", ")
+            )))
+        }
+    }
+}
+
+/// Resolve a device the user asked for, ranked and recorded in the usage of
+/// the pack store.
+#[cfg(feature = "fs")]
+pub(crate) fn resolve_used_device<'a>(
+    config: &Config,
+    packs: &'a [Package],
+    query: &str,
+    l: &Logger,
+) -> Result<DeviceCandidate<'a>, FailError> {
+    let found = resolve_device_ranked(packs, query, &Usage::load(config))?;
+    if let Err(e) = Usage::record(config, |usage| usage.use_device(&found)) {
+        debug!(l, "Could not record the use of {}: {}", query, e);
+    }
+    Ok(found)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl Devices {
+    /// The devices whose name or one of its aliases matches `pattern`,
+    /// ignoring case. `*` and `?` match like in a shell glob; without them
+    /// the whole name must match.
+    pub fn find(&self, pattern: &str) -> Vec<&Device> {
+        let pattern = lowercase_chars(pattern);
+        self.iter()
+            .filter(|dev| {
+                Some(&dev.name)
+                    .into_iter()
+                    .chain(dev.aliases.iter())
+                    .any(|name| glob_match(&pattern, &lowercase_chars(name)))
+            }).collect()
+    }
+
+    /// The device with the name closest to `name`, to suggest when `find`
+    /// found nothing. Names differing in more than a third of their
+    /// characters are not suggested.
+    pub fn nearest(&self, name: &str) -> Option<&Device> {
+        let wanted = lowercase_chars(name);
+        let limit = (wanted.len() / 3).max(1);
+        self.iter()
+            .map(|dev| (edit_distance(&wanted, &lowercase_chars(&dev.name)), dev))
+            .filter(|&(distance, _)| distance <= limit)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, dev)| dev)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::{Discard, Logger};
+    use utils::parse::{FromElem, ParseOptions};
+
+    fn pack(vendor: &str, devices: &[&str]) -> Package {
+        let l = Logger::root(Discard, o!());
+        let devices: String = devices
+            .iter()
+            .map(|name| format!(r#"<device Dname="{}"/>"#, name))
+            .collect();
+        Package::from_string(
+            &format!(
+                r#"<package>
+                  <vendor>{0}</vendor>
+                  <name>Test</name>
+                  <description>Lookup</description>
+                  <url>http://www.example.com/</url>
+                  <releases><release version="1.0.0">First</release></releases>
+                  <devices>
+                    <family Dfamily="Series" Dvendor="{0}:1">
+                      <processor Dcore="Cortex-M4"/>
+                      {1}
+                    </family>
+                  </devices>
+                </package>"#,
+                vendor, devices
+            ),
+            &ParseOptions::default(),
+            &l,
+        ).unwrap()
+    }
+
+    #[test]
+    fn find_by_pattern() {
+        let pack = pack("ST", &["STM32F407VG", "STM32F407ZG", "STM32F429ZI"]);
+        let names = |pattern| -> Vec<String> {
+            pack.devices
+                .find(pattern)
+                .into_iter()
+                .map(|dev| dev.name.clone())
+                .collect()
+        };
+        assert_eq!(names("stm32f407*"), vec!["STM32F407VG", "STM32F407ZG"]);
+        assert_eq!(names("STM32F4?9ZI"), vec!["STM32F429ZI"]);
+        assert!(names("STM32F407").is_empty());
+    }
+
+    #[test]
+    fn suggest_the_nearest_device_of_every_pack() {
+        let packs = vec![
+            pack("Far", &["STM32F030", "STM32F470AB"]),
+            pack("Near", &["STM32F470VE"]),
+        ];
+        let err = resolve_device(&packs, "STM32F470VG").unwrap_err();
+        assert!(err.to_string().ends_with("did you mean STM32F470VE?"));
+        assert_eq!(nearest_device(&packs, "STM32F470VG").unwrap().vendor(), "Near");
+        let err = resolve_device(&packs, "nRF52832").unwrap_err();
+        assert!(!err.to_string().contains("did you mean"));
+    }
+
+    #[test]
+    fn resolve_by_vendor() {
+        let packs = vec![pack("A", &["Shared"]), pack("B", &["Shared", "Own"])];
+        assert!(resolve_device(&packs, "Shared").is_err());
+        assert_eq!(resolve_device(&packs, "b::Shared").unwrap().vendor(), "B");
+        assert_eq!(resolve_device(&packs, "Own").unwrap().vendor(), "B");
+        assert!(resolve_device(&packs, "A::Own").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use slog::Logger;
+
+use pack_index::config::Config;
+use utils::parse::{FromElem, ParseOptions};
+
+use report::{FailedFile, ParseReport};
+use Package;
+
+#[cfg(target_os = "linux")]
+fn lower_priority(niceness: i32) -> io::Result<()> {
+    // Linux keeps a nice value per thread, so only the calling thread is affected
+    let ret = unsafe { ::libc::setpriority(::libc::PRIO_PROCESS, 0, niceness) };
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+// Elsewhere the nice value is shared by the whole process
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "the niceness of a thread can only be set on Linux",
+    ))
+}
+
+/// Parse the PDSC files at `paths`, using as many threads and the niceness
+/// requested by `config`. Files that fail to parse are logged and skipped;
+/// the others are returned in the order of `paths`.
+pub fn parse_packages<I, P>(paths: I, config: &Config, l: &Logger) -> Vec<Package>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    parse_packages_with_report(paths, config, l).0
+}
+
+/// Parse the PDSC files at `paths` like `parse_packages`, also returning
+/// the files, devices and elements that had to be left out.
+pub fn parse_packages_with_report<I, P>(
+    paths: I,
+    config: &Config,
+    l: &Logger,
+) -> (Vec<Package>, ParseReport)
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let parse = |path: &PathBuf, opts: &ParseOptions, l: &Logger| {
+        Package::from_path(path, opts, l).map_err(|e| {
+            error!(l, "parsing {:?}: {}", path, e);
+            FailedFile {
+                path: path.clone(),
+                reason: e.to_string(),
+            }
+        })
+    };
+    let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+    let threads = config.parse_threads.max(1);
+    // Without a niceness to apply, a single thread may as well be this one
+    if threads == 1 && config.parse_niceness.is_none() {
+        let opts = &config.parse_options;
+        return collect_parsed(paths.iter().map(|path| parse(path, opts, l)));
+    }
+    let work = Arc::new(Mutex::new(paths.into_iter().enumerate()));
+    let (send, recv) = channel();
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let work = work.clone();
+            let send = send.clone();
+            let niceness = config.parse_niceness;
+            let opts = config.parse_options;
+            let l = l.clone();
+            thread::spawn(move || {
+                if let Some(niceness) = niceness {
+                    if let Err(e) = lower_priority(niceness) {
+                        debug!(l, "could not set parse thread niceness to {}: {}", niceness, e);
+                    }
+                }
+                loop {
+                    let next = work.lock().ok().and_then(|mut work| work.next());
+                    match next {
+                        Some((index, path)) => {
+                            if send.send((index, parse(&path, &opts, &l))).is_err() {
+                                break;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+            })
+        }).collect();
+    drop(send);
+    let mut parsed: Vec<(usize, Result<Package, FailedFile>)> = recv.iter().collect();
+    for worker in workers {
+        if worker.join().is_err() {
+            error!(l, "a parse thread panicked");
+        }
+    }
+    parsed.sort_by_key(|&(index, _)| index);
+    collect_parsed(parsed.into_iter().map(|(_, pack)| pack))
+}
+
+fn collect_parsed<I>(parsed: I) -> (Vec<Package>, ParseReport)
+where
+    I: IntoIterator<Item = Result<Package, FailedFile>>,
+{
+    let mut report = ParseReport::default();
+    let packs = parsed
+        .into_iter()
+        .filter_map(|parsed| match parsed {
+            Ok(pack) => {
+                report.extend(pack.report().clone());
+                Some(pack)
+            }
+            Err(failed) => {
+                report.failed_files.push(failed);
+                None
+            }
+        }).collect();
+    (packs, report)
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use minidom::{Element, Error};
+use slog::Logger;
+
+use device::DroppedDevice;
+
+/// An element of a family, sub-family or device that could not be parsed
+/// and was left out of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedElement {
+    /// The family, sub-family or device the element belongs to
+    pub parent: String,
+    pub element: String,
+    pub reason: String,
+}
+
+/// A PDSC that could not be parsed at all.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Everything parsing left out instead of failing: the devices, the
+/// elements and the files that were broken.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParseReport {
+    pub dropped_devices: Vec<DroppedDevice>,
+    pub skipped_elements: Vec<SkippedElement>,
+    pub failed_files: Vec<FailedFile>,
+}
+
+impl ParseReport {
+    pub fn is_empty(&self) -> bool {
+        self.dropped_devices.is_empty()
+            && self.skipped_elements.is_empty()
+            && self.failed_files.is_empty()
+    }
+
+    pub fn extend(&mut self, other: ParseReport) {
+        self.dropped_devices.extend(other.dropped_devices);
+        self.skipped_elements.extend(other.skipped_elements);
+        self.failed_files.extend(other.failed_files);
+    }
+
+    /// Keep the value parsed from `element` of `parent`, or record why
+    /// there is none.
+    pub(crate) fn keep_or_skip<T>(
+        &mut self,
+        parent: Option<&str>,
+        element: &Element,
+        parsed: Result<T, Error>,
+        l: &Logger,
+    ) -> Option<T> {
+        match parsed {
+            Ok(kept) => Some(kept),
+            Err(e) => {
+                let parent = parent.unwrap_or("<unnamed>").to_string();
+                warn!(l, "Skipping {} of {}: {}", element.name(), parent, e);
+                self.skipped_elements.push(SkippedElement {
+                    parent,
+                    element: element.name().to_string(),
+                    reason: e.to_string(),
+                });
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use device::{Device, Memory};
+use intern::Name;
+
+/// What a memory region is used for, independently of what the vendor called
+/// it (IROM1, FLASH, IRAM2, SRAM_D1, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryRole {
+    PrimaryFlash,
+    SecondaryFlash,
+    PrimaryRam,
+    SecondaryRam,
+    BackupRam,
+    Peripheral,
+}
+
+/// Heuristics mapping memory region ids to roles. Overrides take precedence
+/// over the heuristics and are matched ignoring case.
+#[derive(Debug, Clone)]
+pub struct RoleHeuristics {
+    overrides: Vec<(String, MemoryRole)>,
+    backup_markers: Vec<String>,
+    flash_markers: Vec<String>,
+}
+
+impl Default for RoleHeuristics {
+    fn default() -> Self {
+        RoleHeuristics {
+            overrides: Vec::new(),
+            backup_markers: vec![String::from("BKP"), String::from("BACKUP")],
+            flash_markers: vec![String::from("ROM"), String::from("FLASH")],
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Kind {
+    Flash,
+    Ram,
+}
+
+fn assign(
+    mut candidates: Vec<(&Name, &Memory)>,
+    primary: MemoryRole,
+    secondary: MemoryRole,
+    roles: &mut BTreeMap<String, MemoryRole>,
+) {
+    candidates.sort_by_key(|&(id, mem)| (!mem.startup, !mem.default, mem.start, id.clone()));
+    let mut candidates = candidates.into_iter();
+    // An override may already have picked the primary region
+    if !roles.values().any(|role| *role == primary) {
+        if let Some((id, _)) = candidates.next() {
+            roles.insert(id.to_string(), primary);
+        }
+    }
+    for (id, _) in candidates {
+        roles.insert(id.to_string(), secondary);
+    }
+}
+
+impl RoleHeuristics {
+    pub fn with_override<S: Into<String>>(mut self, id: S, role: MemoryRole) -> Self {
+        self.overrides.push((id.into(), role));
+        self
+    }
+
+    pub fn with_backup_marker<S: Into<String>>(mut self, marker: S) -> Self {
+        self.backup_markers.push(marker.into());
+        self
+    }
+
+    pub fn with_flash_marker<S: Into<String>>(mut self, marker: S) -> Self {
+        self.flash_markers.push(marker.into());
+        self
+    }
+
+    fn override_for(&self, id: &str) -> Option<MemoryRole> {
+        self.overrides
+            .iter()
+            .find(|&&(ref name, _)| name.eq_ignore_ascii_case(id))
+            .map(|&(_, role)| role)
+    }
+
+    fn marked(markers: &[String], id: &str) -> bool {
+        let id = id.to_uppercase();
+        markers.iter().any(|m| id.contains(&m.to_uppercase()))
+    }
+
+    fn kind(&self, id: &str, mem: &Memory) -> Kind {
+        if Self::marked(&self.flash_markers, id) || (mem.access.execute && !mem.access.write) {
+            Kind::Flash
+        } else {
+            Kind::Ram
+        }
+    }
+
+    /// Assign a role to every memory region of `device`. Of each kind, the
+    /// region marked as startup or default is primary, falling back to the one
+    /// at the lowest address.
+    pub fn roles(&self, device: &Device) -> BTreeMap<String, MemoryRole> {
+        let mut roles = BTreeMap::new();
+        let mut flash = Vec::new();
+        let mut ram = Vec::new();
+        for (id, mem) in &device.memories.0 {
+            if let Some(role) = self.override_for(id) {
+                roles.insert(id.to_string(), role);
+            } else if mem.access.peripheral {
+                roles.insert(id.to_string(), MemoryRole::Peripheral);
+            } else if Self::marked(&self.backup_markers, id) {
+                roles.insert(id.to_string(), MemoryRole::BackupRam);
+            } else if self.kind(id, mem) == Kind::Flash {
+                flash.push((id, mem));
+            } else {
+                ram.push((id, mem));
+            }
+        }
+        assign(flash, MemoryRole::PrimaryFlash, MemoryRole::SecondaryFlash, &mut roles);
+        assign(ram, MemoryRole::PrimaryRam, MemoryRole::SecondaryRam, &mut roles);
+        roles
+    }
+}
+
+impl Device {
+    /// The role of each memory region, using the default heuristics.
+    pub fn memory_roles(&self) -> BTreeMap<String, MemoryRole> {
+        RoleHeuristics::default().roles(self)
+    }
+}
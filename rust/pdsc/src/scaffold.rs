@@ -0,0 +1,287 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure::{err_msg, Error as FailError};
+use slog::Logger;
+
+use pack_index::config::Config;
+
+use component::FileCategory;
+use device::{for_processor, CoreInfo, Device, Memory, FPU};
+use intern::Name;
+use lookup::resolve_used_device;
+use pool::parse_packages;
+use roles::MemoryRole;
+use Package;
+
+/// The files of a minimal GCC project targeting one device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSkeleton {
+    pub linker_script: String,
+    /// Where to find the startup code of the device in its pack
+    pub startup: String,
+    pub flags: String,
+    pub main: String,
+}
+
+fn gcc_target(core: &CoreInfo) -> Vec<String> {
+    use device::Core::*;
+    let cpu = match core.core {
+        CortexM0 | SC000 => "-mcpu=cortex-m0",
+        CortexM0Plus => "-mcpu=cortex-m0plus",
+        CortexM1 => "-mcpu=cortex-m1",
+        CortexM3 | SC300 => "-mcpu=cortex-m3",
+        CortexM4 => "-mcpu=cortex-m4",
+        CortexM7 => "-mcpu=cortex-m7",
+        CortexM23 => "-mcpu=cortex-m23",
+        CortexM33 => "-mcpu=cortex-m33",
+        ARMV8MBL => "-march=armv8-m.base",
+        ARMV8MML => "-march=armv8-m.main",
+        CortexR4 => "-mcpu=cortex-r4",
+        CortexR5 => "-mcpu=cortex-r5",
+        CortexR7 => "-mcpu=cortex-r7",
+        CortexR8 => "-mcpu=cortex-r8",
+        CortexA5 => "-mcpu=cortex-a5",
+        CortexA7 => "-mcpu=cortex-a7",
+        CortexA8 => "-mcpu=cortex-a8",
+        CortexA9 => "-mcpu=cortex-a9",
+        CortexA15 => "-mcpu=cortex-a15",
+        CortexA17 => "-mcpu=cortex-a17",
+        CortexA32 => "-mcpu=cortex-a32",
+        CortexA35 => "-mcpu=cortex-a35",
+        CortexA53 => "-mcpu=cortex-a53",
+        CortexA57 => "-mcpu=cortex-a57",
+        CortexA72 => "-mcpu=cortex-a72",
+        CortexA73 => "-mcpu=cortex-a73",
+    };
+    let fpu = match (core.fpu, core.core) {
+        (FPU::None, _) => None,
+        (FPU::SinglePrecision, CortexM4) => Some("fpv4-sp-d16"),
+        (FPU::SinglePrecision, _) => Some("fpv5-sp-d16"),
+        (FPU::DoublePrecision, _) => Some("fpv5-d16"),
+    };
+    let mut flags = vec![cpu.to_string(), String::from("-mthumb")];
+    match fpu {
+        Some(fpu) => {
+            flags.push(format!("-mfpu={}", fpu));
+            flags.push(String::from("-mfloat-abi=hard"));
+        }
+        None => flags.push(String::from("-mfloat-abi=soft")),
+    }
+    flags
+}
+
+macro_rules! sections {
+    () => {
+        "}}
+
+ENTRY(Reset_Handler)
+
+SECTIONS
+{{
+  .text :
+  {{
+    KEEP(*(.vectors))
+    *(.text*)
+    *(.rodata*)
+  }} > {flash}
+
+  __etext = .;
+
+  .data : AT (__etext)
+  {{
+    __data_start__ = .;
+    *(.data*)
+    __data_end__ = .;
+  }} > {ram}
+
+  .bss (NOLOAD) :
+  {{
+    __bss_start__ = .;
+    *(.bss*)
+    *(COMMON)
+    __bss_end__ = .;
+  }} > {ram}
+
+  __StackTop = ORIGIN({ram}) + LENGTH({ram});
+}}
+"
+    };
+}
+
+// Memory region names of GNU ld are identifiers
+pub(crate) fn region_name(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+impl ProjectSkeleton {
+    /// Lay out a project for processor `pname` of `device`, as described by
+    /// `pack`. The device needs both a flash and a RAM region to link into.
+    pub fn new(pack: &Package, device: &Device, pname: Option<&str>) -> Result<Self, FailError> {
+        let core = device
+            .topology()
+            .into_iter()
+            .find(|core| pname.is_none() || core.name == pname)
+            .ok_or_else(|| err_msg(format!("{} has no processor {:?}", device.name, pname)))?;
+        let roles = device.memory_roles();
+        let mut memories: Vec<(&Name, &Memory)> = device
+            .memories
+            .0
+            .iter()
+            .filter(|&(_, mem)| {
+                !mem.access.peripheral && !mem.uninit && for_processor(&mem.pname, pname)
+            })
+            .collect();
+        memories.sort_by_key(|&(id, mem)| (mem.start, id.clone()));
+        let with_role = |role: MemoryRole| {
+            memories
+                .iter()
+                .find(|&&(id, _)| roles.get(id.as_str()) == Some(&role))
+                .map(|&(id, _)| region_name(id))
+                .ok_or_else(|| err_msg(format!("{} has no {:?} memory", device.name, role)))
+        };
+        let flash = with_role(MemoryRole::PrimaryFlash)?;
+        let ram = with_role(MemoryRole::PrimaryRam)?;
+
+        let mut linker_script = format!(
+            "/* {} from {}.{} */\n\nMEMORY\n{{\n",
+            device.name, pack.vendor, pack.name
+        );
+        for &(id, mem) in &memories {
+            let mut access = String::new();
+            if mem.access.read {
+                access.push('r');
+            }
+            if mem.access.write {
+                access.push('w');
+            }
+            if mem.access.execute {
+                access.push('x');
+            }
+            writeln!(
+                linker_script,
+                "  {} ({}) : ORIGIN = 0x{:08X}, LENGTH = 0x{:X}",
+                region_name(id),
+                access,
+                mem.start,
+                mem.size
+            )?;
+        }
+        write!(linker_script, sections!(), flash = flash, ram = ram)?;
+
+        let mut startup = format!(
+            "# Startup code of {} ({}), from pack {}.{}.{}\n",
+            device.name,
+            core.core,
+            pack.vendor,
+            pack.name,
+            pack.releases.latest_release().version
+        );
+        let mut found = false;
+        for comp in pack.make_components() {
+            if comp.class != "Device" || comp.group != "Startup" {
+                continue;
+            }
+            for file in &comp.files {
+                match file.category {
+                    FileCategory::Source
+                    | FileCategory::SourceAsm
+                    | FileCategory::SourceC
+                    | FileCategory::Header => (),
+                    _ => continue,
+                }
+                found = true;
+                match file.condition.as_ref().or_else(|| comp.condition.as_ref()) {
+                    Some(condition) => {
+                        writeln!(startup, "{} (when {})", file.path.display(), condition)?
+                    }
+                    None => writeln!(startup, "{}", file.path.display())?,
+                }
+            }
+        }
+        if !found {
+            startup.push_str("# The pack provides no Device::Startup component\n");
+        }
+
+        let mut flags = gcc_target(&core);
+        flags.push(String::from("-ffunction-sections"));
+        flags.push(String::from("-fdata-sections"));
+        flags.push(String::from("-Wl,--gc-sections"));
+        flags.push(String::from("-Tlinker.ld"));
+        Ok(ProjectSkeleton {
+            linker_script,
+            startup,
+            flags: flags.join("\n") + "\n",
+            main: String::from("int main(void)\n{\n    for (;;) {\n    }\n}\n"),
+        })
+    }
+
+    /// Write the project into `dir`, returning the files written.
+    pub fn write_to(&self, dir: &Path) -> Result<Vec<PathBuf>, FailError> {
+        create_dir_all(dir)?;
+        let files = [
+            ("linker.ld", &self.linker_script),
+            ("startup.txt", &self.startup),
+            ("flags.txt", &self.flags),
+            ("main.c", &self.main),
+        ];
+        let mut written = Vec::new();
+        for &(name, content) in &files {
+            let path = dir.join(name);
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)?
+                .write_all(content.as_bytes())?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+pub fn new_project_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("new-project")
+        .about("Create a minimal project skeleton for a device from the pack store")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("DEVICE")
+                .help("Device the project targets, as NAME or VENDOR::NAME")
+                .required(true)
+                .index(1),
+        ).arg(
+            Arg::with_name("processor")
+                .long("processor")
+                .takes_value(true)
+                .value_name("PNAME")
+                .help("Processor to target on a multi-core device"),
+        ).arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Directory to create the project in, named after the device by default"),
+        )
+}
+
+pub fn new_project_command<'a>(
+    c: &Config,
+    args: &ArgMatches<'a>,
+    l: &Logger,
+) -> Result<(), FailError> {
+    let query = args.value_of("DEVICE").unwrap();
+    let packs = parse_packages(c.pdsc_files(), c, l);
+    let found = resolve_used_device(c, &packs, query, l)?;
+    let skeleton = ProjectSkeleton::new(found.pack, found.device, args.value_of("processor"))?;
+    let dir = Path::new(args.value_of("output").unwrap_or(&found.device.name));
+    for path in skeleton.write_to(dir)? {
+        info!(l, "Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
+use std::io::Read;
+
+#[cfg(feature = "fs")]
+use clap::{App, Arg, ArgMatches, SubCommand};
+#[cfg(feature = "fs")]
+use failure::{err_msg, Error as FailError};
+use serde_json::{self, Map, Value};
+#[cfg(feature = "fs")]
+use slog::Logger;
+
+#[cfg(feature = "fs")]
+use pack_index::config::Config;
+
+/// JSON Schema of the device database written by `dump-devices`, for both the
+/// single file and the per vendor shards.
+pub const DEVICES_SCHEMA: &str = include_str!("../schema/devices.schema.json");
+
+/// Bumped on every incompatible change to `DEVICES_SCHEMA`; it is the last
+/// component of the schema `$id`.
+pub const DEVICES_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_u64() || value.is_i64(),
+        _ => false,
+    }
+}
+
+// Only the keywords used by DEVICES_SCHEMA are supported
+struct Validator<'s> {
+    root: &'s Value,
+    found: Vec<SchemaViolation>,
+}
+
+impl<'s> Validator<'s> {
+    fn violation(&mut self, path: &str, message: String) {
+        self.found.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&'s Value> {
+        let root = self.root;
+        reference
+            .trim_left_matches('#')
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .fold(Some(root), |schema, part| schema.and_then(|s| s.get(part)))
+    }
+
+    fn properties(&mut self, schema: &'s Map<String, Value>, obj: &Map<String, Value>, path: &str) {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !obj.contains_key(name) {
+                    self.violation(path, format!("missing property \"{}\"", name));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, value) in obj {
+            let child_path = format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+            match (properties.and_then(|p| p.get(name)), schema.get("additionalProperties")) {
+                (Some(property), _) => self.check(property, value, &child_path),
+                (None, Some(&Value::Bool(false))) => {
+                    self.violation(path, format!("unexpected property \"{}\"", name))
+                }
+                (None, Some(additional)) => self.check(additional, value, &child_path),
+                (None, None) => (),
+            }
+        }
+    }
+
+    fn check(&mut self, schema: &'s Value, value: &Value, path: &str) {
+        let schema = match schema.as_object() {
+            Some(schema) => schema,
+            None => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(resolved) => self.check(resolved, value, path),
+                None => self.violation(path, format!("unresolved reference {}", reference)),
+            }
+            return;
+        }
+        if let Some(types) = schema.get("type") {
+            let allowed: Vec<&str> = match *types {
+                Value::String(ref name) => vec![name.as_str()],
+                Value::Array(ref names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !allowed.iter().any(|name| type_matches(name, value)) {
+                self.violation(path, format!("expected {}", allowed.join(" or ")));
+                return;
+            }
+        }
+        if let Some(choices) = schema.get("enum").and_then(Value::as_array) {
+            if !choices.contains(value) {
+                self.violation(path, format!("{} is not one of the allowed values", value));
+            }
+        }
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    self.violation(path, format!("{} is less than {}", value, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    self.violation(path, format!("{} is greater than {}", value, max));
+                }
+            }
+        }
+        if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+            let matching = alternatives
+                .iter()
+                .filter(|alt| {
+                    let mut inner = Validator {
+                        root: self.root,
+                        found: Vec::new(),
+                    };
+                    inner.check(alt, value, path);
+                    inner.found.is_empty()
+                }).count();
+            if matching != 1 {
+                self.violation(path, format!("matches {} of the alternatives, not 1", matching));
+            }
+        }
+        if let Some(obj) = value.as_object() {
+            self.properties(schema, obj, path);
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (index, item) in array.iter().enumerate() {
+                self.check(items, item, &format!("{}/{}", path, index));
+            }
+        }
+    }
+}
+
+/// Check `dump` against `DEVICES_SCHEMA`, returning every violation found.
+pub fn validate_devices(dump: &Value) -> Vec<SchemaViolation> {
+    let schema: Value =
+        serde_json::from_str(DEVICES_SCHEMA).expect("the device schema is valid JSON");
+    let mut validator = Validator {
+        root: &schema,
+        found: Vec::new(),
+    };
+    validator.check(&schema, dump, "");
+    validator.found
+}
+
+#[cfg(feature = "fs")]
+pub fn schema_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("schema")
+        .about("Print the JSON Schema of dumped devices, or validate a dump against it")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("validate")
+                .long("validate")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Validate a dump-devices JSON file"),
+        )
+}
+
+#[cfg(feature = "fs")]
+pub fn schema_command<'a>(_: &Config, args: &ArgMatches<'a>, l: &Logger) -> Result<(), FailError> {
+    match args.value_of("validate") {
+        Some(filename) => {
+            let mut contents = Vec::new();
+            OpenOptions::new()
+                .read(true)
+                .open(filename)?
+                .read_to_end(&mut contents)?;
+            let dump: Value = serde_json::from_slice(&contents)?;
+            let violations = validate_devices(&dump);
+            for violation in &violations {
+                error!(l, "{}", violation);
+            }
+            if violations.is_empty() {
+                info!(l, "{} is valid for schema version {}", filename, DEVICES_SCHEMA_VERSION);
+                Ok(())
+            } else {
+                Err(err_msg(format!(
+                    "{} has {} schema violations",
+                    filename,
+                    violations.len()
+                )))
+            }
+        }
+        None => {
+            print!("{}", DEVICES_SCHEMA);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+    use utils::parse::{FromElem, ParseOptions};
+    use {device_database, Package};
+
+    #[test]
+    fn database_follows_the_schema() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Schema</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Test Series" Dvendor="ARM:82">
+                  <processor Dcore="Cortex-M4" DcoreVersion="r0p1" Dfpu="SP_FPU" Dmpu="MPU"/>
+                  <device Dname="Dev">
+                    <memory id="IROM1" start="0x08000000" size="0x10000" startup="1"/>
+                    <memory id="IRAM1" start="0x20000000" size="0x4000"/>
+                    <algorithm name="Flash/Dev.FLM" start="0x08000000" size="0x10000" default="1"/>
+                  </device>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let dump = serde_json::to_value(&device_database(&[&pack])).unwrap();
+        assert!(dump.get("Dev").is_some());
+        assert_eq!(validate_devices(&dump), Vec::new());
+    }
+}
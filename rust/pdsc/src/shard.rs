@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use failure::{err_msg, Error};
+use serde::de::IgnoredAny;
+use serde_json;
+use slog::Logger;
+
+use utils::natural::{natural_cmp, Natural};
+
+use {DumpDevice, Package};
+
+const SHARD_INDEX: &str = "index.json";
+
+// Every byte of `vendor` other than an ASCII letter or digit is escaped as
+// `_` and two hexadecimal digits, so that no two vendors share a file
+fn shard_file_name(vendor: &str) -> String {
+    let mut name = String::with_capacity(vendor.len() + ".json".len());
+    for &byte in vendor.as_bytes() {
+        if byte.is_ascii_alphanumeric() {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("_{:02X}", byte));
+        }
+    }
+    name.push_str(".json");
+    name
+}
+
+// The vendor whose devices are in the shard `file_name`
+fn shard_vendor(file_name: &str) -> Option<String> {
+    let mut escaped = file_name.trim_right_matches(".json").as_bytes();
+    let mut vendor = Vec::with_capacity(escaped.len());
+    while let Some((&byte, rest)) = escaped.split_first() {
+        escaped = if byte == b'_' {
+            let hex = rest.get(..2).and_then(|hex| str::from_utf8(hex).ok())?;
+            vendor.push(u8::from_str_radix(hex, 16).ok()?);
+            &rest[2..]
+        } else {
+            vendor.push(byte);
+            rest
+        };
+    }
+    String::from_utf8(vendor).ok()
+}
+
+fn read_json_file(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut file_contents = Vec::new();
+    let mut fd = OpenOptions::new().read(true).open(path)?;
+    fd.read_to_end(&mut file_contents)?;
+    Ok(file_contents)
+}
+
+// The contents of `path`, or nothing when it does not exist yet
+fn read_existing(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match read_json_file(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) => match e.downcast::<io::Error>() {
+            Ok(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Ok(e) => Err(e.into()),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+fn write_json_file<T: ::serde::Serialize>(path: &Path, contents: &T) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    options.create(true);
+    options.truncate(true);
+    let fd = options.open(path)?;
+    serde_json::to_writer_pretty(fd, contents)?;
+    Ok(())
+}
+
+// Every device of the shards in `dest`, with the vendor of its shard
+fn shard_index(dest: &Path) -> Result<BTreeSet<(String, String)>, Error> {
+    let mut index = BTreeSet::new();
+    for entry in dest.read_dir()? {
+        let path = entry?.path();
+        let vendor = match path.file_name().and_then(|name| name.to_str()) {
+            Some(SHARD_INDEX) | None => continue,
+            Some(name) if !name.ends_with(".json") => continue,
+            Some(name) => match shard_vendor(name) {
+                Some(vendor) => vendor,
+                None => continue,
+            },
+        };
+        let devices: HashMap<String, IgnoredAny> = serde_json::from_slice(&read_json_file(&path)?)
+            .map_err(|e| err_msg(format!("could not read shard {:?}: {}", path, e)))?;
+        index.extend(devices.into_iter().map(|(name, _)| (vendor.clone(), name)));
+    }
+    Ok(index)
+}
+
+/// Dump the devices of `pdscs` into one JSON file per pack vendor within
+/// `dest`, alongside an index of the devices of every shard there, by
+/// vendor. The index is rebuilt from the shards on each dump.
+pub fn dump_device_shards<'a, P, I>(pdscs: I, dest: P, l: &Logger) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a Package>,
+{
+    let dest = dest.as_ref();
+    create_dir_all(dest)?;
+    let mut shards: BTreeMap<&str, BTreeMap<Natural<&str>, DumpDevice>> = BTreeMap::new();
+    let pdscs: Vec<&Package> = pdscs.into_iter().collect();
+    for pdsc in pdscs.iter() {
+        shards
+            .entry(pdsc.vendor.as_str())
+            .or_insert_with(BTreeMap::new)
+            .extend(
+                pdsc.make_dump_devices()
+                    .into_iter()
+                    .map(|(name, dev)| (Natural(name), dev)),
+            );
+    }
+    for (vendor, devices) in shards {
+        let shard_path = dest.join(shard_file_name(vendor));
+        let old_devices: HashMap<String, DumpDevice> = match read_existing(&shard_path)? {
+            Some(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| err_msg(format!("could not read shard {:?}: {}", shard_path, e)))?,
+            None => HashMap::new(),
+        };
+        let mut all_devices = BTreeMap::new();
+        all_devices.extend(old_devices.iter().map(|(k, v)| (Natural(k.as_str()), v)));
+        all_devices.extend(devices.iter().map(|(k, v)| (*k, v)));
+        debug!(l, "Writing {} devices to shard {:?}", all_devices.len(), shard_path);
+        write_json_file(&shard_path, &all_devices)?;
+    }
+    write_json_file(&dest.join(SHARD_INDEX), &shard_index(dest)?)
+}
+
+/// A set of vendor shards written by `dump_device_shards`. Shards are only
+/// read from disk once they are requested, either explicitly through `load`
+/// or implicitly when looking up a device.
+pub struct DeviceShards {
+    root: PathBuf,
+    index: BTreeSet<(String, String)>,
+    loaded: HashMap<String, BTreeMap<String, DumpDevice<'static>>>,
+}
+
+impl DeviceShards {
+    pub fn open<P: Into<PathBuf>>(root: P) -> Result<Self, Error> {
+        let root = root.into();
+        let index = serde_json::from_slice(&read_json_file(&root.join(SHARD_INDEX))?)?;
+        Ok(Self {
+            root,
+            index,
+            loaded: HashMap::new(),
+        })
+    }
+
+    pub fn vendors(&self) -> Vec<&str> {
+        let mut vendors: Vec<&str> = self
+            .index
+            .iter()
+            .map(|&(ref vendor, _)| vendor.as_str())
+            .collect();
+        vendors.sort_by(|a, b| natural_cmp(a, b));
+        vendors.dedup();
+        vendors
+    }
+
+    /// The vendors of the packs describing a device named `name`.
+    pub fn vendors_of(&self, name: &str) -> Vec<&str> {
+        self.index
+            .iter()
+            .filter(|&&(_, ref device)| device == name)
+            .map(|&(ref vendor, _)| vendor.as_str())
+            .collect()
+    }
+
+    pub fn is_loaded(&self, vendor: &str) -> bool {
+        self.loaded.contains_key(vendor)
+    }
+
+    pub fn load<I, S>(&mut self, vendors: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for vendor in vendors {
+            self.vendor(vendor.as_ref())?;
+        }
+        Ok(())
+    }
+
+    pub fn vendor(&mut self, vendor: &str) -> Result<&BTreeMap<String, DumpDevice<'static>>, Error> {
+        if !self.loaded.contains_key(vendor) {
+            let shard_path = self.root.join(shard_file_name(vendor));
+            let contents = read_json_file(&shard_path)
+                .map_err(|e| err_msg(format!("could not read shard {:?}: {}", shard_path, e)))?;
+            let devices = serde_json::from_slice(&contents)?;
+            self.loaded.insert(vendor.to_string(), devices);
+        }
+        Ok(&self.loaded[vendor])
+    }
+
+    pub fn device(
+        &mut self,
+        vendor: &str,
+        name: &str,
+    ) -> Result<Option<&DumpDevice<'static>>, Error> {
+        if !self.index.contains(&(vendor.to_string(), name.to_string())) {
+            return Ok(None);
+        }
+        Ok(self.vendor(vendor)?.get(name))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::Discard;
+    use std::fs::remove_file;
+    use tempfile::tempdir;
+    use utils::parse::{FromElem, ParseOptions};
+
+    fn pack(vendor: &str, device: &str) -> Package {
+        let l = Logger::root(Discard, o!());
+        Package::from_string(
+            &format!(
+                r#"<package>
+                  <vendor>{}</vendor>
+                  <name>Test</name>
+                  <description>Shards</description>
+                  <url>http://www.example.com/</url>
+                  <releases><release version="1.0.0">First</release></releases>
+                  <devices>
+                    <family Dfamily="Test Series" Dvendor="ARM:82">
+                      <processor Dcore="Cortex-M4"/>
+                      <device Dname="{}"><memory id="IROM1" start="0x0" size="0x1000"/></device>
+                    </family>
+                  </devices>
+                </package>"#,
+                vendor, device
+            ),
+            &ParseOptions::default(),
+            &l,
+        ).unwrap()
+    }
+
+    #[test]
+    fn shard_names_differ() {
+        assert_ne!(shard_file_name("A-B"), shard_file_name("A_B"));
+        assert_ne!(shard_file_name("A_B"), shard_file_name("A_5FB"));
+        assert_eq!(shard_file_name("NXP"), "NXP.json");
+        for vendor in &["NXP", "A-B", "A_B", "Ünïcode"] {
+            assert_eq!(shard_vendor(&shard_file_name(vendor)).unwrap(), *vendor);
+        }
+    }
+
+    #[test]
+    fn keep_escaped_names_of_the_shard() {
+        let l = Logger::root(Discard, o!());
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("shards");
+        dump_device_shards(&[pack("ARM", r"Dev\1&quot;")], &dest, &l).unwrap();
+        dump_device_shards(&[pack("ARM", "Dev2")], &dest, &l).unwrap();
+        let mut shards = DeviceShards::open(&dest).unwrap();
+        assert!(shards.device("ARM", "Dev\\1\"").unwrap().is_some());
+        assert!(shards.device("ARM", "Dev2").unwrap().is_some());
+    }
+
+    #[test]
+    fn index_devices_by_vendor() {
+        let l = Logger::root(Discard, o!());
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("shards");
+        dump_device_shards(&[pack("ARM", "Dev"), pack("Keil", "Dev")], &dest, &l).unwrap();
+        let mut shards = DeviceShards::open(&dest).unwrap();
+        assert_eq!(shards.vendors_of("Dev"), vec!["ARM", "Keil"]);
+        assert!(shards.device("Keil", "Dev").unwrap().is_some());
+        assert!(shards.device("NXP", "Dev").unwrap().is_none());
+
+        // The devices of a shard removed since are no longer listed
+        remove_file(dest.join(shard_file_name("Keil"))).unwrap();
+        dump_device_shards(&[pack("ARM", "Dev2")], &dest, &l).unwrap();
+        let shards = DeviceShards::open(&dest).unwrap();
+        assert_eq!(shards.vendors_of("Dev"), vec!["ARM"]);
+        assert_eq!(shards.vendors(), vec!["ARM"]);
+    }
+}
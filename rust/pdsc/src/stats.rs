@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use clap::{App, Arg, ArgMatches, SubCommand};
+#[cfg(feature = "fs")]
+use failure::Error as FailError;
+use minidom::Element;
+#[cfg(feature = "fs")]
+use serde_json;
+use slog::Logger;
+
+#[cfg(feature = "fs")]
+use pack_index::config::Config;
+use utils::parse::{child_text, element_from_path, element_name, FromElem, ParseOptions};
+
+use device::Core;
+#[cfg(feature = "fs")]
+use labels::Labels;
+use Package;
+
+#[derive(Debug, Default, Serialize)]
+pub struct VendorStats {
+    pub packs: usize,
+    pub failed_packs: usize,
+    pub families: usize,
+    pub devices: usize,
+    pub unknown_cores: BTreeSet<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CoverageReport(pub BTreeMap<String, VendorStats>);
+
+fn count_elements(e: &Element, name: &str, opts: &ParseOptions) -> usize {
+    let here = if element_name(e, opts) == name { 1 } else { 0 };
+    here + e
+        .children()
+        .map(|c| count_elements(c, name, opts))
+        .sum::<usize>()
+}
+
+fn collect_unknown_cores(e: &Element, into: &mut BTreeSet<String>) {
+    if let Some(core) = e.attr("Dcore") {
+        if core.parse::<Core>().is_err() {
+            into.insert(core.to_string());
+        }
+    }
+    for child in e.children() {
+        collect_unknown_cores(child, into);
+    }
+}
+
+// Pack store descriptions are named Vendor.Pack.Version.pdsc
+fn vendor_from_file_name(path: &Path) -> String {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .unwrap_or("<unknown>")
+        .to_string()
+}
+
+impl CoverageReport {
+    pub fn add_path(&mut self, path: &Path, opts: &ParseOptions, l: &Logger) {
+        let root = match element_from_path(path) {
+            Ok(root) => root,
+            Err(e) => {
+                warn!(l, "parsing {:?}: {}", path, e);
+                let stats = self.0.entry(vendor_from_file_name(path)).or_default();
+                stats.packs += 1;
+                stats.failed_packs += 1;
+                return;
+            }
+        };
+        let vendor = child_text(&root, "vendor", "package", opts)
+            .unwrap_or_else(|_| vendor_from_file_name(path));
+        let stats = self.0.entry(vendor).or_default();
+        stats.packs += 1;
+        stats.families += count_elements(&root, "family", opts);
+        collect_unknown_cores(&root, &mut stats.unknown_cores);
+        match Package::from_elem(&root, opts, l) {
+            Ok(pack) => stats.devices += pack.devices.0.len(),
+            Err(e) => {
+                warn!(l, "parsing {:?}: {}", path, e);
+                stats.failed_packs += 1;
+            }
+        }
+    }
+}
+
+pub fn coverage_report<I, P>(paths: I, opts: &ParseOptions, l: &Logger) -> CoverageReport
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut report = CoverageReport::default();
+    for path in paths {
+        report.add_path(path.as_ref(), opts, l);
+    }
+    report
+}
+
+#[cfg(feature = "fs")]
+pub fn stats_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("stats")
+        .about("Report per vendor pack and device coverage")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("json")
+                .short("j")
+                .long("json")
+                .help("Print the report as JSON"),
+        ).arg(
+            Arg::with_name("INPUT")
+                .help("Input files to report on, defaults to the pack store")
+                .multiple(true)
+                .index(1),
+        )
+}
+
+#[cfg(feature = "fs")]
+pub fn stats_command<'a>(c: &Config, args: &ArgMatches<'a>, l: &Logger) -> Result<(), FailError> {
+    let report = match args.values_of("INPUT") {
+        Some(inputs) => coverage_report(inputs, &c.parse_options, l),
+        None => coverage_report(c.pdsc_files(), &c.parse_options, l),
+    };
+    if args.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!(
+            "{:<24} {:>6} {:>7} {:>9} {:>8}  unknown cores",
+            "vendor", "packs", "failed", "families", "devices"
+        );
+        let labels = Labels::default();
+        for (vendor, stats) in &report.0 {
+            let unknown: Vec<&str> = stats.unknown_cores.iter().map(String::as_str).collect();
+            println!(
+                "{:<24} {:>6} {:>7} {:>9} {:>8}  {}",
+                labels.vendor(vendor),
+                stats.packs,
+                stats.failed_packs,
+                stats.families,
+                stats.devices,
+                unknown.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
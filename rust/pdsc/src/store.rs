@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, rename, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use failure::{err_msg, Error};
+use serde_json;
+use slog::Logger;
+
+use {DumpDevice, Package};
+
+const STORE_DATA: &str = "devices.dat";
+const STORE_INDEX: &str = "devices.idx";
+
+/// Location of a single serialized device within the data file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Record {
+    offset: u64,
+    length: u64,
+}
+
+fn read_index(path: &Path) -> Result<BTreeMap<String, Record>, Error> {
+    let mut contents = Vec::new();
+    OpenOptions::new()
+        .read(true)
+        .open(path)?
+        .read_to_end(&mut contents)?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+fn read_record(data: &Path, record: Record) -> Result<Vec<u8>, Error> {
+    let mut fd = OpenOptions::new().read(true).open(data)?;
+    fd.seek(SeekFrom::Start(record.offset))?;
+    let mut contents = vec![0u8; record.length as usize];
+    fd.read_exact(&mut contents)?;
+    Ok(contents)
+}
+
+/// Dump the devices of `pdscs` into a keyed store within `dest`: a data file
+/// holding one JSON record per device and an index of the byte range of each
+/// record. Devices already in the store are kept unless `pdscs` replace them.
+pub fn dump_device_store<'a, P, I>(pdscs: I, dest: P, l: &Logger) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a Package>,
+{
+    let dest = dest.as_ref();
+    create_dir_all(dest)?;
+    let data_path = dest.join(STORE_DATA);
+    let index_path = dest.join(STORE_INDEX);
+    let mut records: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    if let Ok(old_index) = read_index(&index_path) {
+        for (name, record) in old_index {
+            records.insert(name, read_record(&data_path, record)?);
+        }
+    }
+    for pdsc in pdscs {
+        for (name, device) in pdsc.make_dump_devices() {
+            records.insert(name.to_string(), serde_json::to_vec(&device)?);
+        }
+    }
+
+    let temp = data_path.with_extension("part");
+    let mut fd = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp)?;
+    let mut index = BTreeMap::new();
+    let mut offset = 0;
+    for (name, contents) in records {
+        fd.write_all(&contents)?;
+        fd.write_all(b"\n")?;
+        let length = contents.len() as u64;
+        index.insert(name, Record { offset, length });
+        offset += length + 1;
+    }
+    fd.flush()?;
+    debug!(l, "Writing {} devices to store {:?}", index.len(), dest);
+    rename(&temp, &data_path)?;
+    let fd = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&index_path)?;
+    serde_json::to_writer(fd, &index)?;
+    Ok(())
+}
+
+/// A keyed store written by `dump_device_store`. Opening it only reads the
+/// index; each lookup reads and deserializes a single device.
+pub struct DeviceStore {
+    data: PathBuf,
+    index: BTreeMap<String, Record>,
+}
+
+impl DeviceStore {
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, Error> {
+        let root = root.as_ref();
+        let index_path = root.join(STORE_INDEX);
+        let index = read_index(&index_path)
+            .map_err(|e| err_msg(format!("could not read index {:?}: {}", index_path, e)))?;
+        Ok(Self {
+            data: root.join(STORE_DATA),
+            index,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(String::as_str)
+    }
+
+    pub fn device_by_name(&self, name: &str) -> Result<Option<DumpDevice<'static>>, Error> {
+        match self.index.get(name) {
+            Some(&record) => {
+                let contents = read_record(&self.data, record)?;
+                Ok(Some(serde_json::from_slice(&contents)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
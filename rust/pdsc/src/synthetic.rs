@@ -0,0 +1,178 @@
+use std::fmt::Write as FmtWrite;
+#[cfg(feature = "fs")]
+use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
+use std::io::{self, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+const CORES: &[&str] = &[
+    "Cortex-M0",
+    "Cortex-M0+",
+    "Cortex-M3",
+    "Cortex-M4",
+    "Cortex-M7",
+    "Cortex-M33",
+];
+
+/// Shape of a generated pack. The output only depends on these fields, so the
+/// same shape always yields the same PDSC.
+#[derive(Debug, Clone)]
+pub struct SyntheticPack {
+    pub vendor: String,
+    pub name: String,
+    pub families: usize,
+    /// Sub families per family; devices sit directly in the family when 0
+    pub sub_families: usize,
+    /// Devices per family, or per sub family when there are some
+    pub devices: usize,
+    /// Variants per device
+    pub variants: usize,
+    /// Seeds the choice of cores, memory sizes and optional elements
+    pub seed: u64,
+}
+
+impl Default for SyntheticPack {
+    fn default() -> Self {
+        SyntheticPack {
+            vendor: "Synthetic".to_string(),
+            name: "Synthetic_DFP".to_string(),
+            families: 4,
+            sub_families: 2,
+            devices: 8,
+            variants: 2,
+            seed: 0,
+        }
+    }
+}
+
+// xorshift64*, good enough to vary the output without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn pick<'a>(&mut self, from: &[&'a str]) -> &'a str {
+        from[(self.next() % from.len() as u64) as usize]
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+impl SyntheticPack {
+    /// Number of devices the generated PDSC describes, counting variants.
+    pub fn device_count(&self) -> usize {
+        self.families * self.sub_families.max(1) * self.devices * self.variants.max(1)
+    }
+
+    fn device(&self, out: &mut String, rng: &mut Rng, name: &str) {
+        let flash_kb = 32u64 << (rng.next() % 6);
+        let ram_kb = flash_kb / 4;
+        writeln!(out, "          <device Dname=\"{}\">", name).unwrap();
+        writeln!(
+            out,
+            "            <memory id=\"IROM1\" start=\"0x00000000\" size=\"0x{:08X}\" startup=\"1\" default=\"1\"/>",
+            flash_kb * 1024
+        ).unwrap();
+        writeln!(
+            out,
+            "            <memory id=\"IRAM1\" start=\"0x20000000\" size=\"0x{:08X}\" init=\"0\" default=\"1\"/>",
+            ram_kb * 1024
+        ).unwrap();
+        if rng.chance(90) {
+            writeln!(
+                out,
+                "            <algorithm name=\"Flash/{}.FLM\" start=\"0x00000000\" size=\"0x{:08X}\" default=\"1\"/>",
+                name,
+                flash_kb * 1024
+            ).unwrap();
+        }
+        if rng.chance(80) {
+            writeln!(out, "            <debug svd=\"SVD/{}.svd\"/>", name).unwrap();
+        }
+        for variant in 0..self.variants {
+            writeln!(
+                out,
+                "            <variant Dvariant=\"{}{}\"/>",
+                name,
+                (b'A' + (variant % 26) as u8) as char
+            )
+            .unwrap();
+        }
+        writeln!(out, "          </device>").unwrap();
+    }
+
+    /// Render the pack description.
+    pub fn to_pdsc(&self) -> String {
+        let mut rng = Rng(self.seed.wrapping_add(0x9E37_79B9_7F4A_7C15) | 1);
+        let mut out = String::new();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(
+            out,
+            "<package schemaVersion=\"1.4\" xmlns:xs=\"http://www.w3.org/2001/XMLSchema-instance\" \
+             xs:noNamespaceSchemaLocation=\"PACK.xsd\">"
+        )
+        .unwrap();
+        writeln!(out, "  <vendor>{}</vendor>", self.vendor).unwrap();
+        writeln!(out, "  <name>{}</name>", self.name).unwrap();
+        writeln!(
+            out,
+            "  <description>Synthetic device family pack</description>"
+        )
+        .unwrap();
+        writeln!(out, "  <url>http://www.example.com/packs/</url>").unwrap();
+        writeln!(out, "  <releases>").unwrap();
+        writeln!(out, "    <release version=\"1.0.0\">Generated</release>").unwrap();
+        writeln!(out, "  </releases>").unwrap();
+        writeln!(out, "  <devices>").unwrap();
+        for family in 0..self.families {
+            writeln!(
+                out,
+                "    <family Dfamily=\"SYN{} Series\" Dvendor=\"{}:0\">",
+                family, self.vendor
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "      <processor Dcore=\"{}\" DcoreVersion=\"r0p0\" Dfpu=\"{}\" Dmpu=\"{}\"/>",
+                rng.pick(CORES),
+                rng.pick(&["NO_FPU", "SP_FPU"]),
+                rng.pick(&["NO_MPU", "MPU"])
+            )
+            .unwrap();
+            for sub in 0..self.sub_families.max(1) {
+                if self.sub_families > 0 {
+                    writeln!(out, "      <subFamily DsubFamily=\"SYN{}{}\">", family, sub).unwrap();
+                }
+                for device in 0..self.devices {
+                    let name = format!("SYN{}{}{:03}", family, sub, device);
+                    self.device(&mut out, &mut rng, &name);
+                }
+                if self.sub_families > 0 {
+                    writeln!(out, "      </subFamily>").unwrap();
+                }
+            }
+            writeln!(out, "    </family>").unwrap();
+        }
+        writeln!(out, "  </devices>").unwrap();
+        writeln!(out, "</package>").unwrap();
+        out
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        fd.write_all(self.to_pdsc().as_bytes())
+    }
+}
@@ -0,0 +1,118 @@
+use device::{Device, Devices};
+
+#[derive(Debug, Serialize)]
+pub struct SubFamilyNode<'a> {
+    pub name: &'a str,
+    pub devices: Vec<&'a Device>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FamilyNode<'a> {
+    pub name: &'a str,
+    pub sub_families: Vec<SubFamilyNode<'a>>,
+    /// Devices described directly in the family
+    pub devices: Vec<&'a Device>,
+}
+
+/// Devices grouped by family and sub-family, in the order the description
+/// gives them.
+#[derive(Debug, Default, Serialize)]
+pub struct DeviceTree<'a> {
+    pub families: Vec<FamilyNode<'a>>,
+}
+
+impl<'a> DeviceTree<'a> {
+    pub fn new<I: IntoIterator<Item = &'a Device>>(devices: I) -> Self {
+        let mut tree = DeviceTree::default();
+        for dev in devices {
+            tree.insert(dev);
+        }
+        tree
+    }
+
+    fn insert(&mut self, dev: &'a Device) {
+        let found = self.families.iter().position(|f| f.name == dev.family);
+        let index = found.unwrap_or_else(|| {
+            self.families.push(FamilyNode {
+                name: &dev.family,
+                sub_families: Vec::new(),
+                devices: Vec::new(),
+            });
+            self.families.len() - 1
+        });
+        let family = &mut self.families[index];
+        match dev.sub_family {
+            None => family.devices.push(dev),
+            Some(ref name) => {
+                let found = family.sub_families.iter().position(|sub| sub.name == name);
+                match found {
+                    Some(index) => family.sub_families[index].devices.push(dev),
+                    None => family.sub_families.push(SubFamilyNode {
+                        name,
+                        devices: vec![dev],
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl Devices {
+    pub fn tree(&self) -> DeviceTree {
+        DeviceTree::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use slog::{Discard, Logger};
+    use utils::parse::{FromElem, ParseOptions};
+    use Package;
+
+    #[test]
+    fn families_and_sub_families() {
+        let l = Logger::root(Discard, o!());
+        let pack = Package::from_bytes(
+            br#"<package>
+              <vendor>ARM</vendor>
+              <name>Test</name>
+              <description>Families</description>
+              <url>http://www.example.com/</url>
+              <releases><release version="1.0.0">First</release></releases>
+              <devices>
+                <family Dfamily="Test Series" Dvendor="ARM:82">
+                  <processor Dcore="Cortex-M4"/>
+                  <memory id="IROM1" start="0x0" size="0x1000"/>
+                  <subFamily DsubFamily="Small">
+                    <device Dname="Dev1"/>
+                    <device Dname="Dev2">
+                      <variant Dvariant="Dev2A"/>
+                    </device>
+                  </subFamily>
+                  <device Dname="Dev3"/>
+                </family>
+              </devices>
+            </package>"#,
+            &ParseOptions::default(),
+            &l,
+        ).unwrap();
+        let dev2a = pack.devices().get("Dev2A").unwrap();
+        assert_eq!(dev2a.family, "Test Series");
+        assert_eq!(dev2a.sub_family, Some("Small".to_string()));
+        let tree = pack.devices().tree();
+        assert_eq!(tree.families.len(), 1);
+        let family = &tree.families[0];
+        assert_eq!(family.name, "Test Series");
+        let names: Vec<&str> = family.sub_families[0]
+            .devices
+            .iter()
+            .map(|dev| dev.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Dev1", "Dev2A"]);
+        assert_eq!(family.devices[0].name, "Dev3");
+    }
+}
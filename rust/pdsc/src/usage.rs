@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::fs::OpenOptions;
+#[cfg(feature = "fs")]
+use std::io::{Read, Write};
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+
+#[cfg(feature = "fs")]
+use failure::Error;
+#[cfg(feature = "fs")]
+use serde_json;
+
+#[cfg(feature = "fs")]
+use pack_index::config::Config;
+
+use view::DeviceRef;
+
+#[cfg(feature = "fs")]
+const USAGE_FILE: &str = "usage.json";
+
+/// How often the devices, packs and vendors of the pack store were looked up
+/// or installed. Updates fetch the descriptions of the most used packs first,
+/// and devices described by several vendors are listed most used first.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Usage {
+    /// Uses of each device, keyed as `Vendor::Name`
+    #[serde(default)]
+    devices: BTreeMap<String, u64>,
+    /// Uses of each pack, keyed as `Vendor.Name`
+    #[serde(default)]
+    packs: BTreeMap<String, u64>,
+    #[serde(default)]
+    vendors: BTreeMap<String, u64>,
+}
+
+#[cfg(feature = "fs")]
+impl Usage {
+    fn path(config: &Config) -> PathBuf {
+        config.vidx_list.with_file_name(USAGE_FILE)
+    }
+
+    /// The recorded usage, empty when none was recorded or it can not be read.
+    pub fn load(config: &Config) -> Self {
+        let mut content = String::new();
+        OpenOptions::new()
+            .read(true)
+            .open(Self::path(config))
+            .and_then(|mut fd| fd.read_to_string(&mut content))
+            .ok()
+            .and_then(|_| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Update the recorded usage with `f`.
+    pub fn record<F: FnOnce(&mut Usage)>(config: &Config, f: F) -> Result<(), Error> {
+        let mut usage = Self::load(config);
+        f(&mut usage);
+        let content = serde_json::to_string(&usage)?;
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(Self::path(config))?
+            .write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Usage {
+    pub fn use_device(&mut self, device: &DeviceRef) {
+        let key = format!("{}::{}", device.vendor(), device.name());
+        *self.devices.entry(key).or_insert(0) += 1;
+        self.use_pack(device.vendor(), &device.pack.name);
+    }
+
+    pub fn use_pack(&mut self, vendor: &str, name: &str) {
+        *self.packs.entry(format!("{}.{}", vendor, name)).or_insert(0) += 1;
+        *self.vendors.entry(vendor.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn device_uses(&self, vendor: &str, name: &str) -> u64 {
+        let key = format!("{}::{}", vendor, name);
+        self.devices.get(&key).cloned().unwrap_or(0)
+    }
+
+    pub fn pack_uses(&self, vendor: &str, name: &str) -> u64 {
+        let key = format!("{}.{}", vendor, name);
+        self.packs.get(&key).cloned().unwrap_or(0)
+    }
+
+    pub fn vendor_uses(&self, vendor: &str) -> u64 {
+        self.vendors.get(vendor).cloned().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendors.is_empty()
+    }
+}
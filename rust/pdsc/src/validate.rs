@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "fs")]
+use clap::{App, Arg, ArgMatches, SubCommand};
+#[cfg(feature = "fs")]
+use failure::Error as FailError;
+#[cfg(feature = "fs")]
+use serde_json;
+use slog::Logger;
+
+#[cfg(feature = "fs")]
+use pack_index::config::Config;
+use utils::parse::{FromElem, ParseOptions};
+
+use Package;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Outcome {
+    /// 0 when everything passed, 1 when anything failed and 2 when there
+    /// were only warnings.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Pass => 0,
+            Outcome::Fail => 1,
+            Outcome::Warn => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationReport {
+    pub files: Vec<FileReport>,
+}
+
+impl ValidationReport {
+    /// The worst outcome of any file; an empty report passes.
+    pub fn outcome(&self) -> Outcome {
+        self.files
+            .iter()
+            .map(|file| file.outcome)
+            .max()
+            .unwrap_or(Outcome::Pass)
+    }
+
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.files.iter().filter(|f| f.outcome == outcome).count()
+    }
+}
+
+fn pack_warnings(pack: &Package, l: &Logger) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let conditions = pack.make_condition_lookup(l);
+    let known = |cond: &Option<String>| {
+        cond.as_ref()
+            .map(|name| conditions.contains_key(name.as_str()))
+            .unwrap_or(true)
+    };
+    for comp in pack.make_components() {
+        if !known(&comp.condition) {
+            warnings.push(format!(
+                "component {}::{} references an unknown condition",
+                comp.class, comp.group
+            ));
+        }
+        for file in comp.files.iter().filter(|f| !known(&f.condition)) {
+            warnings.push(format!(
+                "file {} references an unknown condition",
+                file.path.display()
+            ));
+        }
+    }
+    for dropped in &pack.dropped_devices {
+        warnings.push(format!("device {} dropped: {}", dropped.name, dropped.reason));
+    }
+    for dev in pack.devices.iter() {
+        let completeness = dev.completeness();
+        if !completeness.is_complete() {
+            warnings.push(format!(
+                "device {} is missing {}",
+                dev.name,
+                completeness.missing().join(", ")
+            ));
+        }
+    }
+    warnings
+}
+
+/// Validate a single description. Files only readable with the lenient
+/// parsing policies produce warnings rather than failures.
+pub fn validate_file(path: &Path, l: &Logger) -> FileReport {
+    let strict = Package::from_path(path, &ParseOptions::strict(), l);
+    let (parsed, mut messages) = match strict {
+        Ok(pack) => (Ok(pack), Vec::new()),
+        Err(e) => (
+            Package::from_path(path, &ParseOptions::default(), l),
+            vec![e.to_string()],
+        ),
+    };
+    let outcome = match parsed {
+        Ok(pack) => {
+            messages.extend(pack_warnings(&pack, l));
+            if messages.is_empty() {
+                Outcome::Pass
+            } else {
+                Outcome::Warn
+            }
+        }
+        Err(_) => Outcome::Fail,
+    };
+    FileReport {
+        path: path.to_path_buf(),
+        outcome,
+        messages,
+    }
+}
+
+fn collect_pdscs(dir: &Path, into: &mut Vec<PathBuf>) {
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        // Symbolic links to directories are not followed, so loops can't occur
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            collect_pdscs(&path, into);
+        } else {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.ends_with(".pdsc") || name.ends_with(".pdsc.gz") {
+                into.push(path);
+            }
+        }
+    }
+}
+
+/// Validate every description found under `root`, in path order.
+pub fn validate_tree(root: &Path, l: &Logger) -> ValidationReport {
+    let mut paths = Vec::new();
+    collect_pdscs(root, &mut paths);
+    paths.sort();
+    ValidationReport {
+        files: paths.iter().map(|path| validate_file(path, l)).collect(),
+    }
+}
+
+#[cfg(feature = "fs")]
+pub fn validate_args<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("validate")
+        .about("Validate every description in a directory tree")
+        .after_help("Exits with 1 when any description failed, and 2 when there were only warnings")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("json")
+                .short("j")
+                .long("json")
+                .help("Print the report as JSON"),
+        ).arg(
+            Arg::with_name("DIR")
+                .help("Directory to validate, defaults to the pack store")
+                .index(1),
+        )
+}
+
+#[cfg(feature = "fs")]
+pub fn validate_command<'a>(
+    c: &Config,
+    args: &ArgMatches<'a>,
+    l: &Logger,
+) -> Result<Outcome, FailError> {
+    let root = args
+        .value_of("DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| c.pack_store.clone());
+    let report = validate_tree(&root, l);
+    if args.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for file in &report.files {
+            if file.outcome != Outcome::Pass {
+                println!("{:?}: {}", file.outcome, file.path.display());
+                for message in &file.messages {
+                    println!("    {}", message);
+                }
+            }
+        }
+        println!(
+            "{} passed, {} with warnings, {} failed",
+            report.count(Outcome::Pass),
+            report.count(Outcome::Warn),
+            report.count(Outcome::Fail)
+        );
+    }
+    Ok(report.outcome())
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use utils::natural::natural_cmp;
+
+use device::{Algorithm, CoreInfo, Device, Memories, Processors};
+use roles::MemoryRole;
+use {DumpDevice, FromPack, Package};
+
+/// A device of the in-memory database along with the pack describing it,
+/// borrowed from the parsed packs rather than copied out of them.
+#[derive(Clone, Copy)]
+pub struct DeviceRef<'a> {
+    pub pack: &'a Package,
+    pub device: &'a Device,
+}
+
+impl<'a> DeviceRef<'a> {
+    pub fn new(pack: &'a Package, device: &'a Device) -> Self {
+        DeviceRef { pack, device }
+    }
+
+    pub fn name(&self) -> &'a str {
+        &self.device.name
+    }
+
+    pub fn aliases(&self) -> &'a [String] {
+        &self.device.aliases
+    }
+
+    pub fn vendor(&self) -> &'a str {
+        &self.pack.vendor
+    }
+
+    pub fn pack_name(&self) -> &'a str {
+        &self.pack.name
+    }
+
+    /// Version of the latest release of the pack
+    pub fn version(&self) -> &'a str {
+        &self.pack.releases.latest_release().version
+    }
+
+    pub fn memories(&self) -> &'a Memories {
+        &self.device.memories
+    }
+
+    pub fn algorithms(&self) -> &'a [Algorithm] {
+        &self.device.algorithms
+    }
+
+    pub fn processor(&self) -> &'a Processors {
+        &self.device.processor
+    }
+
+    pub fn svd(&self) -> Option<&'a Path> {
+        self.device.svd.as_ref().map(|p| p.as_path())
+    }
+
+    pub fn svd_for(&self, pname: Option<&str>) -> Option<&'a Path> {
+        self.device.svd_for(pname)
+    }
+
+    pub fn topology(&self) -> Vec<CoreInfo<'a>> {
+        self.device.topology()
+    }
+
+    pub fn memory_roles(&self) -> BTreeMap<String, MemoryRole> {
+        self.device.memory_roles()
+    }
+
+    /// The entry written by `dump-devices` for this device, which borrows
+    /// all of its memories, algorithms and processors.
+    pub fn to_dump(&self) -> DumpDevice<'a> {
+        let pack = self.pack;
+        DumpDevice::from_device(
+            self.device,
+            FromPack::new(&pack.vendor, &pack.name, self.version(), &pack.url),
+        )
+    }
+}
+
+impl Package {
+    /// Borrowed views of the devices of this pack, in the order they are
+    /// described in the PDSC.
+    pub fn device_refs<'a>(&'a self) -> impl Iterator<Item = DeviceRef<'a>> + 'a {
+        self.devices.iter().map(move |device| DeviceRef::new(self, device))
+    }
+}
+
+/// Borrowed views of every device of `packs`.
+pub fn device_refs<'a, I>(packs: I) -> impl Iterator<Item = DeviceRef<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    packs.into_iter().flat_map(Package::device_refs)
+}
+
+/// Borrowed views of every device of `packs`, ordered by name with the
+/// numbers in names compared by value, then by vendor.
+pub fn sorted_device_refs<'a, I>(packs: I) -> Vec<DeviceRef<'a>>
+where
+    I: IntoIterator<Item = &'a Package>,
+{
+    let mut devices: Vec<DeviceRef<'a>> = device_refs(packs).collect();
+    devices.sort_by(|a, b| {
+        natural_cmp(a.name(), b.name()).then_with(|| natural_cmp(a.vendor(), b.vendor()))
+    });
+    devices
+}
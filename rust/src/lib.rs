@@ -1 +1,82 @@
+//! One entry point to the pack store: keeping the descriptions up to date,
+//! installing packs and looking devices up.
 
+extern crate cmsis_update;
+extern crate failure;
+extern crate pack_index;
+extern crate pdsc;
+extern crate slog;
+
+use std::path::PathBuf;
+
+use failure::{err_msg, Error};
+use slog::Logger;
+
+use cmsis_update::Context;
+use pack_index::config::{Config, ConfigBuilder};
+use pdsc::{find_devices, DeviceRef, Index};
+
+/// The pack store of a `Config` along with its parsed descriptions.
+pub struct PackManager {
+    config: Config,
+    logger: Logger,
+    index: Index,
+}
+
+impl PackManager {
+    /// Open the pack store of `config`, parsing every description in it.
+    pub fn new(config: Config, logger: Logger) -> Self {
+        let index = Index::load(&config, &logger);
+        PackManager {
+            config,
+            logger,
+            index,
+        }
+    }
+
+    /// Open the pack store in its default location.
+    pub fn with_defaults(logger: Logger) -> Result<Self, Error> {
+        Ok(Self::new(ConfigBuilder::new().build()?, logger))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// Download the descriptions that changed in the vendor indexes listed
+    /// by the configuration, then parse the pack store again.
+    pub fn update(&mut self) -> Result<Vec<PathBuf>, Error> {
+        let vidx_list = self.config.read_vidx_list(&self.logger);
+        let updated = Context::new(&self.config, &self.logger).update(vidx_list, None)?;
+        if !updated.is_empty() {
+            self.index = Index::load(&self.config, &self.logger);
+        }
+        Ok(updated)
+    }
+
+    /// Download and extract the latest release of the pack `vendor`.`name`.
+    pub fn install(&self, vendor: &str, name: &str) -> Result<Vec<PathBuf>, Error> {
+        let pack = self.index.pack(vendor, name).ok_or_else(|| {
+            err_msg(format!("{}.{} is not described in the pack store", vendor, name))
+        })?;
+        Context::new(&self.config, &self.logger).install(Some(pack))
+    }
+
+    /// Every description of the device `name`, one per pack describing it.
+    pub fn device(&self, name: &str) -> Vec<DeviceRef> {
+        self.index.device(name)
+    }
+
+    /// The devices whose name matches `pattern`, a glob ignoring case.
+    pub fn search(&self, pattern: &str) -> Vec<DeviceRef> {
+        find_devices(self.index.packs(), pattern)
+    }
+}
@@ -8,12 +8,25 @@ extern crate pdsc;
 extern crate slog_async;
 extern crate slog_term;
 
-use clap::{App, Arg};
-use cmsis_update::{install_args, install_command, update_args, update_command};
+use clap::{App, Arg, ArgMatches};
+use cmsis_update::{
+    gc_args, gc_command, install_args, install_command, migrate_args, migrate_command,
+    rollback_args, rollback_command, signatures_args, signatures_command, snapshot_args,
+    snapshot_command, sync_args, sync_command, uninstall_args, uninstall_command, update_args,
+    update_command, usage_args, usage_command, verify_args, verify_command, verify_packs_args,
+    verify_packs_command,
+};
 use failure::Error;
-use pack_index::config::Config;
-use pdsc::{check_args, check_command, dump_devices_args, dump_devices_command};
-use slog::Drain;
+use pack_index::config::{Config, ConfigBuilder, PackFilter};
+use pdsc::{
+    check_args, check_command, diff_args, diff_command, dump_devices_args, dump_devices_command,
+    dump_json_args, dump_json_command, find_args, find_command, list_devices_args,
+    list_devices_command, new_project_args, new_project_command, schema_args, schema_command,
+    stats_args, stats_command, validate_args, validate_command,
+};
+use slog::{Drain, Logger};
+use std::path::PathBuf;
+use std::process;
 
 fn main() {
     // Note: This argument parser should do nothing more than handle
@@ -24,10 +37,69 @@ fn main() {
             Arg::with_name("verbose")
                 .short("v")
                 .help("Sets the level of verbosity"),
+        ).arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .help("Only use files downloaded earlier, never connecting to a server"),
+        ).arg(
+            Arg::with_name("discard-archives")
+                .long("discard-archives")
+                .help("Remove the archive of a pack once it is extracted"),
+        ).arg(
+            Arg::with_name("vendor")
+                .long("vendor")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only update and install the packs of this vendor"),
+        ).arg(
+            Arg::with_name("exclude-vendor")
+                .long("exclude-vendor")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Neither update nor install the packs of this vendor"),
+        ).arg(
+            Arg::with_name("pack")
+                .long("pack")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only update and install the packs with a name matching this glob"),
+        ).arg(
+            Arg::with_name("exclude-pack")
+                .long("exclude-pack")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Neither update nor install the packs with a name matching this glob"),
+        ).arg(
+            Arg::with_name("signers")
+                .long("signers")
+                .takes_value(true)
+                .help("Check pack signatures against the certificates of a PEM file or directory"),
         ).subcommand(update_args())
         .subcommand(check_args())
         .subcommand(dump_devices_args())
+        .subcommand(dump_json_args())
+        .subcommand(list_devices_args())
+        .subcommand(find_args())
+        .subcommand(stats_args())
+        .subcommand(schema_args())
+        .subcommand(diff_args())
+        .subcommand(new_project_args())
+        .subcommand(validate_args())
         .subcommand(install_args())
+        .subcommand(uninstall_args())
+        .subcommand(gc_args())
+        .subcommand(usage_args())
+        .subcommand(sync_args())
+        .subcommand(verify_args())
+        .subcommand(verify_packs_args())
+        .subcommand(signatures_args())
+        .subcommand(migrate_args())
+        .subcommand(snapshot_args())
+        .subcommand(rollback_args())
         .get_matches();
 
     let decorator = slog_term::TermDecorator::new().build();
@@ -37,31 +109,58 @@ fn main() {
 
     debug!(log, "Logging ready.");
 
-    match matches.subcommand() {
-        ("update", Some(sub_m)) => {
-            Config::new()
-                .map_err(Error::from)
-                .and_then(|config| update_command(&config, sub_m, &log))
-                .unwrap();
-        }
-        ("install", Some(sub_m)) => {
-            Config::new()
-                .map_err(Error::from)
-                .and_then(|config| install_command(&config, sub_m, &log))
-                .unwrap();
-        }
-        ("check", Some(sub_m)) => {
-            Config::new()
-                .map_err(Error::from)
-                .and_then(|config| check_command(&config, sub_m, &log))
-                .unwrap();
+    let offline = matches.is_present("offline");
+    let discard_archives = matches.is_present("discard-archives");
+    let values = |name: &str| {
+        matches
+            .values_of(name)
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default()
+    };
+    let pack_filter = PackFilter {
+        vendors: values("vendor"),
+        excluded_vendors: values("exclude-vendor"),
+        names: values("pack"),
+        excluded_names: values("exclude-pack"),
+    };
+    let signers = matches.value_of("signers").map(PathBuf::from);
+    let config = || {
+        let conf_bld = ConfigBuilder::new()
+            .with_offline(offline)
+            .with_discard_archives(discard_archives)
+            .with_pack_filter(pack_filter.clone());
+        match signers {
+            Some(ref signers) => conf_bld.with_signers(signers.clone()).build(),
+            None => conf_bld.build(),
         }
-        ("dump-devices", Some(sub_m)) => {
-            Config::new()
-                .map_err(Error::from)
-                .and_then(|config| dump_devices_command(&config, sub_m, &log))
-                .unwrap();
+    };
+
+    match matches.subcommand() {
+        ("update", Some(sub_m)) => run(config(), sub_m, log, update_command),
+        ("install", Some(sub_m)) => run(config(), sub_m, log, install_command),
+        ("uninstall", Some(sub_m)) => run(config(), sub_m, log, uninstall_command),
+        ("gc", Some(sub_m)) => run(config(), sub_m, log, gc_command),
+        ("usage", Some(sub_m)) => run(config(), sub_m, log, usage_command),
+        ("sync", Some(sub_m)) => run(config(), sub_m, log, sync_command),
+        ("verify", Some(sub_m)) => run(config(), sub_m, log, verify_command),
+        ("verify-packs", Some(sub_m)) => run(config(), sub_m, log, verify_packs_command),
+        ("signatures", Some(sub_m)) => run(config(), sub_m, log, signatures_command),
+        ("snapshot", Some(sub_m)) => run(config(), sub_m, log, snapshot_command),
+        ("rollback", Some(sub_m)) => run(config(), sub_m, log, rollback_command),
+        ("migrate", Some(sub_m)) => run(config(), sub_m, log, migrate_command),
+        ("check", Some(sub_m)) => run(config(), sub_m, log, check_command),
+        ("dump-devices", Some(sub_m)) => run(config(), sub_m, log, dump_devices_command),
+        ("dump-json", Some(sub_m)) => run(config(), sub_m, log, dump_json_command),
+        ("list-devices", Some(sub_m)) => run(config(), sub_m, log, list_devices_command),
+        ("find", Some(sub_m)) => run(config(), sub_m, log, find_command),
+        ("stats", Some(sub_m)) => run(config(), sub_m, log, stats_command),
+        ("diff", Some(sub_m)) => run(config(), sub_m, log, diff_command),
+        ("new-project", Some(sub_m)) => run(config(), sub_m, log, new_project_command),
+        ("validate", Some(sub_m)) => {
+            let outcome = run(config(), sub_m, log, validate_command);
+            process::exit(outcome.exit_code());
         }
+        ("schema", Some(sub_m)) => run(config(), sub_m, log, schema_command),
         (bad_command, Some(_)) => {
             println!("I did not understand the command {}", bad_command);
         }
@@ -71,3 +170,21 @@ fn main() {
         }
     }
 }
+
+/// Run a subcommand, exiting with an error status when it fails or when the
+/// configuration could not be built.
+fn run<'a, T, F>(config: Result<Config, Error>, args: &ArgMatches<'a>, log: Logger, command: F) -> T
+where
+    F: FnOnce(&Config, &ArgMatches<'a>, &Logger) -> Result<T, Error>,
+{
+    let ran = config.and_then(|config| command(&config, args, &log));
+    match ran {
+        Ok(value) => value,
+        Err(e) => {
+            error!(log, "{}", e);
+            // Flush the asynchronous drain before exiting
+            drop(log);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
+use std::char;
+use std::str;
+
+use minidom::{Error, ErrorKind};
+use slog::Logger;
+
+// Replaced characters listed in a warning, the others are only counted
+const MAX_LISTED: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingPolicy {
+    /// Reject files containing bytes that are invalid in their encoding
+    Strict,
+    /// Replace invalid bytes, with a warning, and read files that are not
+    /// UTF-8 and declare no encoding as Windows-1252
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+// Windows-1252 differs from Latin-1 in 0x80 to 0x9F; the bytes it leaves
+// undefined are mapped as in Latin-1
+const WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160,
+    0x2039, 0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022,
+    0x2013, 0x2014, 0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+impl Encoding {
+    /// The encoding named by an XML declaration.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" | "us-ascii" | "ascii" => Some(Encoding::Utf8),
+            "utf-16le" => Some(Encoding::Utf16Le),
+            "utf-16" | "utf-16be" => Some(Encoding::Utf16Be),
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1" => {
+                Some(Encoding::Latin1)
+            }
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            _ => None,
+        }
+    }
+
+    fn from_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        if bytes.starts_with(b"\xEF\xBB\xBF") {
+            Some((Encoding::Utf8, 3))
+        } else if bytes.starts_with(b"\xFF\xFE") {
+            Some((Encoding::Utf16Le, 2))
+        } else if bytes.starts_with(b"\xFE\xFF") {
+            Some((Encoding::Utf16Be, 2))
+        } else {
+            None
+        }
+    }
+}
+
+// The `encoding` pseudo-attribute of the XML declaration, which is ASCII
+// in every encoding it may name apart from UTF-16
+fn declared_label(bytes: &[u8]) -> Option<&str> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = bytes.iter().take(1024).position(|&b| b == b'>')?;
+    let decl = str::from_utf8(&bytes[..end]).ok()?;
+    let value = decl[decl.find("encoding")? + "encoding".len()..]
+        .trim_left()
+        .trim_left_matches('=')
+        .trim_left();
+    let quote = value.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    value[1..].split(quote).next()
+}
+
+fn position(bytes: &[u8], offset: usize) -> (usize, usize) {
+    let before = &bytes[..offset];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = offset - before.iter().rposition(|&b| b == b'\n').map_or(0, |nl| nl + 1) + 1;
+    (line, column)
+}
+
+// Decoded text, along with the byte offsets of the sequences replaced by
+// U+FFFD because they were invalid
+fn decode_utf8(bytes: &[u8]) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(bytes.len());
+    let mut replaced = Vec::new();
+    let mut offset = 0;
+    loop {
+        match str::from_utf8(&bytes[offset..]) {
+            Ok(rest) => {
+                out.push_str(rest);
+                return (out, replaced);
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                out.push_str(str::from_utf8(&bytes[offset..offset + valid]).unwrap_or_default());
+                out.push(char::REPLACEMENT_CHARACTER);
+                replaced.push(offset + valid);
+                offset += valid + e.error_len().unwrap_or(bytes.len() - offset - valid);
+            }
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> (String, Vec<usize>) {
+    let units = bytes.chunks(2).map(|pair| match (pair.get(0), pair.get(1)) {
+        (Some(&a), Some(&b)) if big_endian => u16::from(a) << 8 | u16::from(b),
+        (Some(&a), Some(&b)) => u16::from(b) << 8 | u16::from(a),
+        _ => 0xFFFD,
+    });
+    let mut out = String::with_capacity(bytes.len() / 2);
+    let mut replaced = Vec::new();
+    let mut unit = 0;
+    for decoded in char::decode_utf16(units) {
+        match decoded {
+            Ok(c) => {
+                out.push(c);
+                unit += c.len_utf16();
+            }
+            Err(_) => {
+                out.push(char::REPLACEMENT_CHARACTER);
+                replaced.push(unit * 2);
+                unit += 1;
+            }
+        }
+    }
+    (out, replaced)
+}
+
+fn decode_single_byte(bytes: &[u8], encoding: Encoding) -> String {
+    bytes
+        .iter()
+        .map(|&b| match (encoding, b) {
+            (Encoding::Windows1252, 0x80...0x9F) => {
+                char::from_u32(u32::from(WINDOWS_1252_HIGH[(b - 0x80) as usize]))
+                    .unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            _ => char::from(b),
+        }).collect()
+}
+
+fn replaced_error(bytes: &[u8], replaced: &[usize], encoding: Encoding) -> String {
+    let listed: Vec<String> = replaced
+        .iter()
+        .take(MAX_LISTED)
+        .map(|&offset| match encoding {
+            Encoding::Utf8 => {
+                let (line, column) = position(bytes, offset);
+                format!("0x{:02X} at {}:{}", bytes[offset], line, column)
+            }
+            _ => format!("unpaired surrogate at byte {}", offset),
+        }).collect();
+    let more = if replaced.len() > MAX_LISTED {
+        format!(" and {} more", replaced.len() - MAX_LISTED)
+    } else {
+        String::new()
+    };
+    format!("invalid {:?}: {}{}", encoding, listed.join(", "), more)
+}
+
+/// Decode an XML document according to its byte order mark or encoding
+/// declaration, defaulting to UTF-8. Under the lenient policy, invalid
+/// bytes are replaced and listed in a warning.
+pub fn decode_xml(bytes: &[u8], policy: EncodingPolicy, l: &Logger) -> Result<String, Error> {
+    let (encoding, start, declared) = match Encoding::from_bom(bytes) {
+        Some((encoding, bom)) => (encoding, bom, true),
+        None => match declared_label(bytes) {
+            Some(label) => match Encoding::from_label(label) {
+                Some(encoding) => (encoding, 0, true),
+                None => {
+                    warn!(l, "unknown encoding \"{}\", reading as UTF-8", label);
+                    (Encoding::Utf8, 0, false)
+                }
+            },
+            None => (Encoding::Utf8, 0, false),
+        },
+    };
+    let body = &bytes[start..];
+    let (text, replaced) = match encoding {
+        Encoding::Utf8 => decode_utf8(body),
+        Encoding::Utf16Le => decode_utf16(body, false),
+        Encoding::Utf16Be => decode_utf16(body, true),
+        single => (decode_single_byte(body, single), Vec::new()),
+    };
+    if replaced.is_empty() {
+        return Ok(text);
+    }
+    match policy {
+        EncodingPolicy::Strict => Err(Error::from_kind(ErrorKind::Msg(replaced_error(
+            body, &replaced, encoding,
+        )))),
+        EncodingPolicy::Lenient if encoding == Encoding::Utf8 && !declared => {
+            warn!(l, "not valid UTF-8 and no encoding declared, reading as Windows-1252");
+            Ok(decode_single_byte(body, Encoding::Windows1252))
+        }
+        EncodingPolicy::Lenient => {
+            warn!(l, "{}; replaced", replaced_error(body, &replaced, encoding));
+            Ok(text)
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::{Discard, Logger};
+
+    #[test]
+    fn declared_latin1() {
+        let log = Logger::root(Discard, o!());
+        let xml = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><name>Caf\xE9</name>";
+        let text = decode_xml(xml, EncodingPolicy::Lenient, &log).unwrap();
+        assert!(text.ends_with("<name>Caf\u{E9}</name>"));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let log = Logger::root(Discard, o!());
+        let declared = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><name>\x93A\x94</name>";
+        let lenient = decode_xml(declared, EncodingPolicy::Lenient, &log).unwrap();
+        assert!(lenient.ends_with("<name>\u{FFFD}A\u{FFFD}</name>"));
+        assert!(decode_xml(declared, EncodingPolicy::Strict, &log).is_err());
+        // Without a declaration, such bytes are most likely Windows-1252
+        let undeclared = b"<name>\x93A\x94</name>";
+        let text = decode_xml(undeclared, EncodingPolicy::Lenient, &log).unwrap();
+        assert_eq!(text, "<name>\u{201C}A\u{201D}</name>");
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use minidom::{Error, ErrorKind};
+
+/// Why an element of a description could not be parsed.
+///
+/// Parsers return it as the cause of a `minidom::Error`, keeping the
+/// message it displays; `PdscError::find` gets it back.
+#[derive(Debug)]
+pub enum PdscError {
+    MissingAttribute { elem: String, attr: String },
+    MissingChild { elem: String, child: String },
+    UnexpectedElement { expected: String, found: String },
+    InvalidHex { elem: String, attr: String, value: String },
+    InvalidValue { elem: String, attr: String, value: String, reason: String },
+    UnknownCore(String),
+    /// A value outside of those allowed for `what`, such as an FPU
+    Unknown { what: &'static str, value: String },
+    /// An element left `missing` by itself and all of its parents
+    Incomplete { elem: String, missing: &'static str },
+    /// A device and its parent describe the same thing differently
+    Conflict(String),
+    Io(io::Error),
+    Xml(String),
+}
+
+impl PdscError {
+    /// The structured error behind `e`, when a parser returned it.
+    pub fn find(e: &Error) -> Option<&PdscError> {
+        e.1.next_error
+            .as_ref()
+            .and_then(|cause| cause.downcast_ref::<PdscError>())
+    }
+}
+
+impl fmt::Display for PdscError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PdscError::MissingAttribute { ref elem, ref attr } => {
+                write!(f, "{} not found in {} element", attr, elem)
+            }
+            PdscError::MissingChild {
+                ref elem,
+                ref child,
+            } => write!(f, "child element \"{}\" not found in \"{}\" element", child, elem),
+            PdscError::UnexpectedElement {
+                ref expected,
+                ref found,
+            } => write!(
+                f,
+                "tried to parse element \"{}\" from element \"{}\"",
+                expected, found
+            ),
+            PdscError::InvalidHex {
+                ref elem,
+                ref attr,
+                ref value,
+            } => write!(f, "{} of {} element is not a number: {:?}", attr, elem, value),
+            PdscError::InvalidValue {
+                ref elem,
+                ref attr,
+                ref value,
+                ref reason,
+            } => write!(f, "{} of {} element: {:?}: {}", attr, elem, value, reason),
+            PdscError::UnknownCore(ref core) => write!(f, "Unknown core {}", core),
+            PdscError::Unknown { what, ref value } => write!(f, "Unknown {} {}", what, value),
+            PdscError::Incomplete { ref elem, missing } => {
+                write!(f, "No {} found for {}", missing, elem)
+            }
+            PdscError::Conflict(ref what) => write!(f, "{}", what),
+            PdscError::Io(ref e) => write!(f, "{}", e),
+            PdscError::Xml(ref e) => write!(f, "malformed XML: {}", e),
+        }
+    }
+}
+
+impl StdError for PdscError {
+    fn description(&self) -> &str {
+        "invalid description"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            PdscError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PdscError {
+    fn from(e: io::Error) -> Self {
+        PdscError::Io(e)
+    }
+}
+
+impl From<PdscError> for Error {
+    fn from(e: PdscError) -> Self {
+        let msg = e.to_string();
+        Error::with_chain(e, ErrorKind::Msg(msg))
+    }
+}
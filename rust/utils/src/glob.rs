@@ -0,0 +1,51 @@
+pub fn lowercase_chars(s: &str) -> Vec<char> {
+    s.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// `*` matches any run of characters and `?` any single one
+pub fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` when the rest fails to match
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `name` matches `pattern`, ignoring case.
+pub fn matches_ignoring_case(pattern: &str, name: &str) -> bool {
+    glob_match(&lowercase_chars(pattern), &lowercase_chars(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(matches_ignoring_case("STM32F4*", "stm32f407vg"));
+        assert!(matches_ignoring_case("stm32f4?7*", "STM32F407VG"));
+        assert!(matches_ignoring_case("*F407*", "STM32F407VG"));
+        assert!(matches_ignoring_case("STM32F4*7*G", "STM32F407VG"));
+        assert!(!matches_ignoring_case("STM32F4", "STM32F407VG"));
+        assert!(!matches_ignoring_case("STM32F4?", "STM32F407VG"));
+    }
+}
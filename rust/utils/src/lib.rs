@@ -1,7 +1,11 @@
+extern crate flate2;
 extern crate minidom;
 extern crate quick_xml;
+extern crate serde;
 #[macro_use]
 extern crate slog;
+#[cfg(feature = "log")]
+extern crate slog_stdlog;
 
 pub trait ResultLogExt<T, E> {
     fn ok_warn(self, log: &Logger) -> Option<T>;
@@ -34,4 +38,19 @@ where
     }
 }
 
+/// The Logger of the functions that take none. Records go to the log crate
+/// with the "log" feature, and are discarded without it.
+pub fn default_logger() -> Logger {
+    #[cfg(feature = "log")]
+    let drain = slog::Drain::fuse(slog_stdlog::StdLog);
+    #[cfg(not(feature = "log"))]
+    let drain = slog::Discard;
+    Logger::root(drain, o!())
+}
+
+pub mod encoding;
+pub mod error;
+pub mod glob;
+pub mod natural;
 pub mod parse;
+pub mod span;
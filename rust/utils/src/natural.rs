@@ -0,0 +1,79 @@
+use std::cmp::Ordering;
+
+use serde::{Serialize, Serializer};
+
+// The leading run of digits, or of anything but digits, and what follows
+fn chunk(s: &str) -> Option<(&str, &str)> {
+    let digits = s.chars().next()?.is_ascii_digit();
+    let end = s.find(|c: char| c.is_ascii_digit() != digits).unwrap_or(s.len());
+    Some(s.split_at(end))
+}
+
+fn is_number(chunk: &str) -> bool {
+    chunk.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Compare strings with their runs of digits taken as numbers, so that
+/// "STM32F4" comes before "STM32F10" and "1.9" before "1.10". Strings that
+/// only differ in leading zeros are ordered lexically.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut left, mut right) = (a, b);
+    loop {
+        let (l, r) = match (chunk(left), chunk(right)) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) => (l, r),
+        };
+        let ord = if is_number(l.0) && is_number(r.0) {
+            let (ln, rn) = (l.0.trim_left_matches('0'), r.0.trim_left_matches('0'));
+            ln.len().cmp(&rn.len()).then_with(|| ln.cmp(rn))
+        } else {
+            l.0.cmp(r.0)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+        left = l.1;
+        right = r.1;
+    }
+}
+
+/// A string ordered by `natural_cmp`, for use as the key of sorted maps
+/// and sets. It serializes as the string it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Natural<T>(pub T);
+
+impl<T: AsRef<str> + Eq> Ord for Natural<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(self.0.as_ref(), other.0.as_ref())
+    }
+}
+
+impl<T: AsRef<str> + Eq> PartialOrd for Natural<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: AsRef<str>> Serialize for Natural<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers_in_names() {
+        let mut names = vec!["STM32F103RB", "STM32F10", "STM32F4", "STM32F103C8", "STM32F010"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["STM32F4", "STM32F010", "STM32F10", "STM32F103C8", "STM32F103RB"]
+        );
+        assert_eq!(natural_cmp("1.9.0", "1.10.0"), Ordering::Less);
+    }
+}
@@ -1,13 +1,23 @@
+#![cfg_attr(
+    feature = "cargo-clippy",
+    deny(option_unwrap_used, result_unwrap_used)
+)]
+
 use std::fmt::Display;
-use std::io::BufRead;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
 use std::str::FromStr;
 
-use minidom::{Children, Element, Error, ErrorKind};
+use flate2::read::GzDecoder;
+use minidom::{Children, Element, Error};
 use quick_xml::reader::Reader;
 use slog::Logger;
 
-use ResultLogExt;
+use encoding::{decode_xml, EncodingPolicy};
+use error::PdscError;
+use span::Span;
+use {default_logger, ResultLogExt};
 
 #[macro_export]
 macro_rules! err_msg {
@@ -16,13 +26,20 @@ macro_rules! err_msg {
     };
 }
 
+fn missing(name: &str, elemname: &str) -> Error {
+    PdscError::MissingAttribute {
+        elem: elemname.to_string(),
+        attr: name.to_string(),
+    }.into()
+}
+
 pub fn attr_map<'a, T>(from: &'a Element, name: &str, elemname: &'static str) -> Result<T, Error>
 where
     T: From<&'a str>,
 {
     from.attr(name)
         .map(T::from)
-        .ok_or_else(|| err_msg!("{} not found in {} element", name, elemname))
+        .ok_or_else(|| missing(name, elemname))
 }
 
 pub fn attr_parse_hex<'a>(
@@ -31,15 +48,22 @@ pub fn attr_parse_hex<'a>(
     elemname: &'static str,
 ) -> Result<u64, Error> {
     from.attr(name)
-        .ok_or_else(|| err_msg!("{} not found in {} element", name, elemname))
+        .ok_or_else(|| missing(name, elemname))
         .and_then(|st| {
-            if st.starts_with("0x") {
-                u64::from_str_radix(&st[2..], 16).map_err(|e| err_msg!("{}", e))
+            let parsed = if st.starts_with("0x") {
+                u64::from_str_radix(&st[2..], 16)
             } else if st.starts_with('0') {
-                u64::from_str_radix(&st[1..], 8).map_err(|e| err_msg!("{}", e))
+                u64::from_str_radix(&st[1..], 8)
             } else {
-                u64::from_str_radix(st, 10).map_err(|e| err_msg!("{}", e))
-            }
+                u64::from_str_radix(st, 10)
+            };
+            parsed.map_err(|_| {
+                PdscError::InvalidHex {
+                    elem: elemname.to_string(),
+                    attr: name.to_string(),
+                    value: st.to_string(),
+                }.into()
+            })
         })
 }
 
@@ -53,64 +77,344 @@ where
     E: Display,
 {
     from.attr(name)
-        .ok_or_else(|| err_msg!("{} not found in {} element", name, elemname))
-        .and_then(|st| st.parse::<T>().map_err(|e| err_msg!("{}", e)))
+        .ok_or_else(|| missing(name, elemname))
+        .and_then(|st| {
+            st.parse::<T>().map_err(|e| {
+                PdscError::InvalidValue {
+                    elem: elemname.to_string(),
+                    attr: name.to_string(),
+                    value: st.to_string(),
+                    reason: e.to_string(),
+                }.into()
+            })
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoolPolicy {
+    /// Only accept `true`, `false`, `1` and `0`
+    Strict,
+    /// Also accept differently cased, padded or empty values, with a warning
+    Lenient,
+}
+
+pub fn parse_bool(from: &str, policy: BoolPolicy, l: &Logger) -> Result<bool, Error> {
+    match from {
+        "true" | "1" => return Ok(true),
+        "false" | "0" => return Ok(false),
+        _ => (),
+    }
+    let lenient = match from.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" | "" => Some(false),
+        _ => None,
+    };
+    match (policy, lenient) {
+        (BoolPolicy::Lenient, Some(value)) => {
+            warn!(l, "non-standard boolean \"{}\" interpreted as {}", from, value);
+            Ok(value)
+        }
+        _ => Err(PdscError::Unknown {
+            what: "boolean",
+            value: from.to_string(),
+        }.into()),
+    }
+}
+
+pub fn attr_parse_bool<'a>(
+    from: &'a Element,
+    name: &str,
+    elemname: &'static str,
+    opts: &ParseOptions,
+    l: &Logger,
+) -> Result<bool, Error> {
+    from.attr(name)
+        .ok_or_else(|| missing(name, elemname))
+        .and_then(|st| {
+            parse_bool(st, opts.bools, l).map_err(|e| {
+                PdscError::InvalidValue {
+                    elem: elemname.to_string(),
+                    attr: name.to_string(),
+                    value: st.to_string(),
+                    reason: e.to_string(),
+                }.into()
+            })
+        })
 }
 
 pub fn child_text<'a>(
     from: &'a Element,
     name: &str,
     elemname: &'static str,
+    opts: &ParseOptions,
 ) -> Result<String, Error> {
-    match get_child_no_ns(from, name) {
+    match get_child_no_ns(from, name, opts) {
         Some(child) => Ok(child.text()),
-        None => Err(err_msg!(
-            "child element \"{}\" not found in \"{}\" element",
-            name,
-            elemname
-        )),
+        None => Err(PdscError::MissingChild {
+            elem: elemname.to_string(),
+            child: name.to_string(),
+        }.into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamespacePolicy {
+    /// Element names must match exactly, namespace prefix included
+    Strict,
+    /// Match elements by their local name, ignoring any namespace prefix
+    Lenient,
+}
+
+/// What to do when a device and its parent describe different memories
+/// under the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryMergePolicy {
+    /// Keep the memory of the device
+    ChildWins,
+    /// Keep the memory of the parent
+    ParentWins,
+    /// Drop the device
+    Error,
+    /// Keep both, adding a numeric suffix to the name of the parent's memory
+    RenameWithSuffix,
+}
+
+/// What to do when a PDSC describes several devices with the same name.
+/// Every description that is not kept is reported as a dropped device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateDevicePolicy {
+    /// Keep the last description
+    LastWins,
+    /// Keep the first description
+    FirstWins,
+    /// Keep none of them
+    Reject,
+}
+
+/// How descriptions are read. The default reads what vendors publish,
+/// warning about what does not follow the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub bools: BoolPolicy,
+    pub namespaces: NamespacePolicy,
+    pub encoding: EncodingPolicy,
+    pub memory_merge: MemoryMergePolicy,
+    pub duplicate_devices: DuplicateDevicePolicy,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            bools: BoolPolicy::Lenient,
+            namespaces: NamespacePolicy::Lenient,
+            encoding: EncodingPolicy::Lenient,
+            memory_merge: MemoryMergePolicy::ChildWins,
+            duplicate_devices: DuplicateDevicePolicy::LastWins,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Reject booleans, namespaces and encodings that do not follow the
+    /// schema, for validating descriptions.
+    pub fn strict() -> Self {
+        ParseOptions {
+            bools: BoolPolicy::Strict,
+            namespaces: NamespacePolicy::Strict,
+            encoding: EncodingPolicy::Strict,
+            ..ParseOptions::default()
+        }
+    }
+}
+
+/// The part of a qualified name following its namespace prefix.
+pub fn local_name(name: &str) -> &str {
+    match name.find(':') {
+        Some(index) => &name[index + 1..],
+        None => name,
     }
 }
 
-pub fn get_child_no_ns<'a>(from: &'a Element, name: &str) -> Option<&'a Element> {
+/// The name to match `e` by, according to the namespace policy of `opts`.
+pub fn element_name<'a>(e: &'a Element, opts: &ParseOptions) -> &'a str {
+    match opts.namespaces {
+        NamespacePolicy::Strict => e.name(),
+        NamespacePolicy::Lenient => local_name(e.name()),
+    }
+}
+
+fn has_prefixed_elements(e: &Element) -> bool {
+    e.name().contains(':') || e.children().any(has_prefixed_elements)
+}
+
+pub fn get_child_no_ns<'a>(
+    from: &'a Element,
+    name: &str,
+    opts: &ParseOptions,
+) -> Option<&'a Element> {
     for child in from.children() {
-        if child.name() == name {
+        if element_name(child, opts) == name {
             return Some(child);
         }
     }
     None
 }
 
-pub fn assert_root_name(from: &Element, name: &str) -> Result<(), Error> {
-    if from.name() != name {
-        Err(err_msg!(
-            "tried to parse element \"{}\" from element \"{}\"",
-            name,
-            from.name()
-        ))
+pub fn assert_root_name(from: &Element, name: &str, opts: &ParseOptions) -> Result<(), Error> {
+    if element_name(from, opts) != name {
+        Err(PdscError::UnexpectedElement {
+            expected: name.to_string(),
+            found: from.name().to_string(),
+        }.into())
     } else {
         Ok(())
     }
 }
 
+pub fn element_from_reader<T: BufRead>(r: &mut Reader<T>) -> Result<Element, Error> {
+    let mut root = Element::from_reader(r).map_err(|e| PdscError::Xml(e.to_string()))?;
+    root.set_attr::<&str, Option<String>>("xmlns:xs", None);
+    Ok(root)
+}
+
+pub fn is_gzip(p: &Path) -> bool {
+    p.extension().map(|ext| ext == "gz").unwrap_or(false)
+}
+
+fn open_path(p: &Path) -> Result<Box<BufRead>, Error> {
+    let fd = File::open(p).map_err(PdscError::Io)?;
+    Ok(if is_gzip(p) {
+        Box::new(BufReader::new(GzDecoder::new(fd)))
+    } else {
+        Box::new(BufReader::new(fd))
+    })
+}
+
+/// Open an XML file for reading, decompressing it when it ends in `.gz`.
+pub fn reader_from_path(p: &Path) -> Result<Reader<Box<BufRead>>, Error> {
+    Ok(Reader::from_reader(open_path(p)?))
+}
+
+pub fn element_from_path(p: &Path) -> Result<Element, Error> {
+    let mut r = reader_from_path(p)?;
+    element_from_reader(&mut r)
+}
+
 pub trait FromElem: Sized {
-    fn from_elem(e: &Element, l: &Logger) -> Result<Self, Error>;
+    fn from_elem(e: &Element, opts: &ParseOptions, l: &Logger) -> Result<Self, Error>;
 
-    fn from_reader<T: BufRead>(r: &mut Reader<T>, l: &Logger) -> Result<Self, Error> {
-        let mut root = Element::from_reader(r)?;
-        root.set_attr::<&str, Option<String>>("xmlns:xs", None);
-        Self::from_elem(&root, l)
+    fn from_reader<T: BufRead>(
+        r: &mut Reader<T>,
+        opts: &ParseOptions,
+        l: &Logger,
+    ) -> Result<Self, Error> {
+        let root = element_from_reader(r)?;
+        if opts.namespaces == NamespacePolicy::Lenient && has_prefixed_elements(&root) {
+            warn!(
+                l,
+                "\"{}\" contains namespace prefixed elements, matching them by local name",
+                root.name()
+            );
+        }
+        Self::from_elem(&root, opts, l)
     }
-    fn from_string(s: &str, l: &Logger) -> Result<Self, Error> {
+    fn from_string(s: &str, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
         let mut r = Reader::from_str(s);
-        Self::from_reader(&mut r, l)
+        Self::from_reader(&mut r, opts, l)
+    }
+    /// Parse a document in any encoding `decode_xml` knows of.
+    fn from_bytes(b: &[u8], opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        Self::from_string(&decode_xml(b, opts.encoding, l)?, opts, l)
     }
-    fn from_path(p: &Path, l: &Logger) -> Result<Self, Error> {
-        let mut r = Reader::from_file(p)?;
-        Self::from_reader(&mut r, l)
+    fn from_path(p: &Path, opts: &ParseOptions, l: &Logger) -> Result<Self, Error> {
+        let _span = Span::enter(l, "parse", p.display());
+        let mut bytes = Vec::new();
+        open_path(p)?
+            .read_to_end(&mut bytes)
+            .map_err(PdscError::Io)?;
+        let file_log = l.new(o!("file" => p.display().to_string()));
+        let text = decode_xml(&bytes, opts.encoding, &file_log)?;
+        Self::from_string(&text, opts, l)
     }
-    fn vec_from_children(clds: Children, l: &Logger) -> Vec<Self> {
-        clds.flat_map(move |cld| Self::from_elem(cld, l).ok_warn(l).into_iter())
+    /// `from_string` for callers without a Logger, see `default_logger`,
+    /// with the default options.
+    fn parse_string(s: &str) -> Result<Self, Error> {
+        Self::from_string(s, &ParseOptions::default(), &default_logger())
+    }
+    fn parse_bytes(b: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes(b, &ParseOptions::default(), &default_logger())
+    }
+    fn parse_path(p: &Path) -> Result<Self, Error> {
+        Self::from_path(p, &ParseOptions::default(), &default_logger())
+    }
+    fn vec_from_children(clds: Children, opts: &ParseOptions, l: &Logger) -> Vec<Self> {
+        clds.flat_map(move |cld| Self::from_elem(cld, opts, l).ok_warn(l).into_iter())
             .collect()
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(option_unwrap_used, result_unwrap_used)
+)]
+mod test {
+    use super::*;
+    use slog::{Discard, Logger};
+
+    #[test]
+    fn bool_strict() {
+        let log = Logger::root(Discard, o!());
+        assert_eq!(parse_bool("1", BoolPolicy::Strict, &log).unwrap(), true);
+        assert_eq!(parse_bool("false", BoolPolicy::Strict, &log).unwrap(), false);
+        for bad in &["TRUE", "True", "", " 1", "yes"] {
+            assert!(parse_bool(bad, BoolPolicy::Strict, &log).is_err());
+        }
+    }
+
+    #[test]
+    fn bool_lenient() {
+        let log = Logger::root(Discard, o!());
+        for good in &["TRUE", "True", " 1", "yes"] {
+            assert_eq!(parse_bool(good, BoolPolicy::Lenient, &log).unwrap(), true);
+        }
+        for good in &["FALSE", "", "No"] {
+            assert_eq!(parse_bool(good, BoolPolicy::Lenient, &log).unwrap(), false);
+        }
+        assert!(parse_bool("maybe", BoolPolicy::Lenient, &log).is_err());
+    }
+
+    #[test]
+    fn namespace_local_name() {
+        assert_eq!(local_name("cmsis:device"), "device");
+        assert_eq!(local_name("device"), "device");
+    }
+
+    #[test]
+    fn namespace_policy_matching() {
+        let mut r = Reader::from_str(
+            "<p:package xmlns:p=\"http://www.keil.com/pack\"><p:releases/></p:package>",
+        );
+        let root = element_from_reader(&mut r).unwrap();
+        let lenient = ParseOptions::default();
+        assert!(assert_root_name(&root, "package", &lenient).is_ok());
+        assert!(get_child_no_ns(&root, "releases", &lenient).is_some());
+        let strict = ParseOptions::strict();
+        assert!(assert_root_name(&root, "package", &strict).is_err());
+        assert!(get_child_no_ns(&root, "releases", &strict).is_none());
+    }
+
+    #[test]
+    fn structured_errors() {
+        let mut r = Reader::from_str("<memory id=\"IROM1\" start=\"0xZZ\"/>");
+        let memory = element_from_reader(&mut r).unwrap();
+        match PdscError::find(&attr_parse_hex(&memory, "start", "memory").unwrap_err()) {
+            Some(&PdscError::InvalidHex { ref value, .. }) => assert_eq!(value, "0xZZ"),
+            other => panic!("unexpected error {:?}", other),
+        }
+        match PdscError::find(&attr_map::<&str>(&memory, "size", "memory").unwrap_err()) {
+            Some(&PdscError::MissingAttribute { ref attr, .. }) => assert_eq!(attr, "size"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
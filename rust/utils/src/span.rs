@@ -0,0 +1,51 @@
+//! Spans time a phase of work, such as parsing a description or downloading
+//! a pack. With the "spans" feature, a span logs at debug level when it is
+//! entered and when it is left, along with the time spent in it. Without the
+//! feature, entering a span does nothing, and its subject is not formatted.
+
+use std::fmt::Display;
+#[cfg(feature = "spans")]
+use std::time::Instant;
+
+use slog::Logger;
+
+/// A phase of work, which ends when the span is dropped.
+#[must_use]
+pub struct Span {
+    #[cfg(feature = "spans")]
+    logger: Logger,
+    #[cfg(feature = "spans")]
+    start: Instant,
+}
+
+impl Span {
+    /// Enter the phase `phase` of the work on `subject`, such as a file or
+    /// an url.
+    #[cfg(feature = "spans")]
+    pub fn enter<D: Display>(l: &Logger, phase: &'static str, subject: D) -> Self {
+        let logger = l.new(o!("span" => phase, "subject" => subject.to_string()));
+        debug!(logger, "entered {} of {}", phase, subject);
+        Span {
+            logger,
+            start: Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "spans"))]
+    pub fn enter<D: Display>(_: &Logger, _: &'static str, _: D) -> Self {
+        Span {}
+    }
+}
+
+#[cfg(feature = "spans")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        debug!(
+            self.logger,
+            "left after {}.{:03}s",
+            elapsed.as_secs(),
+            elapsed.subsec_nanos() / 1_000_000
+        );
+    }
+}